@@ -0,0 +1,75 @@
+// 上位k件の選択をヒープと全件ソートで比べる（cargo bench --bench select_top）
+// バイナリのクレートのため、依存のないモジュールをそのまま読み込む
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// テスト用の定義はベンチマークでは使わない
+#[allow(dead_code)]
+#[path = "../src/service/top_k.rs"]
+mod top_k;
+
+use top_k::{Scored, select_top};
+
+struct Item(String, f32);
+
+impl Scored for Item {
+    fn score(&self) -> f32 {
+        self.1
+    }
+
+    fn id(&self) -> &str {
+        &self.0
+    }
+}
+
+// 全件をソートして上位k件を返す（select_top と同じ順位付け）
+fn sort_top(mut items: Vec<Item>, k: usize) -> Vec<Item> {
+    items.sort_by(|a, b| {
+        b.score()
+            .total_cmp(&a.score())
+            .then_with(|| a.id().cmp(b.id()))
+    });
+    items.truncate(k);
+    items
+}
+
+// 計測の繰り返し回数
+const ITERATIONS: u32 = 20;
+
+// 同じ入力を毎回作り直して選択し、1回あたりの平均時間を返す
+fn measure(
+    items: &[(String, f32)],
+    k: usize,
+    select: fn(Vec<Item>, usize) -> Vec<Item>,
+) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let input: Vec<Item> = items
+            .iter()
+            .map(|(id, score)| Item(id.clone(), *score))
+            .collect();
+        let started = Instant::now();
+        black_box(select(black_box(input), k));
+        total += started.elapsed();
+    }
+    total / ITERATIONS
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(42);
+    for n in [1_000, 10_000, 100_000] {
+        let items: Vec<(String, f32)> = (0..n)
+            .map(|i| (format!("customer-{}", i), rng.random::<f32>()))
+            .collect();
+        for k in [10, 100] {
+            let heap = measure(&items, k, select_top);
+            let sort = measure(&items, k, sort_top);
+            println!(
+                "n={:>7} k={:>3}  heap: {:>10.3?}  sort: {:>10.3?}",
+                n, k, heap, sort
+            );
+        }
+    }
+}
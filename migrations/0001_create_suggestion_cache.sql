@@ -0,0 +1,7 @@
+-- 推薦結果のキャッシュ（SUGGESTION_CACHE_TTL_SECS を指定した場合に使用）
+-- cache_key はリクエスト内容と推薦の設定から作成したハッシュ
+CREATE TABLE IF NOT EXISTS suggestion_cache (
+    cache_key CHAR(36) NOT NULL PRIMARY KEY,
+    suggestions MEDIUMTEXT NOT NULL,
+    created_at DATETIME NOT NULL
+);
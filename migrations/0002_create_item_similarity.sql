@@ -0,0 +1,9 @@
+-- 商品間の類似度（同じ注文で購入された回数から計算、/admin/rebuild-item-similarity で作り直す）
+-- score は2つの商品を含む注文の集合のコサイン類似度（共起した注文数 / √(一方の注文数 × もう一方の注文数)）
+CREATE TABLE IF NOT EXISTS item_similarity (
+    variant_id VARCHAR(255) NOT NULL,
+    similar_variant_id VARCHAR(255) NOT NULL,
+    score FLOAT NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (variant_id, similar_variant_id)
+);
@@ -0,0 +1,11 @@
+-- 商品の配送温度帯（Normal / Cold / Frozen、?temperature= で推薦を絞り込む際に使用）
+-- 以前はシード時に追加していたため、列がすでにある場合は追加しない
+SET @ddl = IF(
+    (SELECT COUNT(*) FROM information_schema.columns
+     WHERE table_schema = DATABASE() AND table_name = 'products' AND column_name = 'shipping_temperature') = 0,
+    'ALTER TABLE products ADD COLUMN shipping_temperature VARCHAR(16) NOT NULL DEFAULT ''Normal''',
+    'DO 0'
+);
+PREPARE add_column FROM @ddl;
+EXECUTE add_column;
+DEALLOCATE PREPARE add_column;
@@ -0,0 +1,11 @@
+-- 商品のカテゴリ（?exclude_categories= で推薦から除外する際に使用）
+-- 以前はシード時に追加していたため、列がすでにある場合は追加しない
+SET @ddl = IF(
+    (SELECT COUNT(*) FROM information_schema.columns
+     WHERE table_schema = DATABASE() AND table_name = 'products' AND column_name = 'category') = 0,
+    'ALTER TABLE products ADD COLUMN category VARCHAR(64) NOT NULL DEFAULT ''food''',
+    'DO 0'
+);
+PREPARE add_column FROM @ddl;
+EXECUTE add_column;
+DEALLOCATE PREPARE add_column;
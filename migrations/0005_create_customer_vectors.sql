@@ -0,0 +1,9 @@
+-- 事前計算済みの顧客ベクトル（vectors コマンドや /admin/rebuild-customer-vectors で作り直す）
+-- vector は商品IDから数量への疎なベクトルのJSON、updated_at は集計を始めた日時
+-- 以前は作り直しの際に作成していたため、すでにある場合は作成しない
+CREATE TABLE IF NOT EXISTS customer_vectors (
+    customer_id VARCHAR(255) NOT NULL PRIMARY KEY,
+    vector MEDIUMTEXT NOT NULL,
+    province_code VARCHAR(255) NOT NULL,
+    updated_at DATETIME NOT NULL
+);
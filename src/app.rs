@@ -0,0 +1,301 @@
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, Request},
+    http::Method,
+    middleware,
+    routing::{get, post, put},
+};
+use std::time::Duration;
+use tower_http::catch_panic::CatchPanicLayer;
+use tower_http::cors::{AllowHeaders, Any, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+
+use crate::config::{self, server::CorsConfig};
+use crate::controller;
+use crate::error;
+use crate::state::AppState;
+
+// リクエストに設ける上限
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    // リクエストボディの最大サイズ（バイト）
+    pub max_body_bytes: usize,
+    // クライアントが X-Request-Timeout-Ms で指定できる処理時間の上限
+    pub max_request_timeout: Duration,
+}
+
+impl Limits {
+    // 環境変数（MAX_BODY_BYTES / MAX_REQUEST_TIMEOUT_MS）から読み込む
+    pub fn from_env() -> Self {
+        Limits {
+            max_body_bytes: config::server::get_max_body_bytes(),
+            max_request_timeout: config::server::get_max_request_timeout(),
+        }
+    }
+}
+
+// CORSの設定からミドルウェアを作成
+pub fn cors_layer(cors_config: CorsConfig) -> CorsLayer {
+    let mut cors = CorsLayer::new()
+        // すべてのメソッドを許可
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ]);
+    cors = match cors_config.allowed_origins {
+        // 指定したオリジンだけを許可
+        Some(origins) => cors.allow_origin(origins),
+        // 指定がなければすべてのオリジンを許可
+        None => cors.allow_origin(Any),
+    };
+    cors = if cors_config.allow_credentials {
+        // 資格情報付きの場合はワイルドカードが使えないため、要求されたヘッダーをそのまま許可する
+        cors.allow_credentials(true)
+            .allow_headers(AllowHeaders::mirror_request())
+    } else {
+        // すべてのヘッダーを許可
+        cors.allow_headers(Any)
+    };
+    if let Some(max_age) = cors_config.max_age {
+        // プリフライトの結果をブラウザにキャッシュさせる
+        cors = cors.max_age(max_age);
+    }
+    cors
+}
+
+// APIのルーターを作成
+pub fn router(app_state: AppState, cors: CorsLayer, limits: Limits) -> Router {
+    let routes = Router::new()
+        .route("/health", get(controller::health::get_health))
+        .route("/ready", get(controller::health::get_ready))
+        .route("/users", get(controller::users::get_users))
+        .route("/products", get(controller::products::get_products))
+        .route(
+            "/products/unsold",
+            get(controller::products::get_unsold_products),
+        )
+        .route(
+            "/products/{variant_id}/suspension",
+            put(controller::products::put_product_suspension),
+        )
+        .route(
+            "/admin/products/suspend",
+            post(controller::products::post_products_suspension),
+        )
+        .route(
+            "/admin/rebuild-customer-vectors",
+            post(controller::admin::post_rebuild_customer_vectors),
+        )
+        .route(
+            "/admin/rebuild-item-similarity",
+            post(controller::admin::post_rebuild_item_similarity),
+        )
+        .route("/admin/jobs/{id}", get(controller::admin::get_job))
+        .route("/admin/config", get(controller::admin::get_config))
+        .route("/provinces", get(controller::provinces::get_provinces))
+        .route("/suggestions", get(controller::cart::get_suggestions))
+        .route(
+            "/suggestions/neighbors",
+            post(controller::cart::post_neighbors),
+        )
+        .route("/rerank", post(controller::cart::post_rerank))
+        .route("/similarity", post(controller::cart::post_similarity))
+        .route("/stats", get(controller::stats::get_stats))
+        .route(
+            "/customers/segments",
+            get(controller::customers::get_customer_segments),
+        )
+        .route(
+            "/customers/{id}/orders",
+            get(controller::customers::get_customer_orders),
+        )
+        .route("/orders/{id}", get(controller::orders::get_order))
+        // 登録済みのルートを許可されていないメソッドで呼んだ場合はJSONのエラーで405を返す
+        // （ルートの登録より後に設定する。CORSのプリフライトは CorsLayer がルーターより先に応答する）
+        .method_not_allowed_fallback(error::method_not_allowed)
+        .with_state(app_state);
+
+    with_layers(routes, cors, limits)
+}
+
+// ルーター全体に共通のミドルウェアを設定
+pub fn with_layers(routes: Router, cors: CorsLayer, limits: Limits) -> Router {
+    routes
+        // X-Request-Timeout-Ms で指定された期限（MAX_REQUEST_TIMEOUT_MS が上限）を過ぎたら504を返す
+        .layer(middleware::from_fn_with_state(
+            limits.max_request_timeout,
+            controller::deadline::enforce_deadline,
+        ))
+        // リクエストボディのサイズを制限（ストリーミングで受け取るルートを追加する場合は対象外にする）
+        // axum既定の上限ではなく MAX_BODY_BYTES で制限する
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(limits.max_body_bytes))
+        .layer(CatchPanicLayer::custom(error::handle_panic)) // パニックを500エラーに変換
+        .layer(cors) // CORSミドルウェアを追加
+        // リクエストごとにIDを振り（x-request-id が指定されていればそれを使う）、
+        // 処理中のログをそのIDを持つスパンに含める。IDはレスポンスヘッダーでも返す
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request| {
+                let request_id = request
+                    .headers()
+                    .get("x-request-id")
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default();
+                tracing::info_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    request_id,
+                )
+            }),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{StatusCode, header};
+    use std::sync::Arc;
+
+    use crate::mock::MockStore;
+    use crate::testing;
+
+    // 固定データのストアと指定した上限を使うルーター
+    fn mock_app(limits: Limits) -> Router {
+        let store = Arc::new(MockStore);
+        router(
+            testing::state(store.clone(), store),
+            CorsLayer::permissive(),
+            limits,
+        )
+    }
+
+    #[tokio::test]
+    async fn oversized_body_is_rejected_with_413() {
+        let limits = Limits {
+            max_body_bytes: 1024,
+            ..testing::limits()
+        };
+        let candidates: Vec<String> = (0..200).map(|i| format!("variant-{}", i)).collect();
+        let body = serde_json::json!({
+            "province_code": "JP-13",
+            "products": [{"product_variant_id": "mock-variant-1"}],
+            "candidates": candidates,
+        })
+        .to_string();
+        assert!(body.len() > limits.max_body_bytes);
+
+        // Content-Length がない場合は読み込み中に上限を超えた時点でJSONのエラーにする
+        let (status, json) = testing::send(
+            mock_app(limits),
+            testing::post_json("/rerank", body.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(json["message"], "Request body too large");
+
+        // Content-Length が上限を超えている場合は本文を読まずに断る
+        let mut request = testing::post_json("/rerank", body.clone());
+        request
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, body.len().into());
+        let (status, _) = testing::send(mock_app(limits), request).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn preflight_to_suggestions_is_answered_by_cors_layer() {
+        let cors = cors_layer(CorsConfig {
+            allowed_origins: Some(vec!["https://example.com".parse().unwrap()]),
+            max_age: Some(Duration::from_secs(600)),
+            allow_credentials: false,
+        });
+        let store = Arc::new(MockStore);
+        let app = router(
+            testing::state(store.clone(), store),
+            cors,
+            testing::limits(),
+        );
+        let request = axum::http::Request::options("/suggestions")
+            .header(header::ORIGIN, "https://example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "x-request-timeout-ms",
+            )
+            .body(axum::body::Body::empty())
+            .unwrap();
+
+        let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert!(
+            headers[header::ACCESS_CONTROL_ALLOW_METHODS]
+                .to_str()
+                .unwrap()
+                .contains("GET")
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[tokio::test]
+    async fn credentialed_preflight_mirrors_the_requested_headers() {
+        let cors = cors_layer(CorsConfig {
+            allowed_origins: Some(vec!["https://example.com".parse().unwrap()]),
+            max_age: None,
+            allow_credentials: true,
+        });
+        let store = Arc::new(MockStore);
+        let app = router(
+            testing::state(store.clone(), store),
+            cors,
+            testing::limits(),
+        );
+        let request = axum::http::Request::options("/suggestions")
+            .header(header::ORIGIN, "https://example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+            .body(axum::body::Body::empty())
+            .unwrap();
+
+        let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        // 資格情報付きではワイルドカードが使えないため、要求されたヘッダーをそのまま返す
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "authorization"
+        );
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_MAX_AGE));
+    }
+
+    #[tokio::test]
+    async fn post_to_get_only_route_is_a_json_405() {
+        let (status, json) = testing::send(
+            mock_app(testing::limits()),
+            testing::post_json("/users", "{}"),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(json, serde_json::json!({"message": "Method not allowed"}));
+    }
+}
@@ -0,0 +1,41 @@
+use mysql::prelude::*;
+use mysql::*;
+
+// 1回のINSERTでまとめて挿入する行数
+pub const BATCH_SIZE: usize = 1000;
+
+// 複数行をまとめて挿入し、主キーが重複する行は指定した列を更新する
+pub fn upsert_batch(
+    tx: &mut Transaction,
+    table: &str,
+    columns: &[&str],
+    update_columns: &[&str],
+    rows: Vec<Vec<Value>>,
+) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let updates: Vec<String> = update_columns
+        .iter()
+        .map(|column| format!("{0} = VALUES({0})", column))
+        .collect();
+    let query = format!(
+        "{} ON DUPLICATE KEY UPDATE {}",
+        insert_query(table, columns, rows.len()),
+        updates.join(", ")
+    );
+    let params: Vec<Value> = rows.into_iter().flatten().collect();
+    tx.exec_drop(query, params)
+}
+
+// (?, ?, ...) を行数分並べたINSERT文を作成
+fn insert_query(table: &str, columns: &[&str], row_count: usize) -> String {
+    let placeholders = format!("({})", vec!["?"; columns.len()].join(", "));
+    format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        table,
+        columns.join(", "),
+        vec![placeholders; row_count].join(", ")
+    )
+}
@@ -0,0 +1,206 @@
+use clap::ValueEnum;
+use mysql::prelude::*;
+use mysql::*;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::csv_error;
+use crate::config;
+
+// エクスポート可能なテーブル
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportTable {
+    Customers,
+    Orders,
+    #[value(name = "order_products")]
+    OrderProducts,
+}
+
+impl ExportTable {
+    pub fn table_name(&self) -> &'static str {
+        match self {
+            ExportTable::Customers => "customers",
+            ExportTable::Orders => "orders",
+            ExportTable::OrderProducts => "order_products",
+        }
+    }
+}
+
+// MySQLの値をCSVのフィールド文字列に変換
+fn value_to_field(value: &Value) -> String {
+    match value {
+        Value::NULL => String::new(),
+        Value::Bytes(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        Value::Int(v) => v.to_string(),
+        Value::UInt(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Double(v) => v.to_string(),
+        Value::Date(year, month, day, hour, minute, second, _) => format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year, month, day, hour, minute, second
+        ),
+        Value::Time(negative, days, hours, minutes, seconds, _) => format!(
+            "{}{:02}:{:02}:{:02}",
+            if *negative { "-" } else { "" },
+            *days * 24 + *hours as u32,
+            minutes,
+            seconds
+        ),
+    }
+}
+
+// テーブルの内容をCSVとして書き出し、書き出した行数を返す
+pub fn write_table<W: Write>(conn: &mut PooledConn, table: ExportTable, out: W) -> Result<usize> {
+    // テーブル全体をメモリに載せず、1行ずつ読み出して書き込む
+    let mut result = conn.query_iter(format!("SELECT * FROM {}", table.table_name()))?;
+
+    let headers: Vec<String> = result
+        .columns()
+        .as_ref()
+        .iter()
+        .map(|column| column.name_str().into_owned())
+        .collect();
+
+    write_csv(
+        out,
+        &headers,
+        result.by_ref().map(|row| row.map(Row::unwrap)),
+    )
+}
+
+// 列名と行をCSVとして書き出し、書き出した行数を返す
+// JSONなどカンマや引用符を含む値は csv クレートが引用符で囲む
+fn write_csv<W: Write>(
+    out: W,
+    headers: &[String],
+    rows: impl Iterator<Item = Result<Vec<Value>>>,
+) -> Result<usize> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(headers).map_err(csv_error)?;
+
+    let mut written = 0;
+    for row in rows {
+        let fields: Vec<String> = row?.iter().map(value_to_field).collect();
+        writer.write_record(&fields).map_err(csv_error)?;
+
+        written += 1;
+        // 進捗表示（10,000件ごと）
+        if written % 10000 == 0 {
+            println!("{}件 書き出し完了", written);
+        }
+    }
+
+    writer.flush()?;
+    Ok(written)
+}
+
+// テーブルの内容をCSVファイルに書き出す
+pub async fn export_table(table: ExportTable, out: PathBuf) -> Result<()> {
+    println!(
+        "{}テーブルを{}に書き出します",
+        table.table_name(),
+        out.display()
+    );
+
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let written = write_table(&mut conn, table, File::create(&out)?)?;
+        println!("{}件のデータを書き出しました", written);
+
+        Ok::<(), mysql::Error>(())
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 書き出したCSVを読み込み直し、列名と各行のフィールドを返す
+    fn parse(csv: &[u8]) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut reader = csv::Reader::from_reader(csv);
+        let headers = reader.headers().unwrap().iter().map(String::from).collect();
+        let records = reader
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect();
+        (headers, records)
+    }
+
+    #[test]
+    fn fields_with_commas_and_quotes_survive_a_round_trip() {
+        let address =
+            r#"{"province": "東京都", "address1": "千代田区1-1, 2F", "note": "\"至急\""}"#;
+        let headers = [
+            "id".to_string(),
+            "shipping_address".to_string(),
+            "note".to_string(),
+        ];
+        let rows = vec![
+            Ok(vec![
+                Value::Int(1),
+                Value::Bytes(address.into()),
+                Value::NULL,
+            ]),
+            Ok(vec![
+                Value::Int(2),
+                Value::Bytes(b"{}".to_vec()),
+                Value::Bytes(b"a\nb".to_vec()),
+            ]),
+        ];
+        let mut csv = Vec::new();
+
+        let written = write_csv(&mut csv, &headers, rows.into_iter()).unwrap();
+
+        assert_eq!(written, 2);
+        let (parsed_headers, records) = parse(&csv);
+        assert_eq!(parsed_headers, headers);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], ["1", address, ""]);
+        assert_eq!(records[1][2], "a\nb");
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    async fn exported_customers_can_be_parsed_back() {
+        use crate::command::seed;
+
+        let pool = crate::testing::database_pool();
+        let email_domain = format!("{}.export.example.com", uuid::Uuid::new_v4());
+        let options = seed::CustomerOptions {
+            email_domain: email_domain.clone(),
+            on_conflict: seed::OnConflict::Skip,
+            progress: seed::Progress::new(true, 10_000),
+        };
+        let provinces = seed::province_distribution(None).unwrap();
+        seed::seed_customers(pool.clone(), 5, 1, provinces, options)
+            .await
+            .unwrap();
+        let mut conn = pool.get_conn().unwrap();
+        let count: usize = conn
+            .query_first("SELECT COUNT(*) FROM customers")
+            .unwrap()
+            .unwrap();
+
+        let mut csv = Vec::new();
+        let written = write_table(&mut conn, ExportTable::Customers, &mut csv).unwrap();
+
+        assert_eq!(written, count);
+        let (headers, records) = parse(&csv);
+        assert_eq!(records.len(), count);
+        let column = |name: &str| headers.iter().position(|header| header == name).unwrap();
+        let (id, email) = (column("id"), column("email"));
+        let sampled = seed::customer_id(3);
+        let record = records.iter().find(|record| record[id] == sampled).unwrap();
+        assert_eq!(record[email], seed::customer_email(&sampled, &email_domain));
+    }
+}
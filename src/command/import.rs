@@ -0,0 +1,343 @@
+use chrono::Utc;
+use mysql::*;
+use std::path::PathBuf;
+
+use super::{batch, csv_error, invalid_input};
+use crate::config;
+
+// 取り込み時に必須の列
+const REQUIRED_COLUMNS: [&str; 5] = [
+    "id",
+    "email",
+    "first_name",
+    "last_name",
+    "shipping_province_code",
+];
+
+// 取り込み可能な customers テーブルの列
+const CUSTOMER_COLUMNS: [&str; 12] = [
+    "id",
+    "email",
+    "is_infomercial",
+    "password",
+    "accepts_marketing",
+    "first_name",
+    "last_name",
+    "shipping_province_code",
+    "shipping_address_line1",
+    "shipping_phone",
+    "created_at",
+    "updated_at",
+];
+
+// 既存の顧客と主キーが重複した場合に更新しない列
+const KEEP_ON_UPDATE: [&str; 2] = ["id", "created_at"];
+
+// CSVの顧客データを customers テーブルに取り込む
+// 同じIDの顧客が既に存在する場合は、CSVの内容で更新する
+pub async fn import_customers(file: PathBuf) -> Result<()> {
+    println!("{}から顧客データを取り込みます", file.display());
+
+    let reader = csv::Reader::from_path(&file).map_err(csv_error)?;
+
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let (imported, skipped) = tokio::task::spawn_blocking(move || import_records(&pool, reader))
+        .await
+        .expect("ブロッキングタスクの実行に失敗")?;
+
+    println!(
+        "顧客データの取り込みが完了しました（取り込み: {}件、スキップ: {}件）",
+        imported, skipped
+    );
+
+    Ok(())
+}
+
+// CSVの全行を1つのトランザクションで取り込み、取り込んだ件数とスキップした件数を返す
+fn import_records<R: std::io::Read>(
+    pool: &mysql::Pool,
+    mut reader: csv::Reader<R>,
+) -> Result<(usize, usize)> {
+    let layout = CustomerLayout::from_headers(reader.headers().map_err(csv_error)?)?;
+    let update_columns: Vec<&str> = layout
+        .columns
+        .iter()
+        .copied()
+        .filter(|column| !KEEP_ON_UPDATE.contains(column))
+        .collect();
+
+    // 作成日時・更新日時がCSVにない場合は現在時刻を設定
+    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut conn = pool.get_conn()?;
+    let mut tx = conn.start_transaction(TxOpts::default())?;
+
+    let mut rows: Vec<Vec<Value>> = Vec::with_capacity(batch::BATCH_SIZE);
+    let mut imported = 0;
+    let mut skipped = 0;
+
+    for (i, record) in reader.records().enumerate() {
+        // ヘッダー行を1行目として数える
+        let line = i + 2;
+
+        // 不正な行はスキップして報告する
+        match layout.row(line, record, &now) {
+            Ok(row) => rows.push(row),
+            Err(message) => {
+                eprintln!("{}", message);
+                skipped += 1;
+                continue;
+            }
+        }
+
+        if rows.len() >= batch::BATCH_SIZE {
+            imported += rows.len();
+            batch::upsert_batch(
+                &mut tx,
+                "customers",
+                &layout.columns,
+                &update_columns,
+                std::mem::take(&mut rows),
+            )?;
+            println!("{}件 取り込み完了", imported);
+        }
+    }
+
+    imported += rows.len();
+    batch::upsert_batch(&mut tx, "customers", &layout.columns, &update_columns, rows)?;
+
+    tx.commit()?;
+
+    Ok((imported, skipped))
+}
+
+// CSVの列と customers テーブルの列の対応
+struct CustomerLayout {
+    // 挿入する列（CSVにない作成日時・更新日時を末尾に含む）
+    columns: Vec<&'static str>,
+    // 挿入する列のCSV上の位置（CSVにある列のみ）
+    positions: Vec<usize>,
+    // 必須列のCSV上の位置
+    required: Vec<(&'static str, usize)>,
+    // 現在時刻を設定する列の数
+    default_timestamps: usize,
+    // CSVの列数
+    width: usize,
+}
+
+impl CustomerLayout {
+    // ヘッダーを検証し、取り込む列とCSV上の位置を対応付ける
+    fn from_headers(headers: &csv::StringRecord) -> Result<Self> {
+        let missing: Vec<&str> = REQUIRED_COLUMNS
+            .iter()
+            .copied()
+            .filter(|column| !headers.iter().any(|header| header == *column))
+            .collect();
+        if !missing.is_empty() {
+            return Err(invalid_input(format!(
+                "必須の列がありません: {}",
+                missing.join(", ")
+            )));
+        }
+
+        let mut columns: Vec<&'static str> = Vec::new();
+        let mut positions: Vec<usize> = Vec::new();
+        for (position, header) in headers.iter().enumerate() {
+            match CUSTOMER_COLUMNS.iter().find(|column| **column == header) {
+                Some(column) => {
+                    columns.push(column);
+                    positions.push(position);
+                }
+                None => println!("未知の列を無視します: {}", header),
+            }
+        }
+
+        let default_timestamps: Vec<&'static str> = ["created_at", "updated_at"]
+            .into_iter()
+            .filter(|column| !columns.contains(column))
+            .collect();
+        columns.extend(&default_timestamps);
+
+        let required = REQUIRED_COLUMNS
+            .iter()
+            .map(|column| {
+                let position = headers.iter().position(|header| header == *column).unwrap();
+                (*column, position)
+            })
+            .collect();
+
+        Ok(CustomerLayout {
+            columns,
+            positions,
+            required,
+            default_timestamps: default_timestamps.len(),
+            width: headers.len(),
+        })
+    }
+
+    // CSVの1行を挿入する値に変換する（不正な行は行番号付きの理由を返す）
+    fn row(
+        &self,
+        line: usize,
+        record: csv::Result<csv::StringRecord>,
+        now: &str,
+    ) -> std::result::Result<Vec<Value>, String> {
+        let record = match record {
+            Ok(record) if record.len() == self.width => record,
+            Ok(record) => {
+                return Err(format!(
+                    "{}行目: 列数が一致しません（{}列、期待値{}列）",
+                    line,
+                    record.len(),
+                    self.width
+                ));
+            }
+            Err(err) => return Err(format!("{}行目: 読み込みに失敗しました: {}", line, err)),
+        };
+
+        if let Some((column, _)) = self
+            .required
+            .iter()
+            .find(|(_, position)| record[*position].trim().is_empty())
+        {
+            return Err(format!("{}行目: 必須の列 {} が空です", line, column));
+        }
+
+        // 空の任意項目はNULLとして挿入
+        let mut row: Vec<Value> = self
+            .positions
+            .iter()
+            .map(|&position| match record[position].trim() {
+                "" => Value::NULL,
+                value => Value::from(value),
+            })
+            .collect();
+        row.extend((0..self.default_timestamps).map(|_| Value::from(now)));
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use mysql::prelude::*;
+
+    const NOW: &str = "2024-01-01 00:00:00";
+
+    fn reader(csv: &str) -> csv::Reader<&[u8]> {
+        csv::Reader::from_reader(csv.as_bytes())
+    }
+
+    // CSVの全行を変換する（不正な行は理由を返す）
+    fn parse(csv: &str) -> (CustomerLayout, Vec<std::result::Result<Vec<Value>, String>>) {
+        let mut reader = reader(csv);
+        let layout = CustomerLayout::from_headers(reader.headers().unwrap()).unwrap();
+        let rows = reader
+            .records()
+            .enumerate()
+            .map(|(i, record)| layout.row(i + 2, record, NOW))
+            .collect();
+        (layout, rows)
+    }
+
+    #[test]
+    fn valid_csv_maps_columns_and_fills_timestamps() {
+        let (layout, rows) = parse(
+            "id,email,first_name,last_name,shipping_province_code,shipping_phone,nickname\n\
+             c-1,a@example.com,Taro,Yamada,JP-13,,taro\n",
+        );
+
+        assert_eq!(
+            layout.columns,
+            [
+                "id",
+                "email",
+                "first_name",
+                "last_name",
+                "shipping_province_code",
+                "shipping_phone",
+                "created_at",
+                "updated_at",
+            ]
+        );
+        assert_eq!(
+            rows,
+            [Ok(vec![
+                Value::from("c-1"),
+                Value::from("a@example.com"),
+                Value::from("Taro"),
+                Value::from("Yamada"),
+                Value::from("JP-13"),
+                Value::NULL,
+                Value::from(NOW),
+                Value::from(NOW),
+            ])]
+        );
+    }
+
+    #[test]
+    fn malformed_rows_are_reported_with_their_line_number() {
+        let (_, rows) = parse(
+            "id,email,first_name,last_name,shipping_province_code\n\
+             c-1,a@example.com,Taro,Yamada,JP-13\n\
+             c-2,b@example.com,Hanako\n\
+             c-3,,Jiro,Sato,JP-27\n",
+        );
+
+        assert!(rows[0].is_ok());
+        let errors: Vec<String> = rows[1..]
+            .iter()
+            .map(|row| row.clone().unwrap_err())
+            .collect();
+        assert!(errors[0].starts_with("3行目: "), "{}", errors[0]);
+        assert_eq!(errors[1], "4行目: 必須の列 email が空です");
+    }
+
+    #[test]
+    fn missing_required_columns_are_rejected_up_front() {
+        let mut reader = reader("id,email,first_name\nc-1,a@example.com,Taro\n");
+
+        let err = CustomerLayout::from_headers(reader.headers().unwrap())
+            .err()
+            .unwrap();
+
+        assert!(
+            err.to_string()
+                .contains("last_name, shipping_province_code"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（customers テーブルを含む）が必要"]
+    fn importing_existing_ids_updates_them() {
+        let pool = testing::database_pool();
+        let id = format!("import-{}", uuid::Uuid::new_v4());
+        let csv = |first_name: &str| {
+            format!(
+                "id,email,first_name,last_name,shipping_province_code\n\
+                 {0},{0}@example.com,{1},Yamada,JP-13\n\
+                 {0}-bad,missing-columns\n",
+                id, first_name
+            )
+        };
+
+        let first = import_records(&pool, reader(&csv("Taro"))).unwrap();
+        let second = import_records(&pool, reader(&csv("Hanako"))).unwrap();
+
+        assert_eq!(first, (1, 1));
+        assert_eq!(second, (1, 1));
+        let rows: Vec<(String, String)> = pool
+            .get_conn()
+            .unwrap()
+            .exec("SELECT id, first_name FROM customers WHERE id = ?", (&id,))
+            .unwrap();
+        assert_eq!(rows, [(id, "Hanako".to_string())]);
+    }
+}
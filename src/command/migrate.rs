@@ -0,0 +1,102 @@
+use mysql::prelude::*;
+use mysql::*;
+
+use crate::config;
+
+// スキーマの変更（名前, SQL）
+// 名前の順に適用するため、追加する場合は番号を続けて末尾に加える
+const MIGRATIONS: [(&str, &str); 5] = [
+    (
+        "0001_create_suggestion_cache",
+        include_str!("../../migrations/0001_create_suggestion_cache.sql"),
+    ),
+    (
+        "0002_create_item_similarity",
+        include_str!("../../migrations/0002_create_item_similarity.sql"),
+    ),
+    (
+        "0003_add_products_shipping_temperature",
+        include_str!("../../migrations/0003_add_products_shipping_temperature.sql"),
+    ),
+    (
+        "0004_add_products_category",
+        include_str!("../../migrations/0004_add_products_category.sql"),
+    ),
+    (
+        "0005_create_customer_vectors",
+        include_str!("../../migrations/0005_create_customer_vectors.sql"),
+    ),
+];
+
+// 未適用のスキーマ変更を順に適用し、適用した数を返す
+pub async fn migrate() -> Result<usize> {
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let applied = tokio::task::spawn_blocking(move || apply_migrations(&pool))
+        .await
+        .expect("ブロッキングタスクの実行に失敗")?;
+
+    println!("スキーマ変更を{}件適用しました", applied);
+    Ok(applied)
+}
+
+// 指定した接続プールのデータベースに未適用のスキーマ変更を適用し、適用した数を返す
+// 適用済みの変更は schema_migrations テーブルに記録し、2回目以降は適用しない
+pub fn apply_migrations(pool: &mysql::Pool) -> Result<usize> {
+    let mut conn = pool.get_conn()?;
+
+    conn.query_drop(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            name VARCHAR(255) NOT NULL PRIMARY KEY,
+            applied_at DATETIME NOT NULL
+        )",
+    )?;
+    let done: Vec<String> = conn.query("SELECT name FROM schema_migrations")?;
+
+    let mut applied = 0;
+    for (name, sql) in pending(&done) {
+        println!("スキーマ変更を適用します: {}", name);
+        conn.query_drop(sql)?;
+        conn.exec_drop(
+            "INSERT INTO schema_migrations (name, applied_at) VALUES (?, NOW())",
+            (name,),
+        )?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+// 適用済みの名前を除いたスキーマ変更を適用順に返す
+fn pending(done: &[String]) -> Vec<(&'static str, &'static str)> {
+    MIGRATIONS
+        .into_iter()
+        .filter(|(name, _)| !done.iter().any(|applied| applied == name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_are_listed_in_name_order() {
+        let names: Vec<&str> = MIGRATIONS.iter().map(|(name, _)| *name).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn pending_skips_applied_migrations() {
+        assert_eq!(pending(&[]).len(), MIGRATIONS.len());
+        let done: Vec<String> = MIGRATIONS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        assert!(pending(&done).is_empty());
+    }
+}
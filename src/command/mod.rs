@@ -1 +1,21 @@
+pub mod batch;
+pub mod export;
+pub mod import;
+pub mod migrate;
 pub mod seed;
+pub mod totals;
+pub mod vectors;
+pub mod verify;
+
+// CSVの読み書きエラーをmysql::Errorに変換
+fn csv_error(err: csv::Error) -> mysql::Error {
+    mysql::Error::IoError(err.into())
+}
+
+// 入力不正をmysql::Errorとして返す
+fn invalid_input(message: String) -> mysql::Error {
+    mysql::Error::IoError(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        message,
+    ))
+}
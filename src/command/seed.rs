@@ -1,109 +1,648 @@
 use rand::Rng;
+use rand::distr::Distribution;
+use rand::distr::weighted::WeightedIndex;
+use rand::seq::SliceRandom;
 use mysql::*;
 use mysql::prelude::*;
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use clap::{Args, ValueEnum};
+use std::collections::{HashMap, HashSet};
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::task::JoinSet;
 use uuid::Uuid;
 
+use super::{batch, invalid_input};
 use crate::config;
+use crate::service::cart::Temperature;
+use crate::service::region;
 
+// seed サブコマンドの引数
+#[derive(Args)]
+pub struct SeedArgs {
+    /// 生成件数（省略時は100件）
+    pub count: Option<String>,
+    /// 上限（1,000,000件）を超える生成件数を許可する
+    #[arg(long)]
+    pub force: bool,
+    /// 商品人気度のZipf分布の指数（0で一様分布）
+    #[arg(long, default_value_t = 1.0)]
+    pub zipf_exponent: f64,
+    /// 書き込みを行わず、生成内容のプレビューのみ表示する
+    #[arg(long)]
+    pub dry_run: bool,
+    /// 顧客データを並列に生成するワーカー数
+    #[arg(long, default_value_t = 1)]
+    pub workers: usize,
+    /// 注文IDを毎回ランダムに生成し、既存の注文に追加する（省略時は連番から決まるIDで上書き）
+    #[arg(long)]
+    pub fresh: bool,
+    /// 注文の通貨
+    #[arg(long, value_enum, default_value_t = Currency::Jpy)]
+    pub currency: Currency,
+    /// 注文に適用する税率（0〜1）
+    #[arg(long, default_value_t = 0.1)]
+    pub tax_rate: f64,
+    /// 定期購入の注文にする割合（0〜1）
+    #[arg(long, default_value_t = 0.0)]
+    pub subscription_ratio: f64,
+    /// 顧客に紐づかないゲスト購入の注文にする割合（0〜1）
+    #[arg(long, default_value_t = 0.0)]
+    pub guest_ratio: f64,
+    /// 1注文あたりの商品数の最小値
+    #[arg(long, default_value_t = 2)]
+    pub items_min: usize,
+    /// 1注文あたりの商品数の最大値
+    #[arg(long, default_value_t = 10)]
+    pub items_max: usize,
+    /// 注文商品1件あたりの数量の最小値
+    #[arg(long, default_value_t = 2)]
+    pub qty_min: u32,
+    /// 注文商品1件あたりの数量の最大値
+    #[arg(long, default_value_t = 32)]
+    pub qty_max: u32,
+    /// 顧客の都道府県の重みを記したJSONファイル（例: {"JP-13": 10, "JP-27": 5}、省略時は一様）
+    #[arg(long)]
+    pub province_weights: Option<PathBuf>,
+    /// 顧客のメールアドレスのドメイン（メールアドレスは「顧客ID@ドメイン」のため顧客ごとに一意になる）
+    #[arg(long, default_value = "example.com")]
+    pub email_domain: String,
+    /// 生成する顧客と同じメールアドレスがすでに登録されている場合の扱い
+    #[arg(long, value_enum, default_value_t = OnConflict::Error)]
+    pub on_conflict: OnConflict,
+    /// 進捗を表示しない
+    #[arg(long)]
+    pub quiet: bool,
+    /// 進捗を表示する間隔（件数）
+    #[arg(long, default_value_t = DEFAULT_PROGRESS_EVERY)]
+    pub progress_every: usize,
+}
+
+// 既存の顧客とメールアドレスが重複した場合の扱い
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OnConflict {
+    /// 何も挿入せずにエラーで終了する
+    Error,
+    /// 重複する顧客だけを飛ばして残りを挿入する
+    Skip,
+}
+
+// 生成する顧客の内容
+pub struct CustomerOptions {
+    pub email_domain: String,
+    pub on_conflict: OnConflict,
+    pub progress: Progress,
+}
+
+// 進捗を表示する間隔の既定値
+const DEFAULT_PROGRESS_EVERY: usize = 10_000;
+
+// 生成の進捗の表示間隔（tracing の info レベルで出力するため、ログレベルでも抑止できる）
+#[derive(Clone, Copy)]
+pub struct Progress {
+    // 表示する間隔（None の場合は表示しない）
+    every: Option<usize>,
+}
+
+impl Progress {
+    pub fn new(quiet: bool, every: usize) -> Self {
+        Progress { every: (!quiet && every > 0).then_some(every) }
+    }
+    
+    // done 件を生成した時点で進捗を表示するか
+    pub fn should_report(&self, done: usize) -> bool {
+        self.every.is_some_and(|every| done > 0 && done.is_multiple_of(every))
+    }
+}
+
+// 生成件数の既定値
+const DEFAULT_SEED_COUNT: usize = 100;
+
+// --force なしで生成できる件数の上限
+const MAX_SEED_COUNT: usize = 1_000_000;
+
+// 生成件数の引数を検証する（数値でない・0・上限超過（--force なし）はエラー）
+pub fn parse_count(count: Option<&str>, force: bool) -> std::result::Result<usize, String> {
+    let Some(count) = count else {
+        return Ok(DEFAULT_SEED_COUNT);
+    };
+    let count: usize = count
+        .trim()
+        .parse()
+        .map_err(|_| format!("生成件数には1以上の整数を指定してください（指定: {}）", count))?;
+    if count == 0 {
+        return Err("生成件数には1以上の整数を指定してください（指定: 0）".to_string());
+    }
+    if count > MAX_SEED_COUNT && !force {
+        return Err(format!(
+            "生成件数が上限の{}件を超えています（指定: {}件）。本当に生成する場合は --force を指定してください",
+            MAX_SEED_COUNT, count
+        ));
+    }
+    Ok(count)
+}
 
-pub async fn generate_customers(count: usize) -> Result<()> {
-    println!("{}件のユーザーデータを生成します", count);
+// --{option}-min と --{option}-max の範囲を検証する（最小値は1以上かつ最大値以下）
+pub fn parse_range<T: PartialOrd + From<u8>>(min: T, max: T, option: &str) -> std::result::Result<RangeInclusive<T>, String> {
+    if min < T::from(1) || min > max {
+        return Err(format!("--{}-min は1以上かつ --{}-max 以下にしてください", option, option));
+    }
+    Ok(min..=max)
+}
+
+// 注文に使用できる通貨
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Currency {
+    Jpy,
+    Usd,
+    Eur,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Jpy => "jpy",
+            Currency::Usd => "usd",
+            Currency::Eur => "eur",
+        }
+    }
+    
+    // 通貨コード（orders.currency の値）から変換する
+    pub fn from_code(code: &str) -> Option<Currency> {
+        match code.to_ascii_lowercase().as_str() {
+            "jpy" => Some(Currency::Jpy),
+            "usd" => Some(Currency::Usd),
+            "eur" => Some(Currency::Eur),
+            _ => None,
+        }
+    }
+    
+    // 金額を通貨の最小単位に丸める（円は整数、ドル・ユーロは小数2桁）
+    pub fn round(&self, amount: f64) -> f64 {
+        match self {
+            Currency::Jpy => amount.round(),
+            Currency::Usd | Currency::Eur => (amount * 100.0).round() / 100.0,
+        }
+    }
+}
+
+// 生成する注文の内容
+pub struct OrderOptions {
+    pub currency: Currency,
+    pub tax_rate: f64,
+    // 1注文あたりの商品数
+    pub items: RangeInclusive<usize>,
+    // 注文商品1件あたりの数量
+    pub quantity: RangeInclusive<u32>,
+    // 定期購入の注文にする割合（0〜1）
+    pub subscription_ratio: f64,
+    // ゲスト購入（customer_id が NULL）の注文にする割合（0〜1）
+    pub guest_ratio: f64,
+    // 注文のメールアドレスのドメイン（顧客の生成に使ったものと同じにする）
+    pub email_domain: String,
+    pub progress: Progress,
+}
+
+impl OrderOptions {
+    // 小計（税抜）から税額と合計金額を求める（どちらも通貨の最小単位に丸める）
+    pub fn totals(&self, subtotal: f64) -> (f64, f64) {
+        let total_tax = self.currency.round(subtotal * self.tax_rate);
+        (total_tax, self.currency.round(subtotal + total_tax))
+    }
+}
+
+// 注文のメールアドレス（顧客の注文は顧客と同じアドレス、ゲスト購入は注文ごとのアドレス）
+pub fn order_email(customer_id: Option<&str>, order_id: &str, email_domain: &str) -> String {
+    match customer_id {
+        Some(customer_id) => customer_email(customer_id, email_domain),
+        None => customer_email(&format!("guest-{}", order_id), email_domain),
+    }
+}
+
+// 注文の顧客を選ぶ（guest_ratio の確率、または顧客がいない場合はゲスト購入とし、None を返す）
+pub fn pick_order_customer<'a>(customer_ids: &'a [String], guest_ratio: f64, rng: &mut impl Rng) -> Option<&'a String> {
+    if customer_ids.is_empty() || rng.random_bool(guest_ratio) {
+        return None;
+    }
+    Some(&customer_ids[rng.random_range(0..customer_ids.len())])
+}
+
+// 定期購入のプラン
+pub struct SubscriptionPlan {
+    pub name: &'static str,
+    // 定期購入の割引率（%）
+    pub discount_rate: u32,
+}
+
+// 定期購入の注文に割り当てるプラン（お届け間隔が短いほど割引率が高い）
+pub const SUBSCRIPTION_PLANS: [SubscriptionPlan; 3] = [
+    SubscriptionPlan { name: "毎月お届けプラン", discount_rate: 10 },
+    SubscriptionPlan { name: "隔月お届けプラン", discount_rate: 5 },
+    SubscriptionPlan { name: "3ヶ月ごとお届けプラン", discount_rate: 3 },
+];
+
+// ratio の確率で定期購入とし、そのプランを選ぶ（定期購入でなければ None）
+pub fn pick_subscription_plan(ratio: f64, rng: &mut impl Rng) -> Option<&'static SubscriptionPlan> {
+    if !rng.random_bool(ratio) {
+        return None;
+    }
+    let index = rng.random_range(0..SUBSCRIPTION_PLANS.len());
+    Some(&SUBSCRIPTION_PLANS[index])
+}
+
+// 注文IDを連番から決定的に生成するための名前空間
+const ORDER_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6f1d2c4e_8a3b_4f5e_9c7d_1e2f3a4b5c6d);
+
+// 注文IDを生成する
+// fresh の場合はランダム、そうでなければ連番から同じIDを生成し再実行時に上書きする
+pub fn order_id(seq_num: usize, fresh: bool) -> String {
+    if fresh {
+        Uuid::new_v4().to_string()
+    } else {
+        Uuid::new_v5(&ORDER_ID_NAMESPACE, format!("order-{}", seq_num).as_bytes()).to_string()
+    }
+}
+
+// シード用のbcryptコスト（生成速度を優先して最小値を使用）
+const SEED_BCRYPT_COST: u32 = 4;
+
+// シード顧客の平文パスワードの形式
+const CUSTOMER_PASSWORD_PATTERN: &str = "password-{連番}";
+
+// 連番から顧客の平文パスワードを導出
+pub fn customer_password(seq_num: usize) -> String {
+    format!("password-{}", seq_num)
+}
+
+// 生成する顧客1件分のデータ
+pub struct CustomerRow {
+    pub id: String,
+    pub email: String,
+    pub password_hash: String,
+    pub is_infomercial: u8,
+    pub accepts_marketing: u8,
+    pub first_name: &'static str,
+    pub last_name: &'static str,
+    pub shipping_province_code: String,
+}
+
+// 顧客の都道府県の分布を作成する
+// ファイルを指定しない場合は一様分布とし、指定した場合は記載のない都道府県の重みを1とする
+pub fn province_distribution(weights_file: Option<&Path>) -> std::result::Result<WeightedIndex<f64>, String> {
+    let mut weights = vec![1.0; region::PROVINCE_COUNT];
+    
+    if let Some(path) = weights_file {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("{}を読み込めません: {}", path.display(), err))?;
+        let configured: HashMap<String, f64> = serde_json::from_str(&content)
+            .map_err(|err| format!("{}の形式が不正です: {}", path.display(), err))?;
+        
+        for (province_code, weight) in configured {
+            let province_num = region::province_number(&province_code)
+                .ok_or_else(|| format!("不正な都道府県コードです: {}", province_code))?;
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!("{}の重みには0以上の数値を指定してください", province_code));
+            }
+            weights[province_num as usize - 1] = weight;
+        }
+    }
+    
+    WeightedIndex::new(weights).map_err(|err| format!("都道府県の分布を作成できません: {}", err))
+}
+
+// 顧客IDを連番から決定的に生成するための名前空間
+const CUSTOMER_ID_NAMESPACE: Uuid = Uuid::from_u128(0x2a7c9e14_3b6d_4c8f_b5e1_7d9f0a2c4e68);
+
+// 連番から顧客IDを生成（同じ連番からは常に同じIDになり、再実行時は同じ顧客を上書きする）
+pub fn customer_id(seq_num: usize) -> String {
+    Uuid::new_v5(&CUSTOMER_ID_NAMESPACE, format!("customer-{}", seq_num).as_bytes()).to_string()
+}
+
+// 顧客IDからメールアドレスを生成（顧客IDが一意のため、メールアドレスも一意になる）
+pub fn customer_email(id: &str, email_domain: &str) -> String {
+    format!("{}@{}", id, email_domain)
+}
+
+// 連番から顧客1件分のデータを生成
+// 都道府県は指定した分布に従って選ぶ
+pub fn build_customer(seq_num: usize, provinces: &WeightedIndex<f64>, email_domain: &str) -> CustomerRow {
+    let id = customer_id(seq_num);
+    let email = customer_email(&id, email_domain);
+    // 連番から平文パスワードを導出し、顧客ごとにハッシュ化する
+    let password_hash = bcrypt::hash(customer_password(seq_num), SEED_BCRYPT_COST)
+        .expect("パスワードのハッシュ化に失敗しました");
+    let is_infomercial: u8 = rand::rng().random_range(0..=1);
+    let accepts_marketing: u8 = rand::rng().random_range(0..=1);
+    let province_num = provinces.sample(&mut rand::rng()) + 1;
+    let shipping_province_code = format!("JP-{:02}", province_num);
+    
+    // 日本の名前をランダムに生成
+    let first_names = ["太郎", "次郎", "三郎", "四郎", "五郎", "花子", "梅子", "桃子", "和子", "幸子"];
+    let last_names = ["佐藤", "鈴木", "高橋", "田中", "伊藤", "渡辺", "山本", "中村", "小林", "加藤"];
     
+    let first_name = first_names[rand::rng().random_range(0..first_names.len())];
+    let last_name = last_names[rand::rng().random_range(0..last_names.len())];
+
+    CustomerRow {
+        id,
+        email,
+        password_hash,
+        is_infomercial,
+        accepts_marketing,
+        first_name,
+        last_name,
+        shipping_province_code,
+    }
+}
+
+// 注文日時を生成する期間（2020年1月1日から現在まで）
+pub fn order_date_range() -> (NaiveDateTime, NaiveDateTime) {
+    let start_date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let end_date = Utc::now().naive_utc();
+    (start_date, end_date)
+}
+
+pub async fn generate_customers(count: usize, workers: usize, provinces: WeightedIndex<f64>, options: CustomerOptions) -> Result<()> {
     // データベース接続設定
-    let database_url = config::database::get_database_url();
-    let opts = mysql::Opts::from_url(&database_url).expect("不正なデータベースURL");
+    let opts = config::database::get_database_opts();
     // Optsオブジェクトを使ってプールを作成
     let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+    
+    seed_customers(pool, count, workers, provinces, options).await
+}
 
+// 指定した接続プールに連番 1..=count の顧客を生成する
+pub async fn seed_customers(pool: mysql::Pool, count: usize, workers: usize, provinces: WeightedIndex<f64>, options: CustomerOptions) -> Result<()> {
+    // ワーカー数は1以上、生成件数以下に制限
+    let workers = workers.clamp(1, count.max(1));
+    println!("{}件のユーザーデータを{}並列で生成します", count, workers);
     
-    // 固定のパスワードハッシュ
-    let password_hash = "$2y$10$Ik1i3qUFpBICQyg1ZVGZ3eRB.YiPt7oXsfS7ckNhPDwFMkl4BIDbC";
+    // 挿入前に既存のメールアドレスとの重複を確認する
+    // 一意制約の違反でワーカーのトランザクション全体が失敗しないよう、重複は挿入前に扱いを決める
+    // 同じIDの顧客（前回のシードで生成したもの）は重複とせず上書きする
+    let email_domain = Arc::new(options.email_domain);
+    let existing = {
+        let pool = pool.clone();
+        let email_domain = Arc::clone(&email_domain);
+        tokio::task::spawn_blocking(move || find_conflicting_emails(&pool, count, &email_domain))
+            .await
+            .expect("ブロッキングタスクの実行に失敗")?
+    };
+    if !existing.is_empty() {
+        match options.on_conflict {
+            OnConflict::Error => {
+                let mut examples: Vec<&String> = existing.iter().collect();
+                examples.sort();
+                return Err(invalid_input(format!(
+                    "{}件のメールアドレスがすでに登録されています（例: {}）。--on-conflict skip で重複する顧客を飛ばせます",
+                    existing.len(),
+                    examples[0]
+                )));
+            }
+            OnConflict::Skip => println!("メールアドレスが登録済みの{}件の顧客を飛ばします", existing.len()),
+        }
+    }
+    let existing = Arc::new(existing);
+    
+    // 全ワーカー合計の生成件数と進捗の表示
+    let progress = Arc::new(CustomerProgress { done: AtomicUsize::new(0), total: count, progress: options.progress });
+    
+    // 連番の範囲をワーカーごとに重ならないよう分割し、それぞれ別の接続・トランザクションで挿入
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    // いずれかのワーカーが失敗しても、他のワーカーがコミット済みの分は残る
+    let mut join_set = JoinSet::new();
+    for seq_range in worker_ranges(count, workers) {
+        let pool = pool.clone();
+        let progress = Arc::clone(&progress);
+        let provinces = provinces.clone();
+        let email_domain = Arc::clone(&email_domain);
+        let existing = Arc::clone(&existing);
+        join_set.spawn_blocking(move || insert_customers(&pool, seq_range, &provinces, &email_domain, &existing, &progress));
+    }
     
+    while let Some(result) = join_set.join_next().await {
+        result.expect("ブロッキングタスクの実行に失敗")?;
+    }
+    
+    println!("ユーザーデータの生成が完了しました");
+    println!(
+        "ログイン用パスワードは連番に対応した \"{}\" の形式です（例: {}）",
+        CUSTOMER_PASSWORD_PATTERN,
+        customer_password(1)
+    );
+    
+    Ok(())
+}
+
+// 連番 1..=count をワーカーごとの重ならない範囲に分割する（件数の差は最大1件）
+fn worker_ranges(count: usize, workers: usize) -> Vec<Range<usize>> {
+    (0..workers)
+        .map(|worker| (count * worker / workers + 1)..(count * (worker + 1) / workers + 1))
+        .collect()
+}
+
+// 生成する顧客（連番 1..=count）のメールアドレスのうち、別のIDの顧客がすでに使っているものを取得
+fn find_conflicting_emails(pool: &mysql::Pool, count: usize, email_domain: &str) -> Result<HashSet<String>> {
+    let mut conn = pool.get_conn()?;
+    let emails: Vec<String> = (1..=count)
+        .map(|seq_num| customer_email(&customer_id(seq_num), email_domain))
+        .collect();
+    
+    let mut registered = Vec::new();
+    for chunk in emails.chunks(batch::BATCH_SIZE) {
+        let query = format!(
+            "SELECT id, email FROM customers WHERE email IN ({})",
+            vec!["?"; chunk.len()].join(", ")
+        );
+        let found: Vec<(String, String)> = conn.exec(query, chunk.to_vec())?;
+        registered.extend(found);
+    }
+    Ok(conflicting_emails(registered, email_domain))
+}
+
+// 登録済みの顧客（ID, メールアドレス）のうち、生成する顧客と同じIDでないもののメールアドレスを返す
+// 生成する顧客のメールアドレスはIDから決まるため、同じIDの顧客であればメールアドレスも一致する
+pub fn conflicting_emails(registered: Vec<(String, String)>, email_domain: &str) -> HashSet<String> {
+    registered
+        .into_iter()
+        .filter(|(id, email)| customer_email(id, email_domain) != *email)
+        .map(|(_, email)| email)
+        .collect()
+}
+
+// シード顧客（連番 1..=count）のうち、登録されている顧客のIDを連番の順に取得
+fn find_seeded_customers(conn: &mut PooledConn, count: usize) -> Result<Vec<String>> {
+    let ids: Vec<String> = (1..=count).map(customer_id).collect();
+    
+    let mut found = HashSet::new();
+    for chunk in ids.chunks(batch::BATCH_SIZE) {
+        let query = format!(
+            "SELECT id FROM customers WHERE id IN ({})",
+            vec!["?"; chunk.len()].join(", ")
+        );
+        let rows: Vec<String> = conn.exec(query, chunk.to_vec())?;
+        found.extend(rows);
+    }
+    Ok(ids.into_iter().filter(|id| found.contains(id)).collect())
+}
+
+// 全ワーカー合計の顧客の生成件数と進捗の表示間隔
+struct CustomerProgress {
+    done: AtomicUsize,
+    total: usize,
+    progress: Progress,
+}
+
+impl CustomerProgress {
+    // 1件の生成を記録し、表示する間隔に達していれば進捗を表示する
+    fn increment(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.progress.should_report(done) {
+            tracing::info!("{}/{}件 生成完了", done, self.total);
+        }
+    }
+}
+
+// 指定した連番の範囲の顧客を1つのトランザクションで挿入
+// メールアドレスが existing に含まれる顧客は挿入しない
+// 同じIDの顧客（前回のシードで生成したもの）がすでにあれば上書きし、再実行しても顧客は増えない
+fn insert_customers(pool: &mysql::Pool, seq_range: Range<usize>, provinces: &WeightedIndex<f64>, email_domain: &str, existing: &HashSet<String>, progress: &CustomerProgress) -> Result<()> {
     // 固定値
     let shipping_address = "1-12-123";
     let shipping_phone = "03-1234-5678";
     
-    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
-    tokio::task::spawn_blocking(move || {
-        let mut conn = pool.get_conn()?;
-        let mut tx = conn.start_transaction(TxOpts::default())?;
-        
-        for i in 0..count {
-            // 進捗表示（10,000件ごと）
-            if i % 10000 == 0 && i > 0 {
-                println!("{}/{}件 生成完了", i, count);
-            }
-            
-            let seq_num = i + 1;
-            let id = format!("00000000-0000-4000-0000-{:012}", seq_num);
-            let email = format!("{}@example.com", id);
-            let is_infomercial: u8 = rand::rng().random_range(0..=1);
-            let accepts_marketing: u8 = rand::rng().random_range(0..=1);
-            let province_num = rand::rng().random_range(1..=47);
-            let shipping_province_code = format!("JP-{:02}", province_num);
-            
-            // 日本の名前をランダムに生成
-            let first_names = ["太郎", "次郎", "三郎", "四郎", "五郎", "花子", "梅子", "桃子", "和子", "幸子"];
-            let last_names = ["佐藤", "鈴木", "高橋", "田中", "伊藤", "渡辺", "山本", "中村", "小林", "加藤"];
-            
-            let first_name = first_names[rand::rng().random_range(0..first_names.len())];
-            let last_name = last_names[rand::rng().random_range(0..last_names.len())];
-            
-            // 作成日時と更新日時
-            let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-            
-            // SQLクエリを実行
-            tx.exec_drop(
-                "INSERT INTO customers (id, email, is_infomercial, password, accepts_marketing, 
-                first_name, last_name, shipping_province_code, shipping_address_line1, shipping_phone, created_at, updated_at) 
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-                (&id, &email, is_infomercial, password_hash, accepts_marketing, 
-                 first_name, last_name, shipping_province_code, shipping_address, shipping_phone, &now, &now),
-            )?;
+    let mut conn = pool.get_conn()?;
+    let mut tx = conn.start_transaction(TxOpts::default())?;
+    
+    for seq_num in seq_range {
+        if existing.contains(&customer_email(&customer_id(seq_num), email_domain)) {
+            continue;
         }
+        let customer = build_customer(seq_num, provinces, email_domain);
         
-        tx.commit()?;
-        println!("ユーザーデータの生成が完了しました");
+        // 作成日時と更新日時
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
         
-        Ok::<(), mysql::Error>(())
-    })
-    .await
-    .expect("ブロッキングタスクの実行に失敗")?;
+        // SQLクエリを実行
+        tx.exec_drop(
+            "INSERT INTO customers (id, email, is_infomercial, password, accepts_marketing, 
+            first_name, last_name, shipping_province_code, shipping_address_line1, shipping_phone, created_at, updated_at) 
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE email = VALUES(email), is_infomercial = VALUES(is_infomercial), password = VALUES(password), 
+            accepts_marketing = VALUES(accepts_marketing), first_name = VALUES(first_name), last_name = VALUES(last_name), 
+            shipping_province_code = VALUES(shipping_province_code), updated_at = VALUES(updated_at)",
+            (&customer.id, &customer.email, customer.is_infomercial, &customer.password_hash, customer.accepts_marketing, 
+             customer.first_name, customer.last_name, &customer.shipping_province_code, shipping_address, shipping_phone, &now, &now),
+        )?;
+        
+        // 進捗表示（全ワーカー合計で指定した件数ごと）
+        progress.increment();
+    }
+    
+    tx.commit()
+}
+
+// 商品の人気度をZipf分布で表す
+// 順位kの商品が選ばれる重みは 1 / k^exponent（exponentが0なら一様分布）
+pub fn zipf_distribution(product_count: usize, exponent: f64) -> WeightedIndex<f64> {
+    let weights = (1..=product_count).map(|rank| 1.0 / (rank as f64).powf(exponent));
+    WeightedIndex::new(weights).expect("商品の出現分布の作成に失敗しました")
+}
+
+// 商品に割り当てる配送温度帯の重み（Normal, Cold, Frozen の順）
+const TEMPERATURE_WEIGHTS: [u32; 3] = [70, 20, 10];
+
+// 商品に配送温度帯をランダムに割り当てる（shipping_temperature 列は migrate コマンドで追加しておく）
+fn assign_product_temperatures(conn: &mut PooledConn, products: &[(String, String)]) -> Result<(), mysql::Error> {
+    // 温度帯ごとに商品をまとめて更新する
+    let distribution = WeightedIndex::new(TEMPERATURE_WEIGHTS).expect("温度帯の分布の作成に失敗しました");
+    let mut rng = rand::rng();
+    let mut variant_ids: Vec<Vec<&str>> = vec![Vec::new(); Temperature::ALL.len()];
+    for (_, variant_id) in products {
+        variant_ids[distribution.sample(&mut rng)].push(variant_id);
+    }
+    
+    for (temperature, ids) in Temperature::ALL.iter().zip(variant_ids) {
+        for chunk in ids.chunks(batch::BATCH_SIZE) {
+            let query = format!(
+                "UPDATE products SET shipping_temperature = ? WHERE variant_id IN ({})",
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut params: Vec<Value> = vec![temperature.as_str().into()];
+            params.extend(chunk.iter().map(|id| Value::from(*id)));
+            conn.exec_drop(query, params)?;
+        }
+        println!("配送温度帯 {} の商品: {}件", temperature.as_str(), ids.len());
+    }
     
     Ok(())
 }
 
+// 商品に割り当てるカテゴリとその重み
+const PRODUCT_CATEGORIES: [(&str, u32); 5] = [
+    ("food", 40),
+    ("beverage", 25),
+    ("daily_goods", 15),
+    ("alcohol", 10),
+    ("cosmetics", 10),
+];
 
-pub async fn generate_orders(count: usize) -> Result<()> {
-    println!("{}件の注文データを生成します", count);
+// 商品にカテゴリをランダムに割り当てる（category 列は migrate コマンドで追加しておく）
+fn assign_product_categories(conn: &mut PooledConn, products: &[(String, String)]) -> Result<(), mysql::Error> {
+    // カテゴリごとに商品をまとめて更新する
+    let distribution = WeightedIndex::new(PRODUCT_CATEGORIES.iter().map(|(_, weight)| *weight))
+        .expect("カテゴリの分布の作成に失敗しました");
+    let mut rng = rand::rng();
+    let mut variant_ids: Vec<Vec<&str>> = vec![Vec::new(); PRODUCT_CATEGORIES.len()];
+    for (_, variant_id) in products {
+        variant_ids[distribution.sample(&mut rng)].push(variant_id);
+    }
+    
+    for ((category, _), ids) in PRODUCT_CATEGORIES.iter().zip(variant_ids) {
+        for chunk in ids.chunks(batch::BATCH_SIZE) {
+            let query = format!(
+                "UPDATE products SET category = ? WHERE variant_id IN ({})",
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut params: Vec<Value> = vec![(*category).into()];
+            params.extend(chunk.iter().map(|id| Value::from(*id)));
+            conn.exec_drop(query, params)?;
+        }
+        println!("カテゴリ {} の商品: {}件", category, ids.len());
+    }
     
+    Ok(())
+}
+
+pub async fn generate_orders(count: usize, zipf_exponent: f64, options: OrderOptions, fresh: bool) -> Result<()> {
     // データベース接続設定
-    let database_url = config::database::get_database_url();
-    let opts = mysql::Opts::from_url(&database_url).expect("不正なデータベースURL");
+    let opts = config::database::get_database_opts();
     // Optsオブジェクトを使ってプールを作成
     let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
     
+    seed_orders(pool, count, zipf_exponent, options, fresh).await
+}
+
+// 指定した接続プールに count 件の注文を生成する
+// 注文の顧客は、同じ件数で生成したシード顧客（連番 1..=count）から選ぶ
+pub async fn seed_orders(pool: mysql::Pool, count: usize, zipf_exponent: f64, options: OrderOptions, fresh: bool) -> Result<()> {
+    println!("{}件の注文データを生成します", count);
+    
     // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
     tokio::task::spawn_blocking(move || {
         let mut conn = pool.get_conn()?;
         
         // 顧客IDを取得
         println!("顧客データを取得中...");
-        let customer_ids: Vec<String> = conn.query("SELECT id, email from customers where id like '00000%'")?
-        .into_iter()
-        .map(|row| {
-            // Row型から(String, String)型にマッピング
-            let (id, _email): (String, String) = mysql::from_row(row);
-            id  // idだけを返す
-        })
-        .collect();
+        let customer_ids = find_seeded_customers(&mut conn, count)?;
         
         println!("{}件の顧客データを取得しました", customer_ids.len());
         
         // 商品情報を取得
         println!("商品データを取得中...");
-        let products: Vec<(String, String)> = conn.query("SELECT id, variant_id FROM products WHERE is_suspension = false")?
+        let mut products: Vec<(String, String)> = conn.query("SELECT id, variant_id FROM products WHERE is_suspension = false")?
             .into_iter()
             .map(|row| {
                 let (id, variant_id): (String, String) = mysql::from_row(row);
@@ -113,25 +652,43 @@ pub async fn generate_orders(count: usize) -> Result<()> {
         
         println!("{}件の商品データを取得しました", products.len());
         
+        // 顧客・商品がなければ注文を作れないため、先に用意するよう案内する（すべてゲスト購入なら顧客は不要）
+        if customer_ids.is_empty() && options.guest_ratio < 1.0 {
+            return Err(invalid_input("シード顧客が存在しません。先に顧客データを生成してください".to_string()));
+        }
+        if products.is_empty() {
+            return Err(invalid_input("販売中の商品が存在しません。先に商品データを登録してください".to_string()));
+        }
+        
+        // 温度帯で絞り込めるよう、商品に配送温度帯を割り当てる
+        assign_product_temperatures(&mut conn, &products)?;
+        
+        // カテゴリで推薦から除外できるよう、商品にカテゴリを割り当てる
+        assign_product_categories(&mut conn, &products)?;
+        
+        // 人気商品の顔ぶれを実行ごとに変えるため、並びをシャッフルしてから順位を割り当てる
+        products.shuffle(&mut rand::rng());
+        let product_distribution = zipf_distribution(products.len(), zipf_exponent);
+        
         // トランザクション開始
         let mut tx = conn.start_transaction(TxOpts::default())?;
         
         // 2020年1月1日から現在までの期間を設定
-        let start_date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
-        let end_date = Utc::now().naive_utc();
+        let (start_date, end_date) = order_date_range();
         let date_range = (end_date - start_date).num_seconds() as u64;
         
         let mut order_ids = Vec::with_capacity(count);
+        // 注文ごとの定期購入かどうか（注文商品の is_subscription にも反映する）
+        let mut subscriptions = Vec::with_capacity(count);
         
         for i in 0..count {
-            // 進捗表示（10,000件ごと）
-            if i % 10000 == 0 && i > 0 {
-                println!("{}/{}件 注文データ生成完了", i, count);
+            // 進捗表示（指定した件数ごと）
+            if options.progress.should_report(i) {
+                tracing::info!("{}/{}件 注文データ生成完了", i, count);
             }
             
-            // ランダムな顧客を選択
-            let customer_index = rand::rng().random_range(0..customer_ids.len());
-            let customer_id = &customer_ids[customer_index];
+            // ランダムな顧客を選択（ゲスト購入の場合は顧客なし）
+            let customer_id = pick_order_customer(&customer_ids, options.guest_ratio, &mut rand::rng());
             
             // ランダムな日付を生成（2020年から現在まで）
             let random_seconds = rand::rng().random_range(0..date_range);
@@ -142,16 +699,16 @@ pub async fn generate_orders(count: usize) -> Result<()> {
             let delivery_date = (created_at.date() + Duration::days(7)).format("%Y-%m-%d").to_string();
             
             // 注文IDを生成
-            let order_id = Uuid::new_v4().to_string();
+            let order_id = order_id(i, fresh);
             order_ids.push(order_id.clone());
             
-            // メールアドレスを取得（顧客IDに紐づく）
-            let email = format!("{}@example.com", customer_id); // 簡易的に生成
+            // メールアドレスを取得（顧客IDに紐づく、ゲスト購入は注文ごとのアドレス）
+            let email = order_email(customer_id.map(String::as_str), &order_id, &options.email_domain);
+            // ゲスト購入の customer_id は NULL
+            let customer_id = customer_id.map_or("NULL".to_string(), |customer_id| format!("'{}'", customer_id));
             
             // 配送先住所情報
-            let shipping_address = format!(
-                r#"{{"zip": "100-0001", "city": "千代田区", "phone": "09012345678", "province": "JP-13", "last_name": "テスト", "first_name": "ユーザー", "address_line1": "1-1-1", "address_line2": "テスト住所", "converted_province": "東京都"}}"#
-            );
+            let shipping_address = r#"{"zip": "100-0001", "city": "千代田区", "phone": "09012345678", "province": "JP-13", "last_name": "テスト", "first_name": "ユーザー", "address_line1": "1-1-1", "address_line2": "テスト住所", "converted_province": "東京都"}"#;
             
             // 支払い方法は固定で"credit"
             let payment_method = "credit";
@@ -159,13 +716,14 @@ pub async fn generate_orders(count: usize) -> Result<()> {
             // 配送温度は固定で"Normal"
             let shipping_temperature = "Normal";
             
-            // 定期購入はfalse
             let note = "";
             
-            // 定期購入関連の値も0に設定
-            let subscription_discount_rate = 0;
-            let discount_plan_name = "";
-            let discount_plan_rate = 0;
+            // 指定した割合の注文を定期購入とし、プランの割引率を設定（定期購入でなければ0）
+            let plan = pick_subscription_plan(options.subscription_ratio, &mut rand::rng());
+            subscriptions.push(plan.is_some());
+            let subscription_discount_rate = plan.map_or(0, |plan| plan.discount_rate);
+            let discount_plan_name = plan.map_or("", |plan| plan.name);
+            let discount_plan_rate = subscription_discount_rate;
             
             // 日時フォーマット
             let created_at_str = created_at.format("%Y-%m-%d %H:%M:%S").to_string();
@@ -179,15 +737,20 @@ pub async fn generate_orders(count: usize) -> Result<()> {
                 subscription_discount_rate, discount_plan_name, discount_plan_rate, shipping_temperature, 
                 is_non_face_to_face_receipt, paid_points_discount, free_points_discount, is_fast_delivery, 
                 delivery_location_code) 
-                VALUES ('{}', '{}', '{}', '{}', 'free', '{}', 
-                '{}', 0, 0, 0, 'jpy', 0, 
+                VALUES ('{}', '{}', {}, '{}', 'free', '{}', 
+                '{}', 0, 0, 0, '{}', 0, 
                 0, '{}', 'paid', 'null', 
                 '{}', '{}', '{}', 0, 0, 
                 {}, '{}', {}, '{}', 
                 0, 0, 0, 0, 
-                '00')",
+                '00')
+                ON DUPLICATE KEY UPDATE email = VALUES(email), customer_id = VALUES(customer_id), 
+                delivery_date = VALUES(delivery_date), currency = VALUES(currency), 
+                processed_at = VALUES(processed_at), created_at = VALUES(created_at), updated_at = VALUES(updated_at), 
+                subscription_discount_rate = VALUES(subscription_discount_rate), discount_plan_name = VALUES(discount_plan_name), 
+                discount_plan_rate = VALUES(discount_plan_rate)",
                 order_id, email, customer_id, delivery_date, note,
-                payment_method, shipping_address,
+                payment_method, options.currency.code(), shipping_address,
                 created_at_str, created_at_str, created_at_str,
                 subscription_discount_rate, discount_plan_name, discount_plan_rate, shipping_temperature
             );            
@@ -200,7 +763,7 @@ pub async fn generate_orders(count: usize) -> Result<()> {
         println!("注文データの生成が完了しました。注文商品データを生成します...");
         
         // 注文商品データを生成
-        generate_order_products(&mut tx, &order_ids, &products)?;
+        generate_order_products(&mut tx, &order_ids, &subscriptions, &products, &product_distribution, &options, !fresh)?;
         
         tx.commit()?;
         println!("注文データと注文商品データの生成が完了しました");
@@ -213,33 +776,42 @@ pub async fn generate_orders(count: usize) -> Result<()> {
     Ok(())
 }
 
-fn generate_order_products(tx: &mut Transaction, order_ids: &[String], products: &[(String, String)]) -> Result<(), mysql::Error> {
+// 注文に含める商品を選ぶ（個数は指定範囲からランダムに決める）
+pub fn pick_order_products<'a>(products: &'a [(String, String)], product_distribution: &WeightedIndex<f64>, items: &RangeInclusive<usize>, rng: &mut impl Rng) -> Vec<&'a (String, String)> {
+    let product_count = rng.random_range(items.clone());
+    // 人気の偏りを再現するため、Zipf分布に従って商品を選択
+    (0..product_count).map(|_| &products[product_distribution.sample(rng)]).collect()
+}
+
+fn generate_order_products(tx: &mut Transaction, order_ids: &[String], subscriptions: &[bool], products: &[(String, String)], product_distribution: &WeightedIndex<f64>, options: &OrderOptions, replace_existing: bool) -> Result<(), mysql::Error> {
     for (i, order_id) in order_ids.iter().enumerate() {
-        // 進捗表示（10,000件ごと）
-        if i % 10000 == 0 && i > 0 {
-            println!("{}/{}件 注文商品データ生成完了", i, order_ids.len());
+        // 進捗表示（指定した件数ごと）
+        if options.progress.should_report(i) {
+            tracing::info!("{}/{}件 注文商品データ生成完了", i, order_ids.len());
         }
         
-        // 各注文に2〜10個の商品を追加
-        let product_count = rand::rng().random_range(2..=10);
+        // 同じIDで再生成する場合は、前回の注文商品を削除してから作り直す
+        if replace_existing {
+            tx.exec_drop("DELETE FROM order_products WHERE order_id = ?", (order_id,))?;
+        }
         
         // 先に各注文の商品リストを作成
-        let mut selected_products: Vec<&(String, String)> = Vec::with_capacity(product_count);
-        for _ in 0..product_count {
-            let product_index = rand::rng().random_range(0..products.len());
-            selected_products.push(&products[product_index]);
-        }
+        let selected_products = pick_order_products(products, product_distribution, &options.items, &mut rand::rng());
+        
+        // 注文の小計（税抜）
+        let mut subtotal = 0.0;
         
         // 選択した商品リストでイテレーション
         for (product_id, variant_id) in selected_products {
             // 数量をランダムに決定
-            let quantity = rand::rng().random_range(2..=32);
+            let quantity = rand::rng().random_range(options.quantity.clone());
             
-            // 価格は0円
-            let price = 0;
+            // 単価をランダムに決定
+            let price = rand::rng().random_range(100..=5000);
+            subtotal += price as f64 * quantity as f64;
             
-            // 定期購入はfalse
-            let is_subscription = 0;
+            // 注文が定期購入なら注文商品も定期購入とする
+            let is_subscription = subscriptions[i] as u8;
             
             // 新規割引かどうかをランダムに決定
             let is_brand_new_discount = 0;
@@ -259,7 +831,470 @@ fn generate_order_products(tx: &mut Transaction, order_ids: &[String], products:
 
             tx.exec_drop(query, ())?;
         }
+        
+        // 小計に税を加えて注文の合計金額を更新
+        let (total_tax, total_price) = options.totals(subtotal);
+        tx.exec_drop(
+            "UPDATE orders SET subtotal_price = ?, total_line_items_price = ?, total_tax = ?, total_price = ? WHERE id = ?",
+            (subtotal, subtotal, total_tax, total_price, order_id),
+        )?;
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+
+// 書き込みを行わずに生成内容をプレビューする
+// トランザクションは開始せず、接続確認と件数の取得のみ行う
+pub async fn dry_run(count: usize, provinces: WeightedIndex<f64>, email_domain: String, items: RangeInclusive<usize>) -> Result<()> {
+    println!("[dry-run] データベースへの書き込みは行いません");
+    
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+    
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let lines = tokio::task::spawn_blocking(move || preview(&pool, count, &provinces, &email_domain, &items))
+        .await
+        .expect("ブロッキングタスクの実行に失敗")?;
+    
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+// プレビューの内容を行ごとに返す（読み込みだけを行う）
+fn preview(pool: &mysql::Pool, count: usize, provinces: &WeightedIndex<f64>, email_domain: &str, items: &RangeInclusive<usize>) -> Result<Vec<String>> {
+    // 設定ミスを早期に検出するため接続を確認
+    let mut conn = pool.get_conn()?;
+    conn.query_drop("SELECT 1")?;
+    let mut lines = vec!["[dry-run] データベースに接続できました".to_string()];
+    
+    let customers: Option<u64> = conn.query_first("SELECT COUNT(*) FROM customers")?;
+    let products: Option<u64> = conn.query_first("SELECT COUNT(*) FROM products WHERE is_suspension = false")?;
+    
+    let (start_date, end_date) = order_date_range();
+    
+    lines.push("[dry-run] 対象テーブル: customers, orders, order_products".to_string());
+    lines.push(format!("[dry-run] 既存の顧客数: {}件、有効な商品数: {}件", customers.unwrap_or(0), products.unwrap_or(0)));
+    lines.push(generation_estimate(count, items));
+    lines.push(format!("[dry-run] 注文日時の範囲: {} 〜 {}", start_date, end_date));
+    
+    // 生成されるデータの例
+    for seq_num in 1..=count.min(3) {
+        let customer = build_customer(seq_num, provinces, email_domain);
+        lines.push(format!(
+            "[dry-run] 顧客の例: id={}, email={}, name={} {}, province={}",
+            customer.id, customer.email, customer.last_name, customer.first_name, customer.shipping_province_code
+        ));
+    }
+    
+    Ok(lines)
+}
+
+// 生成予定の件数（注文商品の件数は1注文あたりの商品数の範囲から見積もる）
+pub fn generation_estimate(count: usize, items: &RangeInclusive<usize>) -> String {
+    format!(
+        "[dry-run] 生成予定: 顧客 {}件、注文 {}件、注文商品 {}〜{}件",
+        count, count, count * items.start(), count * items.end()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn seed_count_rejects_non_numeric_and_zero() {
+        assert!(parse_count(Some("abc"), false).is_err());
+        assert!(parse_count(Some("0"), false).is_err());
+        assert_eq!(parse_count(Some("500"), false), Ok(500));
+        assert_eq!(parse_count(None, false), Ok(DEFAULT_SEED_COUNT));
+    }
+
+    #[test]
+    fn seed_count_above_the_cap_needs_force() {
+        let huge = (MAX_SEED_COUNT + 1).to_string();
+        assert!(parse_count(Some(&huge), false).is_err());
+        assert_eq!(parse_count(Some(&huge), true), Ok(MAX_SEED_COUNT + 1));
+    }
+
+    // 重みファイルを一時ディレクトリに書き出す
+    fn weights_file(content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}.province-weights.json", Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+    
+    #[test]
+    fn province_weights_override_only_the_listed_provinces() {
+        let uniform = province_distribution(None).unwrap();
+        assert_eq!(uniform.weights().collect::<Vec<f64>>(), vec![1.0; region::PROVINCE_COUNT]);
+        
+        let path = weights_file(r#"{"JP-13": 10.5, "JP-01": 0}"#);
+        let weights: Vec<f64> = province_distribution(Some(&path)).unwrap().weights().collect();
+        std::fs::remove_file(&path).unwrap();
+        
+        assert_eq!(weights.len(), region::PROVINCE_COUNT);
+        assert_eq!(weights[0], 0.0);
+        assert_eq!(weights[12], 10.5);
+        assert_eq!(weights.iter().filter(|weight| **weight == 1.0).count(), region::PROVINCE_COUNT - 2);
+    }
+    
+    #[test]
+    fn bad_province_weights_are_rejected() {
+        let cases = [
+            (r#"{"JP-13": -1}"#, "JP-13の重みには0以上の数値を指定してください"),
+            (r#"{"JP-48": 1}"#, "不正な都道府県コードです: JP-48"),
+            (r#"{"TOKYO": 1}"#, "不正な都道府県コードです: TOKYO"),
+        ];
+        for (content, expected) in cases {
+            let path = weights_file(content);
+            let result = province_distribution(Some(&path));
+            std::fs::remove_file(&path).unwrap();
+            
+            assert_eq!(result.unwrap_err(), expected, "{}", content);
+        }
+        
+        // 形式の誤り・重みがすべて0の場合もエラーになる
+        let all_zero: String = format!(
+            "{{{}}}",
+            (1..=region::PROVINCE_COUNT).map(|num| format!(r#""JP-{:02}": 0"#, num)).collect::<Vec<_>>().join(",")
+        );
+        for content in [r#"{"JP-13": "heavy"}"#, "not json", all_zero.as_str()] {
+            let path = weights_file(content);
+            let result = province_distribution(Some(&path));
+            std::fs::remove_file(&path).unwrap();
+            
+            assert!(result.is_err(), "{}", content);
+        }
+        
+        let missing = std::env::temp_dir().join(format!("{}.missing.json", Uuid::new_v4()));
+        assert!(province_distribution(Some(&missing)).unwrap_err().contains("を読み込めません"));
+    }
+    
+    #[test]
+    fn item_and_quantity_ranges_must_start_at_one_and_not_exceed_the_max() {
+        assert_eq!(parse_range(1usize, 5, "items"), Ok(1..=5));
+        assert_eq!(parse_range(3u32, 3, "qty"), Ok(3..=3));
+        assert_eq!(parse_range(0usize, 5, "items").unwrap_err(), "--items-min は1以上かつ --items-max 以下にしてください");
+        assert_eq!(parse_range(4u32, 2, "qty").unwrap_err(), "--qty-min は1以上かつ --qty-max 以下にしてください");
+    }
+    
+    #[test]
+    fn every_order_has_an_item_count_inside_the_range() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let products: Vec<(String, String)> = (0..10).map(|i| (format!("product-{}", i), format!("variant-{}", i))).collect();
+        let distribution = zipf_distribution(products.len(), 1.0);
+        let items = 2..=4;
+        
+        let counts: Vec<usize> = (0..1_000)
+            .map(|_| pick_order_products(&products, &distribution, &items, &mut rng).len())
+            .collect();
+        
+        assert!(counts.iter().all(|count| items.contains(count)), "{:?}", counts);
+        // 範囲の両端も選ばれる
+        assert!(counts.contains(&2) && counts.contains(&4));
+    }
+    
+    #[test]
+    fn subscription_ratio_marks_roughly_that_fraction() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let orders = 10_000;
+        let subscriptions = (0..orders)
+            .filter_map(|_| pick_subscription_plan(0.3, &mut rng))
+            .inspect(|plan| assert!(plan.discount_rate > 0 && !plan.name.is_empty()))
+            .count();
+        let fraction = subscriptions as f64 / orders as f64;
+        assert!((0.28..=0.32).contains(&fraction), "{}", fraction);
+
+        // 0 では定期購入にせず、1 ではすべて定期購入にする
+        assert!((0..100).all(|_| pick_subscription_plan(0.0, &mut rng).is_none()));
+        assert!((0..100).all(|_| pick_subscription_plan(1.0, &mut rng).is_some()));
+    }
+    
+    #[test]
+    fn guest_ratio_leaves_roughly_that_fraction_without_customer() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let customer_ids = vec!["customer-1".to_string(), "customer-2".to_string()];
+        let orders = 10_000;
+        let customers: Vec<Option<&String>> = (0..orders)
+            .map(|_| pick_order_customer(&customer_ids, 0.2, &mut rng))
+            .collect();
+        let guests = customers.iter().filter(|customer| customer.is_none()).count();
+        let fraction = guests as f64 / orders as f64;
+        assert!((0.18..=0.22).contains(&fraction), "{}", fraction);
+        // ゲスト購入でない注文は既存の顧客に紐づく
+        assert!(customers.iter().flatten().all(|customer| customer_ids.contains(customer)));
+        
+        assert!((0..100).all(|_| pick_order_customer(&customer_ids, 0.0, &mut rng).is_some()));
+    }
+    
+    #[test]
+    fn empty_customer_ids_yield_guest_orders() {
+        let mut rng = StdRng::seed_from_u64(42);
+        
+        assert!((0..100).all(|_| pick_order_customer(&[], 0.0, &mut rng).is_none()));
+        assert!((0..100).all(|_| pick_order_customer(&[], 1.0, &mut rng).is_none()));
+    }
+    
+    #[test]
+    fn zipf_selection_follows_the_rank_weights() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let samples = 100_000;
+        let sample_counts = |distribution: &WeightedIndex<f64>, rng: &mut StdRng| {
+            let mut counts = [0usize; 4];
+            for _ in 0..samples {
+                counts[distribution.sample(rng)] += 1;
+            }
+            counts
+        };
+        
+        // 指数1では順位kの商品が 1/k の重みで選ばれる
+        let counts = sample_counts(&zipf_distribution(4, 1.0), &mut rng);
+        let total_weight: f64 = (1..=4).map(|rank| 1.0 / rank as f64).sum();
+        for (i, &count) in counts.iter().enumerate() {
+            let expected = 1.0 / (i + 1) as f64 / total_weight;
+            let fraction = count as f64 / samples as f64;
+            assert!((fraction - expected).abs() < 0.01, "順位{}: {} != {}", i + 1, fraction, expected);
+        }
+        
+        // 指数0では一様に選ばれる
+        let counts = sample_counts(&zipf_distribution(4, 0.0), &mut rng);
+        for count in counts {
+            let fraction = count as f64 / samples as f64;
+            assert!((fraction - 0.25).abs() < 0.01, "{}", fraction);
+        }
+    }
+    
+    #[test]
+    fn generated_password_hash_verifies_against_the_derived_password() {
+        let seq_num = 42;
+        let customer = build_customer(seq_num, &province_distribution(None).unwrap(), "example.com");
+        
+        assert!(bcrypt::verify(customer_password(seq_num), &customer.password_hash).unwrap());
+        // 別の顧客のパスワードでは認証できない
+        assert!(!bcrypt::verify(customer_password(seq_num + 1), &customer.password_hash).unwrap());
+    }
+    
+    #[test]
+    fn customer_ids_are_derived_from_the_sequence_number() {
+        assert_eq!(customer_id(1), customer_id(1));
+        assert_ne!(customer_id(1), customer_id(2));
+        assert!(Uuid::parse_str(&customer_id(1)).is_ok());
+    }
+    
+    #[test]
+    fn only_emails_owned_by_another_id_conflict() {
+        let seeded = customer_id(1);
+        let registered = vec![
+            // 前回のシードで生成した顧客（上書きする）
+            (seeded.clone(), customer_email(&seeded, "example.com")),
+            // 生成する顧客のメールアドレスを別のIDの顧客が使っている
+            ("existing-customer".to_string(), customer_email(&customer_id(2), "example.com")),
+        ];
+        
+        let conflicts = conflicting_emails(registered, "example.com");
+        
+        assert_eq!(conflicts, HashSet::from([customer_email(&customer_id(2), "example.com")]));
+    }
+    
+    // 指定したメールアドレスの顧客を生成する顧客とは別のIDで登録する
+    fn insert_other_customer(pool: &mysql::Pool, email: &str) {
+        pool.get_conn().unwrap().exec_drop(
+            "INSERT INTO customers (id, email, is_infomercial, password, accepts_marketing, 
+            first_name, last_name, shipping_province_code, shipping_address_line1, shipping_phone, created_at, updated_at) 
+            VALUES (UUID(), ?, 0, '', 0, '', '', 'JP-13', '', '', NOW(), NOW())",
+            (email,),
+        ).unwrap();
+    }
+    
+    // 指定したドメインのメールアドレスを持つ顧客を数える
+    fn count_customers(pool: &mysql::Pool, email_domain: &str) -> u64 {
+        pool.get_conn().unwrap()
+            .exec_first("SELECT COUNT(*) FROM customers WHERE email LIKE ?", (format!("%@{}", email_domain),))
+            .unwrap().unwrap()
+    }
+    
+    #[test]
+    fn worker_ranges_split_the_sequence_without_overlap() {
+        let ranges = worker_ranges(1000, 7);
+        assert_eq!(ranges.len(), 7);
+        assert!(ranges.iter().all(|range| range.len() == 142 || range.len() == 143));
+        
+        // すべての連番がいずれか1つのワーカーに割り当てられ、IDも重複しない
+        let seqs: Vec<usize> = ranges.into_iter().flatten().collect();
+        assert_eq!(seqs, (1..=1000).collect::<Vec<_>>());
+        let ids: HashSet<String> = seqs.into_iter().map(customer_id).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+    
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    async fn parallel_seeding_inserts_every_customer_once() {
+        let pool = crate::testing::database_pool();
+        let email_domain = format!("{}.parallel.example.com", Uuid::new_v4());
+        let options = CustomerOptions { email_domain: email_domain.clone(), on_conflict: OnConflict::Error, progress: Progress::new(true, DEFAULT_PROGRESS_EVERY) };
+        
+        seed_customers(pool.clone(), 1000, 4, province_distribution(None).unwrap(), options).await.unwrap();
+        
+        let distinct: u64 = pool.get_conn().unwrap()
+            .exec_first("SELECT COUNT(DISTINCT id) FROM customers WHERE email LIKE ?", (format!("%@{}", email_domain),))
+            .unwrap().unwrap();
+        assert_eq!(count_customers(&pool, &email_domain), 1000);
+        assert_eq!(distinct, 1000);
+    }
+    
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    async fn pre_existing_email_follows_the_on_conflict_option() {
+        let pool = crate::testing::database_pool();
+        let email_domain = format!("{}.on-conflict.example.com", Uuid::new_v4());
+        insert_other_customer(&pool, &customer_email(&customer_id(3), &email_domain));
+        let seed = |on_conflict| {
+            let options = CustomerOptions { email_domain: email_domain.clone(), on_conflict, progress: Progress::new(true, DEFAULT_PROGRESS_EVERY) };
+            seed_customers(pool.clone(), 5, 2, province_distribution(None).unwrap(), options)
+        };
+        
+        // error では何も挿入せずに失敗する
+        assert!(seed(OnConflict::Error).await.is_err());
+        assert_eq!(count_customers(&pool, &email_domain), 1);
+        
+        // skip では重複する顧客だけを飛ばし、再実行しても同じIDの顧客は上書きされる
+        seed(OnConflict::Skip).await.unwrap();
+        seed(OnConflict::Skip).await.unwrap();
+        assert_eq!(count_customers(&pool, &email_domain), 5);
+    }
+    
+    // 注文数を数える
+    fn count_orders(pool: &mysql::Pool) -> u64 {
+        pool.get_conn().unwrap().query_first("SELECT COUNT(*) FROM orders").unwrap().unwrap()
+    }
+    
+    fn order_options() -> OrderOptions {
+        OrderOptions {
+            currency: Currency::Jpy,
+            tax_rate: 0.1,
+            subscription_ratio: 0.0,
+            guest_ratio: 0.0,
+            items: 2..=3,
+            quantity: 1..=2,
+            email_domain: "seed-twice.example.com".to_string(),
+            progress: Progress::new(true, DEFAULT_PROGRESS_EVERY),
+        }
+    }
+    
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を登録済み）が必要"]
+    async fn seeding_twice_yields_the_same_order_count() {
+        let pool = crate::testing::database_pool();
+        let seed = || async {
+            let customer_options = CustomerOptions {
+                email_domain: "seed-twice.example.com".to_string(),
+                on_conflict: OnConflict::Skip,
+                progress: Progress::new(true, DEFAULT_PROGRESS_EVERY),
+            };
+            seed_customers(pool.clone(), 20, 2, province_distribution(None).unwrap(), customer_options).await.unwrap();
+            seed_orders(pool.clone(), 20, 1.0, order_options(), false).await.unwrap();
+            count_orders(&pool)
+        };
+        
+        let first = seed().await;
+        let second = seed().await;
+        
+        assert_eq!(first, second);
+    }
+    
+    #[test]
+    fn totals_apply_the_tax_rate_in_the_currency() {
+        let options = |currency: Currency, tax_rate: f64| OrderOptions { currency, tax_rate, ..order_options() };
+        
+        assert_eq!(options(Currency::Jpy, 0.1).totals(1234.0), (123.0, 1357.0));
+        // ドルはセント単位に丸める
+        assert_eq!(options(Currency::Usd, 0.0825).totals(19.99), (1.65, 21.64));
+        assert_eq!(options(Currency::Eur, 0.0).totals(10.0), (0.0, 10.0));
+    }
+    
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を登録済み）が必要"]
+    async fn seeded_orders_use_the_currency_and_tax_rate() {
+        let pool = crate::testing::database_pool();
+        let email_domain = format!("{}.currency.example.com", Uuid::new_v4());
+        let customer_options = CustomerOptions {
+            email_domain: email_domain.clone(),
+            on_conflict: OnConflict::Skip,
+            progress: Progress::new(true, DEFAULT_PROGRESS_EVERY),
+        };
+        seed_customers(pool.clone(), 5, 1, province_distribution(None).unwrap(), customer_options).await.unwrap();
+        let options = OrderOptions {
+            currency: Currency::Usd,
+            tax_rate: 0.08,
+            email_domain: email_domain.clone(),
+            ..order_options()
+        };
+        seed_orders(pool.clone(), 10, 1.0, options, false).await.unwrap();
+        
+        let orders: Vec<(String, f64, f64, f64)> = pool
+            .get_conn()
+            .unwrap()
+            .exec(
+                "SELECT currency, subtotal_price, total_tax, total_price FROM orders WHERE email LIKE ?",
+                (format!("%@{}", email_domain),),
+            )
+            .unwrap();
+        assert!(!orders.is_empty());
+        for (currency, subtotal, tax, total) in orders {
+            assert_eq!(currency, "usd");
+            assert!((tax - Currency::Usd.round(subtotal * 0.08)).abs() < 0.005, "{} {}", subtotal, tax);
+            assert!((total - subtotal - tax).abs() < 0.005, "{} {} {}", subtotal, tax, total);
+        }
+    }
+    
+    #[test]
+    fn order_emails_use_the_configured_domain() {
+        let customer = customer_id(1);
+        // 顧客の注文は顧客のメールアドレスと同じになる
+        assert_eq!(order_email(Some(&customer), "order-1", "shop.test"), customer_email(&customer, "shop.test"));
+        assert_eq!(order_email(None, "order-2", "shop.test"), "guest-order-2@shop.test");
+    }
+    
+    #[test]
+    fn dry_run_estimate_follows_the_items_range() {
+        assert_eq!(generation_estimate(100, &(2..=10)), "[dry-run] 生成予定: 顧客 100件、注文 100件、注文商品 200〜1000件");
+        assert_eq!(generation_estimate(100, &(1..=3)), "[dry-run] 生成予定: 顧客 100件、注文 100件、注文商品 100〜300件");
+    }
+    
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    fn dry_run_preview_writes_nothing() {
+        let pool = crate::testing::database_pool();
+        let counts = || -> Vec<u64> {
+            let mut conn = pool.get_conn().unwrap();
+            ["customers", "orders", "order_products"]
+                .iter()
+                .map(|table| conn.query_first(format!("SELECT COUNT(*) FROM {}", table)).unwrap().unwrap())
+                .collect()
+        };
+        let before = counts();
+        
+        let lines = preview(&pool, 5, &province_distribution(None).unwrap(), "dry-run.example.com", &(1..=4)).unwrap();
+        
+        assert!(lines.contains(&generation_estimate(5, &(1..=4))));
+        assert_eq!(lines.iter().filter(|line| line.contains("@dry-run.example.com")).count(), 3);
+        assert_eq!(counts(), before);
+    }
+    
+    #[test]
+    fn progress_reports_at_configured_interval() {
+        let progress = Progress::new(false, 250);
+        let reported: Vec<usize> = (0..=1000).filter(|&done| progress.should_report(done)).collect();
+        assert_eq!(reported, [250, 500, 750, 1000]);
+        
+        // --quiet では表示しない
+        let quiet = Progress::new(true, 250);
+        assert!((0..=1000).all(|done| !quiet.should_report(done)));
+    }
+}
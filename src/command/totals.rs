@@ -0,0 +1,219 @@
+use mysql::prelude::*;
+use mysql::*;
+use std::collections::HashMap;
+
+use super::batch;
+use super::seed::Currency;
+use crate::config;
+
+// 注文ごとの小計・税額・合計金額を明細（order_products の価格×数量）から計算し直し、更新した注文数を返す
+// 価格の修正より前に生成したデータ（合計が0の注文）を、シードし直さずに直すために使う
+// 全件を1つのトランザクションで更新するため、途中で失敗した場合は何も変更されない
+pub async fn recompute_totals(tax_rate: f64) -> Result<usize> {
+    println!("注文の合計金額を再計算します（税率: {}）", tax_rate);
+
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let updated = tokio::task::spawn_blocking(move || recompute(&pool, tax_rate))
+        .await
+        .expect("ブロッキングタスクの実行に失敗")?;
+
+    println!("注文の合計金額の再計算が完了しました（{}件）", updated);
+
+    Ok(updated)
+}
+
+// 合計金額を計算し直し、値が変わった注文だけを更新して件数を返す
+// 計算済みの注文は更新しないため、繰り返し実行しても2回目以降は何も変わらない
+fn recompute(pool: &mysql::Pool, tax_rate: f64) -> Result<usize> {
+    let mut conn = pool.get_conn()?;
+    let mut tx = conn.start_transaction(TxOpts::default())?;
+
+    let orders: Vec<StoredOrder> = tx.query(
+        "SELECT id, currency, subtotal_price, total_tax, total_price FROM orders ORDER BY id",
+    )?;
+    let total = orders.len();
+    let mut checked = 0;
+    let mut updated = 0;
+
+    for chunk in orders.chunks(batch::BATCH_SIZE) {
+        // 明細のない注文は小計0とする
+        let subtotals: Vec<(String, f64)> = tx.exec(
+            format!(
+                "SELECT order_id, SUM(price * quantity) FROM order_products
+                 WHERE order_id IN ({})
+                 GROUP BY order_id",
+                vec!["?"; chunk.len()].join(", ")
+            ),
+            chunk.iter().map(|(id, ..)| id.as_str()).collect::<Vec<_>>(),
+        )?;
+        let subtotals: HashMap<String, f64> = subtotals.into_iter().collect();
+
+        let changed: Vec<(&String, OrderTotals)> = chunk
+            .iter()
+            .map(|(order_id, currency, subtotal, tax, total)| {
+                let subtotal_price = subtotals.get(order_id).copied().unwrap_or(0.0);
+                let totals = OrderTotals::new(subtotal_price, tax_rate, currency.as_deref());
+                let current = [*subtotal, *tax, *total];
+                (order_id, totals, current)
+            })
+            .filter(|(_, totals, current)| !totals.matches(current))
+            .map(|(order_id, totals, _)| (order_id, totals))
+            .collect();
+
+        updated += changed.len();
+        tx.exec_batch(
+            "UPDATE orders
+             SET subtotal_price = ?, total_line_items_price = ?, total_tax = ?, total_price = ?
+             WHERE id = ?",
+            changed.into_iter().map(|(order_id, totals)| {
+                (
+                    totals.subtotal,
+                    totals.subtotal,
+                    totals.tax,
+                    totals.total,
+                    order_id,
+                )
+            }),
+        )?;
+
+        checked += chunk.len();
+        println!("{}/{}件 確認完了（更新: {}件）", checked, total, updated);
+    }
+
+    tx.commit()?;
+
+    Ok(updated)
+}
+
+// 保存済みの注文（ID, 通貨, 小計, 税額, 合計）
+type StoredOrder = (
+    String,
+    Option<String>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+);
+
+// 明細から計算した注文の金額
+#[derive(Debug, PartialEq)]
+struct OrderTotals {
+    subtotal: f64,
+    tax: f64,
+    total: f64,
+}
+
+impl OrderTotals {
+    fn new(subtotal: f64, tax_rate: f64, currency: Option<&str>) -> Self {
+        // 通貨の最小単位に丸める（不明な通貨は小数2桁）
+        let round = |amount: f64| match currency.and_then(Currency::from_code) {
+            Some(currency) => currency.round(amount),
+            None => (amount * 100.0).round() / 100.0,
+        };
+        let tax = round(subtotal * tax_rate);
+        OrderTotals {
+            subtotal,
+            tax,
+            total: subtotal + tax,
+        }
+    }
+
+    // 保存済みの小計・税額・合計と一致するか（DECIMAL(.., 2) に保存した誤差は無視する）
+    fn matches(&self, current: &[Option<f64>; 3]) -> bool {
+        [self.subtotal, self.tax, self.total]
+            .iter()
+            .zip(current)
+            .all(|(expected, current)| {
+                current.is_some_and(|current| (expected - current).abs() < 0.005)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::seed;
+
+    #[test]
+    fn totals_add_the_tax_rounded_to_the_currency() {
+        assert_eq!(
+            OrderTotals::new(1234.0, 0.1, Some("JPY")),
+            OrderTotals {
+                subtotal: 1234.0,
+                tax: 123.0,
+                total: 1357.0,
+            }
+        );
+        assert_eq!(OrderTotals::new(10.05, 0.08, Some("USD")).tax, 0.8);
+        assert_eq!(OrderTotals::new(10.05, 0.08, None).tax, 0.8);
+    }
+
+    #[test]
+    fn stored_totals_match_only_when_every_amount_agrees() {
+        let totals = OrderTotals::new(1000.0, 0.1, Some("JPY"));
+
+        assert!(totals.matches(&[Some(1000.0), Some(100.0), Some(1100.0)]));
+        assert!(!totals.matches(&[Some(1000.0), Some(100.0), Some(0.0)]));
+        assert!(!totals.matches(&[None, Some(100.0), Some(1100.0)]));
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を登録済み）が必要"]
+    async fn recomputed_totals_equal_the_line_items_and_are_idempotent() {
+        let pool = crate::testing::database_pool();
+        let customer_options = seed::CustomerOptions {
+            email_domain: "totals.example.com".to_string(),
+            on_conflict: seed::OnConflict::Skip,
+            progress: seed::Progress::new(true, 0),
+        };
+        let order_options = seed::OrderOptions {
+            currency: Currency::Jpy,
+            tax_rate: 0.1,
+            subscription_ratio: 0.0,
+            guest_ratio: 0.0,
+            items: 1..=3,
+            quantity: 1..=3,
+            email_domain: "totals.example.com".to_string(),
+            progress: seed::Progress::new(true, 0),
+        };
+        let provinces = seed::province_distribution(None).unwrap();
+        seed::seed_customers(pool.clone(), 5, 1, provinces, customer_options)
+            .await
+            .unwrap();
+        seed::seed_orders(pool.clone(), 10, 1.0, order_options, false)
+            .await
+            .unwrap();
+        // 価格の修正前に生成したデータと同じく合計を0にする
+        pool.get_conn()
+            .unwrap()
+            .query_drop(
+                "UPDATE orders SET subtotal_price = 0, total_line_items_price = 0,
+                 total_tax = 0, total_price = 0",
+            )
+            .unwrap();
+
+        let first = recompute(&pool, 0.1).unwrap();
+        let second = recompute(&pool, 0.1).unwrap();
+
+        assert!(first > 0);
+        assert_eq!(second, 0);
+        let mismatched: u64 = pool
+            .get_conn()
+            .unwrap()
+            .query_first(
+                "SELECT COUNT(*) FROM orders o
+                 LEFT JOIN (
+                     SELECT order_id, SUM(price * quantity) AS subtotal
+                     FROM order_products GROUP BY order_id
+                 ) items ON items.order_id = o.id
+                 WHERE ABS(o.subtotal_price - COALESCE(items.subtotal, 0)) >= 0.005
+                    OR ABS(o.total_price - o.subtotal_price - o.total_tax) >= 0.005",
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(mismatched, 0);
+    }
+}
@@ -0,0 +1,168 @@
+use chrono::Utc;
+use mysql::*;
+use std::sync::Arc;
+
+use super::batch;
+use crate::config;
+use crate::service::cart;
+
+// customer_vectors テーブルの列
+const VECTOR_COLUMNS: [&str; 4] = ["customer_id", "vector", "province_code", "updated_at"];
+
+// 購入履歴から顧客ごとのベクトルを計算し、customer_vectors テーブルに保存する
+pub async fn build_customer_vectors() -> Result<()> {
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+
+    rebuild_customer_vectors(Arc::new(pool)).await?;
+    Ok(())
+}
+
+// 指定した接続プールで customer_vectors テーブルを作り直し、保存した顧客数を返す
+// （サーバーの /admin/rebuild-customer-vectors からも使う）
+pub async fn rebuild_customer_vectors(pool: Arc<mysql::Pool>) -> Result<usize> {
+    println!("顧客ベクトルの計算を開始します...");
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let total = tokio::task::spawn_blocking(move || {
+        // customer_vectors テーブルはスキーマ変更（migrate）で作成済みとする
+        let mut conn = pool.get_conn()?;
+
+        // 集計開始時刻を更新日時とし、集計中に更新された注文があれば古いと判定されるようにする
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let customer_products = cart::fetch_customer_purchases(&mut conn, None, 1, false)?;
+        let total = customer_products.len();
+
+        let mut tx = conn.start_transaction(TxOpts::default())?;
+        let mut rows: Vec<Vec<Value>> = Vec::with_capacity(batch::BATCH_SIZE);
+        let mut saved = 0;
+
+        for (customer_id, (province_code, products)) in customer_products {
+            rows.push(vec![
+                Value::from(customer_id),
+                Value::from(cart::encode_cached_vector(&products)),
+                Value::from(province_code),
+                Value::from(&now),
+            ]);
+
+            if rows.len() >= batch::BATCH_SIZE {
+                saved += rows.len();
+                batch::upsert_batch(
+                    &mut tx,
+                    "customer_vectors",
+                    &VECTOR_COLUMNS,
+                    &VECTOR_COLUMNS[1..],
+                    std::mem::take(&mut rows),
+                )?;
+                println!("{}/{}件 保存完了", saved, total);
+            }
+        }
+
+        batch::upsert_batch(
+            &mut tx,
+            "customer_vectors",
+            &VECTOR_COLUMNS,
+            &VECTOR_COLUMNS[1..],
+            rows,
+        )?;
+
+        tx.commit()?;
+        println!("顧客ベクトルの保存が完了しました（{}件）", total);
+
+        Ok::<usize, mysql::Error>(total)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::service::cart::{
+        NormalizationMode, OrderVector, ProductDimensions, ProductItem, QuantityTransform,
+        VectorEncoding,
+    };
+    use crate::testing;
+
+    fn assert_same_vector(cached: &OrderVector, fresh: &OrderVector) {
+        assert_eq!(cached.region_vector, fresh.region_vector);
+        assert_eq!(cached.province, fresh.province);
+        assert_eq!(cached.region_encoding, fresh.region_encoding);
+        assert_eq!(cached.product_vector.len(), fresh.product_vector.len());
+        for (a, b) in cached.product_vector.iter().zip(&fresh.product_vector) {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn saved_vectors_decode_to_the_freshly_computed_vectors() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into(), "c".into()]);
+        let item = |id: &str, quantity| ProductItem {
+            product_variant_id: id.into(),
+            quantity,
+            weight: None,
+        };
+        // 同じ商品の重複（後の値で上書き）と、次元にない商品を含む購入履歴
+        let products = [item("a", 3), item("c", 1), item("a", 2), item("z", 5)];
+        let saved = cart::encode_cached_vector(&products);
+
+        for transform in [
+            QuantityTransform::Identity,
+            QuantityTransform::Sqrt,
+            QuantityTransform::Log1p,
+        ] {
+            for normalization in [
+                NormalizationMode::None,
+                NormalizationMode::L1,
+                NormalizationMode::L2,
+            ] {
+                let encoding = VectorEncoding {
+                    transform,
+                    normalization,
+                };
+                let fresh = cart::create_order_vector("JP-13", &products, &dimensions, encoding);
+                let cached =
+                    cart::decode_cached_vector("JP-13", &saved, &dimensions, encoding).unwrap();
+                assert_same_vector(&cached, &fresh);
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（注文を登録済み）が必要"]
+    async fn rebuilt_vectors_match_the_purchase_history() {
+        let pool = Arc::new(testing::database_pool());
+        rebuild_customer_vectors(pool.clone()).await.unwrap();
+
+        let mut conn = pool.get_conn().unwrap();
+        let dimensions = cart::fetch_product_dimensions(&mut conn).unwrap();
+        assert!(cart::customer_vectors_are_fresh(&mut conn).unwrap());
+        let encoding = VectorEncoding::default();
+        let history = |conn: &mut PooledConn, use_cached_vectors| {
+            let mut vectors = cart::fetch_user_purchase_history(
+                conn,
+                &dimensions,
+                encoding,
+                1,
+                false,
+                use_cached_vectors,
+            )
+            .unwrap();
+            vectors.sort_by(|a, b| a.0.cmp(&b.0));
+            vectors
+        };
+
+        let cached = history(&mut conn, true);
+        let fresh = history(&mut conn, false);
+
+        assert_eq!(cached.len(), fresh.len());
+        for ((cached_id, cached), (fresh_id, fresh)) in cached.iter().zip(&fresh) {
+            assert_eq!(cached_id, fresh_id);
+            assert_same_vector(cached, fresh);
+        }
+    }
+}
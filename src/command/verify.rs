@@ -0,0 +1,135 @@
+use mysql::prelude::*;
+use mysql::*;
+
+use crate::config;
+
+// 参照関係（説明、参照元テーブル、参照元の列、参照先テーブル、参照先の列）
+const RELATIONSHIPS: [(&str, &str, &str, &str, &str); 3] = [
+    (
+        "order_products.order_id → orders.id",
+        "order_products",
+        "order_id",
+        "orders",
+        "id",
+    ),
+    (
+        "orders.customer_id → customers.id",
+        "orders",
+        "customer_id",
+        "customers",
+        "id",
+    ),
+    (
+        "order_products.variant_id → products.variant_id",
+        "order_products",
+        "variant_id",
+        "products",
+        "variant_id",
+    ),
+];
+
+// 参照先が存在しない行（孤立した行）を参照関係ごとに数えて表示し、合計件数を返す
+// 参照元の列がNULLの行は参照していないものとして数えない
+pub async fn verify_integrity() -> Result<u64> {
+    println!("データの整合性を検証します...");
+
+    // データベース接続設定
+    let opts = config::database::get_database_opts();
+    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let orphans = tokio::task::spawn_blocking(move || count_orphans(&mut pool.get_conn()?))
+        .await
+        .expect("ブロッキングタスクの実行に失敗")?;
+
+    let mut total = 0;
+    for (label, count) in orphans {
+        println!("{}: 孤立した行 {}件", label, count);
+        total += count;
+    }
+
+    if total == 0 {
+        println!("不整合は見つかりませんでした");
+    }
+
+    Ok(total)
+}
+
+// 孤立した行が見つかった場合はエラーにする（終了コードを0以外にするため）
+pub fn ensure_no_orphans(total: u64) -> std::result::Result<(), String> {
+    match total {
+        0 => Ok(()),
+        total => Err(format!("{}件の孤立した行が見つかりました", total)),
+    }
+}
+
+// 参照関係ごとの孤立した行の数
+fn count_orphans(conn: &mut impl Queryable) -> Result<Vec<(&'static str, u64)>> {
+    RELATIONSHIPS
+        .iter()
+        .map(|(label, child, child_column, parent, parent_column)| {
+            let orphans: Option<u64> = conn.query_first(format!(
+                "SELECT COUNT(*)
+                 FROM {child} c
+                 LEFT JOIN {parent} p ON p.{parent_column} = c.{child_column}
+                 WHERE c.{child_column} IS NOT NULL AND p.{parent_column} IS NULL",
+            ))?;
+            Ok((*label, orphans.unwrap_or(0)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn orphans_fail_the_check() {
+        assert_eq!(ensure_no_orphans(0), Ok(()));
+        assert_eq!(
+            ensure_no_orphans(2),
+            Err("2件の孤立した行が見つかりました".to_string())
+        );
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を登録済み）が必要"]
+    fn orphan_order_items_are_reported() {
+        let pool = testing::database_pool();
+        let mut conn = pool.get_conn().unwrap();
+        let order_id = format!("verify-{}", uuid::Uuid::new_v4());
+        let (product_id, variant_id): (String, String) = conn
+            .query_first("SELECT id, variant_id FROM products LIMIT 1")
+            .unwrap()
+            .expect("商品が登録されていません");
+        let order_items = |conn: &mut PooledConn| {
+            count_orphans(conn)
+                .unwrap()
+                .into_iter()
+                .find(|(label, _)| label.starts_with("order_products.order_id"))
+                .unwrap()
+                .1
+        };
+        let before = order_items(&mut conn);
+
+        // 存在しない注文を参照する明細（外部キー制約がある場合も作れるよう検査を止める）
+        conn.query_drop("SET FOREIGN_KEY_CHECKS = 0").unwrap();
+        conn.exec_drop(
+            "INSERT INTO order_products (order_id, product_id, variant_id, quantity, price,
+             is_subscription, is_brand_new_discount, created_at, updated_at)
+             VALUES (?, ?, ?, 1, 100, 0, 0, NOW(), NOW())",
+            (&order_id, &product_id, &variant_id),
+        )
+        .unwrap();
+        let after = order_items(&mut conn);
+        conn.exec_drop(
+            "DELETE FROM order_products WHERE order_id = ?",
+            (&order_id,),
+        )
+        .unwrap();
+
+        assert_eq!(after, before + 1);
+        assert!(ensure_no_orphans(after).is_err());
+    }
+}
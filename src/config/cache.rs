@@ -0,0 +1,94 @@
+use std::env;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use super::Vars;
+
+// 商品次元情報の更新間隔のデフォルト（秒）
+const DEFAULT_DIMENSIONS_REFRESH_SECS: u64 = 300;
+
+// 集計値のキャッシュ期間のデフォルト（秒）
+const DEFAULT_STATS_CACHE_TTL_SECS: u64 = 60;
+
+// 推薦結果のキャッシュ期間のデフォルト（秒、既定ではキャッシュしない）
+const DEFAULT_SUGGESTION_CACHE_TTL_SECS: u64 = 0;
+
+// 近傍顧客の購入商品のキャッシュ期間のデフォルト（秒）
+const DEFAULT_USER_PRODUCTS_CACHE_TTL_SECS: u64 = 300;
+
+// 近傍顧客の購入商品をキャッシュする顧客数のデフォルト
+const DEFAULT_USER_PRODUCTS_CACHE_SIZE: usize = 10_000;
+
+// 商品次元情報のキャッシュを更新する間隔
+// DIMENSIONS_REFRESH_SECS=0 の場合は定期更新を行わない
+pub fn get_dimensions_refresh_interval() -> Option<Duration> {
+    let secs = get_secs("DIMENSIONS_REFRESH_SECS", DEFAULT_DIMENSIONS_REFRESH_SECS);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+// /stats の集計値をキャッシュする期間（STATS_CACHE_TTL_SECS=0 でキャッシュしない）
+pub fn get_stats_cache_ttl() -> Duration {
+    Duration::from_secs(get_secs(
+        "STATS_CACHE_TTL_SECS",
+        DEFAULT_STATS_CACHE_TTL_SECS,
+    ))
+}
+
+// 同じカートに対する推薦結果をキャッシュする期間（SUGGESTION_CACHE_TTL_SECS を指定した場合のみキャッシュする）
+// キャッシュした結果は商品の販売停止状態を変更した際に削除される
+// 推薦の設定の一部として読み込むため、値の引き方は config::recommendation::load_from から受け取る
+pub fn get_suggestion_cache_ttl(vars: Vars) -> Duration {
+    Duration::from_secs(parse_secs(
+        "SUGGESTION_CACHE_TTL_SECS",
+        vars("SUGGESTION_CACHE_TTL_SECS"),
+        DEFAULT_SUGGESTION_CACHE_TTL_SECS,
+    ))
+}
+
+// 起動時にユーザーベクトルを事前計算し、商品次元情報と同じ間隔で更新するか（WARM_USER_VECTORS=true で有効）
+pub fn get_warm_user_vectors() -> bool {
+    env::var("WARM_USER_VECTORS")
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+// 近傍顧客の購入商品をキャッシュする顧客数とキャッシュ期間
+// USER_PRODUCTS_CACHE_SIZE=0 または USER_PRODUCTS_CACHE_TTL_SECS=0 の場合はキャッシュしない（None）
+pub fn get_user_products_cache() -> Option<(NonZeroUsize, Duration)> {
+    let size = match env::var("USER_PRODUCTS_CACHE_SIZE") {
+        Ok(value) => value.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!(
+                "USER_PRODUCTS_CACHE_SIZE が不正です（{}）。{}件を使用します",
+                value, DEFAULT_USER_PRODUCTS_CACHE_SIZE
+            );
+            DEFAULT_USER_PRODUCTS_CACHE_SIZE
+        }),
+        Err(_) => DEFAULT_USER_PRODUCTS_CACHE_SIZE,
+    };
+    let ttl = get_secs(
+        "USER_PRODUCTS_CACHE_TTL_SECS",
+        DEFAULT_USER_PRODUCTS_CACHE_TTL_SECS,
+    );
+
+    let size = NonZeroUsize::new(size)?;
+    (ttl > 0).then(|| (size, Duration::from_secs(ttl)))
+}
+
+// 環境変数から秒数を取得（未設定・不正な場合はデフォルト値）
+fn get_secs(name: &str, default: u64) -> u64 {
+    parse_secs(name, env::var(name).ok(), default)
+}
+
+// 秒数の値を読み込む（未設定・不正な場合はデフォルト値）
+fn parse_secs(name: &str, value: Option<String>, default: u64) -> u64 {
+    match value {
+        Some(value) => value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!(
+                "{} が不正です（{}）。{}秒を使用します",
+                name, value, default
+            );
+            default
+        }),
+        None => default,
+    }
+}
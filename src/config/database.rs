@@ -1,5 +1,11 @@
 use dotenv::dotenv;
+use mysql::{Opts, OptsBuilder, PoolConstraints, PoolOpts, SslOpts};
 use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// 低速クエリとして警告する実行時間のデフォルト（ミリ秒）
+const DEFAULT_SLOW_QUERY_MS: u64 = 500;
 
 pub fn get_database_url() -> String {
     // .envファイルを読み込む
@@ -15,14 +21,11 @@ pub fn get_database_url() -> String {
             let host = env::var("MYSQL_HOST").unwrap_or_default();
             let database = env::var("MYSQL_DATABASE").unwrap_or_default();
 
-            let url = url
-                .replace("${MYSQL_USER}", &user)
+            url.replace("${MYSQL_USER}", &user)
                 .replace("${MYSQL_PASSWORD}", &password)
                 .replace("${MYSQL_PORT}", &port)
                 .replace("${MYSQL_HOST}", &host)
-                .replace("${MYSQL_DATABASE}", &database);
-
-            url
+                .replace("${MYSQL_DATABASE}", &database)
         }
         Err(_) => {
             // DATABASE_URLが設定されていない場合は手動で構築
@@ -38,3 +41,114 @@ pub fn get_database_url() -> String {
         }
     }
 }
+
+// データベースの接続設定
+//
+// 設定は次の順に適用する
+// 1. 接続先（ホスト・ユーザー・データベースなど）は get_database_url のURLから読み込む
+//    （DATABASE_URL があればそれを、なければ MYSQL_* から組み立てたURLを使う）
+// 2. MYSQL_SSL=true の場合はTLSで接続する（URLにはTLSの指定がないため、環境変数の指定がそのまま使われる）
+//    MYSQL_SSL_CA を指定した場合はその証明書（.pem / .der）を信頼するルート証明書に加える
+pub fn get_database_opts() -> Opts {
+    let database_url = get_database_url();
+    let opts = Opts::from_url(&database_url).expect("不正なデータベースURL");
+    with_ssl_opts(opts)
+}
+
+// 読み込み専用レプリカの接続設定（DATABASE_REPLICA_URL、未設定の場合は None）
+// TLSの設定は主データベースと同じものを使う
+pub fn get_replica_database_opts() -> Option<Opts> {
+    let replica_url = env::var("DATABASE_REPLICA_URL")
+        .ok()
+        .filter(|url| !url.is_empty())?;
+    let opts = Opts::from_url(&replica_url).expect("不正なレプリカのデータベースURL");
+    Some(with_ssl_opts(opts))
+}
+
+// 開発用の固定データで起動するか（MOCK_DB=true で有効、--mock と同じ）
+pub fn get_mock_db() -> bool {
+    env::var("MOCK_DB")
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+// 固定データで起動する場合の接続設定
+// 起動時には接続せず（最小接続数0）、データベースを使うルートへのリクエストは接続に失敗して503になる
+pub fn get_mock_database_opts() -> Opts {
+    let constraints = PoolConstraints::new(0, 1).expect("不正な接続数の範囲");
+    OptsBuilder::new()
+        .pool_opts(PoolOpts::default().with_constraints(constraints))
+        .into()
+}
+
+// MYSQL_SSL / MYSQL_SSL_CA のTLS設定を接続設定に適用する
+fn with_ssl_opts(opts: Opts) -> Opts {
+    let ssl_opts = parse_ssl_opts(env::var("MYSQL_SSL").ok(), env::var("MYSQL_SSL_CA").ok());
+    OptsBuilder::from_opts(opts).ssl_opts(ssl_opts).into()
+}
+
+// TLSの設定を読み込む（MYSQL_SSL が有効でなければ None、デフォルトは無効）
+// MYSQL_SSL_CA が空の場合はルート証明書を追加しない
+fn parse_ssl_opts(enabled: Option<String>, root_cert_path: Option<String>) -> Option<SslOpts> {
+    let enabled = enabled
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    enabled.then(|| {
+        let root_cert_path = root_cert_path
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        SslOpts::default().with_root_cert_path(root_cert_path)
+    })
+}
+
+// 低速クエリとして警告する実行時間（SLOW_QUERY_MS）
+pub fn get_slow_query_threshold() -> Duration {
+    let millis = match env::var("SLOW_QUERY_MS") {
+        Ok(value) => value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!(
+                "SLOW_QUERY_MS が不正です（{}）。{}ミリ秒を使用します",
+                value, DEFAULT_SLOW_QUERY_MS
+            );
+            DEFAULT_SLOW_QUERY_MS
+        }),
+        Err(_) => DEFAULT_SLOW_QUERY_MS,
+    };
+    Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn var(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn ssl_is_disabled_unless_mysql_ssl_is_truthy() {
+        assert!(parse_ssl_opts(None, var("/etc/ssl/ca.pem")).is_none());
+        for value in ["false", "0", "no", ""] {
+            assert!(parse_ssl_opts(var(value), None).is_none(), "{}", value);
+        }
+        for value in ["true", "1", "YES"] {
+            assert!(parse_ssl_opts(var(value), None).is_some(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn ssl_ca_path_is_added_as_a_root_certificate() {
+        let ssl_opts = parse_ssl_opts(var("true"), var("/etc/ssl/ca.pem")).unwrap();
+        assert_eq!(
+            ssl_opts.root_cert_path(),
+            Some(Path::new("/etc/ssl/ca.pem"))
+        );
+        assert!(!ssl_opts.accept_invalid_certs());
+
+        // 空の MYSQL_SSL_CA は未指定と同じく、システムのルート証明書だけを使う
+        for root_cert_path in [None, var("")] {
+            let ssl_opts = parse_ssl_opts(var("true"), root_cert_path).unwrap();
+            assert_eq!(ssl_opts.root_cert_path(), None);
+        }
+    }
+}
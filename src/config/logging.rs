@@ -0,0 +1,25 @@
+use std::env;
+
+// ログの出力形式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    // 人が読みやすい1行形式
+    Pretty,
+    // ログ収集基盤向けのJSON形式（1行1オブジェクト）
+    Json,
+}
+
+// ログの出力形式（LOG_FORMAT=json / pretty、既定は pretty）
+pub fn get_log_format() -> LogFormat {
+    match env::var("LOG_FORMAT") {
+        Ok(value) => match value.to_ascii_lowercase().as_str() {
+            "json" => LogFormat::Json,
+            "pretty" => LogFormat::Pretty,
+            _ => {
+                eprintln!("LOG_FORMAT が不正です（{}）。pretty を使用します", value);
+                LogFormat::Pretty
+            }
+        },
+        Err(_) => LogFormat::Pretty,
+    }
+}
@@ -0,0 +1,128 @@
+use std::env;
+
+use super::Vars;
+use crate::service::cart::{
+    NormalizationMode, QuantityTransform, RecommendationConfig, VectorEncoding,
+};
+
+// リクエストで指定できる近傍顧客の人数の上限のデフォルト
+const DEFAULT_MAX_NEIGHBORS: usize = 100;
+
+// 近傍から推薦するのに必要な類似度の分散のデフォルト
+const DEFAULT_MIN_SIMILARITY_VARIANCE: f32 = 1e-6;
+
+// 環境変数から推薦の設定を読み込む（起動時に使う）
+pub fn load() -> RecommendationConfig {
+    load_from(&|name| env::var(name).ok())
+}
+
+// 名前から値を引く関数で推薦の設定を読み込む
+// SIGHUP による .env の再読み込みでは、プロセスの環境変数を書き換えずに .env の値を渡す
+pub fn load_from(vars: Vars) -> RecommendationConfig {
+    RecommendationConfig {
+        max_neighbors: get_max_neighbors(vars),
+        mean_center: get_mean_center(vars),
+        encoding: VectorEncoding {
+            transform: get_quantity_transform(vars),
+            normalization: get_normalization(vars),
+        },
+        cooccurrence_half_life_days: get_cooccurrence_half_life_days(vars),
+        cache_ttl: super::cache::get_suggestion_cache_ttl(vars),
+        min_similarity_variance: get_min_similarity_variance(vars),
+        include_orderless_customers: get_include_orderless_customers(vars),
+        ..RecommendationConfig::default()
+    }
+}
+
+// 類似度の計算前に商品ベクトルを平均中心化するか（RECOMMENDATION_MEAN_CENTER=true で有効）
+pub fn get_mean_center(vars: Vars) -> bool {
+    vars("RECOMMENDATION_MEAN_CENTER")
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+// 商品ベクトルの正規化方法（RECOMMENDATION_NORMALIZATION=l2 / l1 / none、既定は none）
+pub fn get_normalization(vars: Vars) -> NormalizationMode {
+    match vars("RECOMMENDATION_NORMALIZATION") {
+        Some(value) => NormalizationMode::parse(&value).unwrap_or_else(|| {
+            eprintln!(
+                "RECOMMENDATION_NORMALIZATION が不正です（{}）。正規化なしを使用します",
+                value
+            );
+            NormalizationMode::default()
+        }),
+        None => NormalizationMode::default(),
+    }
+}
+
+// 注文のない顧客も地域だけのベクトルで近傍に含めるか（RECOMMENDATION_INCLUDE_ORDERLESS_CUSTOMERS=true で有効）
+// 購入履歴のない顧客の地域の傾向を、地域を重視した推薦に生かすために使う
+pub fn get_include_orderless_customers(vars: Vars) -> bool {
+    vars("RECOMMENDATION_INCLUDE_ORDERLESS_CUSTOMERS")
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+// 正規化の前に数量へ適用する変換（RECOMMENDATION_QUANTITY_TRANSFORM=identity / sqrt / log1p / binary、既定は identity）
+pub fn get_quantity_transform(vars: Vars) -> QuantityTransform {
+    match vars("RECOMMENDATION_QUANTITY_TRANSFORM") {
+        Some(value) => QuantityTransform::parse(&value).unwrap_or_else(|| {
+            eprintln!(
+                "RECOMMENDATION_QUANTITY_TRANSFORM が不正です（{}）。変換なしを使用します",
+                value
+            );
+            QuantityTransform::default()
+        }),
+        None => QuantityTransform::default(),
+    }
+}
+
+// 共起回数を注文の新しさで減衰させる半減期（RECOMMENDATION_COOCCURRENCE_HALF_LIFE_DAYS、未設定時は減衰させない）
+pub fn get_cooccurrence_half_life_days(vars: Vars) -> Option<f32> {
+    let value = vars("RECOMMENDATION_COOCCURRENCE_HALF_LIFE_DAYS")?;
+    match value.parse::<f32>() {
+        Ok(days) if days.is_finite() && days > 0.0 => Some(days),
+        _ => {
+            eprintln!(
+                "RECOMMENDATION_COOCCURRENCE_HALF_LIFE_DAYS が不正です（{}）。減衰させずに数えます",
+                value
+            );
+            None
+        }
+    }
+}
+
+// リクエストの neighbors で指定できる近傍顧客の人数の上限（MAX_NEIGHBORS）
+pub fn get_max_neighbors(vars: Vars) -> usize {
+    match vars("MAX_NEIGHBORS") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(max) if max > 0 => max,
+            _ => {
+                eprintln!(
+                    "MAX_NEIGHBORS が不正です（{}）。{}人を使用します",
+                    value, DEFAULT_MAX_NEIGHBORS
+                );
+                DEFAULT_MAX_NEIGHBORS
+            }
+        },
+        None => DEFAULT_MAX_NEIGHBORS,
+    }
+}
+
+// 近傍から推薦するのに必要な、顧客との類似度の分散（RECOMMENDATION_MIN_SIMILARITY_VARIANCE、0で判定しない）
+// これ未満の場合は類似度がほぼ揃っていて近傍を選べないため、人気商品で代替する
+pub fn get_min_similarity_variance(vars: Vars) -> f32 {
+    match vars("RECOMMENDATION_MIN_SIMILARITY_VARIANCE") {
+        Some(value) => match value.parse::<f32>() {
+            Ok(variance) if variance.is_finite() && variance >= 0.0 => variance,
+            _ => {
+                eprintln!(
+                    "RECOMMENDATION_MIN_SIMILARITY_VARIANCE が不正です（{}）。{}を使用します",
+                    value, DEFAULT_MIN_SIMILARITY_VARIANCE
+                );
+                DEFAULT_MIN_SIMILARITY_VARIANCE
+            }
+        },
+        None => DEFAULT_MIN_SIMILARITY_VARIANCE,
+    }
+}
@@ -0,0 +1,73 @@
+use std::env;
+
+// tokioランタイムのスレッド数の設定
+//
+// データベースへのアクセスはすべて spawn_blocking でブロッキングスレッド上で行う
+// （db.rs の各関数に加え、推薦のハンドラもセッションの開始から計算の終わりまでをブロッキング実行する。
+// ワーカースレッドはクエリで塞がらないため、少なくしても X-Request-Timeout-Ms の期限は守られる）
+// 推薦の1リクエストは処理中ずっとプールの接続を1本保持するため、同時に実行できるのは
+// 接続プールの上限（mysqlクレートのデフォルトは100本）までで、それを超えたタスクは接続が空くまで
+// ブロッキングスレッドを占有したまま待つ
+// TOKIO_BLOCKING_THREADS をプールの上限より大きくしても同時実行数は増えず、待機するスレッドが増えるだけなので、
+// プールの上限と同程度にしておくとよい（未指定時はtokioのデフォルトの512）
+
+// 非同期タスクを実行するワーカースレッド数（TOKIO_WORKER_THREADS、未指定時はCPUコア数）
+pub fn get_worker_threads() -> Option<usize> {
+    get_thread_count("TOKIO_WORKER_THREADS")
+}
+
+// ブロッキングタスクを実行するスレッド数の上限（TOKIO_BLOCKING_THREADS、未指定時は512）
+pub fn get_blocking_threads() -> Option<usize> {
+    get_thread_count("TOKIO_BLOCKING_THREADS")
+}
+
+// 環境変数からスレッド数を取得（未設定・不正な場合は None でtokioのデフォルトを使う）
+fn get_thread_count(name: &str) -> Option<usize> {
+    parse_thread_count(name, env::var(name).ok())
+}
+
+// スレッド数の値を読み込む（未設定・0・数値でない場合は None）
+fn parse_thread_count(name: &str, value: Option<String>) -> Option<usize> {
+    let value = value?;
+    match value.parse::<usize>() {
+        Ok(count) if count > 0 => Some(count),
+        _ => {
+            eprintln!(
+                "{} が不正です（{}）。tokioのデフォルトを使用します",
+                name, value
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_counts_are_positive_integers() {
+        assert_eq!(
+            parse_thread_count("TOKIO_WORKER_THREADS", Some("4".to_string())),
+            Some(4)
+        );
+        assert_eq!(
+            parse_thread_count("TOKIO_BLOCKING_THREADS", Some("100".to_string())),
+            Some(100)
+        );
+        // 未設定の場合はtokioのデフォルトを使う
+        assert_eq!(parse_thread_count("TOKIO_WORKER_THREADS", None), None);
+    }
+
+    #[test]
+    fn invalid_thread_counts_fall_back_to_the_tokio_default() {
+        for value in ["0", "-1", "four", "", "1.5"] {
+            assert_eq!(
+                parse_thread_count("TOKIO_BLOCKING_THREADS", Some(value.to_string())),
+                None,
+                "{}",
+                value
+            );
+        }
+    }
+}
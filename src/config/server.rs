@@ -0,0 +1,234 @@
+use axum::http::HeaderValue;
+use std::env;
+use std::time::Duration;
+
+// リクエストボディの最大サイズのデフォルト（256 KiB）
+const DEFAULT_MAX_BODY_BYTES: usize = 256 * 1024;
+
+// リクエストボディの最大サイズ（バイト）
+pub fn get_max_body_bytes() -> usize {
+    match env::var("MAX_BODY_BYTES") {
+        Ok(value) => value.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!(
+                "MAX_BODY_BYTES が不正です（{}）。{}バイトを使用します",
+                value, DEFAULT_MAX_BODY_BYTES
+            );
+            DEFAULT_MAX_BODY_BYTES
+        }),
+        Err(_) => DEFAULT_MAX_BODY_BYTES,
+    }
+}
+
+// クライアントが X-Request-Timeout-Ms で指定できる処理時間の上限のデフォルト（30秒）
+const DEFAULT_MAX_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+// クライアントが指定できる処理時間の上限（これより長い指定は上限に切り詰める）
+pub fn get_max_request_timeout() -> Duration {
+    let millis = match env::var("MAX_REQUEST_TIMEOUT_MS") {
+        Ok(value) => value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!(
+                "MAX_REQUEST_TIMEOUT_MS が不正です（{}）。{}ミリ秒を使用します",
+                value, DEFAULT_MAX_REQUEST_TIMEOUT_MS
+            );
+            DEFAULT_MAX_REQUEST_TIMEOUT_MS
+        }),
+        Err(_) => DEFAULT_MAX_REQUEST_TIMEOUT_MS,
+    };
+    Duration::from_millis(millis)
+}
+
+// リクエストヘッダーの受信を待つ時間のデフォルト（10秒）
+const DEFAULT_HTTP_HEADER_TIMEOUT_MS: u64 = 10_000;
+
+// HTTP/2 の接続の生存確認の間隔のデフォルト（60秒）
+const DEFAULT_HTTP_KEEPALIVE_SECS: u64 = 60;
+
+// リクエストヘッダーを受信し終えるまでの時間の上限（HTTP_HEADER_TIMEOUT_MS）
+// ヘッダーを少しずつ送り続けて接続を占有するクライアント（slow loris）への対策
+pub fn get_http_header_timeout() -> Duration {
+    let millis = match env::var("HTTP_HEADER_TIMEOUT_MS") {
+        Ok(value) => match value.parse::<u64>() {
+            Ok(millis) if millis > 0 => millis,
+            _ => {
+                eprintln!(
+                    "HTTP_HEADER_TIMEOUT_MS が不正です（{}）。{}ミリ秒を使用します",
+                    value, DEFAULT_HTTP_HEADER_TIMEOUT_MS
+                );
+                DEFAULT_HTTP_HEADER_TIMEOUT_MS
+            }
+        },
+        Err(_) => DEFAULT_HTTP_HEADER_TIMEOUT_MS,
+    };
+    Duration::from_millis(millis)
+}
+
+// HTTP/2 の接続の生存確認（PING）の間隔（HTTP_KEEPALIVE_SECS=0 の場合は接続を使い回さない）
+// アイドル時間の上限ではない（使われていない HTTP/1.1 の接続は HTTP_HEADER_TIMEOUT_MS で閉じる）
+pub fn get_http_keepalive_interval() -> Option<Duration> {
+    let secs = match env::var("HTTP_KEEPALIVE_SECS") {
+        Ok(value) => value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!(
+                "HTTP_KEEPALIVE_SECS が不正です（{}）。{}秒を使用します",
+                value, DEFAULT_HTTP_KEEPALIVE_SECS
+            );
+            DEFAULT_HTTP_KEEPALIVE_SECS
+        }),
+        Err(_) => DEFAULT_HTTP_KEEPALIVE_SECS,
+    };
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+// 同時に計算する推薦の数の上限のデフォルト
+const DEFAULT_MAX_CONCURRENT_SUGGESTIONS: usize = 32;
+
+// 同時に計算する推薦の数の上限（MAX_CONCURRENT_SUGGESTIONS、超えたリクエストは503）
+pub fn get_max_concurrent_suggestions() -> usize {
+    match env::var("MAX_CONCURRENT_SUGGESTIONS") {
+        Ok(value) => match value.parse::<usize>() {
+            Ok(max) if max > 0 => max,
+            _ => {
+                eprintln!(
+                    "MAX_CONCURRENT_SUGGESTIONS が不正です（{}）。{}を使用します",
+                    value, DEFAULT_MAX_CONCURRENT_SUGGESTIONS
+                );
+                DEFAULT_MAX_CONCURRENT_SUGGESTIONS
+            }
+        },
+        Err(_) => DEFAULT_MAX_CONCURRENT_SUGGESTIONS,
+    }
+}
+
+// CORSの設定
+pub struct CorsConfig {
+    // 許可するオリジン（None の場合はすべてのオリジンを許可）
+    pub allowed_origins: Option<Vec<HeaderValue>>,
+    // プリフライトの結果をブラウザがキャッシュする期間（None の場合は Access-Control-Max-Age を返さない）
+    pub max_age: Option<Duration>,
+    // Cookieなどの資格情報付きのリクエストを許可するか
+    pub allow_credentials: bool,
+}
+
+// CORSの設定（CORS_ALLOWED_ORIGINS / CORS_MAX_AGE_SECS / CORS_ALLOW_CREDENTIALS）
+pub fn get_cors_config() -> Result<CorsConfig, String> {
+    parse_cors_config(
+        env::var("CORS_ALLOWED_ORIGINS").ok(),
+        env::var("CORS_MAX_AGE_SECS").ok(),
+        env::var("CORS_ALLOW_CREDENTIALS").ok(),
+    )
+}
+
+// CORSの設定の値を読み込む
+// CORS_ALLOWED_ORIGINS はカンマ区切りのオリジン（例: https://example.com,http://localhost:5173）
+// 未指定・空・ワイルドカード（*）を含む場合はすべてのオリジンを許可する
+// 資格情報付きのリクエストではワイルドカードのオリジンが使えないため、
+// CORS_ALLOW_CREDENTIALS=true の場合はオリジンの指定を必須とし、なければ起動時にエラーとする
+fn parse_cors_config(
+    allowed_origins: Option<String>,
+    max_age: Option<String>,
+    allow_credentials: Option<String>,
+) -> Result<CorsConfig, String> {
+    let origins: Vec<&str> = allowed_origins
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .collect();
+    let allowed_origins = if origins.is_empty() || origins.contains(&"*") {
+        None
+    } else {
+        Some(
+            origins
+                .into_iter()
+                .map(|origin| {
+                    HeaderValue::from_str(origin).map_err(|_| {
+                        format!("CORS_ALLOWED_ORIGINS のオリジンが不正です（{}）", origin)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    };
+
+    let max_age = max_age.and_then(|value| match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            eprintln!(
+                "CORS_MAX_AGE_SECS が不正です（{}）。Access-Control-Max-Age は返しません",
+                value
+            );
+            None
+        }
+    });
+
+    let allow_credentials = allow_credentials
+        .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    if allow_credentials && allowed_origins.is_none() {
+        return Err(
+            "CORS_ALLOW_CREDENTIALS=true の場合は CORS_ALLOWED_ORIGINS でオリジンを指定してください"
+                .to_string(),
+        );
+    }
+
+    Ok(CorsConfig {
+        allowed_origins,
+        max_age,
+        allow_credentials,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn cors_origins_and_max_age_are_parsed() {
+        let cors = parse_cors_config(
+            var("https://example.com, http://localhost:5173,"),
+            var("600"),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            cors.allowed_origins.unwrap(),
+            ["https://example.com", "http://localhost:5173"]
+        );
+        assert_eq!(cors.max_age, Some(Duration::from_secs(600)));
+        assert!(!cors.allow_credentials);
+
+        // 不正な max-age は Access-Control-Max-Age を返さない
+        let cors = parse_cors_config(None, var("ten minutes"), None).unwrap();
+        assert!(cors.allowed_origins.is_none());
+        assert_eq!(cors.max_age, None);
+    }
+
+    #[test]
+    fn credentials_require_explicit_origins() {
+        let cors = parse_cors_config(var("https://example.com"), None, var("true")).unwrap();
+        assert!(cors.allow_credentials);
+
+        // ワイルドカード（未指定・空・*）とは組み合わせられない
+        for origins in [None, var(""), var("*"), var("https://example.com,*")] {
+            let result = parse_cors_config(origins.clone(), None, var("true"));
+            assert!(result.is_err(), "{:?}", origins);
+        }
+        assert!(
+            parse_cors_config(var("*"), None, None)
+                .unwrap()
+                .allowed_origins
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn invalid_origin_is_rejected() {
+        let result = parse_cors_config(var("https://exa\u{7f}mple.com"), None, None);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::command;
+use crate::controller::auth::AuthUser;
+use crate::error::AppError;
+use crate::repository::RecommendationRepository;
+use crate::service::cart::RecommendationConfig;
+use crate::service::jobs::{Job, JobRegistry, JobStatus};
+
+// 顧客ベクトルの再計算の処理の種類
+const REBUILD_CUSTOMER_VECTORS: &str = "rebuild_customer_vectors";
+
+// 商品間の類似度の再計算の処理の種類
+const REBUILD_ITEM_SIMILARITY: &str = "rebuild_item_similarity";
+
+#[derive(Serialize)]
+pub struct JobResponse {
+    id: String,
+    kind: &'static str,
+    status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl From<Job> for JobResponse {
+    fn from(job: Job) -> Self {
+        JobResponse {
+            id: job.id,
+            kind: job.kind,
+            status: job.status,
+            message: job.message,
+        }
+    }
+}
+
+// customer_vectors テーブルの再計算をバックグラウンドで開始する（要認証）
+// 商品構成の変更後に、シェルを使わずに作り直すために使う
+// 再計算が実行中の場合は409を返す
+pub async fn post_rebuild_customer_vectors(
+    AuthUser(user): AuthUser,
+    State(pool): State<Arc<mysql::Pool>>,
+    State(jobs): State<Arc<JobRegistry>>,
+) -> Result<(StatusCode, Json<JobResponse>), AppError> {
+    let Some(job_id) = jobs.try_start(REBUILD_CUSTOMER_VECTORS) else {
+        return Err(AppError::Conflict(
+            "Customer vectors are already being rebuilt".to_string(),
+        ));
+    };
+    tracing::info!(
+        user_id = user.id,
+        job_id = %job_id,
+        "顧客ベクトルの再計算を開始しました"
+    );
+
+    spawn_job(jobs.clone(), job_id.clone(), async move {
+        command::vectors::rebuild_customer_vectors(pool)
+            .await
+            .map(|total| format!("Saved vectors for {} customers", total))
+            .map_err(|err| {
+                tracing::error!(error = %err, "顧客ベクトルの再計算に失敗しました");
+                err.to_string()
+            })
+    });
+
+    let job = jobs.get(&job_id).expect("登録したジョブが見つからない");
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
+}
+
+// item_similarity テーブルの再計算をバックグラウンドで開始する（要認証）
+// 商品構成の変更後に、シェルを使わずに作り直すために使う
+// 再計算が実行中の場合は409を返す
+pub async fn post_rebuild_item_similarity(
+    AuthUser(user): AuthUser,
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(jobs): State<Arc<JobRegistry>>,
+) -> Result<(StatusCode, Json<JobResponse>), AppError> {
+    let Some(job_id) = jobs.try_start(REBUILD_ITEM_SIMILARITY) else {
+        return Err(AppError::Conflict(
+            "Item similarity is already being rebuilt".to_string(),
+        ));
+    };
+    tracing::info!(
+        user_id = user.id,
+        job_id = %job_id,
+        "商品間の類似度の再計算を開始しました"
+    );
+
+    spawn_job(jobs.clone(), job_id.clone(), async move {
+        // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+        let result = tokio::task::spawn_blocking(move || {
+            recommendations.session()?.rebuild_item_similarity()
+        })
+        .await;
+        match result {
+            Ok(result) => result
+                .map(|saved| format!("Saved similarities for {} item pairs", saved))
+                .map_err(|err| {
+                    tracing::error!(error = %err, "商品間の類似度の再計算に失敗しました");
+                    err.to_string()
+                }),
+            Err(err) => Err(job_panicked(err)),
+        }
+    });
+
+    let job = jobs.get(&job_id).expect("登録したジョブが見つからない");
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
+}
+
+// ジョブの処理をバックグラウンドで実行し、結果をジョブの状態に記録する
+// 処理がパニックした場合も失敗として記録し、実行中のまま残らないようにする
+fn spawn_job(
+    jobs: Arc<JobRegistry>,
+    job_id: String,
+    task: impl Future<Output = Result<String, String>> + Send + 'static,
+) {
+    tokio::spawn(async move {
+        let result = match tokio::spawn(task).await {
+            Ok(result) => result,
+            Err(err) => Err(job_panicked(err)),
+        };
+        jobs.finish(&job_id, result);
+    });
+}
+
+// 処理のタスクが異常終了したことを記録し、ジョブの失敗理由を返す
+fn job_panicked(err: tokio::task::JoinError) -> String {
+    tracing::error!(error = %err, "ジョブの処理が異常終了しました");
+    "Job terminated unexpectedly".to_string()
+}
+
+// バックグラウンドで実行している処理の状態を返す
+pub async fn get_job(
+    AuthUser(_): AuthUser,
+    State(jobs): State<Arc<JobRegistry>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobResponse>, AppError> {
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", job_id)))?;
+
+    Ok(Json(job.into()))
+}
+
+#[derive(Serialize)]
+pub struct ConfigResponse {
+    suggestion_limit: usize,
+    neighbors: usize,
+    max_neighbors: usize,
+    region_weight: f32,
+    candidate_cap: usize,
+    mean_center: bool,
+    quantity_transform: &'static str,
+    normalization: &'static str,
+    min_neighbor_items: usize,
+    min_neighbor_similarity: Option<f32>,
+    cooccurrence_half_life_days: Option<f32>,
+    cache_ttl_secs: u64,
+    min_similarity_variance: f32,
+    include_orderless_customers: bool,
+}
+
+impl From<&RecommendationConfig> for ConfigResponse {
+    fn from(config: &RecommendationConfig) -> Self {
+        ConfigResponse {
+            suggestion_limit: config.suggestion_limit,
+            neighbors: config.top_users,
+            max_neighbors: config.max_neighbors,
+            region_weight: config.region_weight,
+            candidate_cap: config.candidate_cap,
+            mean_center: config.mean_center,
+            quantity_transform: config.encoding.transform.as_str(),
+            normalization: config.encoding.normalization.as_str(),
+            min_neighbor_items: config.min_neighbor_items,
+            min_neighbor_similarity: config.min_neighbor_similarity,
+            cooccurrence_half_life_days: config.cooccurrence_half_life_days,
+            cache_ttl_secs: config.cache_ttl.as_secs(),
+            min_similarity_variance: config.min_similarity_variance,
+            include_orderless_customers: config.include_orderless_customers,
+        }
+    }
+}
+
+// 現在の推薦の設定を返す（要認証、環境変数の指定が反映されているかの確認用）
+// 推薦の設定には接続情報などの秘密は含まれないため、値をそのまま返す
+pub async fn get_config(
+    AuthUser(_): AuthUser,
+    State(config): State<RecommendationConfig>,
+) -> Json<ConfigResponse> {
+    Json((&config).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use std::sync::Arc;
+
+    use crate::mock::MockStore;
+    use crate::testing;
+
+    use crate::repository::{RecommendationRepository, RecommendationSession};
+
+    // 推薦データの問い合わせを始めるとパニックするストア
+    struct PanickingStore;
+
+    impl RecommendationRepository for PanickingStore {
+        fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+            panic!("テスト用のパニック");
+        }
+    }
+
+    // ジョブを開始し、実行中でなくなるまで状態を問い合わせて最後の状態を返す
+    async fn run_job(app: axum::Router, uri: &str) -> serde_json::Value {
+        let (status, mut job) =
+            testing::send(app.clone(), testing::authorized(testing::post(uri))).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(job["status"], "running");
+
+        let job_uri = format!("/admin/jobs/{}", job["id"].as_str().unwrap());
+        for _ in 0..100 {
+            if job["status"] != "running" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            let (status, polled) =
+                testing::send(app.clone(), testing::authorized(testing::get(&job_uri))).await;
+            assert_eq!(status, StatusCode::OK);
+            job = polled;
+        }
+        job
+    }
+
+    #[tokio::test]
+    async fn rebuild_item_similarity_is_accepted_and_completes() {
+        let app = testing::app(testing::state(
+            Arc::new(testing::TokenUsers),
+            Arc::new(MockStore),
+        ));
+
+        let job = run_job(app, "/admin/rebuild-item-similarity").await;
+
+        assert_eq!(job["kind"], "rebuild_item_similarity");
+        assert_eq!(job["status"], "completed");
+        assert_eq!(job["message"], "Saved similarities for 0 item pairs");
+    }
+
+    #[tokio::test]
+    async fn rebuild_item_similarity_that_panics_marks_the_job_failed() {
+        let app = testing::app(testing::state(
+            Arc::new(testing::TokenUsers),
+            Arc::new(PanickingStore),
+        ));
+
+        let job = run_job(app.clone(), "/admin/rebuild-item-similarity").await;
+
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["message"], "Job terminated unexpectedly");
+
+        // 失敗したジョブは実行中として残らないため、もう一度開始できる
+        let (status, _) = testing::send(
+            app,
+            testing::authorized(testing::post("/admin/rebuild-item-similarity")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn config_lists_every_recommendation_setting() {
+        let app = testing::app(testing::state(
+            Arc::new(testing::TokenUsers),
+            Arc::new(MockStore),
+        ));
+
+        let (status, config) =
+            testing::send(app, testing::authorized(testing::get("/admin/config"))).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let mut keys: Vec<&str> = config
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "cache_ttl_secs",
+                "candidate_cap",
+                "cooccurrence_half_life_days",
+                "include_orderless_customers",
+                "max_neighbors",
+                "mean_center",
+                "min_neighbor_items",
+                "min_neighbor_similarity",
+                "min_similarity_variance",
+                "neighbors",
+                "normalization",
+                "quantity_transform",
+                "region_weight",
+                "suggestion_limit",
+            ]
+        );
+        // 未設定の値は null で返す
+        assert!(config["min_neighbor_similarity"].is_null());
+    }
+
+    #[tokio::test]
+    async fn rebuild_item_similarity_requires_authentication() {
+        let app = testing::app(testing::state(
+            Arc::new(testing::TokenUsers),
+            Arc::new(MockStore),
+        ));
+
+        let (status, _) = testing::send(app, testing::post("/admin/rebuild-item-similarity")).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
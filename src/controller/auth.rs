@@ -0,0 +1,37 @@
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use std::sync::Arc;
+
+use crate::db::User;
+use crate::error::AppError;
+use crate::repository::UserRepository;
+
+// Authorization: Bearer <APIトークン> で認証されたユーザー
+pub struct AuthUser(pub User);
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+    Arc<dyn UserRepository>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or(AppError::Unauthorized)?;
+
+        let users = Arc::<dyn UserRepository>::from_ref(state);
+        match users.find_user_by_api_token(token).await? {
+            Some(user) => Ok(AuthUser(user)),
+            None => Err(AppError::Unauthorized),
+        }
+    }
+}
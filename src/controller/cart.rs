@@ -1,99 +1,2109 @@
 use axum::{
     Json,
-    extract::{Query, State},
+    body::Body,
+    extract::{RawQuery, State},
+    http::{Uri, header},
+    response::{IntoResponse, Response},
+};
+use base64::{
+    Engine, alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
 };
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
+use crate::controller::extract::{Json as JsonBody, Query};
+use crate::error::{AppError, FieldError};
+use crate::repository::{RecommendationRepository, RecommendationSession};
 use crate::service;
+use crate::service::dimensions::DimensionsCache;
+use crate::service::limiter::ConcurrencyLimiter;
+use crate::service::recommender::{RecommendContext, Recommender, RecommenderRegistry};
+use crate::service::region;
 
+// カート内の商品は次のどちらかの形式で指定する
+// - products: JSON配列を文字列にしたもの（例: products=[{"product_variant_id":"X","quantity":2}]）
+// - variant: 商品IDと数量をコロンで区切ったものを繰り返す（例: variant=X:2&variant=Y:1）
+// どちらの形式でも数量を省略した商品は数量1として扱う（例: products=[{"product_variant_id":"X"}]、variant=X）
+//
+// API explorer など文字列化したJSONを扱いにくいクライアント向けに、リクエスト全体を
+// JSON（products は配列のまま）にして base64 で符号化した cart=<base64> も受け付ける。
+// cart がある場合はほかのパラメータ（variant を含む）は使わない
 #[derive(Deserialize)]
 pub struct CartRequest {
     pub province_code: String,
-    #[serde(deserialize_with = "deserialize_products")]
-    pub products: Vec<CartProduct>,
+    #[serde(default, deserialize_with = "deserialize_products")]
+    pub products: Option<Vec<CartProduct>>,
+    // trueの場合、各推薦商品に近傍顧客ごとの寄与を含める
+    #[serde(default)]
+    pub explain: bool,
+    // 地域類似度の計算方法（cosine または adjacency）
+    #[serde(default)]
+    pub region_similarity: service::cart::RegionSimilarity,
+    // 地域と商品の類似度のまとめ方（blended または unified）
+    #[serde(default)]
+    pub similarity_model: service::cart::SimilarityModel,
+    // 商品類似度の計算方法（cosine または adjusted_cosine、blended のみ）
+    #[serde(default)]
+    pub metric: service::cart::SimilarityMetric,
+    // 推薦アルゴリズム（collaborative / cooccurrence / blended / popular）
+    // 未指定時は blend があれば blended、なければ collaborative
+    pub strategy: Option<String>,
+    // 協調フィルタリングと共起のブレンド比率（0.0〜1.0、blended で未指定時は0.5）
+    pub blend: Option<f32>,
+    // 推薦に使う近傍顧客の人数（未指定時は既定値、MAX_NEIGHBORS を超える場合は上限に切り詰める）
+    pub neighbors: Option<usize>,
+    // 近傍顧客として扱うのに必要な購入商品の種類数（未指定時は1で、購入履歴のある顧客すべて）
+    pub min_neighbor_items: Option<usize>,
+    // 近傍顧客として扱うのに必要なカートとの類似度（-1〜1、未指定時は類似度によらず上位 neighbors 人）
+    pub min_neighbor_similarity: Option<f32>,
+    // 購入の扱い方（quantity または binary、binary では数量によらず購入の有無だけで類似度を計算する）
+    #[serde(default)]
+    pub presence: service::cart::Presence,
+    // 人気商品で代替する際の集計期間（日数、未指定時は全期間）
+    pub popular_window: Option<u32>,
+    // 推薦商品を限定する配送温度帯（Normal / Cold / Frozen、未指定時は限定しない）
+    pub temperature: Option<service::cart::Temperature>,
+    // trueの場合、配送温度帯ごとに分けた推薦商品を groups で返す（一緒に配送できない温度帯の商品を別々に提案する）
+    #[serde(default)]
+    pub group_by_temperature: bool,
+    // 指定した顧客が過去に購入した商品を推薦から除く
+    pub exclude_customer: Option<String>,
+    // 推薦から除く商品カテゴリ（カンマ区切り、例: exclude_categories=alcohol,cosmetics）
+    pub exclude_categories: Option<String>,
+    // trueの場合、推薦は行わずカートから作成したベクトルのみを返す（調整用）
+    #[serde(default)]
+    pub vectorize_only: bool,
+    // trueの場合、キャッシュ済みの推薦結果を使わず、計算結果も保存しない
+    #[serde(default)]
+    pub nocache: bool,
+    // レスポンスの形式（json または ndjson）
+    #[serde(default)]
+    pub format: ResponseFormat,
+    // スコアの尺度（raw または percent、percent では返す推薦商品の中で0〜100に正規化する）
+    #[serde(default)]
+    pub score_scale: service::cart::ScoreScale,
+}
+
+// 推薦結果のレスポンス形式
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    // 推薦商品の配列をメッセージとともに1つのJSONで返す
+    #[default]
+    Json,
+    // 推薦商品を1行に1つのJSONオブジェクトとして順に送る（application/x-ndjson）
+    Ndjson,
 }
 
 #[derive(Deserialize)]
 pub struct CartProduct {
     pub product_variant_id: String,
+    // 省略時は1（負の数・小数は受け付けず、MAX_QUANTITY を超える場合は上限に切り詰める）
+    #[serde(
+        default = "default_quantity",
+        deserialize_with = "deserialize_quantity"
+    )]
     pub quantity: u32,
+    // 小数の重み（指定時は数量より優先される）
+    pub weight: Option<f32>,
+}
+
+// 数量が省略された商品の数量
+fn default_quantity() -> u32 {
+    1
+}
+
+// 1商品あたりの数量の上限（極端な数量で商品ベクトルが1商品に偏らないようにする）
+const MAX_QUANTITY: u32 = 10_000;
+
+// 数量を上限に切り詰める
+fn cap_quantity(quantity: u64) -> u32 {
+    if quantity > u64::from(MAX_QUANTITY) {
+        tracing::info!(
+            max_quantity = MAX_QUANTITY,
+            requested = quantity,
+            "数量を上限に切り詰めます"
+        );
+        return MAX_QUANTITY;
+    }
+    quantity as u32
 }
 
 // カスタムデシリアライザ
-fn deserialize_products<'de, D>(deserializer: D) -> Result<Vec<CartProduct>, D::Error>
+// 数量は0以上の整数のみ受け付ける（0は検証で422にする）
+// 符号なし64ビットに収まらない整数はJSONの解析で浮動小数点数になるため、小数部のない巨大な値は上限として扱う
+fn deserialize_quantity<'de, D>(deserializer: D) -> Result<u32, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s = String::deserialize(deserializer)?;
-    serde_json::from_str(&s).map_err(serde::de::Error::custom)
+    struct QuantityVisitor;
+
+    impl serde::de::Visitor<'_> for QuantityVisitor {
+        type Value = u32;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a non-negative integer")
+        }
+
+        fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<u32, E> {
+            Ok(cap_quantity(value))
+        }
+
+        fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<u32, E> {
+            u64::try_from(value)
+                .map(cap_quantity)
+                .map_err(|_| E::custom("quantity must be a non-negative integer"))
+        }
+
+        fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<u32, E> {
+            if value.is_finite() && value.fract() == 0.0 && value >= u64::MAX as f64 {
+                return Ok(cap_quantity(u64::MAX));
+            }
+            Err(E::custom(
+                "quantity must be a non-negative integer (use weight for fractional amounts)",
+            ))
+        }
+    }
+
+    deserializer.deserialize_any(QuantityVisitor)
 }
 
-#[derive(Serialize)]
+// products の値（クエリでは文字列化したJSON、cart のJSONでは配列）
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProductsValue {
+    Stringified(String),
+    List(Vec<CartProduct>),
+}
+
+// カスタムデシリアライザ
+fn deserialize_products<'de, D>(deserializer: D) -> Result<Option<Vec<CartProduct>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match ProductsValue::deserialize(deserializer)? {
+        ProductsValue::Stringified(s) => serde_json::from_str(&s)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        ProductsValue::List(products) => Ok(Some(products)),
+    }
+}
+
+// cart の base64（標準・URLセーフのどちらの文字でも、パディングの有無も問わない）
+const CART_ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// 繰り返しのキーを扱うため、クエリ文字列をキーと値の組として読み直す
+fn query_pairs(raw_query: Option<&str>) -> Result<Vec<(String, String)>, AppError> {
+    raw_query
+        .map(serde_urlencoded::from_str)
+        .transpose()
+        .map_err(|err| AppError::BadRequest(vec![FieldError::new("query", err.to_string())]))
+        .map(Option::unwrap_or_default)
+}
+
+// cart があれば base64 を復号してJSONとして、なければクエリ文字列の各パラメータからリクエストを取り出す
+fn parse_cart_request(uri: &Uri, pairs: &[(String, String)]) -> Result<CartRequest, AppError> {
+    let Some((_, cart)) = pairs.iter().find(|(key, _)| key == "cart") else {
+        let axum::extract::Query(params) = axum::extract::Query::try_from_uri(uri)?;
+        return Ok(params);
+    };
+
+    // 標準の base64 の + はクエリ文字列では空白として届くため、URLセーフの文字にそろえてから復号する
+    let normalized: String = cart
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' | ' ' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+    let json = CART_ENGINE.decode(normalized).map_err(|_| {
+        AppError::BadRequest(vec![FieldError::new("cart", "must be base64-encoded JSON")])
+    })?;
+    serde_json::from_slice(&json)
+        .map_err(|err| AppError::BadRequest(vec![FieldError::new("cart", err.to_string())]))
+}
+
+// products と variant のどちらの形式で送られたかを判定し、カート内の商品を取り出す
+fn resolve_products(
+    products: Option<Vec<CartProduct>>,
+    pairs: &[(String, String)],
+) -> Result<Vec<CartProduct>, AppError> {
+    let variants: Vec<&str> = pairs
+        .iter()
+        .filter(|(key, _)| key == "variant")
+        .map(|(_, value)| value.as_str())
+        .collect();
+
+    match (products, variants.is_empty()) {
+        (Some(products), true) => Ok(products),
+        (None, false) => parse_variants(&variants),
+        (Some(_), false) => Err(AppError::Validation(vec![FieldError::new(
+            "products",
+            "must not be combined with variant",
+        )])),
+        (None, true) => Err(AppError::BadRequest(vec![FieldError::new(
+            "products",
+            "is required",
+        )])),
+    }
+}
+
+// variant=商品ID:数量 の形式をカート内の商品に変換する（variant=商品ID のみの場合は数量1）
+fn parse_variants(variants: &[&str]) -> Result<Vec<CartProduct>, AppError> {
+    let mut products = Vec::with_capacity(variants.len());
+    let mut errors = Vec::new();
+
+    for (i, variant) in variants.iter().enumerate() {
+        let parsed = match variant.rsplit_once(':') {
+            Some((id, quantity)) => quantity
+                .parse::<u64>()
+                .ok()
+                .map(|quantity| (id, cap_quantity(quantity))),
+            None => Some((*variant, default_quantity())),
+        };
+        match parsed {
+            Some((id, quantity)) if !id.is_empty() => products.push(CartProduct {
+                product_variant_id: id.to_string(),
+                quantity,
+                weight: None,
+            }),
+            _ => errors.push(FieldError::new(
+                format!("variant[{}]", i),
+                "must match the format VARIANT_ID or VARIANT_ID:QUANTITY (e.g. 123:2)",
+            )),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(products)
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+// デシリアライズ後のカート内容を検証し、不正な項目をすべて返す
+fn validate_cart(params: &CartRequest, products: &[CartProduct]) -> Vec<FieldError> {
+    let mut errors = validate_province_and_products(&params.province_code, products);
+
+    // unified は地域ベクトルを連結するため、隣接関係による地域類似度とは組み合わせられない
+    errors.extend(validate_similarity(
+        params.similarity_model,
+        params.region_similarity,
+        params.metric,
+    ));
+
+    if let Some(blend) = params.blend
+        && !(0.0..=1.0).contains(&blend)
+    {
+        errors.push(FieldError::new("blend", "must be between 0 and 1"));
+    }
+
+    if params.neighbors == Some(0) {
+        errors.push(FieldError::new("neighbors", "must be at least 1"));
+    }
+
+    if params.min_neighbor_items == Some(0) {
+        errors.push(FieldError::new("min_neighbor_items", "must be at least 1"));
+    }
+
+    errors.extend(validate_min_neighbor_similarity(
+        params.min_neighbor_similarity,
+    ));
+
+    // ベクトルは推薦商品の列ではないため、NDJSONでは返さない
+    if params.vectorize_only && params.format == ResponseFormat::Ndjson {
+        errors.push(FieldError::new(
+            "format",
+            "ndjson cannot be combined with vectorize_only",
+        ));
+    }
+
+    // 温度帯ごとの推薦はすべての温度帯を返すため、1つの温度帯への限定や1列のNDJSONとは組み合わせられない
+    if params.group_by_temperature {
+        if params.temperature.is_some() {
+            errors.push(FieldError::new(
+                "temperature",
+                "cannot be combined with group_by_temperature",
+            ));
+        }
+        if params.format == ResponseFormat::Ndjson {
+            errors.push(FieldError::new(
+                "format",
+                "ndjson cannot be combined with group_by_temperature",
+            ));
+        }
+    }
+
+    errors
+}
+
+// 都道府県コードとカート内の商品を検証する
+fn validate_province_and_products(
+    province_code: &str,
+    products: &[CartProduct],
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    // JP-XX 形式（XXは2桁の数字）であり、実在する都道府県（01〜47）であること
+    // 範囲外のコードは region_to_vector で黙って既定値になるため、ここで弾く
+    let is_valid_format = province_code
+        .strip_prefix("JP-")
+        .is_some_and(|digits| digits.len() == 2 && digits.bytes().all(|b| b.is_ascii_digit()));
+    if !is_valid_format {
+        errors.push(FieldError::new(
+            "province_code",
+            "must match the format JP-NN (e.g. JP-13)",
+        ));
+    } else if region::province_number(province_code).is_none() {
+        errors.push(FieldError::new(
+            "province_code",
+            "must be between JP-01 and JP-47 (see /provinces)",
+        ));
+    }
+
+    for (i, product) in products.iter().enumerate() {
+        if product.quantity < 1 {
+            errors.push(FieldError::new(
+                format!("products[{}].quantity", i),
+                "must be at least 1",
+            ));
+        }
+        if let Some(weight) = product.weight
+            && !(weight.is_finite() && weight >= 0.0)
+        {
+            errors.push(FieldError::new(
+                format!("products[{}].weight", i),
+                "must be a finite, non-negative number",
+            ));
+        }
+    }
+
+    errors
+}
+
+// 近傍顧客に必要な類似度は、類似度の取りうる範囲（-1〜1）で指定する
+fn validate_min_neighbor_similarity(min_neighbor_similarity: Option<f32>) -> Option<FieldError> {
+    min_neighbor_similarity
+        .filter(|min_similarity| !(-1.0..=1.0).contains(min_similarity))
+        .map(|_| FieldError::new("min_neighbor_similarity", "must be between -1 and 1"))
+}
+
+// 地域類似度・商品類似度の計算方法と類似度のまとめ方の組み合わせを検証する
+fn validate_similarity(
+    model: service::cart::SimilarityModel,
+    region: service::cart::RegionSimilarity,
+    metric: service::cart::SimilarityMetric,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if model == service::cart::SimilarityModel::Unified {
+        if region == service::cart::RegionSimilarity::Adjacency {
+            errors.push(FieldError::new(
+                "region_similarity",
+                "adjacency cannot be combined with similarity_model=unified",
+            ));
+        }
+        if metric == service::cart::SimilarityMetric::AdjustedCosine {
+            errors.push(FieldError::new(
+                "metric",
+                "adjusted_cosine cannot be combined with similarity_model=unified",
+            ));
+        }
+    }
+    errors
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContributionResponse {
+    neighbor: String,
+    similarity: f32,
+    contribution: f32,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct SuggestionResponse {
     product_variant_id: String,
     score: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contributions: Option<Vec<ContributionResponse>>,
+}
+
+// ベクトルの0でない成分
+#[derive(Serialize)]
+pub struct VectorComponentResponse {
+    index: usize,
+    product_variant_id: String,
+    value: f32,
+}
+
+// カートから作成したベクトル
+#[derive(Serialize)]
+pub struct VectorResponse {
+    region_vector: Vec<f32>,
+    product_components: Vec<VectorComponentResponse>,
+}
+
+// 配送温度帯ごとの推薦商品
+#[derive(Serialize)]
+pub struct TemperatureGroupResponse {
+    temperature: &'static str,
+    suggestions: Vec<SuggestionResponse>,
 }
 
 #[derive(Serialize)]
 pub struct ApiResponse {
     message: String,
     suggestions: Vec<SuggestionResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<TemperatureGroupResponse>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vector: Option<VectorResponse>,
 }
 
-pub async fn get_suggestions(
-    State(pool): State<Arc<mysql::Pool>>,
-    Query(params): Query<CartRequest>,
-) -> Json<ApiResponse> {
-    // 商品次元情報を取得
-    let product_dimensions = match service::cart::fetch_product_dimensions(&pool).await {
-        Ok(dimensions) => dimensions,
-        Err(err) => {
-            return Json(ApiResponse {
-                message: format!("Error fetching product dimensions: {}", err),
-                suggestions: vec![],
+// 推薦結果を指定された形式・スコアの尺度のレスポンスにする
+// キャッシュには計算したままのスコアを保存し、尺度はレスポンスを作るときに合わせる
+fn suggestions_response(
+    format: ResponseFormat,
+    score_scale: service::cart::ScoreScale,
+    message: &str,
+    mut suggestions: Vec<SuggestionResponse>,
+) -> Response {
+    score_scale.apply(
+        suggestions
+            .iter_mut()
+            .map(|suggestion| &mut suggestion.score),
+    );
+    match format {
+        ResponseFormat::Json => Json(ApiResponse {
+            message: message.to_string(),
+            suggestions,
+            groups: None,
+            vector: None,
+        })
+        .into_response(),
+        ResponseFormat::Ndjson => {
+            // 推薦商品を1件ずつJSONにして改行で区切り、そのまま本文として流す
+            let lines = suggestions.into_iter().map(|suggestion| {
+                let mut line = serde_json::to_vec(&suggestion)?;
+                line.push(b'\n');
+                Ok::<_, serde_json::Error>(line)
             });
+            (
+                [(header::CONTENT_TYPE, "application/x-ndjson")],
+                Body::from_stream(futures_util::stream::iter(lines)),
+            )
+                .into_response()
         }
-    };
+    }
+}
 
-    // CartProductをProductItemに変換
-    let product_items: Vec<service::cart::ProductItem> = params
-        .products
+// 推薦結果のキャッシュキーを作成する
+// 同じカートは商品の指定順によらず同じキーになるよう、商品IDの順に並べてから正規化する
+// 推薦結果に影響するクエリパラメータと、リクエスト時点の推薦の設定全体（近傍の人数などを反映したもの）を含める。
+// 設定を読み込み直した場合も、変更前の設定で計算した結果は使われない
+fn suggestion_cache_key(
+    params: &CartRequest,
+    products: &[CartProduct],
+    strategy: &str,
+    config: &service::cart::RecommendationConfig,
+) -> String {
+    let mut items: Vec<String> = products
+        .iter()
+        .map(|p| format!("{}:{}:{:?}", p.product_variant_id, p.quantity, p.weight))
+        .collect();
+    items.sort();
+
+    let canonical = format!(
+        "{}|{}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{:?}",
+        params.province_code,
+        items.join(","),
+        strategy,
+        params.blend,
+        params.region_similarity,
+        params.similarity_model,
+        params.metric,
+        params.popular_window,
+        params.temperature,
+        params.exclude_customer,
+        excluded_categories(params),
+        params.explain,
+        config,
+    );
+    service::suggestion_cache::cache_key(&canonical)
+}
+
+// exclude_categories のカンマ区切りを、重複を除いて名前順に並べた一覧にする
+fn excluded_categories(params: &CartRequest) -> Vec<String> {
+    let categories: BTreeSet<String> = params
+        .exclude_categories
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|category| !category.is_empty())
+        .map(str::to_string)
+        .collect();
+    categories.into_iter().collect()
+}
+
+// 除外するカテゴリがすべて存在するか確認する（存在しないカテゴリは422）
+fn validate_categories(
+    session: &mut dyn RecommendationSession,
+    categories: &[String],
+) -> Result<(), AppError> {
+    if categories.is_empty() {
+        return Ok(());
+    }
+
+    let known = session.fetch_product_categories()?;
+    let unknown: Vec<&str> = categories
+        .iter()
+        .filter(|category| !known.contains(*category))
+        .map(String::as_str)
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(vec![FieldError::new(
+            "exclude_categories",
+            format!("unknown categories: {}", unknown.join(", ")),
+        )]))
+    }
+}
+
+// キャッシュ済みの商品次元情報を取得（キャッシュがなければ取得してキャッシュする）
+fn cached_dimensions(
+    dimensions: &DimensionsCache,
+    session: &mut dyn RecommendationSession,
+) -> Result<Arc<service::cart::ProductDimensions>, mysql::Error> {
+    match dimensions.get() {
+        Some(product_dimensions) => Ok(product_dimensions),
+        None => session
+            .fetch_product_dimensions()
+            .map(|product_dimensions| dimensions.replace(product_dimensions)),
+    }
+}
+
+// CartProductをProductItemに変換
+fn to_product_items(products: &[CartProduct]) -> Vec<service::cart::ProductItem> {
+    products
         .iter()
         .map(|p| service::cart::ProductItem {
             product_variant_id: p.product_variant_id.clone(),
             quantity: p.quantity,
+            weight: p.weight,
         })
-        .collect();
+        .collect()
+}
+
+pub async fn get_suggestions(
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions): State<Arc<DimensionsCache>>,
+    State(mut config): State<service::cart::RecommendationConfig>,
+    State(recommenders): State<Arc<RecommenderRegistry>>,
+    State(limiter): State<Arc<ConcurrencyLimiter>>,
+    uri: Uri,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, AppError> {
+    // 同時に計算できる数を超えている場合は待たずに503を返す
+    // 枠は推薦の計算（ブロッキングタスク）に渡し、期限切れでハンドラが打ち切られても計算が終わるまで保持する
+    let Some(permit) = limiter.try_acquire() else {
+        tracing::warn!("同時に計算できる推薦の数を超えたため断ります");
+        return Err(AppError::Overloaded);
+    };
+
+    // 入力値を検証
+    let pairs = query_pairs(raw_query.as_deref())?;
+    let mut params = parse_cart_request(&uri, &pairs)?;
+    // cart で送られた場合は variant の指定も使わない
+    let variant_pairs = if pairs.iter().any(|(key, _)| key == "cart") {
+        &[][..]
+    } else {
+        &pairs[..]
+    };
+    let products = resolve_products(params.products.take(), variant_pairs)?;
+    let mut errors = validate_cart(&params, &products);
+
+    // 推薦アルゴリズムが登録済みであること
+    let strategy = params.strategy.as_deref().unwrap_or(match params.blend {
+        Some(_) => "blended",
+        None => "collaborative",
+    });
+    let recommender = recommenders.get(strategy);
+    if recommender.is_none() {
+        errors.push(FieldError::new(
+            "strategy",
+            format!("must be one of: {}", recommenders.names().join(", ")),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    apply_neighbor_options(
+        &mut config,
+        params.neighbors,
+        params.min_neighbor_items,
+        params.min_neighbor_similarity,
+        params.presence,
+    );
+
+    // MySQLはasyncに対応していないため、データベースの問い合わせを含む推薦の計算はブロッキング実行する
+    // 非同期のワーカーを塞がないため、X-Request-Timeout-Ms の期限を過ぎた時点で504を返せる
+    let strategy = strategy.to_string();
+    let recommender = recommender.expect("検証済みの推薦アルゴリズム");
+    tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        suggest(
+            recommendations.as_ref(),
+            &dimensions,
+            &config,
+            recommender.as_ref(),
+            &params,
+            &products,
+            &strategy,
+        )
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")
+}
+
+// リクエストでの近傍顧客の選び方の指定を推薦の設定に反映する（推薦と近傍のプレビューで共通）
+// 近傍顧客の人数の指定は、走査と購入商品の一括取得が膨らまないよう上限で切り詰める
+// 購入の扱い方はベクトルの作り方に含めるため、推薦結果のキャッシュのキーにも反映される
+fn apply_neighbor_options(
+    config: &mut service::cart::RecommendationConfig,
+    neighbors: Option<usize>,
+    min_neighbor_items: Option<usize>,
+    min_neighbor_similarity: Option<f32>,
+    presence: service::cart::Presence,
+) {
+    if let Some(neighbors) = neighbors {
+        if neighbors > config.max_neighbors {
+            tracing::info!(
+                max_neighbors = config.max_neighbors,
+                requested = neighbors,
+                "近傍顧客の人数を上限に切り詰めます"
+            );
+        }
+        config.top_users = neighbors.min(config.max_neighbors);
+    }
+    if let Some(min_neighbor_items) = min_neighbor_items {
+        config.min_neighbor_items = min_neighbor_items;
+    }
+    if let Some(min_neighbor_similarity) = min_neighbor_similarity {
+        config.min_neighbor_similarity = Some(min_neighbor_similarity);
+    }
+    config.encoding = presence.apply(config.encoding);
+}
+
+// 検証済みのリクエストから推薦結果のレスポンスを作成する（データベースに問い合わせるためブロッキング実行する）
+fn suggest(
+    recommendations: &dyn RecommendationRepository,
+    dimensions: &DimensionsCache,
+    config: &service::cart::RecommendationConfig,
+    recommender: &dyn Recommender,
+    params: &CartRequest,
+    products: &[CartProduct],
+    strategy: &str,
+) -> Result<Response, AppError> {
+    // リクエスト全体で1つの接続を使い回す
+    // 接続できない・途中で切れた場合は AppError で503になる
+    let mut session = recommendations.session()?;
+
+    // 除外カテゴリは存在するものだけを受け付ける
+    let excluded_categories = excluded_categories(params);
+    validate_categories(session.as_mut(), &excluded_categories)?;
+
+    // 同じ内容のリクエストの推薦結果がキャッシュされていればそれを返す（温度帯ごとの推薦はキャッシュしない）
+    let cache_key = (!params.nocache
+        && !params.vectorize_only
+        && !params.group_by_temperature
+        && !config.cache_ttl.is_zero())
+    .then(|| suggestion_cache_key(params, products, strategy, config));
+    if let Some(key) = &cache_key {
+        match session.fetch_cached_suggestions(key, config.cache_ttl) {
+            Ok(Some(cached)) => match serde_json::from_str(&cached) {
+                Ok(suggestions) => {
+                    tracing::info!("キャッシュ済みの推薦結果を返します");
+                    return Ok(suggestions_response(
+                        params.format,
+                        params.score_scale,
+                        "Successfully generated suggestions (cached)",
+                        suggestions,
+                    ));
+                }
+                Err(err) => tracing::warn!(error = %err, "キャッシュ済みの推薦結果が不正です"),
+            },
+            Ok(None) => {}
+            Err(err) => tracing::warn!(error = %err, "推薦結果のキャッシュ取得エラー"),
+        }
+    }
+
+    // 商品次元情報を取得（キャッシュがなければ取得してキャッシュする）
+    let product_dimensions = cached_dimensions(dimensions, session.as_mut())?;
+
+    // CartProductをProductItemに変換
+    let product_items = to_product_items(products);
 
     // 現在のユーザーベクトルを作成
     let current_user = service::cart::create_order_vector(
         &params.province_code,
         &product_items,
         &product_dimensions,
+        config.encoding,
     );
 
-    // 他のユーザーの履歴を取得して類似度を計算
-    let similar_product_scores = service::cart::get_similar_products(
-        &pool,
-        &current_user,
-        &product_items,
-        &product_dimensions,
-    )
-    .await;
+    // vectorize_only 指定時は近傍の探索を行わずにベクトルを返す
+    if params.vectorize_only {
+        let product_components = current_user
+            .product_vector
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value != 0.0)
+            .filter_map(|(index, &value)| {
+                let product_variant_id = product_dimensions.get_product_id_from_index(index)?;
+                Some(VectorComponentResponse {
+                    index,
+                    product_variant_id: product_variant_id.clone(),
+                    value,
+                })
+            })
+            .collect();
+
+        return Ok(Json(ApiResponse {
+            message: "Successfully vectorized cart".to_string(),
+            suggestions: vec![],
+            groups: None,
+            vector: Some(VectorResponse {
+                region_vector: current_user.region_vector,
+                product_components,
+            }),
+        })
+        .into_response());
+    }
+
+    // 推薦候補の絞り込み条件（顧客指定時はその顧客の購入済み商品を除く）
+    let mut filter = service::cart::SuggestionFilter {
+        temperature: params.temperature,
+        excluded_categories,
+        customer_id: params.exclude_customer.clone(),
+        ..Default::default()
+    };
+    if let Some(customer_id) = &params.exclude_customer {
+        let mut purchases = session.fetch_user_products(std::slice::from_ref(customer_id))?;
+        filter.excluded_variant_ids = purchases
+            .remove(customer_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| p.product_variant_id)
+            .collect();
+    }
+
+    // 選択された推薦アルゴリズムで推薦商品を取得
+    let context = RecommendContext {
+        config,
+        current_order: &current_user,
+        current_products: &product_items,
+        product_dimensions: &product_dimensions,
+        similarity: service::cart::SimilarityMethod {
+            model: params.similarity_model,
+            region: params.region_similarity,
+            metric: params.metric,
+        },
+        blend: params.blend,
+        popular_window: params.popular_window,
+        filter: &filter,
+    };
+
+    // 温度帯ごとに絞り込んで推薦し、温度帯ごとの組にして返す
+    if params.group_by_temperature {
+        let groups = service::cart::Temperature::ALL
+            .into_iter()
+            .map(|temperature| {
+                let filter = service::cart::SuggestionFilter {
+                    temperature: Some(temperature),
+                    ..filter.clone()
+                };
+                let context = RecommendContext {
+                    filter: &filter,
+                    ..context
+                };
+                let mut suggestions =
+                    recommend_suggestions(session.as_mut(), recommender, &context, params)?;
+                params.score_scale.apply(
+                    suggestions
+                        .iter_mut()
+                        .map(|suggestion| &mut suggestion.score),
+                );
+                Ok(TemperatureGroupResponse {
+                    temperature: temperature.as_str(),
+                    suggestions,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        return Ok(Json(ApiResponse {
+            message: "Successfully generated suggestions by temperature".to_string(),
+            suggestions: vec![],
+            groups: Some(groups),
+            vector: None,
+        })
+        .into_response());
+    }
+
+    let suggestions = recommend_suggestions(session.as_mut(), recommender, &context, params)?;
+
+    // 推薦結果をキャッシュに保存（失敗しても結果はそのまま返す）
+    if let Some(key) = &cache_key {
+        let serialized = serde_json::to_string(&suggestions).expect("推薦結果のシリアライズに失敗");
+        if let Err(err) = session.store_cached_suggestions(key, &serialized, config.cache_ttl) {
+            tracing::warn!(error = %err, "推薦結果のキャッシュ保存エラー");
+        }
+    }
+
+    Ok(suggestions_response(
+        params.format,
+        params.score_scale,
+        "Successfully generated suggestions",
+        suggestions,
+    ))
+}
+
+// 推薦アルゴリズムで推薦商品を求め、レスポンスの形にする（近傍から推薦できない場合は人気商品で代替する）
+fn recommend_suggestions(
+    session: &mut dyn RecommendationSession,
+    recommender: &dyn Recommender,
+    context: &RecommendContext<'_>,
+    params: &CartRequest,
+) -> Result<Vec<SuggestionResponse>, AppError> {
+    let mut similar_product_scores = recommender.recommend(session, context)?;
+
+    // 近傍から推薦できない場合は人気商品で代替
+    if similar_product_scores.is_empty() {
+        tracing::info!("類似商品がないため人気商品で代替します");
+        similar_product_scores = service::cart::get_popular_products(
+            session,
+            context.config,
+            context.current_products,
+            params.popular_window,
+            context.filter,
+        )?;
+    }
+
+    tracing::info!(
+        count = similar_product_scores.len(),
+        "類似商品を取得しました"
+    );
+
+    Ok(similar_product_scores
+        .into_iter()
+        .map(|suggestion| SuggestionResponse {
+            product_variant_id: suggestion.product_id,
+            score: suggestion.score,
+            // explain指定時のみ寄与の内訳を返す
+            contributions: params.explain.then(|| {
+                suggestion
+                    .contributions
+                    .into_iter()
+                    .map(|c| ContributionResponse {
+                        neighbor: c.neighbor,
+                        similarity: c.similarity,
+                        contribution: c.contribution,
+                    })
+                    .collect()
+            }),
+        })
+        .collect())
+}
+
+// 並べ替える候補商品とカートの内容
+#[derive(Deserialize)]
+pub struct RerankRequest {
+    pub province_code: String,
+    pub products: Vec<CartProduct>,
+    // 並べ替える候補商品のID
+    pub candidates: Vec<String>,
+    // 地域類似度の計算方法（cosine または adjacency）
+    #[serde(default)]
+    pub region_similarity: service::cart::RegionSimilarity,
+    // 地域と商品の類似度のまとめ方（blended または unified）
+    #[serde(default)]
+    pub similarity_model: service::cart::SimilarityModel,
+    // 商品類似度の計算方法（cosine または adjusted_cosine、blended のみ）
+    #[serde(default)]
+    pub metric: service::cart::SimilarityMetric,
+}
+
+// 指定された候補商品をカートとの関連度の高い順に並べ替える
+// 推薦候補の全体を計算し直さず、近傍顧客によるスコアを候補だけについて求める
+pub async fn post_rerank(
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions): State<Arc<DimensionsCache>>,
+    State(config): State<service::cart::RecommendationConfig>,
+    JsonBody(body): JsonBody<RerankRequest>,
+) -> Result<Json<ApiResponse>, AppError> {
+    // 入力値を検証
+    let mut errors = validate_province_and_products(&body.province_code, &body.products);
+    errors.extend(validate_similarity(
+        body.similarity_model,
+        body.region_similarity,
+        body.metric,
+    ));
+    if body.candidates.is_empty() {
+        errors.push(FieldError::new("candidates", "must not be empty"));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    // MySQLはasyncに対応していないため、データベースの問い合わせを含む計算はブロッキング実行する
+    let reranked = tokio::task::spawn_blocking(move || {
+        // リクエスト全体で1つの接続を使い回す
+        // 接続できない・途中で切れた場合は AppError で503になる
+        let mut session = recommendations.session()?;
+
+        let product_dimensions = cached_dimensions(&dimensions, session.as_mut())?;
+
+        let product_items = to_product_items(&body.products);
+        let current_user = service::cart::create_order_vector(
+            &body.province_code,
+            &product_items,
+            &product_dimensions,
+            config.encoding,
+        );
 
-    println!("{}件の類似商品を取得しました", similar_product_scores.len());
+        service::cart::rerank_candidates(
+            session.as_mut(),
+            &config,
+            &current_user,
+            &product_items,
+            &product_dimensions,
+            service::cart::SimilarityMethod {
+                model: body.similarity_model,
+                region: body.region_similarity,
+                metric: body.metric,
+            },
+            &body.candidates,
+        )
+        .map_err(AppError::from)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    tracing::info!(count = reranked.len(), "候補商品を並べ替えました");
 
-    let suggestions = similar_product_scores
+    let suggestions = reranked
         .into_iter()
-        .map(|(product_id, score)| SuggestionResponse {
-            product_variant_id: product_id,
-            score,
+        .map(|suggestion| SuggestionResponse {
+            product_variant_id: suggestion.product_id,
+            score: suggestion.score,
+            contributions: None,
         })
         .collect();
 
-    Json(ApiResponse {
-        message: "Successfully generated suggestions".to_string(),
-        suggestions: suggestions,
+    Ok(Json(ApiResponse {
+        message: "Successfully reranked candidates".to_string(),
+        suggestions,
+        groups: None,
+        vector: None,
+    }))
+}
+
+// 近傍顧客を確認するカートの内容
+#[derive(Deserialize)]
+pub struct NeighborsRequest {
+    pub province_code: String,
+    pub products: Vec<CartProduct>,
+    // 地域類似度の計算方法（cosine または adjacency）
+    #[serde(default)]
+    pub region_similarity: service::cart::RegionSimilarity,
+    // 地域と商品の類似度のまとめ方（blended または unified）
+    #[serde(default)]
+    pub similarity_model: service::cart::SimilarityModel,
+    // 商品類似度の計算方法（cosine または adjusted_cosine、blended のみ）
+    #[serde(default)]
+    pub metric: service::cart::SimilarityMetric,
+}
+
+#[derive(Deserialize)]
+pub struct NeighborsQuery {
+    // 返す近傍顧客の人数（未指定時は既定値、MAX_NEIGHBORS を超える場合は上限に切り詰める）
+    pub neighbors: Option<usize>,
+    // 近傍顧客として扱うのに必要な購入商品の種類数（未指定時は1）
+    pub min_neighbor_items: Option<usize>,
+    // 近傍顧客として扱うのに必要なカートとの類似度（-1〜1、未指定時は類似度によらない）
+    pub min_neighbor_similarity: Option<f32>,
+    // 購入の扱い方（quantity または binary）
+    #[serde(default)]
+    pub presence: service::cart::Presence,
+}
+
+#[derive(Serialize)]
+pub struct NeighborResponse {
+    neighbor: String,
+    similarity: f32,
+}
+
+#[derive(Serialize)]
+pub struct NeighborsResponse {
+    message: String,
+    neighbors: Vec<NeighborResponse>,
+}
+
+// カートに類似する近傍顧客とその類似度を返す（推薦の調整用）
+// 推薦と同じ方法で近傍を選ぶが、購入商品の集計は行わない。顧客IDは順位のラベルに置き換える
+pub async fn post_neighbors(
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions): State<Arc<DimensionsCache>>,
+    State(mut config): State<service::cart::RecommendationConfig>,
+    Query(query): Query<NeighborsQuery>,
+    JsonBody(body): JsonBody<NeighborsRequest>,
+) -> Result<Json<NeighborsResponse>, AppError> {
+    // 入力値を検証
+    let mut errors = validate_province_and_products(&body.province_code, &body.products);
+    errors.extend(validate_similarity(
+        body.similarity_model,
+        body.region_similarity,
+        body.metric,
+    ));
+    if query.neighbors == Some(0) {
+        errors.push(FieldError::new("neighbors", "must be at least 1"));
+    }
+    if query.min_neighbor_items == Some(0) {
+        errors.push(FieldError::new("min_neighbor_items", "must be at least 1"));
+    }
+    errors.extend(validate_min_neighbor_similarity(
+        query.min_neighbor_similarity,
+    ));
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    apply_neighbor_options(
+        &mut config,
+        query.neighbors,
+        query.min_neighbor_items,
+        query.min_neighbor_similarity,
+        query.presence,
+    );
+
+    // MySQLはasyncに対応していないため、データベースの問い合わせを含む計算はブロッキング実行する
+    let neighbors = tokio::task::spawn_blocking(move || {
+        // 接続できない・途中で切れた場合は AppError で503になる
+        let mut session = recommendations.session()?;
+
+        let product_dimensions = cached_dimensions(&dimensions, session.as_mut())?;
+
+        let product_items = to_product_items(&body.products);
+        let current_user = service::cart::create_order_vector(
+            &body.province_code,
+            &product_items,
+            &product_dimensions,
+            config.encoding,
+        );
+
+        service::cart::find_neighbors(
+            session.as_mut(),
+            &config,
+            &current_user,
+            &product_dimensions,
+            service::cart::SimilarityMethod {
+                model: body.similarity_model,
+                region: body.region_similarity,
+                metric: body.metric,
+            },
+        )
+        .map_err(AppError::from)
     })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    let neighbors = neighbors
+        .into_iter()
+        .enumerate()
+        .map(|(rank, customer_score)| NeighborResponse {
+            neighbor: service::cart::neighbor_label(rank),
+            similarity: customer_score.score,
+        })
+        .collect();
+
+    Ok(Json(NeighborsResponse {
+        message: "Successfully found neighbors".to_string(),
+        neighbors,
+    }))
+}
+
+// 比較するカートの一方
+#[derive(Deserialize)]
+pub struct SimilarityCart {
+    pub province_code: String,
+    pub products: Vec<CartProduct>,
+}
+
+// 類似度を計算する2つのカート
+#[derive(Deserialize)]
+pub struct SimilarityRequest {
+    pub a: SimilarityCart,
+    pub b: SimilarityCart,
+    // 類似度に占める地域類似度の重み（省略時は設定値）
+    pub region_weight: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct SimilarityResponse {
+    message: String,
+    combined_similarity: f32,
+    product_similarity: f32,
+    region_similarity: f32,
+    region_weight: f32,
+}
+
+// 2つのカートの類似度を、推薦で近傍顧客を選ぶときと同じ方法で計算する（推薦の入力の比較用）
+pub async fn post_similarity(
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions): State<Arc<DimensionsCache>>,
+    State(config): State<service::cart::RecommendationConfig>,
+    JsonBody(body): JsonBody<SimilarityRequest>,
+) -> Result<Json<SimilarityResponse>, AppError> {
+    // 入力値を検証（どちらのカートの項目かわかるよう、項目名に a. / b. を付ける）
+    let mut errors = Vec::new();
+    for (name, cart) in [("a", &body.a), ("b", &body.b)] {
+        errors.extend(
+            validate_province_and_products(&cart.province_code, &cart.products)
+                .into_iter()
+                .map(|error| FieldError::new(format!("{}.{}", name, error.field), error.reason)),
+        );
+    }
+    if let Some(region_weight) = body.region_weight
+        && !(0.0..=1.0).contains(&region_weight)
+    {
+        errors.push(FieldError::new("region_weight", "must be between 0 and 1"));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+    let region_weight = body.region_weight.unwrap_or(config.region_weight);
+
+    // MySQLはasyncに対応していないため、商品次元情報の取得（キャッシュがない場合）はブロッキング実行する
+    let product_dimensions = tokio::task::spawn_blocking(move || {
+        // 接続できない・途中で切れた場合は AppError で503になる
+        let mut session = recommendations.session()?;
+        cached_dimensions(&dimensions, session.as_mut()).map_err(AppError::from)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    let [a, b] = [&body.a, &body.b].map(|cart| {
+        service::cart::create_order_vector(
+            &cart.province_code,
+            &to_product_items(&cart.products),
+            &product_dimensions,
+            config.encoding,
+        )
+    });
+
+    let similarity = service::cart::similarity_breakdown(&a, &b, region_weight)
+        .map_err(service::cart::RecommendError::from)?;
+
+    Ok(Json(SimilarityResponse {
+        message: "Successfully computed similarity".to_string(),
+        combined_similarity: similarity.combined,
+        product_similarity: similarity.product,
+        region_similarity: similarity.region,
+        region_weight,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::mock::MockStore;
+    use crate::testing;
+
+    // 固定データのストアを使うルーター
+    fn mock_app() -> axum::Router {
+        let store = Arc::new(MockStore);
+        testing::app(testing::state(store.clone(), store))
+    }
+
+    // クエリパラメータを符号化した /suggestions のURI
+    fn suggestions_uri(params: &[(&str, &str)]) -> String {
+        format!(
+            "/suggestions?{}",
+            serde_urlencoded::to_string(params).unwrap()
+        )
+    }
+
+    // カート内の商品（products パラメータの値）
+    const CART: &str = r#"[{"product_variant_id": "mock-variant-1", "quantity": 2}]"#;
+
+    fn quantity(json: &str) -> Result<u32, serde_json::Error> {
+        serde_json::from_str::<CartProduct>(&format!(
+            r#"{{"product_variant_id": "a", "quantity": {}}}"#,
+            json
+        ))
+        .map(|product| product.quantity)
+    }
+
+    #[test]
+    fn quantity_accepts_non_negative_integers() {
+        assert_eq!(quantity("3").unwrap(), 3);
+        assert_eq!(quantity("0").unwrap(), 0);
+        let omitted: CartProduct = serde_json::from_str(r#"{"product_variant_id": "a"}"#).unwrap();
+        assert_eq!(omitted.quantity, 1);
+    }
+
+    #[tokio::test]
+    async fn products_without_quantity_are_treated_as_quantity_one() {
+        let explicit = r#"[{"product_variant_id": "mock-variant-1", "quantity": 1}, {"product_variant_id": "mock-variant-2", "quantity": 1}]"#;
+        let omitted = r#"[{"product_variant_id": "mock-variant-1"}, {"product_variant_id": "mock-variant-2"}]"#;
+        let cart = json!({
+            "province_code": "JP-13",
+            "products": serde_json::from_str::<serde_json::Value>(omitted).unwrap(),
+        });
+        let encoded = base64::engine::general_purpose::STANDARD.encode(cart.to_string());
+        let suggest = |params: &[(&str, &str)]| {
+            testing::send(mock_app(), testing::get(&suggestions_uri(params)))
+        };
+
+        let (status, expected) =
+            suggest(&[("province_code", "JP-13"), ("products", explicit)]).await;
+        let (_, stringified) = suggest(&[("province_code", "JP-13"), ("products", omitted)]).await;
+        let (_, array) = suggest(&[("cart", &encoded)]).await;
+        let (_, variants) = suggest(&[
+            ("province_code", "JP-13"),
+            ("variant", "mock-variant-1"),
+            ("variant", "mock-variant-2"),
+        ])
+        .await;
+
+        assert_eq!(status, StatusCode::OK, "{}", expected);
+        assert!(!expected["suggestions"].as_array().unwrap().is_empty());
+        assert_eq!(stringified, expected);
+        assert_eq!(array, expected);
+        assert_eq!(variants, expected);
+    }
+
+    // 推薦された商品ID
+    fn suggested_ids(body: &serde_json::Value) -> Vec<String> {
+        body["suggestions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["product_variant_id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn excluded_categories_never_appear_in_suggestions() {
+        // drink と ice のカテゴリの商品
+        let excluded = ["mock-variant-3", "mock-variant-4", "mock-variant-5"];
+        let suggest = |extra: Vec<(&'static str, &'static str)>| async move {
+            let mut params = vec![("province_code", "JP-13"), ("products", CART)];
+            params.extend(extra);
+            let (status, body) =
+                testing::send(mock_app(), testing::get(&suggestions_uri(&params))).await;
+            assert_eq!(status, StatusCode::OK, "{:?}: {}", params, body);
+            suggested_ids(&body)
+        };
+
+        for strategy in ["collaborative", "cooccurrence", "blended", "popular"] {
+            let all = suggest(vec![("strategy", strategy)]).await;
+            let filtered = suggest(vec![
+                ("strategy", strategy),
+                ("exclude_categories", "drink,ice"),
+            ])
+            .await;
+
+            assert!(
+                all.iter().any(|id| excluded.contains(&id.as_str())),
+                "{}: {:?}",
+                strategy,
+                all
+            );
+            assert!(
+                !filtered.iter().any(|id| excluded.contains(&id.as_str())),
+                "{}: {:?}",
+                strategy,
+                filtered
+            );
+        }
+
+        // 近傍がいない場合の人気商品による代替でも除外する
+        let fallback = suggest(vec![("min_neighbor_similarity", "1")]).await;
+        let filtered_fallback = suggest(vec![
+            ("min_neighbor_similarity", "1"),
+            ("exclude_categories", "drink,ice"),
+        ])
+        .await;
+        assert_eq!(fallback, suggest(vec![("strategy", "popular")]).await);
+        assert!(
+            fallback.iter().any(|id| excluded.contains(&id.as_str())),
+            "{:?}",
+            fallback
+        );
+        assert_eq!(filtered_fallback, ["mock-variant-2"]);
+    }
+
+    #[tokio::test]
+    async fn group_by_temperature_returns_one_group_per_zone() {
+        let uri = suggestions_uri(&[
+            ("province_code", "JP-13"),
+            ("products", CART),
+            ("group_by_temperature", "true"),
+        ]);
+
+        let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        // 固定データの商品の温度帯
+        let zone = |variant_id: &str| match variant_id {
+            "mock-variant-1" | "mock-variant-3" => "Normal",
+            "mock-variant-2" | "mock-variant-4" => "Cold",
+            _ => "Frozen",
+        };
+        let groups = body["groups"].as_array().unwrap();
+        let temperatures: Vec<&str> = groups
+            .iter()
+            .map(|group| group["temperature"].as_str().unwrap())
+            .collect();
+        assert_eq!(temperatures, ["Normal", "Cold", "Frozen"]);
+        for group in groups {
+            let suggestions = group["suggestions"].as_array().unwrap();
+            // どの温度帯にも推薦商品があり、その温度帯の商品だけを含む
+            assert!(!suggestions.is_empty(), "{}", group);
+            for suggestion in suggestions {
+                let variant_id = suggestion["product_variant_id"].as_str().unwrap();
+                assert_eq!(zone(variant_id), group["temperature"], "{}", body);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn group_by_temperature_rejects_a_single_zone_or_ndjson() {
+        let uri = suggestions_uri(&[
+            ("province_code", "JP-13"),
+            ("products", CART),
+            ("group_by_temperature", "true"),
+            ("temperature", "Cold"),
+            ("format", "ndjson"),
+        ]);
+
+        let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<&str> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["temperature", "format"]);
+    }
+
+    #[test]
+    fn weight_must_be_finite_and_non_negative() {
+        let product = |weight: f32| CartProduct {
+            product_variant_id: "a".to_string(),
+            quantity: 1,
+            weight: Some(weight),
+        };
+        let products = [
+            product(-1.0),
+            product(f32::NAN),
+            product(f32::INFINITY),
+            product(0.5),
+            product(0.0),
+        ];
+
+        let fields: Vec<String> = validate_province_and_products("JP-13", &products)
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+
+        assert_eq!(
+            fields,
+            [
+                "products[0].weight",
+                "products[1].weight",
+                "products[2].weight"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fractional_weights_are_accepted_and_negative_ones_rejected() {
+        let cart = |weight: &str| {
+            suggestions_uri(&[
+                ("province_code", "JP-13"),
+                (
+                    "products",
+                    &format!(
+                        r#"[{{"product_variant_id": "mock-variant-1", "weight": {}}}]"#,
+                        weight
+                    ),
+                ),
+            ])
+        };
+
+        let (status, body) = testing::send(mock_app(), testing::get(&cart("0.5"))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let (status, body) = testing::send(mock_app(), testing::get(&cart("-0.5"))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], "products[0].weight");
+    }
+
+    #[test]
+    fn quantity_rejects_negative_and_fractional_values() {
+        let negative = quantity("-2").unwrap_err().to_string();
+        assert!(negative.contains("non-negative integer"), "{}", negative);
+
+        let fractional = quantity("1.5").unwrap_err().to_string();
+        assert!(
+            fractional.contains("non-negative integer"),
+            "{}",
+            fractional
+        );
+
+        // 小数部が0でも小数の表記は整数として扱わない
+        assert!(quantity("2.0").is_err());
+        assert!(quantity(r#""2""#).is_err());
+    }
+
+    #[test]
+    fn quantity_clamps_huge_values_to_the_cap() {
+        assert_eq!(quantity("10001").unwrap(), MAX_QUANTITY);
+        assert_eq!(quantity("4294967296").unwrap(), MAX_QUANTITY);
+        // 64ビットに収まらない整数
+        assert_eq!(quantity("100000000000000000000000").unwrap(), MAX_QUANTITY);
+    }
+
+    #[test]
+    fn neighbor_options_override_the_config_and_clamp_the_count() {
+        let defaults = service::cart::RecommendationConfig::default();
+
+        let mut config = defaults.clone();
+        let ((), lines) = testing::capture_logs(|| {
+            apply_neighbor_options(
+                &mut config,
+                Some(100_000),
+                Some(3),
+                Some(0.5),
+                service::cart::Presence::Binary,
+            )
+        });
+        assert_eq!(config.top_users, defaults.max_neighbors);
+        // 切り詰めたことは推薦・近傍のプレビューのどちらでもログに残る
+        assert!(lines.iter().any(|line| {
+            line["fields"]["message"] == "近傍顧客の人数を上限に切り詰めます"
+                && line["fields"]["requested"] == 100_000
+                && line["fields"]["max_neighbors"] == defaults.max_neighbors
+        }));
+        assert_eq!(config.min_neighbor_items, 3);
+        assert_eq!(config.min_neighbor_similarity, Some(0.5));
+        assert_eq!(
+            config.encoding,
+            service::cart::Presence::Binary.apply(defaults.encoding)
+        );
+
+        // 指定がなければ設定を変えない
+        let mut config = defaults.clone();
+        apply_neighbor_options(
+            &mut config,
+            None,
+            None,
+            None,
+            service::cart::Presence::Quantity,
+        );
+        assert_eq!(config.top_users, defaults.top_users);
+        assert_eq!(config.min_neighbor_items, defaults.min_neighbor_items);
+        assert_eq!(config.min_neighbor_similarity, None);
+        assert_eq!(config.encoding, defaults.encoding);
+    }
+
+    #[tokio::test]
+    async fn neighbor_preview_lists_ranked_labels_and_similarities() {
+        let body = json!({
+            "province_code": "JP-13",
+            "products": [{"product_variant_id": "mock-variant-1", "quantity": 2}],
+        });
+
+        let (status, preview) = testing::send(
+            mock_app(),
+            testing::post_json("/suggestions/neighbors", body.to_string()),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK, "{}", preview);
+        // 推薦と同じ方法で選んだ近傍顧客を、類似度の高い順に返す
+        let mut session = MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = to_product_items(&[CartProduct {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 2,
+            weight: None,
+        }]);
+        let config = service::cart::RecommendationConfig::default();
+        let order =
+            service::cart::create_order_vector("JP-13", &products, &dimensions, config.encoding);
+        let expected: Vec<(String, f32)> = service::cart::find_neighbors(
+            &mut session,
+            &config,
+            &order,
+            &dimensions,
+            service::cart::SimilarityMethod::default(),
+        )
+        .unwrap()
+        .iter()
+        .enumerate()
+        .map(|(rank, neighbor)| (service::cart::neighbor_label(rank), neighbor.score))
+        .collect();
+        let neighbors: Vec<(String, f32)> = preview["neighbors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| {
+                (
+                    n["neighbor"].as_str().unwrap().to_string(),
+                    n["similarity"].as_f64().unwrap() as f32,
+                )
+            })
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(neighbors, expected);
+        // 顧客IDは順位のラベルに置き換え、レスポンスに含めない
+        assert!(!preview.to_string().contains("mock-customer"));
+    }
+
+    #[tokio::test]
+    async fn neighbors_above_the_maximum_are_clamped() {
+        let store = Arc::new(MockStore);
+        let state = testing::state(store.clone(), store);
+        state.recommendation_config.write().unwrap().max_neighbors = 2;
+        let body = json!({
+            "province_code": "JP-13",
+            "products": [{"product_variant_id": "mock-variant-1", "quantity": 2}],
+        })
+        .to_string();
+        let neighbors = |count: &str| {
+            testing::send(
+                testing::app(state.clone()),
+                testing::post_json(
+                    &format!("/suggestions/neighbors?neighbors={}", count),
+                    body.clone(),
+                ),
+            )
+        };
+
+        let (status, clamped) = neighbors("100").await;
+        assert_eq!(status, StatusCode::OK, "{}", clamped);
+        assert_eq!(clamped["neighbors"].as_array().unwrap().len(), 2);
+
+        let (_, within) = neighbors("1").await;
+        assert_eq!(within["neighbors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn repeated_request_is_served_from_the_suggestion_cache() {
+        let store = testing::RecordingStore::default();
+        let state = testing::state(Arc::new(MockStore), Arc::new(store.clone()));
+        state.recommendation_config.write().unwrap().cache_ttl = std::time::Duration::from_secs(60);
+        let request = |products: &str| {
+            testing::get(&suggestions_uri(&[
+                ("province_code", "JP-13"),
+                ("products", products),
+            ]))
+        };
+        let cart = r#"[{"product_variant_id": "mock-variant-1"}, {"product_variant_id": "mock-variant-2"}]"#;
+        let reordered = r#"[{"product_variant_id": "mock-variant-2"}, {"product_variant_id": "mock-variant-1"}]"#;
+
+        let (status, computed) = testing::send(testing::app(state.clone()), request(cart)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(computed["message"], "Successfully generated suggestions");
+        assert_eq!(store.history_queries(), 1);
+
+        // 商品の指定順が違っても同じカートとしてキャッシュから返し、購入履歴は取得し直さない
+        let (status, cached) = testing::send(testing::app(state.clone()), request(reordered)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            cached["message"],
+            "Successfully generated suggestions (cached)"
+        );
+        assert_eq!(cached["suggestions"], computed["suggestions"]);
+        assert_eq!(store.history_queries(), 1);
+
+        // 推薦の設定が変われば（設定の読み込み直しなど）、変更前の結果は使わない
+        state.recommendation_config.write().unwrap().region_weight = 0.2;
+        let (_, recomputed) = testing::send(testing::app(state), request(cart)).await;
+        assert_eq!(recomputed["message"], "Successfully generated suggestions");
+        assert_eq!(store.history_queries(), 2);
+    }
+
+    #[tokio::test]
+    async fn vectorize_only_returns_the_normalized_cart() {
+        let state = testing::state(Arc::new(MockStore), Arc::new(MockStore));
+        state.recommendation_config.write().unwrap().encoding = service::cart::VectorEncoding {
+            transform: service::cart::QuantityTransform::Identity,
+            normalization: service::cart::NormalizationMode::L2,
+        };
+        let cart = r#"[{"product_variant_id": "mock-variant-1", "quantity": 3}, {"product_variant_id": "mock-variant-3", "quantity": 4}]"#;
+        let uri = suggestions_uri(&[
+            ("province_code", "JP-13"),
+            ("products", cart),
+            ("vectorize_only", "true"),
+        ]);
+
+        let (status, body) = testing::send(testing::app(state), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["suggestions"], json!([]));
+        // 0でない成分はカートの商品だけで、値はL2正規化した数量（3:4 → 0.6:0.8）
+        let components: Vec<(String, f64)> = body["vector"]["product_components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|component| {
+                (
+                    component["product_variant_id"]
+                        .as_str()
+                        .unwrap()
+                        .to_string(),
+                    component["value"].as_f64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(components.len(), 2, "{:?}", components);
+        assert_eq!(components[0].0, "mock-variant-1");
+        assert!((components[0].1 - 0.6).abs() < 1e-6, "{:?}", components);
+        assert_eq!(components[1].0, "mock-variant-3");
+        assert!((components[1].1 - 0.8).abs() < 1e-6, "{:?}", components);
+        assert_eq!(
+            body["vector"]["region_vector"],
+            json!(service::cart::region_to_vector("JP-13"))
+        );
+    }
+
+    #[tokio::test]
+    async fn explained_contributions_sum_to_the_score() {
+        let uri = suggestions_uri(&[
+            ("province_code", "JP-13"),
+            ("products", CART),
+            ("explain", "true"),
+        ]);
+
+        let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let suggestions = body["suggestions"].as_array().unwrap();
+        assert!(!suggestions.is_empty());
+        for suggestion in suggestions {
+            let contributions = suggestion["contributions"].as_array().unwrap();
+            assert!(!contributions.is_empty(), "{}", suggestion);
+            let total: f64 = contributions
+                .iter()
+                .map(|c| c["contribution"].as_f64().unwrap())
+                .sum();
+            let score = suggestion["score"].as_f64().unwrap();
+            assert!((total - score).abs() < 1e-4, "{}", suggestion);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（注文を登録済み）が必要"]
+    async fn suggestion_completes_with_a_single_connection_pool() {
+        // スキーマ変更を適用してから、接続を1つに制限したプールを作る
+        testing::database_pool();
+        let url = std::env::var("TEST_DATABASE_URL").unwrap();
+        let opts = mysql::OptsBuilder::from_opts(mysql::Opts::from_url(&url).unwrap()).pool_opts(
+            mysql::PoolOpts::default().with_constraints(mysql::PoolConstraints::new(1, 1).unwrap()),
+        );
+        let pool = Arc::new(mysql::Pool::new(opts).unwrap());
+        let store = Arc::new(crate::repository::MySqlStore::new(pool.clone(), None));
+        let app = testing::app(crate::state::AppState::with_store(
+            pool,
+            store.clone(),
+            store,
+        ));
+        let uri = suggestions_uri(&[("province_code", "JP-13"), ("products", CART)]);
+
+        // 1リクエストで接続を2つ取得しようとすると、空きを待ち続けて期限を過ぎる
+        let (status, body) = tokio::time::timeout(
+            std::time::Duration::from_secs(30),
+            testing::send(app, testing::get(&uri)),
+        )
+        .await
+        .expect("1つの接続で推薦が完了しません");
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn suggestions_are_refused_with_retry_after_while_the_limiter_is_full() {
+        let mut state = testing::state(Arc::new(MockStore), Arc::new(MockStore));
+        state.suggestion_limiter = Arc::new(ConcurrencyLimiter::new(1));
+        let held = state.suggestion_limiter.try_acquire();
+        assert!(held.is_some());
+        let uri = suggestions_uri(&[("province_code", "JP-13"), ("products", CART)]);
+
+        let response = tower::ServiceExt::oneshot(testing::app(state.clone()), testing::get(&uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(
+            response
+                .headers()
+                .contains_key(axum::http::header::RETRY_AFTER)
+        );
+
+        // 枠が空けば受け付ける
+        drop(held);
+        let (status, _) = testing::send(testing::app(state), testing::get(&uri)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn ndjson_streams_one_suggestion_object_per_line() {
+        let params = [("province_code", "JP-13"), ("products", CART)];
+        let (_, json_body) =
+            testing::send(mock_app(), testing::get(&suggestions_uri(&params))).await;
+        let uri = suggestions_uri(&[params[0], params[1], ("format", "ndjson")]);
+
+        let response = tower::ServiceExt::oneshot(mock_app(), testing::get(&uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(text.ends_with('\n'));
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // 各行が推薦商品1件で、JSON形式の suggestions と同じ内容・順序になる
+        assert!(!lines.is_empty());
+        assert_eq!(serde_json::Value::Array(lines), json_body["suggestions"]);
+    }
+
+    #[tokio::test]
+    async fn missing_products_is_a_json_bad_request() {
+        let uri = suggestions_uri(&[("province_code", "JP-13")]);
+
+        let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["errors"],
+            json!([{"field": "products", "reason": "is required"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_province_code_is_a_json_bad_request() {
+        let uri = suggestions_uri(&[("products", CART)]);
+
+        let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["errors"],
+            json!([{"field": "province_code", "reason": "is required"}])
+        );
+    }
+
+    // /rerank に送るJSON（quantity だけ差し替える）
+    fn rerank_body(quantity: u32) -> String {
+        json!({
+            "province_code": "JP-13",
+            "products": [{"product_variant_id": "mock-variant-1", "quantity": quantity}],
+            "candidates": ["mock-variant-3"],
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn rerank_returns_only_the_candidates_in_reranked_order() {
+        let body = json!({
+            "province_code": "JP-13",
+            "products": [{"product_variant_id": "mock-variant-1", "quantity": 2}],
+            // 重複と、どの近傍顧客も購入していない商品を含める
+            "candidates": ["unknown-variant", "mock-variant-4", "mock-variant-3", "mock-variant-4"],
+        });
+
+        let (status, body) =
+            testing::send(mock_app(), testing::post_json("/rerank", body.to_string())).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let suggestions = body["suggestions"].as_array().unwrap();
+        let ids: Vec<&str> = suggestions
+            .iter()
+            .map(|s| s["product_variant_id"].as_str().unwrap())
+            .collect();
+        let scores: Vec<f64> = suggestions
+            .iter()
+            .map(|s| s["score"].as_f64().unwrap())
+            .collect();
+        assert_eq!(ids, ["mock-variant-3", "mock-variant-4", "unknown-variant"]);
+        assert!(
+            scores.windows(2).all(|pair| pair[0] >= pair[1]),
+            "{:?}",
+            scores
+        );
+        assert_eq!(scores[2], 0.0);
+    }
+
+    #[tokio::test]
+    async fn garbage_json_body_is_a_bad_request() {
+        let (status, body) = testing::send(
+            mock_app(),
+            testing::post_json("/rerank", "{\"province_code\": "),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["message"], "Malformed request");
+    }
+
+    #[tokio::test]
+    async fn zero_quantity_in_valid_json_is_unprocessable() {
+        let (status, body) =
+            testing::send(mock_app(), testing::post_json("/rerank", rerank_body(0))).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["errors"],
+            json!([{"field": "products[0].quantity", "reason": "must be at least 1"}])
+        );
+    }
+
+    // 指定した Content-Type で /rerank にPOSTする（None の場合は Content-Type を付けない）
+    async fn post_rerank_with_content_type(
+        content_type: Option<&str>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = axum::http::Request::post("/rerank");
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        let request = request.body(Body::from(rerank_body(1))).unwrap();
+        testing::send(mock_app(), request).await
+    }
+
+    #[tokio::test]
+    async fn missing_or_non_json_content_type_is_a_json_415() {
+        for content_type in [None, Some("text/plain")] {
+            let (status, body) = post_rerank_with_content_type(content_type).await;
+
+            assert_eq!(
+                status,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "{:?}",
+                content_type
+            );
+            assert_eq!(
+                body["message"],
+                "Expected a JSON body with header Content-Type: application/json"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn json_content_type_with_charset_is_accepted() {
+        let (status, body) =
+            post_rerank_with_content_type(Some("application/json; charset=utf-8")).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    #[tokio::test]
+    async fn province_codes_within_jp_01_to_jp_47_are_accepted() {
+        for province_code in ["JP-01", "JP-47"] {
+            let uri = suggestions_uri(&[("province_code", province_code), ("products", CART)]);
+
+            let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+            assert_eq!(status, StatusCode::OK, "{}: {}", province_code, body);
+        }
+    }
+
+    #[tokio::test]
+    async fn out_of_range_or_malformed_province_codes_are_rejected() {
+        let cases = [
+            ("JP-00", "must be between JP-01 and JP-47 (see /provinces)"),
+            ("JP-48", "must be between JP-01 and JP-47 (see /provinces)"),
+            ("TOKYO", "must match the format JP-NN (e.g. JP-13)"),
+        ];
+        for (province_code, reason) in cases {
+            let uri = suggestions_uri(&[("province_code", province_code), ("products", CART)]);
+
+            let (status, body) = testing::send(mock_app(), testing::get(&uri)).await;
+
+            assert_eq!(
+                status,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{}",
+                province_code
+            );
+            assert_eq!(
+                body["errors"],
+                json!([{"field": "province_code", "reason": reason}])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn base64_cart_gives_same_suggestions_as_query_params() {
+        let cart = json!({
+            "province_code": "JP-13",
+            "products": [{"product_variant_id": "mock-variant-1", "quantity": 2}],
+        });
+        let encoded = base64::engine::general_purpose::STANDARD.encode(cart.to_string());
+
+        let (status, from_cart) = testing::send(
+            mock_app(),
+            testing::get(&suggestions_uri(&[("cart", &encoded)])),
+        )
+        .await;
+        let (_, from_params) = testing::send(
+            mock_app(),
+            testing::get(&suggestions_uri(&[
+                ("province_code", "JP-13"),
+                ("products", CART),
+            ])),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK, "{}", from_cart);
+        assert!(!from_cart["suggestions"].as_array().unwrap().is_empty());
+        assert_eq!(from_cart, from_params);
+    }
+
+    #[tokio::test]
+    async fn json_array_query_and_variants_match_the_json_body() {
+        let products = json!([
+            {"product_variant_id": "mock-variant-1", "quantity": 2},
+            {"product_variant_id": "mock-variant-3", "quantity": 1},
+        ]);
+        let body = json!({"province_code": "JP-13", "products": products});
+        let encoded = base64::engine::general_purpose::STANDARD.encode(body.to_string());
+        let products = products.to_string();
+
+        let (status, from_body) = testing::send(
+            mock_app(),
+            testing::get(&suggestions_uri(&[("cart", &encoded)])),
+        )
+        .await;
+        let (_, from_json_query) = testing::send(
+            mock_app(),
+            testing::get(&suggestions_uri(&[
+                ("province_code", "JP-13"),
+                ("products", &products),
+            ])),
+        )
+        .await;
+        let (_, from_variants) = testing::send(
+            mock_app(),
+            testing::get(&suggestions_uri(&[
+                ("province_code", "JP-13"),
+                ("variant", "mock-variant-1:2"),
+                ("variant", "mock-variant-3"),
+            ])),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK, "{}", from_body);
+        assert!(!from_body["suggestions"].as_array().unwrap().is_empty());
+        assert_eq!(from_json_query, from_body);
+        assert_eq!(from_variants, from_body);
+    }
+
+    #[tokio::test]
+    async fn malformed_base64_cart_is_a_bad_request() {
+        let not_json = base64::engine::general_purpose::STANDARD.encode("not json");
+        let cases = [
+            ("%%%not-base64%%%", "must be base64-encoded JSON"),
+            (not_json.as_str(), "expected ident at line 1 column 2"),
+        ];
+        for (cart, reason) in cases {
+            let (status, body) = testing::send(
+                mock_app(),
+                testing::get(&suggestions_uri(&[("cart", cart)])),
+            )
+            .await;
+
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", cart);
+            assert_eq!(body["errors"], json!([{"field": "cart", "reason": reason}]));
+        }
+    }
+
+    // 接続を取得できないデータストア
+    struct UnavailableStore;
+
+    impl RecommendationRepository for UnavailableStore {
+        fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+            Err(mysql::Error::DriverError(
+                mysql::DriverError::CouldNotConnect(None),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn connection_failure_is_a_503() {
+        let app = testing::app(testing::state(
+            Arc::new(MockStore),
+            Arc::new(UnavailableStore),
+        ));
+        let uri = suggestions_uri(&[("province_code", "JP-13"), ("products", CART)]);
+
+        let (status, body) = testing::send(app, testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, json!({"message": "Database unavailable"}));
+    }
+
+    // セッションの開始（接続の取得）に時間のかかるデータストア
+    struct SlowStore(std::time::Duration);
+
+    impl RecommendationRepository for SlowStore {
+        fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+            std::thread::sleep(self.0);
+            Ok(Box::new(MockStore))
+        }
+    }
+
+    #[tokio::test]
+    async fn request_timeout_shorter_than_a_slow_query_is_a_504() {
+        let app = testing::app(testing::state(
+            Arc::new(MockStore),
+            Arc::new(SlowStore(std::time::Duration::from_secs(1))),
+        ));
+        let uri = suggestions_uri(&[("province_code", "JP-13"), ("products", CART)]);
+        let request = axum::http::Request::get(uri)
+            .header("x-request-timeout-ms", "50")
+            .body(Body::empty())
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let (status, body) = testing::send(app, request).await;
+
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["message"], "Request did not complete within 50 ms");
+        // 問い合わせの完了を待たずに応答する
+        assert!(started.elapsed() < std::time::Duration::from_millis(500));
+    }
 }
@@ -0,0 +1,245 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::controller::extract::Query;
+use crate::controller::pagination::{PageQuery, Paginated};
+use crate::db;
+use crate::error::{AppError, FieldError};
+
+// 1ページあたりの注文件数
+const ORDERS_PER_PAGE: usize = 20;
+
+#[derive(Deserialize)]
+pub struct OrdersQuery {
+    // ページ番号（1始まり）
+    pub page: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct LineItemResponse {
+    variant_id: String,
+    quantity: u32,
+    price: f64,
+}
+
+impl From<db::OrderLineItem> for LineItemResponse {
+    fn from(item: db::OrderLineItem) -> Self {
+        LineItemResponse {
+            variant_id: item.variant_id,
+            quantity: item.quantity,
+            price: item.price,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct OrderResponse {
+    id: String,
+    created_at: String,
+    total_price: f64,
+    line_items: Vec<LineItemResponse>,
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse {
+    message: String,
+    page: usize,
+    orders: Vec<OrderResponse>,
+}
+
+// 顧客の注文履歴を明細付きで返す
+pub async fn get_customer_orders(
+    State(pool): State<Arc<mysql::Pool>>,
+    Path(customer_id): Path<String>,
+    Query(params): Query<OrdersQuery>,
+) -> Result<Json<ApiResponse>, AppError> {
+    let page = params.page.unwrap_or(1);
+    if page < 1 {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "page",
+            "must be at least 1",
+        )]));
+    }
+
+    let orders = db::get_customer_orders(pool, customer_id.clone(), page, ORDERS_PER_PAGE)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Customer {} not found", customer_id)))?;
+
+    let orders = orders
+        .into_iter()
+        .map(|order| OrderResponse {
+            id: order.id,
+            created_at: order.created_at,
+            total_price: order.total_price,
+            line_items: order.line_items.into_iter().map(Into::into).collect(),
+        })
+        .collect();
+
+    Ok(Json(ApiResponse {
+        message: "Successfully retrieved orders".to_string(),
+        page,
+        orders,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct SegmentsQuery {
+    // マーケティング配信の可否で絞り込む
+    pub accepts_marketing: Option<bool>,
+    // trueの場合は顧客IDの一覧もページ単位で返す
+    pub include_ids: Option<bool>,
+    // 顧客一覧のページ指定（PageQuery と同じ）
+    pub after: Option<String>,
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SegmentCountsResponse {
+    total: u64,
+    accepts_marketing: u64,
+    declines_marketing: u64,
+    is_infomercial: u64,
+}
+
+#[derive(Serialize)]
+pub struct SegmentCustomerResponse {
+    id: String,
+    accepts_marketing: bool,
+    is_infomercial: bool,
+}
+
+#[derive(Serialize)]
+pub struct SegmentsResponse {
+    message: String,
+    counts: SegmentCountsResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    customers: Option<Paginated<SegmentCustomerResponse>>,
+}
+
+// マーケティング配信の可否ごとの顧客数を返す
+// include_ids=true の場合は該当する顧客の一覧も返す
+pub async fn get_customer_segments(
+    State(pool): State<Arc<mysql::Pool>>,
+    Query(params): Query<SegmentsQuery>,
+) -> Result<Json<SegmentsResponse>, AppError> {
+    let page = PageQuery {
+        after: params.after,
+        page: params.page,
+        limit: params.limit,
+    }
+    .into_page::<String>()?;
+
+    let counts = db::count_customer_segments(pool.clone(), params.accepts_marketing).await?;
+
+    let customers = if params.include_ids.unwrap_or(false) {
+        let entries =
+            db::get_customer_segment_entries(pool, params.accepts_marketing, page.clone()).await?;
+        let entries = entries
+            .into_iter()
+            .map(|entry| SegmentCustomerResponse {
+                id: entry.id,
+                accepts_marketing: entry.accepts_marketing,
+                is_infomercial: entry.is_infomercial,
+            })
+            .collect();
+        Some(Paginated::new(entries, counts.total, &page, |customer| {
+            customer.id.clone()
+        }))
+    } else {
+        None
+    };
+
+    Ok(Json(SegmentsResponse {
+        message: "Successfully retrieved customer segments".to_string(),
+        counts: SegmentCountsResponse {
+            total: counts.total,
+            accepts_marketing: counts.accepts_marketing,
+            declines_marketing: counts.declines_marketing,
+            is_infomercial: counts.is_infomercial,
+        },
+        customers,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use mysql::prelude::*;
+
+    use super::*;
+    use crate::repository::MySqlStore;
+    use crate::state::AppState;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn segment_filter_must_be_a_boolean() {
+        let state = testing::state(
+            Arc::new(testing::TokenUsers),
+            Arc::new(crate::mock::MockStore),
+        );
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get("/customers/segments?accepts_marketing=maybe"),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（顧客を登録済み）が必要"]
+    async fn accepts_marketing_filter_limits_the_counts_and_ids() {
+        let pool = Arc::new(testing::database_pool());
+        let state = AppState::with_store(
+            pool.clone(),
+            Arc::new(testing::TokenUsers),
+            Arc::new(MySqlStore::new(pool.clone(), None)),
+        );
+        let segments = |query: &'static str| {
+            let state = state.clone();
+            async move {
+                let (status, body) = testing::send(
+                    testing::app(state),
+                    testing::get(&format!("/customers/segments?{}", query)),
+                )
+                .await;
+                assert_eq!(status, StatusCode::OK, "{}", body);
+                body
+            }
+        };
+        let expected: (u64, u64) = pool
+            .get_conn()
+            .unwrap()
+            .query_first(
+                "SELECT COUNT(*), CAST(COALESCE(SUM(accepts_marketing <> 0), 0) AS UNSIGNED)
+                 FROM customers",
+            )
+            .unwrap()
+            .unwrap();
+
+        let all = segments("").await;
+        let accepting = segments("accepts_marketing=true&include_ids=true&limit=1000").await;
+        let declining = segments("accepts_marketing=false").await;
+
+        assert_eq!(all["counts"]["total"], expected.0);
+        assert_eq!(all["counts"]["accepts_marketing"], expected.1);
+        assert!(all.get("customers").is_none());
+        // 絞り込んだ場合は、その条件の顧客だけを数える
+        assert_eq!(accepting["counts"]["total"], expected.1);
+        assert_eq!(accepting["counts"]["declines_marketing"], 0);
+        assert_eq!(declining["counts"]["total"], expected.0 - expected.1);
+        assert_eq!(declining["counts"]["accepts_marketing"], 0);
+        let ids = accepting["customers"]["data"].as_array().unwrap();
+        assert_eq!(ids.len() as u64, expected.1.min(1000));
+        assert!(
+            ids.iter()
+                .all(|customer| customer["accepts_marketing"] == true)
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::Duration;
+
+use crate::error::{AppError, FieldError};
+
+// クライアントが自身の期限に合わせて処理時間を指定するヘッダー（ミリ秒）
+const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+
+// X-Request-Timeout-Ms が指定されていれば、その時間（max_timeout が上限）内に処理が終わらない場合に504を返す
+// 期限を過ぎるとハンドラの処理は打ち切られるが、実行中のブロッキングタスク（クエリ）は終わるまで動き続ける
+pub async fn enforce_deadline(
+    State(max_timeout): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let timeout = match request_timeout(&request) {
+        Ok(Some(timeout)) => timeout.min(max_timeout),
+        Ok(None) => return next.run(request).await,
+        Err(err) => return err.into_response(),
+    };
+
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => AppError::GatewayTimeout(timeout).into_response(),
+    }
+}
+
+// ヘッダーの値を読み込む（指定がなければ None、正の整数でなければ400）
+fn request_timeout(request: &Request) -> Result<Option<Duration>, AppError> {
+    let Some(value) = request.headers().get(REQUEST_TIMEOUT_HEADER) else {
+        return Ok(None);
+    };
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|&millis| millis > 0)
+        .map(|millis| Some(Duration::from_millis(millis)))
+        .ok_or_else(|| {
+            AppError::BadRequest(vec![FieldError::new(
+                "X-Request-Timeout-Ms",
+                "must be a positive integer (milliseconds)",
+            )])
+        })
+}
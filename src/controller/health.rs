@@ -0,0 +1,106 @@
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::repository::RecommendationRepository;
+use crate::service::dimensions::DimensionsCache;
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+}
+
+// 死活監視（プロセスが応答できるかのみを返す）
+pub async fn get_health() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+// 準備状態の確認項目ごとの結果
+#[derive(Serialize)]
+pub struct ReadinessCheck {
+    name: &'static str,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    status: &'static str,
+    checks: Vec<ReadinessCheck>,
+}
+
+// 準備状態の確認（商品次元情報のキャッシュとデータベース接続）
+// すべての確認項目を満たす場合のみ200、それ以外は503を返す
+// データベース接続は推薦データのストアで確認する（レプリカを使う場合はレプリカ、固定データの場合は常に成功）
+pub async fn get_ready(
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions): State<Arc<DimensionsCache>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let dimensions_loaded = dimensions.get().is_some();
+    let dimensions_check = ReadinessCheck {
+        name: "dimensions_cache",
+        ok: dimensions_loaded,
+        detail: (!dimensions_loaded).then(|| "product dimensions are not loaded yet".to_string()),
+    };
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    // 接続の取得時にプールが接続の状態を確かめるため、セッションを開始できれば使用可能とする
+    let ping = tokio::task::spawn_blocking(move || recommendations.session().map(drop))
+        .await
+        .expect("ブロッキングタスクの実行に失敗");
+    let database_check = ReadinessCheck {
+        name: "database",
+        ok: ping.is_ok(),
+        detail: ping.err().map(|err| err.to_string()),
+    };
+
+    let checks = vec![dimensions_check, database_check];
+    let ready = checks.iter().all(|check| check.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            status: if ready { "ready" } else { "not_ready" },
+            checks,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use std::sync::Arc;
+
+    use crate::mock::MockStore;
+    use crate::service::dimensions;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn ready_is_503_before_warm_up_and_200_after() {
+        let store = Arc::new(MockStore);
+        let state = testing::state(store.clone(), store);
+        let app = testing::app(state.clone());
+
+        let (status, body) = testing::send(app.clone(), testing::get("/ready")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["checks"][0]["name"], "dimensions_cache");
+        assert_eq!(body["checks"][0]["ok"], false);
+        assert_eq!(body["checks"][1]["ok"], true);
+
+        // 起動時と同じく商品次元情報を読み込む
+        dimensions::refresh(&state.dimensions, state.recommendations.clone())
+            .await
+            .unwrap();
+
+        let (status, body) = testing::send(app, testing::get("/ready")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+    }
+}
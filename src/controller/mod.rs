@@ -1,2 +1,13 @@
+pub mod admin;
+pub mod auth;
 pub mod cart;
+pub mod customers;
+pub mod deadline;
+pub mod extract;
+pub mod health;
+pub mod orders;
+pub mod pagination;
+pub mod products;
+pub mod provinces;
+pub mod stats;
 pub mod users;
@@ -0,0 +1,190 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::controller::customers::LineItemResponse;
+use crate::db;
+use crate::error::AppError;
+
+#[derive(Serialize)]
+pub struct OrderDetailResponse {
+    id: String,
+    // ゲスト購入の場合は null
+    customer_id: Option<String>,
+    created_at: String,
+    total_price: f64,
+    line_items: Vec<LineItemResponse>,
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse {
+    message: String,
+    order: OrderDetailResponse,
+}
+
+// 注文を明細付きで返す（推薦への寄与を調べる際の確認用）
+pub async fn get_order(
+    State(pool): State<Arc<mysql::Pool>>,
+    Path(order_id): Path<String>,
+) -> Result<Json<ApiResponse>, AppError> {
+    let order = db::get_order(pool, order_id.clone())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Order {} not found", order_id)))?;
+
+    Ok(Json(ApiResponse {
+        message: "Successfully retrieved order".to_string(),
+        order: OrderDetailResponse {
+            id: order.id,
+            customer_id: order.customer_id,
+            created_at: order.created_at,
+            total_price: order.total_price,
+            line_items: order.line_items.into_iter().map(Into::into).collect(),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use mysql::prelude::*;
+
+    use super::*;
+    use crate::repository::MySqlStore;
+    use crate::state::AppState;
+    use crate::testing;
+
+    // TEST_DATABASE_URL のMySQLを使う状態
+    fn database_state() -> (Arc<mysql::Pool>, AppState) {
+        let pool = Arc::new(testing::database_pool());
+        let state = AppState::with_store(
+            pool.clone(),
+            Arc::new(testing::TokenUsers),
+            Arc::new(MySqlStore::new(pool.clone(), None)),
+        );
+        (pool, state)
+    }
+
+    #[tokio::test]
+    async fn order_history_rejects_page_zero() {
+        let state = testing::state(
+            Arc::new(testing::TokenUsers),
+            Arc::new(crate::mock::MockStore),
+        );
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get("/customers/customer-1/orders?page=0"),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], "page");
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（注文を登録済み）が必要"]
+    async fn order_history_lists_the_customers_orders_newest_first() {
+        let (pool, state) = database_state();
+        let customer_id: String = pool
+            .get_conn()
+            .unwrap()
+            .query_first("SELECT customer_id FROM orders WHERE customer_id IS NOT NULL LIMIT 1")
+            .unwrap()
+            .expect("顧客の注文がありません");
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get(&format!("/customers/{}/orders", customer_id)),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["page"], 1);
+        let orders = body["orders"].as_array().unwrap();
+        assert!(!orders.is_empty());
+        let created_at: Vec<&str> = orders
+            .iter()
+            .map(|order| order["created_at"].as_str().unwrap())
+            .collect();
+        assert!(created_at.is_sorted_by(|a, b| a >= b), "{:?}", created_at);
+        assert!(orders.iter().all(|order| order["line_items"].is_array()));
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    async fn order_history_of_an_unknown_customer_is_404() {
+        let (_, state) = database_state();
+        let customer_id = format!("unknown-{}", uuid::Uuid::new_v4());
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get(&format!("/customers/{}/orders", customer_id)),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body["message"],
+            format!("Customer {} not found", customer_id)
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（注文を登録済み）が必要"]
+    async fn order_is_returned_with_its_line_items() {
+        let (pool, state) = database_state();
+        let mut conn = pool.get_conn().unwrap();
+        let order_id: String = conn
+            .query_first("SELECT order_id FROM order_products LIMIT 1")
+            .unwrap()
+            .expect("明細のある注文がありません");
+        let mut expected: Vec<(String, u32)> = conn
+            .exec(
+                "SELECT CAST(variant_id AS CHAR), quantity FROM order_products WHERE order_id = ?",
+                (&order_id,),
+            )
+            .unwrap();
+        expected.sort();
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get(&format!("/orders/{}", order_id)),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["order"]["id"], order_id);
+        let mut line_items: Vec<(String, u32)> = body["order"]["line_items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["variant_id"].as_str().unwrap().to_string(),
+                    item["quantity"].as_u64().unwrap() as u32,
+                )
+            })
+            .collect();
+        line_items.sort();
+        assert_eq!(line_items, expected);
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    async fn missing_order_is_404() {
+        let (_, state) = database_state();
+        let order_id = format!("unknown-{}", uuid::Uuid::new_v4());
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get(&format!("/orders/{}", order_id)),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["message"], format!("Order {} not found", order_id));
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::db::Page;
+use crate::error::{AppError, FieldError};
+
+// 1ページあたりの件数の既定値と上限
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+// 一覧取得のページ指定クエリ
+// after を指定するとカーソル方式（推奨）、page を指定するとオフセット方式で取得する
+// どちらも指定しない場合は先頭からカーソル方式で取得する
+#[derive(Deserialize)]
+pub struct PageQuery {
+    // 前のページの next_cursor
+    pub after: Option<String>,
+    // ページ番号（1始まり、オフセット方式）
+    pub page: Option<usize>,
+    // 1ページあたりの件数
+    pub limit: Option<usize>,
+}
+
+impl PageQuery {
+    // クエリを検証してページ指定に変換する
+    // 解釈できないカーソルは形式の誤り（400）、件数やページ番号の範囲外は検証エラー（422）とする
+    pub fn into_page<C: FromStr>(self) -> Result<Page<C>, AppError> {
+        let cursor = match self.after.as_deref().map(str::parse::<C>) {
+            Some(Err(_)) => {
+                return Err(AppError::BadRequest(vec![FieldError::new(
+                    "after",
+                    "is not a valid cursor",
+                )]));
+            }
+            Some(Ok(cursor)) => Some(cursor),
+            None => None,
+        };
+
+        let mut errors = Vec::new();
+
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            errors.push(FieldError::new(
+                "limit",
+                format!("must be between 1 and {}", MAX_LIMIT),
+            ));
+        }
+
+        let page = match (cursor, self.page) {
+            (Some(_), Some(_)) => {
+                errors.push(FieldError::new("after", "cannot be combined with page"));
+                None
+            }
+            (Some(cursor), None) => Some(Page::After {
+                cursor: Some(cursor),
+                limit,
+            }),
+            (None, Some(page)) if page < 1 => {
+                errors.push(FieldError::new("page", "must be at least 1"));
+                None
+            }
+            (None, Some(page)) => Some(Page::Offset {
+                page,
+                per_page: limit,
+            }),
+            (None, None) => Some(Page::After {
+                cursor: None,
+                limit,
+            }),
+        };
+
+        match page {
+            Some(page) if errors.is_empty() => Ok(page),
+            _ => Err(AppError::Validation(errors)),
+        }
+    }
+}
+
+// 取得件数が上限に達していれば、最後の行の主キーを次のカーソルとする
+fn next_cursor<C: ToString>(last_key: Option<C>, returned: usize, limit: usize) -> Option<String> {
+    if returned == limit {
+        last_key.map(|key| key.to_string())
+    } else {
+        None
+    }
+}
+
+// 一覧レスポンスのページ情報
+#[derive(Debug, Serialize)]
+pub struct PageMeta {
+    // 全件数
+    pub total: u64,
+    // このページの件数
+    pub count: usize,
+    // ページ番号（オフセット方式の場合のみ）
+    pub page: Option<usize>,
+    pub per_page: usize,
+    // 次のページを取得するためのカーソル（最後のページではnull）
+    pub next_cursor: Option<String>,
+}
+
+// 一覧エンドポイント共通のレスポンス
+#[derive(Debug, Serialize)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub meta: PageMeta,
+}
+
+impl<T> Paginated<T> {
+    // 取得した行とページ指定からレスポンスを作成
+    // cursor_key には各行の主キー（カーソル）を返す関数を渡す
+    pub fn new<C, K: ToString>(
+        data: Vec<T>,
+        total: u64,
+        page: &Page<C>,
+        cursor_key: impl Fn(&T) -> K,
+    ) -> Self {
+        let per_page = page.limit();
+        let next_cursor = next_cursor(data.last().map(cursor_key), data.len(), per_page);
+
+        Paginated {
+            meta: PageMeta {
+                total,
+                count: data.len(),
+                page: page.page_number(),
+                per_page,
+                next_cursor,
+            },
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mock::MockStore;
+    use crate::testing;
+
+    // 固定データの /users を取得し、IDの一覧と next_cursor を返す
+    async fn user_page(query: &str) -> (Vec<i64>, serde_json::Value) {
+        let state = testing::state(Arc::new(MockStore), Arc::new(MockStore));
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get(&format!("/users?{}", query)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let ids = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| user["id"].as_i64().unwrap())
+            .collect();
+        (ids, body["meta"]["next_cursor"].clone())
+    }
+
+    #[tokio::test]
+    async fn next_cursor_walks_every_page_until_the_last() {
+        let (first, cursor) = user_page("limit=2").await;
+        assert_eq!(first, [1, 2]);
+        assert_eq!(cursor, "2");
+
+        // 最後のページ（上限に満たない）では next_cursor は null
+        let (last, cursor) = user_page("limit=2&after=2").await;
+        assert_eq!(last, [3]);
+        assert!(cursor.is_null());
+    }
+
+    #[tokio::test]
+    async fn unparsable_cursor_is_a_bad_request() {
+        let state = testing::state(Arc::new(MockStore), Arc::new(MockStore));
+
+        let (status, body) =
+            testing::send(testing::app(state), testing::get("/users?after=abc")).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["errors"][0]["field"], "after");
+    }
+
+    #[test]
+    fn meta_serializes_with_typed_fields() {
+        let page: Page<i32> = Page::After {
+            cursor: None,
+            limit: 2,
+        };
+        let response = Paginated::new(vec![10, 20], 5, &page, |id| *id);
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "data": [10, 20],
+                "meta": {"total": 5, "count": 2, "page": null, "per_page": 2, "next_cursor": "20"},
+            })
+        );
+
+        // オフセット方式ではページ番号が入り、上限に満たないページは next_cursor が null
+        let page: Page<i32> = Page::Offset {
+            page: 3,
+            per_page: 2,
+        };
+        let response = Paginated::new(vec![50], 5, &page, |id| *id);
+        assert_eq!(
+            serde_json::to_value(&response.meta).unwrap(),
+            serde_json::json!({"total": 5, "count": 1, "page": 3, "per_page": 2, "next_cursor": null})
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_validation_errors() {
+        let query = PageQuery {
+            after: None,
+            page: Some(0),
+            limit: Some(MAX_LIMIT + 1),
+        };
+
+        match query.into_page::<i32>() {
+            Err(AppError::Validation(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                assert_eq!(fields, ["limit", "page"]);
+            }
+            other => panic!("検証エラーになりませんでした: {:?}", other),
+        }
+    }
+}
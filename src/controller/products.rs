@@ -0,0 +1,470 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use crate::controller::auth::AuthUser;
+use crate::controller::extract::{Json, Query};
+use crate::controller::pagination::{PageQuery, Paginated};
+use crate::db;
+use crate::error::{AppError, FieldError};
+use crate::repository::{ProductRepository, RecommendationRepository};
+use crate::service::dimensions::{self, DimensionsCache};
+
+#[derive(Serialize)]
+pub struct ProductResponse {
+    id: String,
+    variant_id: String,
+    is_suspension: bool,
+}
+
+// レスポンス本文のハッシュから弱いETagを作成
+fn weak_etag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+// If-None-Match ヘッダーがETagに一致するか（弱い比較）
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || strip_weak(tag) == strip_weak(etag))
+}
+
+// 商品一覧を返す
+// 内容が変わっていなければ If-None-Match に対して304を返す
+pub async fn get_products(
+    State(repository): State<Arc<dyn ProductRepository>>,
+    headers: HeaderMap,
+    Query(params): Query<PageQuery>,
+) -> Result<Response, AppError> {
+    let page = params.into_page::<String>()?;
+
+    let products = repository.get_products(page.clone()).await?;
+    let total = repository.count_products().await?;
+
+    let products = products
+        .into_iter()
+        .map(|product| ProductResponse {
+            id: product.id,
+            variant_id: product.variant_id,
+            is_suspension: product.is_suspension,
+        })
+        .collect();
+
+    let response = Paginated::new(products, total, &page, |product| product.id.clone());
+    let body = serde_json::to_vec(&response).expect("レスポンスのシリアライズに失敗");
+
+    Ok(conditional_json(&headers, body))
+}
+
+// JSONの本文にETagを付けて返す
+// If-None-Match がETagに一致する場合は本文なしの304を返す
+fn conditional_json(headers: &HeaderMap, body: Vec<u8>) -> Response {
+    let etag = weak_etag(&body);
+
+    if etag_matches(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::ETAG, etag),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// 一度も注文されていない販売中の商品の一覧を返す（シード後の網羅性の確認や品揃えの分析用）
+pub async fn get_unsold_products(
+    State(pool): State<Arc<mysql::Pool>>,
+    Query(params): Query<PageQuery>,
+) -> Result<axum::Json<Paginated<ProductResponse>>, AppError> {
+    let page = params.into_page::<String>()?;
+
+    let products = db::get_unsold_products(pool.clone(), page.clone()).await?;
+    let total = db::count_unsold_products(pool).await?;
+
+    let products = products
+        .into_iter()
+        .map(|product| ProductResponse {
+            id: product.id,
+            variant_id: product.variant_id,
+            is_suspension: product.is_suspension,
+        })
+        .collect();
+
+    Ok(axum::Json(Paginated::new(
+        products,
+        total,
+        &page,
+        |product| product.id.clone(),
+    )))
+}
+
+// 販売停止状態の変更を推薦に反映する
+// 商品次元情報を取得し直し、停止した商品を返さないようキャッシュ済みの推薦結果を削除する
+// 変更した直後のため、どちらもレプリカではなく主データベースを使う
+// 失敗した場合は状態の変更自体は成功しているため、ログに出力するだけにする
+async fn refresh_recommendations(
+    dimensions_cache: &DimensionsCache,
+    recommendations: Arc<dyn RecommendationRepository>,
+) {
+    if let Err(err) =
+        dimensions::refresh_from_primary(dimensions_cache, recommendations.clone()).await
+    {
+        tracing::error!(error = %err, "商品次元情報の更新に失敗しました");
+    }
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let cleared = tokio::task::spawn_blocking(move || {
+        recommendations
+            .primary_session()?
+            .clear_cached_suggestions()
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗");
+    if let Err(err) = cleared {
+        tracing::error!(error = %err, "推薦結果のキャッシュの削除に失敗しました");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SuspensionRequest {
+    suspended: bool,
+}
+
+#[derive(Serialize)]
+pub struct SuspensionResponse {
+    variant_id: String,
+    suspended: bool,
+}
+
+// 商品の販売停止状態を切り替える（要認証）
+pub async fn put_product_suspension(
+    AuthUser(user): AuthUser,
+    State(pool): State<Arc<mysql::Pool>>,
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions_cache): State<Arc<DimensionsCache>>,
+    Path(variant_id): Path<String>,
+    Json(body): Json<SuspensionRequest>,
+) -> Result<axum::Json<SuspensionResponse>, AppError> {
+    let found = db::set_product_suspension(pool, variant_id.clone(), body.suspended).await?;
+    if !found {
+        return Err(AppError::NotFound(format!(
+            "Product variant {} not found",
+            variant_id
+        )));
+    }
+
+    tracing::info!(
+        user_id = user.id,
+        variant_id = %variant_id,
+        suspended = body.suspended,
+        "商品の販売停止状態を変更しました"
+    );
+
+    // 推薦対象の商品が変わるため、推薦に使うデータを更新する
+    refresh_recommendations(&dimensions_cache, recommendations).await;
+
+    Ok(axum::Json(SuspensionResponse {
+        variant_id,
+        suspended: body.suspended,
+    }))
+}
+
+// 一度に販売停止状態を切り替えられる商品数の上限
+const MAX_BULK_SUSPENSION: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct BulkSuspensionRequest {
+    variant_ids: Vec<String>,
+    suspended: bool,
+}
+
+#[derive(Serialize)]
+pub struct BulkSuspensionResponse {
+    // 状態が実際に変わった商品の数
+    updated: u64,
+    suspended: bool,
+    // 存在しなかった商品ID（指定ミスに気付けるよう返す）
+    unknown_variant_ids: Vec<String>,
+}
+
+// 複数の商品の販売停止状態をまとめて切り替える（要認証）
+// 推薦の変更を試す際に、まとめて推薦対象から外す・戻すために使う
+pub async fn post_products_suspension(
+    AuthUser(user): AuthUser,
+    State(pool): State<Arc<mysql::Pool>>,
+    State(recommendations): State<Arc<dyn RecommendationRepository>>,
+    State(dimensions_cache): State<Arc<DimensionsCache>>,
+    Json(body): Json<BulkSuspensionRequest>,
+) -> Result<axum::Json<BulkSuspensionResponse>, AppError> {
+    if body.variant_ids.is_empty() {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "variant_ids",
+            "must not be empty",
+        )]));
+    }
+    if body.variant_ids.len() > MAX_BULK_SUSPENSION {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "variant_ids",
+            format!("must contain at most {} items", MAX_BULK_SUSPENSION),
+        )]));
+    }
+
+    let requested = body.variant_ids.len();
+    let result = db::set_products_suspension(pool, body.variant_ids, body.suspended).await?;
+
+    tracing::info!(
+        user_id = user.id,
+        requested,
+        updated = result.updated,
+        unknown = result.unknown_variant_ids.len(),
+        suspended = body.suspended,
+        "複数の商品の販売停止状態を変更しました"
+    );
+
+    // 推薦対象の商品が変わるため、推薦に使うデータを1回だけ更新する
+    if result.updated > 0 {
+        refresh_recommendations(&dimensions_cache, recommendations).await;
+    }
+
+    Ok(axum::Json(BulkSuspensionResponse {
+        updated: result.updated,
+        suspended: body.suspended,
+        unknown_variant_ids: result.unknown_variant_ids,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use mysql::prelude::Queryable;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::mock::MockStore;
+    use crate::repository::{MySqlStore, RecommendationSession};
+    use crate::state::AppState;
+    use crate::testing;
+
+    // どちらのセッションを開始したかを数える固定データのストア
+    #[derive(Default)]
+    struct RoutingStore {
+        sessions: AtomicUsize,
+        primary_sessions: AtomicUsize,
+    }
+
+    impl RecommendationRepository for RoutingStore {
+        fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+            self.sessions.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(MockStore))
+        }
+
+        fn primary_session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+            self.primary_sessions.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(MockStore))
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_after_a_suspension_change_reads_the_primary() {
+        let store = Arc::new(RoutingStore::default());
+        let cache = DimensionsCache::default();
+
+        refresh_recommendations(&cache, store.clone()).await;
+
+        // 次元情報の取得とキャッシュの削除はどちらも主データベースのセッションで行う
+        assert_eq!(store.primary_sessions.load(Ordering::SeqCst), 2);
+        assert_eq!(store.sessions.load(Ordering::SeqCst), 0);
+        assert!(cache.get().is_some());
+    }
+
+    #[tokio::test]
+    async fn matching_etag_is_answered_with_304() {
+        let body = br#"{"data":[{"id":"1"}]}"#.to_vec();
+
+        let response = conditional_json(&HeaderMap::new(), body.clone());
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with("W/\""));
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(bytes, body);
+
+        // 取得したETagを If-None-Match に指定すると本文なしの304になる
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let response = conditional_json(&headers, body.clone());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(bytes.is_empty());
+
+        // 内容が変われば一致しない
+        let response = conditional_json(&headers, br#"{"data":[]}"#.to_vec());
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // カートに1つの商品を入れたときの推薦商品のバリアントID
+    async fn suggested_variants(state: &AppState, variant_id: &str) -> Vec<String> {
+        let products = serde_json::json!([{ "product_variant_id": variant_id }]).to_string();
+        let uri = format!(
+            "/suggestions?{}",
+            serde_urlencoded::to_string([("province_code", "JP-13"), ("products", &products)])
+                .unwrap()
+        );
+        let (status, body) = testing::send(testing::app(state.clone()), testing::get(&uri)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["suggestions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|suggestion| {
+                suggestion["product_variant_id"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    // 販売停止状態を切り替える
+    async fn set_suspension(state: &AppState, variant_id: &str, suspended: bool) -> StatusCode {
+        let request = Request::put(format!("/products/{}/suspension", variant_id))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({ "suspended": suspended }).to_string(),
+            ))
+            .unwrap();
+        let (status, _) =
+            testing::send(testing::app(state.clone()), testing::authorized(request)).await;
+        status
+    }
+
+    // 複数の商品の販売停止状態をまとめて切り替える
+    async fn set_bulk_suspension(
+        state: &AppState,
+        variant_ids: &[String],
+        suspended: bool,
+    ) -> (StatusCode, serde_json::Value) {
+        let body = serde_json::json!({ "variant_ids": variant_ids, "suspended": suspended });
+        testing::send(
+            testing::app(state.clone()),
+            testing::authorized(testing::post_json(
+                "/admin/products/suspend",
+                body.to_string(),
+            )),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を登録済み）が必要"]
+    async fn bulk_suspension_flips_every_known_variant_and_reports_unknown_ones() {
+        let pool = Arc::new(testing::database_pool());
+        let state = AppState::with_store(
+            pool.clone(),
+            Arc::new(testing::TokenUsers),
+            Arc::new(MySqlStore::new(pool.clone(), None)),
+        );
+        let mut conn = pool.get_conn().unwrap();
+        let variant_ids: Vec<String> = conn
+            .query(
+                "SELECT CAST(variant_id AS CHAR) FROM products WHERE is_suspension = false LIMIT 3",
+            )
+            .unwrap();
+        assert_eq!(variant_ids.len(), 3, "販売中の商品が足りません");
+        let unknown = format!("unknown-{}", uuid::Uuid::new_v4());
+        let mut requested = variant_ids.clone();
+        requested.push(unknown.clone());
+
+        let (status, body) = set_bulk_suspension(&state, &requested, true).await;
+        let suspended: Vec<bool> = conn
+            .exec(
+                "SELECT is_suspension FROM products WHERE variant_id IN (?, ?, ?)",
+                variant_ids.clone(),
+            )
+            .unwrap();
+        // 失敗しても他のテストに影響しないよう、確認の前に販売を再開する
+        let (restored, restored_body) = set_bulk_suspension(&state, &variant_ids, false).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["updated"], 3);
+        assert_eq!(body["suspended"], true);
+        assert_eq!(body["unknown_variant_ids"], serde_json::json!([unknown]));
+        assert_eq!(suspended, [true, true, true]);
+        assert_eq!(restored, StatusCode::OK);
+        assert_eq!(restored_body["updated"], 3);
+        assert_eq!(restored_body["unknown_variant_ids"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn bulk_suspension_rejects_an_empty_list() {
+        let state = testing::state(Arc::new(testing::TokenUsers), Arc::new(MockStore));
+
+        let (status, body) = set_bulk_suspension(&state, &[], true).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], "variant_ids");
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（注文を登録済み）が必要"]
+    async fn suspended_product_drops_out_of_suggestions() {
+        let pool = Arc::new(testing::database_pool());
+        let state = AppState::with_store(
+            pool.clone(),
+            Arc::new(testing::TokenUsers),
+            Arc::new(MySqlStore::new(pool.clone(), None)),
+        );
+        let cart_variant: String = pool
+            .get_conn()
+            .unwrap()
+            .query_first(
+                "
+              SELECT CAST(op.variant_id AS CHAR)
+              FROM order_products op
+              JOIN products p ON p.variant_id = op.variant_id
+              WHERE p.is_suspension = false
+              LIMIT 1
+              ",
+            )
+            .unwrap()
+            .expect("販売中の商品の注文がありません");
+
+        let before = suggested_variants(&state, &cart_variant).await;
+        let suspended = before.first().expect("推薦商品がありません").clone();
+
+        assert_eq!(
+            set_suspension(&state, &suspended, true).await,
+            StatusCode::OK
+        );
+        let after = suggested_variants(&state, &cart_variant).await;
+        // 失敗しても他のテストに影響しないよう、確認の前に販売を再開する
+        assert_eq!(
+            set_suspension(&state, &suspended, false).await,
+            StatusCode::OK
+        );
+
+        assert!(!after.contains(&suspended), "{:?}", after);
+    }
+}
@@ -0,0 +1,55 @@
+use axum::Json;
+use serde::Serialize;
+
+use crate::service::region;
+
+// 都道府県コードと都道府県名
+#[derive(Serialize)]
+pub struct ProvinceResponse {
+    code: String,
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct ProvincesResponse {
+    provinces: Vec<ProvinceResponse>,
+}
+
+// 指定可能な都道府県コード（/suggestions の province_code）と都道府県名の一覧を返す
+pub async fn get_provinces() -> Json<ProvincesResponse> {
+    let provinces = region::provinces()
+        .map(|(code, name)| ProvinceResponse { code, name })
+        .collect();
+
+    Json(ProvincesResponse { provinces })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use std::sync::Arc;
+
+    use crate::mock::MockStore;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn lists_all_47_provinces_with_codes_and_names() {
+        let store = Arc::new(MockStore);
+        let app = testing::app(testing::state(store.clone(), store));
+
+        let (status, body) = testing::send(app, testing::get("/provinces")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let provinces = body["provinces"].as_array().unwrap();
+        assert_eq!(provinces.len(), 47);
+        // コードは JP-01 から JP-47 まで順に並ぶ
+        for (i, province) in provinces.iter().enumerate() {
+            assert_eq!(province["code"], format!("JP-{:02}", i + 1));
+            assert!(!province["name"].as_str().unwrap().is_empty());
+        }
+        assert_eq!(provinces[0]["name"], "北海道");
+        assert_eq!(provinces[12]["code"], "JP-13");
+        assert_eq!(provinces[12]["name"], "東京都");
+        assert_eq!(provinces[46]["name"], "沖縄県");
+    }
+}
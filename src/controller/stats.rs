@@ -0,0 +1,127 @@
+use axum::{Json, extract::State};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::error::AppError;
+use crate::service::stats::StatsCache;
+
+// 注文日時の範囲
+#[derive(Serialize)]
+pub struct OrderDateRange {
+    first: Option<String>,
+    last: Option<String>,
+}
+
+// データセットの集計値のレスポンス
+#[derive(Serialize)]
+pub struct StatsResponse {
+    customers: u64,
+    orders: u64,
+    order_lines: u64,
+    active_products: u64,
+    order_date_range: OrderDateRange,
+}
+
+// データセット全体の件数と注文期間を返す
+pub async fn get_stats(
+    State(pool): State<Arc<mysql::Pool>>,
+    State(cache): State<Arc<StatsCache>>,
+) -> Result<Json<StatsResponse>, AppError> {
+    let stats = cache.get(pool).await?;
+
+    Ok(Json(StatsResponse {
+        customers: stats.customers,
+        orders: stats.orders,
+        order_lines: stats.order_lines,
+        active_products: stats.active_products,
+        order_date_range: OrderDateRange {
+            first: stats.first_order_at,
+            last: stats.last_order_at,
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use mysql::prelude::*;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::command::seed;
+    use crate::repository::MySqlStore;
+    use crate::state::AppState;
+    use crate::testing;
+
+    // キャッシュせずに集計する /stats の結果
+    async fn stats(pool: &Arc<mysql::Pool>) -> serde_json::Value {
+        let mut state = AppState::with_store(
+            pool.clone(),
+            Arc::new(testing::TokenUsers),
+            Arc::new(MySqlStore::new(pool.clone(), None)),
+        );
+        state.stats = Arc::new(StatsCache::new(Duration::ZERO));
+        let (status, body) = testing::send(testing::app(state), testing::get("/stats")).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を登録済み）が必要"]
+    async fn stats_count_the_seeded_fixture() {
+        let pool = Arc::new(testing::database_pool());
+        let email_domain = "stats.example.com".to_string();
+        seed::seed_customers(
+            (*pool).clone(),
+            3,
+            1,
+            seed::province_distribution(None).unwrap(),
+            seed::CustomerOptions {
+                email_domain: email_domain.clone(),
+                on_conflict: seed::OnConflict::Skip,
+                progress: seed::Progress::new(true, 0),
+            },
+        )
+        .await
+        .unwrap();
+        let before = stats(&pool).await;
+
+        // 2商品ずつの注文を4件追加する
+        let options = seed::OrderOptions {
+            currency: seed::Currency::Jpy,
+            tax_rate: 0.1,
+            subscription_ratio: 0.0,
+            guest_ratio: 0.0,
+            items: 2..=2,
+            quantity: 1..=1,
+            email_domain,
+            progress: seed::Progress::new(true, 0),
+        };
+        seed::seed_orders((*pool).clone(), 4, 1.0, options, true)
+            .await
+            .unwrap();
+        let after = stats(&pool).await;
+
+        let count = |body: &serde_json::Value, key: &str| body[key].as_u64().unwrap();
+        assert_eq!(count(&after, "orders") - count(&before, "orders"), 4);
+        assert_eq!(
+            count(&after, "order_lines") - count(&before, "order_lines"),
+            8
+        );
+        let mut conn = pool.get_conn().unwrap();
+        let customers: u64 = conn
+            .query_first("SELECT COUNT(*) FROM customers")
+            .unwrap()
+            .unwrap();
+        let active_products: u64 = conn
+            .query_first(
+                "SELECT COUNT(DISTINCT variant_id) FROM products WHERE is_suspension = false",
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(count(&after, "customers"), customers);
+        assert_eq!(count(&after, "active_products"), active_products);
+        assert!(after["order_date_range"]["first"].is_string());
+        assert!(after["order_date_range"]["last"].is_string());
+    }
+}
@@ -1,5 +1,8 @@
-use crate::db;
-use axum::{extract::State, Json};
+use crate::controller::extract::Query;
+use crate::controller::pagination::{PageQuery, Paginated};
+use crate::error::AppError;
+use crate::repository::UserRepository;
+use axum::{Json, extract::State};
 use serde::Serialize;
 use std::sync::Arc;
 
@@ -12,43 +15,93 @@ pub struct UserResponse {
     api_token: String,
 }
 
-// レスポンス全体の構造体
-#[derive(Serialize)]
-pub struct ApiResponse {
-    message: String,
-    users: Vec<UserResponse>,
+// ルートパスのハンドラ - JSONを返すように変更
+pub async fn get_users(
+    State(users): State<Arc<dyn UserRepository>>,
+    Query(params): Query<PageQuery>,
+) -> Result<Json<Paginated<UserResponse>>, AppError> {
+    let page = params.into_page::<i32>()?;
+
+    // ユーザー一覧と全件数を取得
+    let total = users.count_users().await?;
+    let users = users.get_users(page.clone()).await?;
+
+    // ユーザーデータをUserResponse構造体に変換
+    let user_responses: Vec<UserResponse> = users
+        .into_iter()
+        .map(|user| UserResponse {
+            id: user.id,
+            name: user.name,
+            email: user.email,
+            api_token: user.api_token.unwrap_or("".to_string()),
+        })
+        .collect();
+
+    // JSONレスポンスを返す
+    Ok(Json(Paginated::new(user_responses, total, &page, |user| {
+        user.id
+    })))
 }
 
-// ルートパスのハンドラ - JSONを返すように変更
-pub async fn get_users(pool: State<Arc<mysql::Pool>>) -> Json<ApiResponse> {
-    // ユーザー一覧を取得
-    let users_result = db::get_users(pool.0.clone()).await;
-
-    match users_result {
-        Ok(users) => {
-            // ユーザーデータをUserResponse構造体に変換
-            let user_responses: Vec<UserResponse> = users
-                .into_iter()
-                .map(|user| UserResponse {
-                    id: user.id,
-                    name: user.name,
-                    email: user.email,
-                    api_token: user.api_token.unwrap_or("".to_string()),
-                })
-                .collect();
-
-            // JSONレスポンスを返す
-            Json(ApiResponse {
-                message: "Successfully retrieved users".to_string(),
-                users: user_responses,
-            })
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use crate::db::{Page, User};
+    use crate::mock::MockStore;
+    use crate::repository::UserRepository;
+    use crate::testing;
+
+    // 決まったユーザーを返すリポジトリ
+    struct FakeUsers;
+
+    #[async_trait]
+    impl UserRepository for FakeUsers {
+        async fn get_users(&self, _page: Page<i32>) -> Result<Vec<User>, mysql::Error> {
+            Ok(vec![
+                User {
+                    id: 7,
+                    name: "Alice".to_string(),
+                    email: "alice@example.com".to_string(),
+                    api_token: Some("token-7".to_string()),
+                },
+                User {
+                    id: 9,
+                    name: "Bob".to_string(),
+                    email: "bob@example.com".to_string(),
+                    api_token: None,
+                },
+            ])
         }
-        Err(e) => {
-            // エラーの場合も適切なJSONレスポンスを返す
-            Json(ApiResponse {
-                message: format!("データベースエラー: {}", e),
-                users: Vec::new(),
-            })
+
+        async fn count_users(&self) -> Result<u64, mysql::Error> {
+            Ok(2)
         }
+
+        async fn find_user_by_api_token(&self, _token: &str) -> Result<Option<User>, mysql::Error> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn get_users_returns_users_from_repository_as_json() {
+        let app = testing::app(testing::state(Arc::new(FakeUsers), Arc::new(MockStore)));
+
+        let (status, body) = testing::send(app, testing::get("/users")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "data": [
+                    {"id": 7, "name": "Alice", "email": "alice@example.com", "api_token": "token-7"},
+                    {"id": 9, "name": "Bob", "email": "bob@example.com", "api_token": ""},
+                ],
+                "meta": {"total": 2, "count": 2, "page": null, "per_page": 100, "next_cursor": null},
+            })
+        );
     }
 }
@@ -1,6 +1,45 @@
 use mysql::prelude::*;
 use mysql::*;
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::config;
+
+// 低速クエリの閾値（初回の計測時に環境変数から読み込む）
+static SLOW_QUERY_THRESHOLD: OnceLock<std::time::Duration> = OnceLock::new();
+
+// クエリをスパン内で実行し、クエリ名と実行時間（ミリ秒）を記録する
+// 閾値（SLOW_QUERY_MS）を超えた場合は警告を出力する
+pub fn timed<T>(name: &'static str, query: impl FnOnce() -> Result<T>) -> Result<T> {
+    let span = tracing::info_span!("db_query", query = name, elapsed_ms = tracing::field::Empty);
+    let _entered = span.enter();
+
+    let started = Instant::now();
+    let result = query();
+    let elapsed = started.elapsed();
+    let elapsed_ms = elapsed.as_millis() as u64;
+    span.record("elapsed_ms", elapsed_ms);
+
+    let threshold = SLOW_QUERY_THRESHOLD.get_or_init(config::database::get_slow_query_threshold);
+    if elapsed >= *threshold {
+        tracing::warn!(query = name, elapsed_ms, "低速なクエリを検出しました");
+    } else {
+        tracing::debug!(query = name, elapsed_ms, "クエリを実行しました");
+    }
+    result
+}
+
+// 行から商品ID（variant_id）を文字列として読み込む
+// 整数の列（123）と文字列の列（SKU-001）のどちらでも同じ文字列表現になるよう、値の型を見て変換する
+pub fn read_variant_id(row: &Row, column: &str) -> String {
+    match row.get::<Value, _>(column) {
+        Some(Value::Int(id)) => id.to_string(),
+        Some(Value::UInt(id)) => id.to_string(),
+        Some(Value::Bytes(bytes)) => String::from_utf8_lossy(&bytes).into_owned(),
+        _ => String::new(),
+    }
+}
 
 // ユーザー情報を格納する構造体
 #[derive(Debug)]
@@ -11,22 +50,165 @@ pub struct User {
     pub api_token: Option<String>,
 }
 
+// 一覧取得のページ指定
+// 主キー順のキーセット（カーソル）方式を推奨する。オフセット方式は深いページほど遅く、
+// 取得中に行が追加されると重複や欠落が起こりうるため、互換性のために残している
+#[derive(Debug, Clone)]
+pub enum Page<C> {
+    // 指定したカーソル（主キー）より後ろの行を取得
+    After { cursor: Option<C>, limit: usize },
+    // ページ番号（1始まり）で取得
+    Offset { page: usize, per_page: usize },
+}
+
+impl<C> Page<C> {
+    // 1ページあたりの最大件数
+    pub fn limit(&self) -> usize {
+        match self {
+            Page::After { limit, .. } => *limit,
+            Page::Offset { per_page, .. } => *per_page,
+        }
+    }
+
+    // オフセット方式の場合のページ番号
+    pub fn page_number(&self) -> Option<usize> {
+        match self {
+            Page::After { .. } => None,
+            Page::Offset { page, .. } => Some(*page),
+        }
+    }
+}
+
+impl<C: Into<Value>> Page<C> {
+    // WHERE/ORDER BY/LIMIT句とそのパラメータを作成
+    fn into_clause(self, key_column: &str) -> (String, Vec<Value>) {
+        match self {
+            Page::After {
+                cursor: Some(cursor),
+                limit,
+            } => (
+                format!("WHERE {key_column} > ? ORDER BY {key_column} LIMIT ?"),
+                vec![cursor.into(), limit.into()],
+            ),
+            Page::After {
+                cursor: None,
+                limit,
+            } => (format!("ORDER BY {key_column} LIMIT ?"), vec![limit.into()]),
+            Page::Offset { page, per_page } => (
+                format!("ORDER BY {key_column} LIMIT ? OFFSET ?"),
+                vec![per_page.into(), ((page - 1) * per_page).into()],
+            ),
+        }
+    }
+}
+
+// テーブルの全件数を取得する関数
+pub async fn count_rows(pool: Arc<mysql::Pool>, table: &'static str) -> Result<u64> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let count = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let count: Option<u64> = timed("count_rows", || {
+            conn.query_first(format!("SELECT COUNT(*) FROM {}", table))
+        })?;
+        Ok::<u64, mysql::Error>(count.unwrap_or(0))
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(count)
+}
+
+// データセット全体の集計値
+#[derive(Debug, Clone)]
+pub struct DatasetStats {
+    pub customers: u64,
+    pub orders: u64,
+    pub order_lines: u64,
+    pub active_products: u64,
+    pub first_order_at: Option<String>,
+    pub last_order_at: Option<String>,
+}
+
+// データセットの集計値を取得する関数
+// 各集計は独立しているため、別々の接続で並行して実行する
+pub async fn get_dataset_stats(pool: Arc<mysql::Pool>) -> Result<DatasetStats> {
+    let (customers, orders, order_lines, active_products, (first_order_at, last_order_at)) = tokio::try_join!(
+        count_rows(pool.clone(), "customers"),
+        count_rows(pool.clone(), "orders"),
+        count_rows(pool.clone(), "order_products"),
+        count_active_products(pool.clone()),
+        get_order_date_range(pool),
+    )?;
+
+    Ok(DatasetStats {
+        customers,
+        orders,
+        order_lines,
+        active_products,
+        first_order_at,
+        last_order_at,
+    })
+}
+
+// 販売中の商品（バリエーション）の数を取得する関数
+async fn count_active_products(pool: Arc<mysql::Pool>) -> Result<u64> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let count = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let count: Option<u64> = timed("count_active_products", || {
+            conn.query_first(
+                "SELECT COUNT(DISTINCT variant_id) FROM products WHERE is_suspension = false",
+            )
+        })?;
+        Ok::<u64, mysql::Error>(count.unwrap_or(0))
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(count)
+}
+
+// 最初と最後の注文日時を取得する関数（注文がない場合は None）
+async fn get_order_date_range(pool: Arc<mysql::Pool>) -> Result<(Option<String>, Option<String>)> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let range = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let range: Option<(Option<String>, Option<String>)> =
+            timed("get_order_date_range", || {
+                conn.query_first(
+                    "SELECT DATE_FORMAT(MIN(created_at), '%Y-%m-%d %H:%i:%s'),
+                        DATE_FORMAT(MAX(created_at), '%Y-%m-%d %H:%i:%s')
+                 FROM orders",
+                )
+            })?;
+        Ok::<_, mysql::Error>(range.unwrap_or_default())
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(range)
+}
+
 // ユーザー一覧を取得する関数
-pub async fn get_users(pool: Arc<mysql::Pool>) -> Result<Vec<User>> {
+pub async fn get_users(pool: Arc<mysql::Pool>, page: Page<i32>) -> Result<Vec<User>> {
     // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
     let users = tokio::task::spawn_blocking(move || {
         let mut conn = pool.get_conn()?;
 
         // usersテーブルからデータを取得
-        let users: Vec<User> = conn.query_map(
-            "SELECT id, name, email, api_token FROM users",
-            |(id, name, email, api_token)| User {
-                id,
-                name,
-                email,
-                api_token,
-            },
-        )?;
+        let (clause, params) = page.into_clause("id");
+        let users: Vec<User> = timed("get_users", || {
+            conn.exec_map(
+                format!("SELECT id, name, email, api_token FROM users {}", clause),
+                params,
+                |(id, name, email, api_token)| User {
+                    id,
+                    name,
+                    email,
+                    api_token,
+                },
+            )
+        })?;
         Ok::<Vec<User>, mysql::Error>(users)
     })
     .await
@@ -34,3 +216,565 @@ pub async fn get_users(pool: Arc<mysql::Pool>) -> Result<Vec<User>> {
 
     Ok(users)
 }
+
+// APIトークンに対応するユーザーを取得する関数
+pub async fn find_user_by_api_token(pool: Arc<mysql::Pool>, token: String) -> Result<Option<User>> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let user = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let user: Option<User> = timed("find_user_by_api_token", || {
+            conn.exec_first(
+                "SELECT id, name, email, api_token FROM users WHERE api_token = ?",
+                (token,),
+            )
+        })?
+        .map(|(id, name, email, api_token)| User {
+            id,
+            name,
+            email,
+            api_token,
+        });
+        Ok::<Option<User>, mysql::Error>(user)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(user)
+}
+
+// 商品情報を格納する構造体
+#[derive(Debug)]
+pub struct Product {
+    pub id: String,
+    pub variant_id: String,
+    pub is_suspension: bool,
+}
+
+// 商品一覧を取得する関数
+pub async fn get_products(pool: Arc<mysql::Pool>, page: Page<String>) -> Result<Vec<Product>> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let products = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        let (clause, params) = page.into_clause("id");
+        let products: Vec<Product> = timed("get_products", || {
+            conn.exec_map(
+                format!(
+                    "SELECT id, variant_id, is_suspension FROM products {}",
+                    clause
+                ),
+                params,
+                |row: Row| Product {
+                    id: row.get("id").unwrap_or_default(),
+                    variant_id: read_variant_id(&row, "variant_id"),
+                    is_suspension: row.get("is_suspension").unwrap_or_default(),
+                },
+            )
+        })?;
+        Ok::<Vec<Product>, mysql::Error>(products)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(products)
+}
+
+// 一度も注文されていない販売中の商品（order_products に1行もない商品）を取得する副問い合わせ
+const UNSOLD_PRODUCTS: &str = "SELECT p.id, p.variant_id, p.is_suspension
+     FROM products p
+     LEFT JOIN order_products op ON op.variant_id = p.variant_id
+     WHERE op.id IS NULL AND p.is_suspension = false";
+
+// 一度も注文されていない販売中の商品の一覧を取得する関数
+pub async fn get_unsold_products(
+    pool: Arc<mysql::Pool>,
+    page: Page<String>,
+) -> Result<Vec<Product>> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let products = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        // ページ指定の句がWHEREを含むため、絞り込みは副問い合わせで行う
+        let (clause, params) = page.into_clause("id");
+        let products: Vec<Product> = timed("get_unsold_products", || {
+            conn.exec_map(
+                format!(
+                    "SELECT id, variant_id, is_suspension FROM ({}) AS unsold {}",
+                    UNSOLD_PRODUCTS, clause
+                ),
+                params,
+                |row: Row| Product {
+                    id: row.get("id").unwrap_or_default(),
+                    variant_id: read_variant_id(&row, "variant_id"),
+                    is_suspension: row.get("is_suspension").unwrap_or_default(),
+                },
+            )
+        })?;
+        Ok::<Vec<Product>, mysql::Error>(products)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(products)
+}
+
+// 一度も注文されていない販売中の商品の件数を取得する関数
+pub async fn count_unsold_products(pool: Arc<mysql::Pool>) -> Result<u64> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let count = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let count: Option<u64> = timed("count_unsold_products", || {
+            conn.query_first(format!(
+                "SELECT COUNT(*) FROM ({}) AS unsold",
+                UNSOLD_PRODUCTS
+            ))
+        })?;
+        Ok::<u64, mysql::Error>(count.unwrap_or(0))
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(count)
+}
+
+// 商品（バリエーション）の販売停止状態を更新する関数
+// 該当する商品がない場合は false を返す
+pub async fn set_product_suspension(
+    pool: Arc<mysql::Pool>,
+    variant_id: String,
+    suspended: bool,
+) -> Result<bool> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let found = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        // 値が変わらない場合は更新件数が0になるため、存在は別途確認する
+        let exists: Option<u64> = timed("find_product_variant", || {
+            conn.exec_first(
+                "SELECT COUNT(*) FROM products WHERE variant_id = ?",
+                (&variant_id,),
+            )
+        })?;
+        if exists.unwrap_or(0) == 0 {
+            return Ok(false);
+        }
+
+        timed("set_product_suspension", || {
+            conn.exec_drop(
+                "UPDATE products SET is_suspension = ? WHERE variant_id = ?",
+                (suspended, &variant_id),
+            )
+        })?;
+        Ok::<bool, mysql::Error>(true)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(found)
+}
+
+// 複数の商品の販売停止状態をまとめて更新した結果
+#[derive(Debug, Default)]
+pub struct BulkSuspension {
+    // 状態が実際に変わった商品の数（すでに同じ状態の商品は数えない）
+    pub updated: u64,
+    // 指定された商品IDのうち、存在しないもの（指定された順）
+    pub unknown_variant_ids: Vec<String>,
+}
+
+// 複数の商品（バリエーション）の販売停止状態をまとめて更新する関数
+pub async fn set_products_suspension(
+    pool: Arc<mysql::Pool>,
+    variant_ids: Vec<String>,
+    suspended: bool,
+) -> Result<BulkSuspension> {
+    if variant_ids.is_empty() {
+        return Ok(BulkSuspension::default());
+    }
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let result = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+        let placeholders = vec!["?"; variant_ids.len()].join(", ");
+
+        // 値が変わらない場合は更新件数に含まれないため、存在は別途確認する
+        let found: HashSet<String> = timed("find_product_variants", || {
+            conn.exec_map(
+                format!(
+                    "SELECT variant_id FROM products WHERE variant_id IN ({})",
+                    placeholders
+                ),
+                variant_ids.clone(),
+                |row: Row| read_variant_id(&row, "variant_id"),
+            )
+        })?
+        .into_iter()
+        .collect();
+        let mut unknown_variant_ids: Vec<String> = Vec::new();
+        for variant_id in &variant_ids {
+            if !found.contains(variant_id) && !unknown_variant_ids.contains(variant_id) {
+                unknown_variant_ids.push(variant_id.clone());
+            }
+        }
+
+        let query = format!(
+            "UPDATE products SET is_suspension = ? WHERE variant_id IN ({})",
+            placeholders
+        );
+        let mut params: Vec<Value> = Vec::with_capacity(variant_ids.len() + 1);
+        params.push(suspended.into());
+        params.extend(variant_ids.into_iter().map(Value::from));
+
+        timed("set_products_suspension", || conn.exec_drop(query, params))?;
+        Ok::<BulkSuspension, mysql::Error>(BulkSuspension {
+            updated: conn.affected_rows(),
+            unknown_variant_ids,
+        })
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(result)
+}
+
+// マーケティング配信の可否・インフォマーシャル経由かどうかで分けた顧客数
+#[derive(Debug, Default)]
+pub struct CustomerSegmentCounts {
+    pub total: u64,
+    pub accepts_marketing: u64,
+    pub declines_marketing: u64,
+    pub is_infomercial: u64,
+}
+
+// セグメント一覧の顧客1件分
+#[derive(Debug)]
+pub struct CustomerSegmentEntry {
+    pub id: String,
+    pub accepts_marketing: bool,
+    pub is_infomercial: bool,
+}
+
+// 顧客の絞り込み条件（accepts_marketing を指定した場合はその値の顧客だけを対象にする）
+fn customer_segment_filter(accepts_marketing: Option<bool>) -> (&'static str, Vec<Value>) {
+    match accepts_marketing {
+        Some(accepts) => ("WHERE accepts_marketing = ?", vec![accepts.into()]),
+        None => ("", vec![]),
+    }
+}
+
+// マーケティング配信の可否ごとの顧客数を取得する関数
+pub async fn count_customer_segments(
+    pool: Arc<mysql::Pool>,
+    accepts_marketing: Option<bool>,
+) -> Result<CustomerSegmentCounts> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let counts = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        let (filter, params) = customer_segment_filter(accepts_marketing);
+        let groups: Vec<(bool, bool, u64)> = timed("count_customer_segments", || {
+            conn.exec(
+                format!(
+                    "SELECT accepts_marketing <> 0, is_infomercial <> 0, COUNT(*)
+                     FROM customers {}
+                     GROUP BY 1, 2",
+                    filter
+                ),
+                params,
+            )
+        })?;
+
+        let mut counts = CustomerSegmentCounts::default();
+        for (accepts, infomercial, count) in groups {
+            counts.total += count;
+            if accepts {
+                counts.accepts_marketing += count;
+            } else {
+                counts.declines_marketing += count;
+            }
+            if infomercial {
+                counts.is_infomercial += count;
+            }
+        }
+        Ok::<CustomerSegmentCounts, mysql::Error>(counts)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(counts)
+}
+
+// セグメントに含まれる顧客の一覧を取得する関数
+pub async fn get_customer_segment_entries(
+    pool: Arc<mysql::Pool>,
+    accepts_marketing: Option<bool>,
+    page: Page<String>,
+) -> Result<Vec<CustomerSegmentEntry>> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let entries = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        // ページ指定の句がWHEREを含むため、絞り込みは副問い合わせで行う
+        let (filter, mut params) = customer_segment_filter(accepts_marketing);
+        let (clause, page_params) = page.into_clause("id");
+        params.extend(page_params);
+
+        let entries: Vec<CustomerSegmentEntry> = timed("get_customer_segment_entries", || {
+            conn.exec_map(
+                format!(
+                    "SELECT id, accepts_marketing <> 0, is_infomercial <> 0
+                     FROM (SELECT id, accepts_marketing, is_infomercial FROM customers {}) AS segment
+                     {}",
+                    filter, clause
+                ),
+                params,
+                |(id, accepts_marketing, is_infomercial)| CustomerSegmentEntry {
+                    id,
+                    accepts_marketing,
+                    is_infomercial,
+                },
+            )
+        })?;
+        Ok::<Vec<CustomerSegmentEntry>, mysql::Error>(entries)
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(entries)
+}
+
+// 注文の明細
+#[derive(Debug)]
+pub struct OrderLineItem {
+    pub variant_id: String,
+    pub quantity: u32,
+    pub price: f64,
+}
+
+// 明細付きの注文情報
+#[derive(Debug)]
+pub struct CustomerOrder {
+    pub id: String,
+    // ゲスト購入の注文は顧客に紐づかない（NULL）
+    pub customer_id: Option<String>,
+    pub created_at: String,
+    pub total_price: f64,
+    pub line_items: Vec<OrderLineItem>,
+}
+
+// 顧客の注文履歴を明細付きで取得する関数
+// 顧客が存在しない場合は None を返す
+pub async fn get_customer_orders(
+    pool: Arc<mysql::Pool>,
+    customer_id: String,
+    page: usize,
+    per_page: usize,
+) -> Result<Option<Vec<CustomerOrder>>> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let orders = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        // 顧客の存在確認
+        let exists: Option<u8> = timed("find_customer", || {
+            conn.exec_first("SELECT 1 FROM customers WHERE id = ?", (&customer_id,))
+        })?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        // 指定ページの注文を新しい順に取得
+        let mut orders: Vec<CustomerOrder> = timed("get_customer_orders", || {
+            conn.exec_map(
+                "SELECT id, customer_id, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'), total_price
+                 FROM orders
+                 WHERE customer_id = ?
+                 ORDER BY created_at DESC, id
+                 LIMIT ? OFFSET ?",
+                (&customer_id, per_page, (page - 1) * per_page),
+                |(id, customer_id, created_at, total_price)| CustomerOrder {
+                    id,
+                    customer_id,
+                    created_at,
+                    total_price,
+                    line_items: Vec::new(),
+                },
+            )
+        })?;
+
+        // 取得した注文の明細をまとめて取得
+        fetch_line_items(&mut conn, &mut orders)?;
+
+        Ok::<Option<Vec<CustomerOrder>>, mysql::Error>(Some(orders))
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(orders)
+}
+
+// 注文の明細をまとめて取得し、それぞれの注文に追加する
+fn fetch_line_items(conn: &mut PooledConn, orders: &mut [CustomerOrder]) -> Result<()> {
+    if orders.is_empty() {
+        return Ok(());
+    }
+
+    let order_ids: Vec<String> = orders.iter().map(|order| order.id.clone()).collect();
+    let query = format!(
+        "SELECT o.id, op.variant_id, op.quantity, op.price
+         FROM orders o
+         JOIN order_products op ON o.id = op.order_id
+         WHERE o.id IN ({})
+         ORDER BY op.id",
+        vec!["?"; order_ids.len()].join(", ")
+    );
+    let line_items = timed("get_order_line_items", || {
+        conn.exec_map(query, order_ids, |row: mysql::Row| {
+            let order_id: String = row.get("id").unwrap_or_default();
+            let line_item = OrderLineItem {
+                variant_id: read_variant_id(&row, "variant_id"),
+                quantity: row.get("quantity").unwrap_or_default(),
+                price: row.get("price").unwrap_or_default(),
+            };
+            (order_id, line_item)
+        })
+    })?;
+
+    for (order_id, line_item) in line_items {
+        if let Some(order) = orders.iter_mut().find(|order| order.id == order_id) {
+            order.line_items.push(line_item);
+        }
+    }
+
+    Ok(())
+}
+
+// 注文を明細付きで取得する関数
+// 注文が存在しない場合は None を返す
+pub async fn get_order(pool: Arc<mysql::Pool>, order_id: String) -> Result<Option<CustomerOrder>> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let order = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_conn()?;
+
+        let order: Option<CustomerOrder> = timed("get_order", || {
+            conn.exec_first(
+                "SELECT id, customer_id, DATE_FORMAT(created_at, '%Y-%m-%d %H:%i:%s'), total_price
+                 FROM orders
+                 WHERE id = ?",
+                (&order_id,),
+            )
+        })?
+        .map(|(id, customer_id, created_at, total_price)| CustomerOrder {
+            id,
+            customer_id,
+            created_at,
+            total_price,
+            line_items: Vec::new(),
+        });
+
+        let mut orders: Vec<CustomerOrder> = order.into_iter().collect();
+        fetch_line_items(&mut conn, &mut orders)?;
+
+        Ok::<Option<CustomerOrder>, mysql::Error>(orders.pop())
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use std::collections::HashSet;
+
+    use crate::testing;
+
+    #[tokio::test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（注文を登録済み）が必要"]
+    async fn unsold_products_are_active_variants_without_orders() {
+        let pool = Arc::new(testing::database_pool());
+        let mut conn = pool.get_conn().unwrap();
+        // 既存の商品の行を複製して、注文のない販売中・販売停止中の商品を登録する
+        // （IDの型がテーブルによって違っても入るよう、数字だけのIDにする）
+        let base: u32 = rand::rng().random_range(1_000_000_000..2_000_000_000);
+        let (unsold, suspended) = (base.to_string(), (base + 1).to_string());
+        conn.query_drop("CREATE TEMPORARY TABLE unsold_fixture SELECT * FROM products LIMIT 1")
+            .unwrap();
+        for (id, is_suspension) in [(&unsold, false), (&suspended, true)] {
+            conn.exec_drop(
+                "UPDATE unsold_fixture SET id = ?, variant_id = ?, is_suspension = ?",
+                (id, id, is_suspension),
+            )
+            .unwrap();
+            conn.query_drop("INSERT INTO products SELECT * FROM unsold_fixture")
+                .unwrap();
+        }
+        let ordered: String = conn
+            .query_first("SELECT CAST(variant_id AS CHAR) FROM order_products LIMIT 1")
+            .unwrap()
+            .expect("注文がありません");
+
+        let products = get_unsold_products(
+            pool.clone(),
+            Page::After {
+                cursor: None,
+                limit: u32::MAX as usize,
+            },
+        )
+        .await;
+        conn.exec_drop(
+            "DELETE FROM products WHERE variant_id IN (?, ?)",
+            (&unsold, &suspended),
+        )
+        .unwrap();
+
+        let variant_ids: HashSet<String> = products
+            .unwrap()
+            .into_iter()
+            .map(|product| product.variant_id)
+            .collect();
+        assert!(variant_ids.contains(&unsold));
+        assert!(!variant_ids.contains(&suspended));
+        assert!(!variant_ids.contains(&ordered));
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQLが必要"]
+    fn numeric_and_string_variant_ids_decode_to_the_same_text() {
+        let pool = testing::database_pool();
+        let mut conn = pool.get_conn().unwrap();
+        // バイナリプロトコル（exec）では整数の列が Int / UInt として届く
+        let mut exec = |sql: &str, param: Value| -> String {
+            let row: Row = conn.exec_first(sql, (param,)).unwrap().unwrap();
+            read_variant_id(&row, "variant_id")
+        };
+        assert_eq!(
+            exec("SELECT CAST(? AS SIGNED) AS variant_id", Value::Int(123)),
+            "123"
+        );
+        assert_eq!(
+            exec(
+                "SELECT CAST(? AS UNSIGNED) AS variant_id",
+                Value::UInt(u64::MAX)
+            ),
+            u64::MAX.to_string()
+        );
+        assert_eq!(
+            exec(
+                "SELECT CAST(? AS CHAR) AS variant_id",
+                Value::from("SKU-001")
+            ),
+            "SKU-001"
+        );
+
+        // テキストプロトコル（query）では整数も文字列として届く
+        let mut query = |sql: &str| -> String {
+            let row: Row = conn.query_first(sql).unwrap().unwrap();
+            read_variant_id(&row, "variant_id")
+        };
+        assert_eq!(query("SELECT 123 AS variant_id"), "123");
+        assert_eq!(query("SELECT 'SKU-001' AS variant_id"), "SKU-001");
+        assert_eq!(query("SELECT NULL AS variant_id"), "");
+    }
+}
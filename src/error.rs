@@ -0,0 +1,362 @@
+use axum::{
+    Json,
+    extract::rejection::{JsonRejection, QueryRejection},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::any::Any;
+
+use crate::service::cart::RecommendError;
+
+// 入力値の検証で見つかった項目ごとのエラー
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub reason: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+// APIのエラー
+//
+// 入力の不備は次のように使い分ける
+// - BadRequest（400）: クエリ文字列やJSONボディを解釈できない（形式の誤り、必須項目の欠落、型の不一致）
+// - Validation（422）: 解釈はできたが値が業務上の条件を満たさない（数量が0、都道府県コードが不正など）
+// - UnsupportedMediaType（415）: JSONを受け取るエンドポイントに Content-Type: application/json 以外で送られた
+//
+// データベースのエラーは次のように使い分ける
+// - ServiceUnavailable（503）: 接続を取得できない・接続が切れた（プールの枯渇やデータベースの再起動など）
+// - Database（500）: 接続はできたがクエリが失敗した
+//
+// Overloaded（503）はデータベースではなくサーバー側の同時実行数の上限に達したことを表す
+#[derive(Debug)]
+pub enum AppError {
+    // リクエストを解釈できない（400）
+    BadRequest(Vec<FieldError>),
+    // 入力値の検証エラー（422）
+    Validation(Vec<FieldError>),
+    // 認証されていない（401）
+    Unauthorized,
+    // 対象が存在しない（404）
+    NotFound(String),
+    // ルートは存在するがメソッドが許可されていない（405）
+    MethodNotAllowed,
+    // 実行中の処理と競合する（409）
+    Conflict(String),
+    // リクエストボディが大きすぎる（413）
+    PayloadTooLarge,
+    // Content-Type がJSONでない（415）
+    UnsupportedMediaType,
+    // クライアントが指定した期限内に処理が終わらない（504）
+    GatewayTimeout(std::time::Duration),
+    // データベースに接続できない（503）
+    ServiceUnavailable(mysql::Error),
+    // 同時に処理できる数を超えている（503、Retry-After 付き）
+    Overloaded,
+    // データベースエラー（500）
+    Database(mysql::Error),
+    // 想定外のサーバー内部エラー（500）
+    Internal(String),
+}
+
+impl From<mysql::Error> for AppError {
+    fn from(err: mysql::Error) -> Self {
+        if is_connection_error(&err) {
+            AppError::ServiceUnavailable(err)
+        } else {
+            AppError::Database(err)
+        }
+    }
+}
+
+impl From<RecommendError> for AppError {
+    fn from(err: RecommendError) -> Self {
+        match err {
+            RecommendError::Database(err) => AppError::from(err),
+            // 保存済みのベクトルの不整合はクライアントでは直せないため、内容はログにのみ出力する
+            RecommendError::RegionEncodingMismatch(mismatch) => {
+                tracing::error!(error = %mismatch, "推薦の計算に失敗しました");
+                AppError::Internal("Inconsistent region vector encoding".to_string())
+            }
+        }
+    }
+}
+
+// 接続の取得・維持に失敗したエラーか（一時的なものとして503にする）
+fn is_connection_error(err: &mysql::Error) -> bool {
+    matches!(
+        err,
+        mysql::Error::IoError(_)
+            | mysql::Error::DriverError(
+                mysql::DriverError::ConnectTimeout
+                    | mysql::DriverError::CouldNotConnect(_)
+                    | mysql::DriverError::Timeout
+            )
+    )
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::from_rejection("query", rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        // ボディサイズの上限を超えた場合は検証エラーではなく413とする
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return AppError::PayloadTooLarge;
+        }
+        // Content-Type の欠落・誤りは本文の形式の誤りと区別する
+        // （application/json; charset=utf-8 や application/*+json は受け付けられる）
+        if let JsonRejection::MissingJsonContentType(_) = rejection {
+            return AppError::UnsupportedMediaType;
+        }
+        AppError::from_rejection("body", rejection.body_text())
+    }
+}
+
+impl AppError {
+    // 抽出失敗の内容を解釈エラーに変換する
+    // serdeのエラーから不足している項目名を取り出せた場合はその項目のエラーにする
+    fn from_rejection(source: &str, detail: String) -> Self {
+        let missing = detail
+            .split_once("missing field `")
+            .and_then(|(_, rest)| rest.split_once('`'))
+            .map(|(field, _)| field.to_string());
+
+        let error = match missing {
+            Some(field) => FieldError::new(field, "is required"),
+            None => FieldError::new(source, detail),
+        };
+        AppError::BadRequest(vec![error])
+    }
+}
+
+// エラーレスポンスの構造体
+#[derive(Serialize)]
+struct ErrorResponse {
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, body) = match self {
+            AppError::BadRequest(errors) => (
+                StatusCode::BAD_REQUEST,
+                ErrorResponse {
+                    message: "Malformed request".to_string(),
+                    errors,
+                },
+            ),
+            AppError::Validation(errors) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorResponse {
+                    message: "Invalid request parameters".to_string(),
+                    errors,
+                },
+            ),
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ErrorResponse {
+                    message: "Authentication required".to_string(),
+                    errors: vec![],
+                },
+            ),
+            AppError::NotFound(message) => (
+                StatusCode::NOT_FOUND,
+                ErrorResponse {
+                    message,
+                    errors: vec![],
+                },
+            ),
+            AppError::MethodNotAllowed => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                ErrorResponse {
+                    message: "Method not allowed".to_string(),
+                    errors: vec![],
+                },
+            ),
+            AppError::Conflict(message) => (
+                StatusCode::CONFLICT,
+                ErrorResponse {
+                    message,
+                    errors: vec![],
+                },
+            ),
+            AppError::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorResponse {
+                    message: "Request body too large".to_string(),
+                    errors: vec![],
+                },
+            ),
+            AppError::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorResponse {
+                    message: "Expected a JSON body with header Content-Type: application/json"
+                        .to_string(),
+                    errors: vec![],
+                },
+            ),
+            AppError::GatewayTimeout(timeout) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                ErrorResponse {
+                    message: format!("Request did not complete within {} ms", timeout.as_millis()),
+                    errors: vec![],
+                },
+            ),
+            AppError::ServiceUnavailable(err) => {
+                tracing::error!(error = %err, "データベースに接続できません");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    ErrorResponse {
+                        message: "Database unavailable".to_string(),
+                        errors: vec![],
+                    },
+                )
+            }
+            AppError::Overloaded => {
+                // しばらく待てば空く見込みのため、再試行までの秒数を伝える
+                let body = ErrorResponse {
+                    message: "Too many concurrent requests, please retry shortly".to_string(),
+                    errors: vec![],
+                };
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, "1")],
+                    Json(body),
+                )
+                    .into_response();
+            }
+            AppError::Database(err) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorResponse {
+                    message: format!("データベースエラー: {}", err),
+                    errors: vec![],
+                },
+            ),
+            AppError::Internal(message) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorResponse {
+                    message,
+                    errors: vec![],
+                },
+            ),
+        };
+
+        (status, Json(body)).into_response()
+    }
+}
+
+// ハンドラ内のパニックを500のJSONエラーに変換する（CatchPanicLayer用）
+// パニックの内容はログにのみ出力し、レスポンスには含めない
+pub fn handle_panic(err: Box<dyn Any + Send + 'static>) -> Response {
+    let detail = if let Some(message) = err.downcast_ref::<String>() {
+        message.as_str()
+    } else if let Some(message) = err.downcast_ref::<&str>() {
+        message
+    } else {
+        "不明なパニック"
+    };
+    tracing::error!(detail = %detail, "リクエスト処理中にパニックが発生しました");
+
+    AppError::Internal("Internal server error".to_string()).into_response()
+}
+
+// 登録されていないメソッドで呼ばれたルートの応答（method_not_allowed_fallback用）
+// 許可されているメソッドは axum が Allow ヘッダーに付ける
+pub async fn method_not_allowed() -> AppError {
+    AppError::MethodNotAllowed
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::StatusCode, response::IntoResponse, routing::get};
+    use tower_http::cors::CorsLayer;
+
+    use super::{AppError, FieldError};
+    use crate::{app, testing};
+
+    // エラーレスポンスのステータスと本文
+    async fn error_body(error: AppError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn validation_errors_list_each_field() {
+        let (status, body) = error_body(AppError::Validation(vec![
+            FieldError::new("quantity", "must be at least 1"),
+            FieldError::new("province_code", "is not a valid province code"),
+        ]))
+        .await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<&str> = body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["quantity", "province_code"]);
+        assert_eq!(body["errors"][0]["reason"], "must be at least 1");
+    }
+
+    #[tokio::test]
+    async fn missing_query_fields_are_named_in_the_error() {
+        let state = testing::state(
+            std::sync::Arc::new(testing::TokenUsers),
+            std::sync::Arc::new(crate::mock::MockStore),
+        );
+
+        let (status, body) = testing::send(
+            testing::app(state),
+            testing::get("/suggestions?products=%5B%5D"),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["errors"],
+            serde_json::json!([{"field": "province_code", "reason": "is required"}])
+        );
+    }
+
+    async fn panicking_handler() -> &'static str {
+        panic!("テスト用のパニック")
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_returns_clean_json_500() {
+        let routes = Router::new().route("/panic", get(panicking_handler));
+        let app = app::with_layers(routes, CorsLayer::permissive(), testing::limits());
+
+        let (status, body) = testing::send(app.clone(), testing::get("/panic")).await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        // パニックの内容はレスポンスに含めない
+        assert_eq!(
+            body,
+            serde_json::json!({"message": "Internal server error"})
+        );
+
+        // パニックの後も同じルーターでリクエストを処理できる
+        let (status, _) = testing::send(app, testing::get("/panic")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -1,74 +1,286 @@
-use axum::{
-    Router,
-    http::{HeaderValue, Method},
-    routing::get,
-};
-use dotenv::dotenv;
-use std::env;
+use clap::{Parser, Subcommand};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use tokio::net::TcpListener;
-use tower_http::cors::{Any, CorsLayer};
 
+mod app;
 mod command;
 mod config;
 mod controller;
 mod db;
+mod error;
+mod mock;
+mod reload;
+mod repository;
+mod server;
 mod service;
+mod state;
+#[cfg(test)]
+mod testing;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 環境変数の読み込み
-    dotenv().ok();
+// コマンドライン引数
+#[derive(Parser)]
+struct Cli {
+    /// サブコマンドを省略した場合はサーバーを起動
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// 開発用: MySQLを使わず固定データで /users と /suggestions を返す（MOCK_DB=true と同じ）
+    #[arg(long)]
+    mock: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// ダミーの顧客・注文データを生成
+    Seed(command::seed::SeedArgs),
+    /// テーブルの内容をCSVに書き出す
+    Export {
+        /// 書き出すテーブル
+        #[arg(long, value_enum)]
+        table: command::export::ExportTable,
+        /// 出力先のCSVファイル
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// CSVの顧客データを取り込む
+    ImportCustomers {
+        /// 取り込むCSVファイル
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// 購入履歴から顧客ベクトルを計算して保存する
+    BuildCustomerVectors,
+    /// 未適用のスキーマ変更（migrations/*.sql）を順に適用する
+    Migrate,
+    /// 注文・明細・顧客・商品の参照関係の整合性を検証する（不整合があれば異常終了）
+    Verify,
+    /// 注文の小計・税額・合計金額を明細の価格×数量から計算し直す
+    RecomputeTotals {
+        /// 税額の計算に使う税率（0〜1）
+        #[arg(long, default_value_t = 0.1)]
+        tax_rate: f64,
+    },
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // 環境変数の読み込み（ランタイムのスレッド数の設定にも使うため、ランタイムの作成より先に行う）
+    let env_file = reload::EnvFile::load();
+
+    // スレッド数を環境変数で調整できるよう、ランタイムを手動で作成する
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(worker_threads) = config::runtime::get_worker_threads() {
+        builder.worker_threads(worker_threads);
+    }
+    if let Some(blocking_threads) = config::runtime::get_blocking_threads() {
+        builder.max_blocking_threads(blocking_threads);
+    }
+    builder.build()?.block_on(run(env_file))
+}
+
+async fn run(env_file: reload::EnvFile) -> Result<(), Box<dyn std::error::Error>> {
+    // ログ出力の初期化（DBクエリの実行時間や低速クエリの警告を出力する）
+    // JSON形式では時刻・レベル・ターゲットに加え、リクエストIDを含むスパンの一覧を出力する
+    match config::logging::get_log_format() {
+        config::logging::LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+        config::logging::LogFormat::Pretty => tracing_subscriber::fmt().with_target(false).init(),
+    }
 
     // コマンドライン引数を取得
-    let args: Vec<String> = env::args().collect();
-
-    // 引数が "generate-customers" の場合、その関数を実行
-    if args.len() >= 2 && args[1] == "seed" {
-        let count = if args.len() >= 3 {
-            args[2].parse::<usize>().unwrap_or(100)
-        } else {
-            100 // デフォルト値
-        };
-
-        println!("ユーザーデータ生成を開始します...");
-        command::seed::generate_customers(count).await?;
-        command::seed::generate_orders(count).await?;
-        return Ok(());
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Seed(args)) => {
+            if !args.zipf_exponent.is_finite() || args.zipf_exponent < 0.0 {
+                return Err("--zipf-exponent には0以上の数値を指定してください".into());
+            }
+            if !(0.0..=1.0).contains(&args.tax_rate) {
+                return Err("--tax-rate には0〜1の数値を指定してください".into());
+            }
+            if !(0.0..=1.0).contains(&args.subscription_ratio) {
+                return Err("--subscription-ratio には0〜1の数値を指定してください".into());
+            }
+            if !(0.0..=1.0).contains(&args.guest_ratio) {
+                return Err("--guest-ratio には0〜1の数値を指定してください".into());
+            }
+            let items = command::seed::parse_range(args.items_min, args.items_max, "items")?;
+            let quantity = command::seed::parse_range(args.qty_min, args.qty_max, "qty")?;
+            if args.progress_every < 1 {
+                return Err("--progress-every には1以上の整数を指定してください".into());
+            }
+            if args.email_domain.is_empty()
+                || args
+                    .email_domain
+                    .contains(|c: char| c == '@' || c.is_whitespace())
+            {
+                return Err(
+                    "--email-domain には @ や空白を含まないドメインを指定してください".into(),
+                );
+            }
+
+            let count = command::seed::parse_count(args.count.as_deref(), args.force)?;
+
+            let provinces = command::seed::province_distribution(args.province_weights.as_deref())?;
+
+            if args.dry_run {
+                command::seed::dry_run(count, provinces, args.email_domain, items).await?;
+                return Ok(());
+            }
+
+            println!("ユーザーデータ生成を開始します...");
+            let progress = command::seed::Progress::new(args.quiet, args.progress_every);
+            let customer_options = command::seed::CustomerOptions {
+                email_domain: args.email_domain.clone(),
+                on_conflict: args.on_conflict,
+                progress,
+            };
+            command::seed::generate_customers(count, args.workers, provinces, customer_options)
+                .await?;
+            let options = command::seed::OrderOptions {
+                currency: args.currency,
+                tax_rate: args.tax_rate,
+                subscription_ratio: args.subscription_ratio,
+                guest_ratio: args.guest_ratio,
+                items,
+                quantity,
+                email_domain: args.email_domain,
+                progress,
+            };
+            command::seed::generate_orders(count, args.zipf_exponent, options, args.fresh).await?;
+            return Ok(());
+        }
+        Some(Command::Export { table, out }) => {
+            command::export::export_table(table, out).await?;
+            return Ok(());
+        }
+        Some(Command::ImportCustomers { file }) => {
+            command::import::import_customers(file).await?;
+            return Ok(());
+        }
+        Some(Command::BuildCustomerVectors) => {
+            command::vectors::build_customer_vectors().await?;
+            return Ok(());
+        }
+        Some(Command::Migrate) => {
+            command::migrate::migrate().await?;
+            return Ok(());
+        }
+        Some(Command::Verify) => {
+            let orphans = command::verify::verify_integrity().await?;
+            command::verify::ensure_no_orphans(orphans)?;
+            return Ok(());
+        }
+        Some(Command::RecomputeTotals { tax_rate }) => {
+            if !(0.0..=1.0).contains(&tax_rate) {
+                return Err("--tax-rate には0〜1の数値を指定してください".into());
+            }
+            command::totals::recompute_totals(tax_rate).await?;
+            return Ok(());
+        }
+        None => {}
     }
 
     // 通常のサーバー起動処理
-    let database_url = config::database::get_database_url();
-    let opts = mysql::Opts::from_url(&database_url).expect("不正なデータベースURL");
-    let pool = mysql::Pool::new(opts).expect("データベース接続に失敗しました");
-    let arc_pool = std::sync::Arc::new(pool);
-
-    // CORSを許可するミドルウェアを設定
-    let cors = CorsLayer::new()
-        // すべてのオリジンを許可
-        .allow_origin(Any)
-        // すべてのヘッダーを許可
-        .allow_headers(Any)
-        // すべてのメソッドを許可
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ]);
-
-    let app = Router::new()
-        .route("/users", get(controller::users::get_users))
-        .route("/suggestions", get(controller::cart::get_suggestions))
-        .with_state(arc_pool)
-        .layer(cors); // CORSミドルウェアを追加
+    // CORSの設定は接続より先に検証し、不正な組み合わせなら起動しない
+    let cors_config = config::server::get_cors_config()?;
+    let mock = cli.mock || config::database::get_mock_db();
+    let database_opts = (!mock).then(config::database::get_database_opts);
+    let replica_opts = database_opts
+        .is_some()
+        .then(config::database::get_replica_database_opts)
+        .flatten();
+    let app_state = match &database_opts {
+        None => {
+            // 開発用: 固定データを返すストアを使い、MySQLには接続しない
+            tracing::info!("固定データで起動します（開発用）");
+            mock::state()
+        }
+        Some(opts) => {
+            let pool = mysql::Pool::new(opts.clone()).expect("データベース接続に失敗しました");
+            // レプリカが設定されていれば推薦の読み込み用に別のプールを作成する（未設定の場合は主データベースを使う）
+            let replica_pool = replica_opts.clone().map(|opts| {
+                std::sync::Arc::new(mysql::Pool::new(opts).expect("レプリカへの接続に失敗しました"))
+            });
+            state::AppState::new(std::sync::Arc::new(pool), replica_pool)
+        }
+    };
+
+    // 起動時点の推薦の設定（SIGHUP で差し替わっても、起動時に組み立てる処理はこの値を使う）
+    let recommendation_config = app_state
+        .recommendation_config
+        .read()
+        .expect("推薦の設定のロックに失敗")
+        .clone();
 
+    // 実際に使う設定の要約を出力する（パスワードは含めない）
     let addr = SocketAddr::from(([127, 0, 0, 1], 3939));
+    config::log_summary(
+        addr,
+        database_opts.as_ref(),
+        replica_opts.as_ref(),
+        &cors_config,
+        &recommendation_config,
+    );
+
+    // 開発用: SIGHUP を受けたら .env を読み込み直し、推薦の設定を差し替える（接続先などは再起動が必要）
+    #[cfg(unix)]
+    reload::spawn_reload_on_hangup(env_file, app_state.recommendation_config.clone())?;
+    #[cfg(not(unix))]
+    drop(env_file);
+
+    // 商品次元情報のキャッシュを定期的に更新（起動直後に1回目の更新を行う）
+    // 定期更新しない場合も、準備完了とできるよう起動時に1回だけ読み込む
+    // ユーザーベクトルを事前計算する場合は、次元情報の更新に続けてベクトルも計算し直す
+    if config::cache::get_warm_user_vectors() {
+        service::user_vectors::spawn_refresh_task(
+            app_state.user_vectors.clone(),
+            app_state.dimensions.clone(),
+            app_state.recommendations.clone(),
+            recommendation_config.encoding,
+            recommendation_config.include_orderless_customers,
+            config::cache::get_dimensions_refresh_interval(),
+        );
+    } else {
+        match config::cache::get_dimensions_refresh_interval() {
+            Some(interval) => service::dimensions::spawn_refresh_task(
+                app_state.dimensions.clone(),
+                app_state.recommendations.clone(),
+                interval,
+            ),
+            None => {
+                let dimensions = app_state.dimensions.clone();
+                let recommendations = app_state.recommendations.clone();
+                tokio::spawn(async move {
+                    if let Err(err) =
+                        service::dimensions::refresh(&dimensions, recommendations).await
+                    {
+                        tracing::error!(error = %err, "商品次元情報の読み込みに失敗しました");
+                    }
+                });
+            }
+        }
+    }
+
+    let app = app::router(
+        app_state,
+        app::cors_layer(cors_config),
+        app::Limits::from_env(),
+    );
+
     let listener = TcpListener::bind(addr).await.unwrap();
 
-    println!("🚀 Server started at http://{} 🚀", addr);
-    axum::serve(listener, app).await.unwrap();
+    tracing::info!(%addr, "🚀 Server started 🚀");
+    // ヘッダーの受信（使われていない HTTP/1.1 の接続を含む）に時間の上限を設け、HTTP/2 の接続は生存確認する
+    // （HTTP_HEADER_TIMEOUT_MS / HTTP_KEEPALIVE_SECS）
+    let timeouts = server::Timeouts {
+        header: config::server::get_http_header_timeout(),
+        keepalive_interval: config::server::get_http_keepalive_interval(),
+    };
+    server::serve(listener, app, timeouts).await;
 
     Ok(())
 }
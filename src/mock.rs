@@ -0,0 +1,365 @@
+// 開発専用: MySQLなしでサーバーを動かすための固定データのストア（--mock または MOCK_DB=true で有効）
+// /users・/products と /suggestions に必要な読み込みだけを固定データから返す。本番環境では使わないこと
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config;
+use crate::db::{Page, Product, User};
+use crate::repository::{
+    ProductRepository, RecommendationRepository, RecommendationSession, UserRepository,
+};
+use crate::service::cart::{
+    self, ProductDimensions, ProductItem, Temperature, UserVectors, VectorEncoding,
+};
+use crate::state::AppState;
+
+// 固定のユーザー（ID, 名前, メールアドレス）
+const USERS: [(i32, &str, &str); 3] = [
+    (1, "Mock User 1", "mock-user-1@example.com"),
+    (2, "Mock User 2", "mock-user-2@example.com"),
+    (3, "Mock User 3", "mock-user-3@example.com"),
+];
+
+// 固定の商品（バリアントID, カテゴリ, 配送温度帯）
+const PRODUCTS: [(&str, &str, Temperature); 5] = [
+    ("mock-variant-1", "food", Temperature::Normal),
+    ("mock-variant-2", "food", Temperature::Cold),
+    ("mock-variant-3", "drink", Temperature::Normal),
+    ("mock-variant-4", "drink", Temperature::Cold),
+    ("mock-variant-5", "ice", Temperature::Frozen),
+];
+
+// 顧客の購入履歴（顧客ID, 都道府県コード, 購入商品と数量）
+type Purchase = (&'static str, &'static str, &'static [(&'static str, u32)]);
+
+// 固定の顧客の購入履歴
+const PURCHASES: [Purchase; 5] = [
+    (
+        "mock-customer-1",
+        "JP-13",
+        &[("mock-variant-1", 2), ("mock-variant-3", 1)],
+    ),
+    (
+        "mock-customer-2",
+        "JP-14",
+        &[
+            ("mock-variant-1", 1),
+            ("mock-variant-2", 1),
+            ("mock-variant-4", 3),
+        ],
+    ),
+    (
+        "mock-customer-3",
+        "JP-27",
+        &[("mock-variant-3", 2), ("mock-variant-5", 1)],
+    ),
+    ("mock-customer-4", "JP-01", &[("mock-variant-2", 4)]),
+    // 注文のない顧客
+    ("mock-customer-5", "JP-40", &[]),
+];
+
+// 固定データを返すストア
+pub struct MockStore;
+
+// 固定データのストアを使う状態を作成（MySQLには接続しない）
+pub fn state() -> AppState {
+    let pool = mysql::Pool::new(config::database::get_mock_database_opts())
+        .expect("データベース接続に失敗しました");
+    let store = Arc::new(MockStore);
+    AppState::with_store(Arc::new(pool), store.clone(), store.clone()).with_products(store)
+}
+
+fn user((id, name, email): (i32, &str, &str)) -> User {
+    User {
+        id,
+        name: name.to_string(),
+        email: email.to_string(),
+        api_token: None,
+    }
+}
+
+fn product_items(products: &[(&str, u32)]) -> Vec<ProductItem> {
+    products
+        .iter()
+        .map(|&(variant_id, quantity)| ProductItem {
+            product_variant_id: variant_id.to_string(),
+            quantity,
+            weight: None,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl UserRepository for MockStore {
+    async fn get_users(&self, page: Page<i32>) -> Result<Vec<User>, mysql::Error> {
+        let users = USERS.into_iter().map(user);
+        Ok(match page {
+            Page::After { cursor, limit } => users
+                .filter(|user| cursor.is_none_or(|cursor| user.id > cursor))
+                .take(limit)
+                .collect(),
+            Page::Offset { page, per_page } => users
+                .skip(page.saturating_sub(1) * per_page)
+                .take(per_page)
+                .collect(),
+        })
+    }
+
+    async fn count_users(&self) -> Result<u64, mysql::Error> {
+        Ok(USERS.len() as u64)
+    }
+
+    async fn find_user_by_api_token(&self, _token: &str) -> Result<Option<User>, mysql::Error> {
+        // 固定のユーザーにはAPIトークンがないため、認証は常に失敗する
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl ProductRepository for MockStore {
+    async fn get_products(&self, page: Page<String>) -> Result<Vec<Product>, mysql::Error> {
+        let products = PRODUCTS.iter().map(|(variant_id, _, _)| Product {
+            id: variant_id.to_string(),
+            variant_id: variant_id.to_string(),
+            is_suspension: false,
+        });
+        Ok(match page {
+            Page::After { cursor, limit } => products
+                .filter(|product| cursor.as_ref().is_none_or(|cursor| product.id > *cursor))
+                .take(limit)
+                .collect(),
+            Page::Offset { page, per_page } => products
+                .skip(page.saturating_sub(1) * per_page)
+                .take(per_page)
+                .collect(),
+        })
+    }
+
+    async fn count_products(&self) -> Result<u64, mysql::Error> {
+        Ok(PRODUCTS.len() as u64)
+    }
+}
+
+impl RecommendationRepository for MockStore {
+    fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(MockStore))
+    }
+}
+
+impl RecommendationSession for MockStore {
+    fn fetch_product_dimensions(&mut self) -> Result<ProductDimensions, mysql::Error> {
+        Ok(ProductDimensions::new(
+            PRODUCTS
+                .iter()
+                .map(|(variant_id, _, _)| variant_id.to_string())
+                .collect(),
+        ))
+    }
+
+    fn fetch_user_purchase_history(
+        &mut self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        min_items: usize,
+        include_orderless: bool,
+    ) -> Result<UserVectors, mysql::Error> {
+        Ok(std::sync::Arc::new(
+            PURCHASES
+                .iter()
+                .map(|&(customer_id, province_code, products)| {
+                    let order = cart::create_order_vector(
+                        province_code,
+                        &product_items(products),
+                        product_dimensions,
+                        encoding,
+                    );
+                    (customer_id.to_string(), order)
+                })
+                .filter(|(_, order)| {
+                    cart::has_min_items(order, min_items)
+                        && (include_orderless || cart::has_products(order))
+                })
+                .collect(),
+        ))
+    }
+
+    fn fetch_user_products(
+        &mut self,
+        customer_ids: &[String],
+    ) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error> {
+        // データベースと同じく、購入商品のない顧客は結果に含めない
+        Ok(PURCHASES
+            .iter()
+            .filter(|(customer_id, _, products)| {
+                !products.is_empty() && customer_ids.iter().any(|id| id == customer_id)
+            })
+            .map(|&(customer_id, _, products)| (customer_id.to_string(), product_items(products)))
+            .collect())
+    }
+
+    fn fetch_cooccurring_products(
+        &mut self,
+        variant_ids: &[String],
+        _half_life_days: Option<f32>,
+    ) -> Result<HashMap<String, f32>, mysql::Error> {
+        // 固定データには注文日がないため、減衰は行わず共起した顧客数を数える
+        let mut cooccurrence = HashMap::new();
+        for (_, _, products) in PURCHASES {
+            if !products
+                .iter()
+                .any(|(variant_id, _)| variant_ids.iter().any(|id| id == variant_id))
+            {
+                continue;
+            }
+            for (variant_id, _) in products {
+                if !variant_ids.iter().any(|id| id == variant_id) {
+                    *cooccurrence.entry(variant_id.to_string()).or_insert(0.0) += 1.0;
+                }
+            }
+        }
+        Ok(cooccurrence)
+    }
+
+    fn fetch_variants_with_temperature(
+        &mut self,
+        variant_ids: &[String],
+        temperature: Temperature,
+    ) -> Result<HashSet<String>, mysql::Error> {
+        Ok(PRODUCTS
+            .iter()
+            .filter(|(variant_id, _, product_temperature)| {
+                *product_temperature == temperature && variant_ids.iter().any(|id| id == variant_id)
+            })
+            .map(|(variant_id, _, _)| variant_id.to_string())
+            .collect())
+    }
+
+    fn fetch_variants_in_categories(
+        &mut self,
+        variant_ids: &[String],
+        categories: &[String],
+    ) -> Result<HashSet<String>, mysql::Error> {
+        Ok(PRODUCTS
+            .iter()
+            .filter(|(variant_id, category, _)| {
+                categories.iter().any(|c| c == category)
+                    && variant_ids.iter().any(|id| id == variant_id)
+            })
+            .map(|(variant_id, _, _)| variant_id.to_string())
+            .collect())
+    }
+
+    fn fetch_product_categories(&mut self) -> Result<HashSet<String>, mysql::Error> {
+        Ok(PRODUCTS
+            .iter()
+            .map(|(_, category, _)| category.to_string())
+            .collect())
+    }
+
+    fn fetch_popular_products(
+        &mut self,
+        exclude_variant_ids: &[String],
+        _window_days: Option<u32>,
+        temperature: Option<Temperature>,
+        excluded_categories: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, f32)>, mysql::Error> {
+        // 購入数量の合計が多い順（同数の場合はバリアントID順）
+        let mut popular: Vec<(String, f32)> = PRODUCTS
+            .iter()
+            .filter(|(variant_id, category, product_temperature)| {
+                temperature.is_none_or(|t| t == *product_temperature)
+                    && !excluded_categories.iter().any(|c| c == category)
+                    && !exclude_variant_ids.iter().any(|id| id == variant_id)
+            })
+            .map(|(variant_id, _, _)| {
+                let total: u32 = PURCHASES
+                    .iter()
+                    .flat_map(|(_, _, products)| products.iter())
+                    .filter(|(id, _)| id == variant_id)
+                    .map(|(_, quantity)| quantity)
+                    .sum();
+                (variant_id.to_string(), total as f32)
+            })
+            .filter(|(_, total)| *total > 0.0)
+            .collect();
+        popular.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        popular.truncate(limit);
+        Ok(popular)
+    }
+
+    fn fetch_cached_suggestions(
+        &mut self,
+        _key: &str,
+        _ttl: Duration,
+    ) -> Result<Option<String>, mysql::Error> {
+        // 固定データは変わらないため、推薦結果はキャッシュしない
+        Ok(None)
+    }
+
+    fn store_cached_suggestions(
+        &mut self,
+        _key: &str,
+        _suggestions: &str,
+        _ttl: Duration,
+    ) -> Result<(), mysql::Error> {
+        Ok(())
+    }
+
+    fn clear_cached_suggestions(&mut self) -> Result<(), mysql::Error> {
+        Ok(())
+    }
+
+    fn rebuild_item_similarity(&mut self) -> Result<u64, mysql::Error> {
+        // 固定データには注文がないため、保存する商品の組もない
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn products_are_listed_from_the_fixture() {
+        let (status, body) = testing::send(testing::app(state()), testing::get("/products")).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let variant_ids: Vec<&str> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|product| product["variant_id"].as_str().unwrap())
+            .collect();
+        let expected: Vec<&str> = PRODUCTS
+            .iter()
+            .map(|(variant_id, _, _)| *variant_id)
+            .collect();
+        assert_eq!(variant_ids, expected);
+    }
+
+    #[tokio::test]
+    async fn suggestions_are_served_from_the_fixture() {
+        let products = serde_json::json!([{ "product_variant_id": "mock-variant-1" }]).to_string();
+        let uri = format!(
+            "/suggestions?{}",
+            serde_urlencoded::to_string([("province_code", "JP-13"), ("products", &products)])
+                .unwrap()
+        );
+
+        let (status, body) = testing::send(testing::app(state()), testing::get(&uri)).await;
+
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let suggestions = body["suggestions"].as_array().unwrap();
+        assert!(!suggestions.is_empty(), "{}", body);
+        assert!(
+            suggestions
+                .iter()
+                .all(|suggestion| suggestion["product_variant_id"] != "mock-variant-1")
+        );
+    }
+}
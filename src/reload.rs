@@ -0,0 +1,192 @@
+// 開発用: SIGHUP を受けたら .env を読み込み直し、再起動せずに推薦の設定を差し替える
+// データベースの接続先・CORS・ログの出力形式などは起動時に組み立てるため、変更しても再起動するまで反映されない
+// （CORS とログはミドルウェアとログ出力の初期化をやり直す必要があるため、読み込み直しの対象にしていない）
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::config::{self, Vars};
+use crate::controller::admin::ConfigResponse;
+use crate::service::cart::RecommendationConfig;
+
+// 起動時に読み込んだ .env の場所と値、.env より優先するシェルの環境変数
+pub struct EnvFile {
+    path: Option<PathBuf>,
+    shell_vars: HashSet<String>,
+    // 最後に読み込んだ .env の値（変わった項目の出力に使う）
+    values: HashMap<String, String>,
+}
+
+impl EnvFile {
+    // .env を読み込む（シェルで設定済みの環境変数は上書きしない）
+    // プロセスの環境変数に反映するのは、ランタイムのスレッドを作る前のこの1回だけ
+    pub fn load() -> Self {
+        let shell_vars = env::vars_os()
+            .map(|(key, _)| key.to_string_lossy().into_owned())
+            .collect();
+        let path = dotenv::dotenv().ok();
+        let values = path
+            .as_deref()
+            .and_then(|path| read_values(path).ok())
+            .unwrap_or_default();
+        EnvFile {
+            path,
+            shell_vars,
+            values,
+        }
+    }
+
+    // 名前から値を引く（シェルの環境変数を優先し、それ以外は .env の値）
+    fn get(&self, name: &str) -> Option<String> {
+        if self.shell_vars.contains(name) {
+            env::var(name).ok()
+        } else {
+            self.values.get(name).cloned()
+        }
+    }
+}
+
+// .env の値を読み出す（プロセスの環境変数には反映しない）
+fn read_values(path: &Path) -> Result<HashMap<String, String>, dotenv::Error> {
+    #[allow(deprecated)]
+    dotenv::from_path_iter(path)?.collect()
+}
+
+// .env を読み込み直して推薦の設定を差し替え、変わった項目を「項目: 変更前 → 変更後」の形式で返す
+// マルチスレッドで動いているプロセスの環境変数は書き換えず（libcなど標準ライブラリ外からも読まれるため）、
+// 読み出した値から設定を組み立てて差し替える
+pub fn reload(
+    env_file: &mut EnvFile,
+    recommendation_config: &RwLock<RecommendationConfig>,
+) -> Result<Vec<String>, dotenv::Error> {
+    let Some(path) = &env_file.path else {
+        return Ok(vec![]);
+    };
+    let values = read_values(path)?;
+
+    // 値は秘密（パスワードなど）を含みうるため、名前だけを出力する
+    let mut changed_vars: Vec<&String> = values
+        .keys()
+        .chain(env_file.values.keys())
+        .filter(|key| !env_file.shell_vars.contains(*key))
+        .filter(|key| values.get(*key) != env_file.values.get(*key))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if !changed_vars.is_empty() {
+        changed_vars.sort();
+        let names: Vec<&str> = changed_vars.iter().map(|key| key.as_str()).collect();
+        tracing::info!(
+            vars = %names.join(", "),
+            "値が変わった環境変数があります（推薦の設定以外は再起動するまで反映されません）"
+        );
+    }
+    env_file.values = values;
+
+    Ok(swap_config(
+        &|name| env_file.get(name),
+        recommendation_config,
+    ))
+}
+
+// 値の引き方から推薦の設定を組み立てて差し替え、変わった項目を返す
+fn swap_config(vars: Vars, recommendation_config: &RwLock<RecommendationConfig>) -> Vec<String> {
+    let reloaded = config::recommendation::load_from(vars);
+    let mut current = recommendation_config
+        .write()
+        .expect("推薦の設定のロックに失敗");
+    let changes = describe_changes(&current, &reloaded);
+    *current = reloaded;
+    changes
+}
+
+// /admin/config と同じ項目名で、2つの推薦の設定の違いを列挙する
+fn describe_changes(before: &RecommendationConfig, after: &RecommendationConfig) -> Vec<String> {
+    // to_value は f32 を f64 に広げて桁が増えるため、/admin/config と同じ JSON 文字列を経由する
+    let to_map = |config: &RecommendationConfig| {
+        let json =
+            serde_json::to_string(&ConfigResponse::from(config)).expect("設定のシリアライズに失敗");
+        serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&json)
+            .expect("設定のデシリアライズに失敗")
+    };
+    let before = to_map(before);
+    let after = to_map(after);
+    after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(value))
+        .map(|(key, value)| format!("{}: {} → {}", key, before[key], value))
+        .collect()
+}
+
+// SIGHUP を受けるたびに .env を読み込み直す（登録は戻る前に済ませるため、直後のシグナルも受け取れる）
+#[cfg(unix)]
+pub fn spawn_reload_on_hangup(
+    mut env_file: EnvFile,
+    recommendation_config: Arc<RwLock<RecommendationConfig>>,
+) -> std::io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match reload(&mut env_file, &recommendation_config) {
+                Ok(changes) if changes.is_empty() => {
+                    tracing::info!(".env を読み込み直しました（推薦の設定に変更はありません）")
+                }
+                Ok(changes) => tracing::info!(
+                    changes = %changes.join(", "),
+                    ".env を読み込み直し、推薦の設定を変更しました"
+                ),
+                Err(err) => tracing::error!(error = %err, ".env の読み込みに失敗しました"),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_swaps_recommendation_config_from_the_env_file() {
+        let path = env::temp_dir().join(format!("reload-test-{}.env", std::process::id()));
+        std::fs::write(&path, "RECOMMENDATION_MIN_SIMILARITY_VARIANCE=0.25\n").unwrap();
+        let mut env_file = EnvFile {
+            path: Some(path.clone()),
+            shell_vars: HashSet::new(),
+            values: HashMap::new(),
+        };
+        let recommendation_config = RwLock::new(RecommendationConfig::default());
+
+        let changes = reload(&mut env_file, &recommendation_config);
+        std::fs::remove_file(&path).unwrap();
+
+        let changes = changes.unwrap();
+        assert_eq!(
+            recommendation_config
+                .read()
+                .unwrap()
+                .min_similarity_variance,
+            0.25
+        );
+        assert_eq!(changes.len(), 1, "{:?}", changes);
+        assert!(changes[0].starts_with("min_similarity_variance: "));
+        // プロセスの環境変数は書き換えない
+        assert!(env::var("RECOMMENDATION_MIN_SIMILARITY_VARIANCE").is_err());
+    }
+
+    #[test]
+    fn swap_config_reports_nothing_when_values_are_unchanged() {
+        let recommendation_config = RwLock::new(config::recommendation::load_from(&|_| None));
+        assert!(swap_config(&|_| None, &recommendation_config).is_empty());
+
+        let changes = swap_config(
+            &|name| (name == "MAX_NEIGHBORS").then(|| "7".to_string()),
+            &recommendation_config,
+        );
+        assert_eq!(recommendation_config.read().unwrap().max_neighbors, 7);
+        assert_eq!(changes.len(), 1, "{:?}", changes);
+    }
+}
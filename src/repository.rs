@@ -0,0 +1,605 @@
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::db::{self, Page, Product, User};
+use crate::service::cart::{
+    self, ProductDimensions, ProductItem, Temperature, UserVectors, VectorEncoding,
+};
+use crate::service::{item_similarity, suggestion_cache};
+
+// ユーザー情報の取得
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn get_users(&self, page: Page<i32>) -> Result<Vec<User>, mysql::Error>;
+    async fn count_users(&self) -> Result<u64, mysql::Error>;
+    async fn find_user_by_api_token(&self, token: &str) -> Result<Option<User>, mysql::Error>;
+}
+
+// 商品一覧の取得
+#[async_trait]
+pub trait ProductRepository: Send + Sync {
+    async fn get_products(&self, page: Page<String>) -> Result<Vec<Product>, mysql::Error>;
+    async fn count_products(&self) -> Result<u64, mysql::Error>;
+}
+
+// 推薦計算に使うデータの取得
+// 1リクエスト内の問い合わせを同じ接続で行うため、セッションを開始してから使う
+pub trait RecommendationRepository: Send + Sync {
+    fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error>;
+
+    // 書き込みの直後に読み直すためのセッション（レプリカの遅延で古い値を読まないよう主データベースを読む）
+    fn primary_session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        self.session()
+    }
+}
+
+// 1リクエスト分の推薦データの問い合わせ
+pub trait RecommendationSession: Send {
+    fn fetch_product_dimensions(&mut self) -> Result<ProductDimensions, mysql::Error>;
+    fn fetch_user_purchase_history(
+        &mut self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        min_items: usize,
+        include_orderless: bool,
+    ) -> Result<UserVectors, mysql::Error>;
+    fn fetch_user_products(
+        &mut self,
+        customer_ids: &[String],
+    ) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error>;
+    fn fetch_cooccurring_products(
+        &mut self,
+        variant_ids: &[String],
+        half_life_days: Option<f32>,
+    ) -> Result<HashMap<String, f32>, mysql::Error>;
+    fn fetch_variants_with_temperature(
+        &mut self,
+        variant_ids: &[String],
+        temperature: Temperature,
+    ) -> Result<HashSet<String>, mysql::Error>;
+    fn fetch_variants_in_categories(
+        &mut self,
+        variant_ids: &[String],
+        categories: &[String],
+    ) -> Result<HashSet<String>, mysql::Error>;
+    fn fetch_product_categories(&mut self) -> Result<HashSet<String>, mysql::Error>;
+    fn fetch_popular_products(
+        &mut self,
+        exclude_variant_ids: &[String],
+        window_days: Option<u32>,
+        temperature: Option<Temperature>,
+        excluded_categories: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, f32)>, mysql::Error>;
+    fn fetch_cached_suggestions(
+        &mut self,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>, mysql::Error>;
+    fn store_cached_suggestions(
+        &mut self,
+        key: &str,
+        suggestions: &str,
+        ttl: Duration,
+    ) -> Result<(), mysql::Error>;
+    fn clear_cached_suggestions(&mut self) -> Result<(), mysql::Error>;
+    fn rebuild_item_similarity(&mut self) -> Result<u64, mysql::Error>;
+}
+
+// MySQLを使ったデータストア
+// replica を指定した場合は、推薦データの読み込みをレプリカに向ける（推薦結果のキャッシュは書き込むため主データベース）
+pub struct MySqlStore {
+    pool: Arc<mysql::Pool>,
+    replica: Option<Arc<mysql::Pool>>,
+    // 事前計算済みの顧客ベクトル（customer_vectors）が最新で使えるか（商品の次元の更新時に確認する）
+    cached_vectors: Arc<AtomicBool>,
+}
+
+impl MySqlStore {
+    pub fn new(pool: Arc<mysql::Pool>, replica: Option<Arc<mysql::Pool>>) -> Self {
+        MySqlStore {
+            pool,
+            replica,
+            cached_vectors: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for MySqlStore {
+    async fn get_users(&self, page: Page<i32>) -> Result<Vec<User>, mysql::Error> {
+        db::get_users(self.pool.clone(), page).await
+    }
+
+    async fn count_users(&self) -> Result<u64, mysql::Error> {
+        db::count_rows(self.pool.clone(), "users").await
+    }
+
+    async fn find_user_by_api_token(&self, token: &str) -> Result<Option<User>, mysql::Error> {
+        db::find_user_by_api_token(self.pool.clone(), token.to_string()).await
+    }
+}
+
+#[async_trait]
+impl ProductRepository for MySqlStore {
+    async fn get_products(&self, page: Page<String>) -> Result<Vec<Product>, mysql::Error> {
+        db::get_products(self.pool.clone(), page).await
+    }
+
+    async fn count_products(&self) -> Result<u64, mysql::Error> {
+        db::count_rows(self.pool.clone(), "products").await
+    }
+}
+
+impl RecommendationRepository for MySqlStore {
+    fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(match &self.replica {
+            Some(replica) => MySqlSession {
+                conn: replica.get_conn()?,
+                primary: Some(self.pool.clone()),
+                primary_conn: None,
+                cached_vectors: self.cached_vectors.clone(),
+            },
+            None => MySqlSession {
+                conn: self.pool.get_conn()?,
+                primary: None,
+                primary_conn: None,
+                cached_vectors: self.cached_vectors.clone(),
+            },
+        }))
+    }
+
+    fn primary_session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(MySqlSession {
+            conn: self.pool.get_conn()?,
+            primary: None,
+            primary_conn: None,
+            cached_vectors: self.cached_vectors.clone(),
+        }))
+    }
+}
+
+// 1つの接続を保持する推薦データのセッション
+struct MySqlSession {
+    // 推薦データの読み込みに使う接続（レプリカがあればレプリカ）
+    conn: mysql::PooledConn,
+    // レプリカを使う場合の主データベース（推薦結果のキャッシュに使う）
+    primary: Option<Arc<mysql::Pool>>,
+    // 主データベースの接続（キャッシュを使う場合だけ取得する）
+    primary_conn: Option<mysql::PooledConn>,
+    // MySqlStore と共有する、事前計算済みの顧客ベクトルが使えるかどうか
+    cached_vectors: Arc<AtomicBool>,
+}
+
+impl MySqlSession {
+    // 書き込みを伴う処理の接続（レプリカを使わない場合は読み込みと同じ接続）
+    fn primary_conn(&mut self) -> Result<&mut mysql::PooledConn, mysql::Error> {
+        let Some(primary) = &self.primary else {
+            return Ok(&mut self.conn);
+        };
+        if self.primary_conn.is_none() {
+            self.primary_conn = Some(primary.get_conn()?);
+        }
+        Ok(self.primary_conn.as_mut().expect("取得済みの接続"))
+    }
+}
+
+// 推薦データの問い合わせ1回分をスパンで囲む（中のクエリの db_query スパンはこのスパンの子になる）
+fn traced<T>(query: &'static str, f: impl FnOnce() -> T) -> T {
+    tracing::info_span!("recommendation_query", query).in_scope(f)
+}
+
+impl RecommendationSession for MySqlSession {
+    fn fetch_product_dimensions(&mut self) -> Result<ProductDimensions, mysql::Error> {
+        traced("fetch_product_dimensions", || {
+            // 商品の次元は起動時と定期的な更新時に取得するため、ここで事前計算済みベクトルの鮮度も確かめ直す
+            match cart::customer_vectors_are_fresh(&mut self.conn) {
+                Ok(fresh) => self.cached_vectors.store(fresh, Ordering::Relaxed),
+                Err(err) => {
+                    tracing::warn!(error = %err, "事前計算済みの顧客ベクトルの鮮度を確認できません");
+                    self.cached_vectors.store(false, Ordering::Relaxed);
+                }
+            }
+            cart::fetch_product_dimensions(&mut self.conn)
+        })
+    }
+
+    fn fetch_user_purchase_history(
+        &mut self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        min_items: usize,
+        include_orderless: bool,
+    ) -> Result<UserVectors, mysql::Error> {
+        traced("fetch_user_purchase_history", || {
+            cart::fetch_user_purchase_history(
+                &mut self.conn,
+                product_dimensions,
+                encoding,
+                min_items,
+                include_orderless,
+                self.cached_vectors.load(Ordering::Relaxed),
+            )
+            .map(Arc::new)
+        })
+    }
+
+    fn fetch_user_products(
+        &mut self,
+        customer_ids: &[String],
+    ) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error> {
+        traced("fetch_user_products", || {
+            cart::fetch_user_products(&mut self.conn, customer_ids)
+        })
+    }
+
+    fn fetch_cooccurring_products(
+        &mut self,
+        variant_ids: &[String],
+        half_life_days: Option<f32>,
+    ) -> Result<HashMap<String, f32>, mysql::Error> {
+        traced("fetch_cooccurring_products", || {
+            cart::fetch_cooccurring_products(&mut self.conn, variant_ids, half_life_days)
+        })
+    }
+
+    fn fetch_variants_with_temperature(
+        &mut self,
+        variant_ids: &[String],
+        temperature: Temperature,
+    ) -> Result<HashSet<String>, mysql::Error> {
+        traced("fetch_variants_with_temperature", || {
+            cart::fetch_variants_with_temperature(&mut self.conn, variant_ids, temperature)
+        })
+    }
+
+    fn fetch_variants_in_categories(
+        &mut self,
+        variant_ids: &[String],
+        categories: &[String],
+    ) -> Result<HashSet<String>, mysql::Error> {
+        traced("fetch_variants_in_categories", || {
+            cart::fetch_variants_in_categories(&mut self.conn, variant_ids, categories)
+        })
+    }
+
+    fn fetch_product_categories(&mut self) -> Result<HashSet<String>, mysql::Error> {
+        traced("fetch_product_categories", || {
+            cart::fetch_product_categories(&mut self.conn)
+        })
+    }
+
+    fn fetch_popular_products(
+        &mut self,
+        exclude_variant_ids: &[String],
+        window_days: Option<u32>,
+        temperature: Option<Temperature>,
+        excluded_categories: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, f32)>, mysql::Error> {
+        traced("fetch_popular_products", || {
+            cart::fetch_popular_products(
+                &mut self.conn,
+                exclude_variant_ids,
+                window_days,
+                temperature,
+                excluded_categories,
+                limit,
+            )
+        })
+    }
+
+    fn fetch_cached_suggestions(
+        &mut self,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>, mysql::Error> {
+        traced("fetch_cached_suggestions", || {
+            // キャッシュは主データベースに書き込むため、レプリカの遅延で取りこぼさないよう主データベースから読む
+            suggestion_cache::fetch_cached_suggestions(self.primary_conn()?, key, ttl)
+        })
+    }
+
+    fn store_cached_suggestions(
+        &mut self,
+        key: &str,
+        suggestions: &str,
+        ttl: Duration,
+    ) -> Result<(), mysql::Error> {
+        traced("store_cached_suggestions", || {
+            suggestion_cache::store_cached_suggestions(self.primary_conn()?, key, suggestions, ttl)
+        })
+    }
+
+    fn clear_cached_suggestions(&mut self) -> Result<(), mysql::Error> {
+        traced("clear_cached_suggestions", || {
+            suggestion_cache::clear_cached_suggestions(self.primary_conn()?)
+        })
+    }
+
+    fn rebuild_item_similarity(&mut self) -> Result<u64, mysql::Error> {
+        traced("rebuild_item_similarity", || {
+            item_similarity::rebuild_item_similarity(self.primary_conn()?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing;
+
+    // スパンの終了を記録した行のうち、指定した名前のスパンのもの
+    fn closed_spans<'a>(lines: &'a [serde_json::Value], name: &str) -> Vec<&'a serde_json::Value> {
+        lines
+            .iter()
+            .filter(|line| line["fields"]["message"] == "close" && line["span"]["name"] == name)
+            .collect()
+    }
+
+    #[test]
+    fn traced_wraps_the_query_spans_in_a_recommendation_query_span() {
+        let (result, lines) = testing::capture_logs(|| {
+            traced("fetch_product_categories", || {
+                db::timed("fetch_product_categories", || Ok(3))
+            })
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let outer = closed_spans(&lines, "recommendation_query");
+        assert_eq!(outer.len(), 1);
+        assert_eq!(outer[0]["span"]["query"], "fetch_product_categories");
+
+        // db_query のスパンは recommendation_query の中で閉じる
+        let inner = closed_spans(&lines, "db_query");
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0]["spans"][0]["name"], "recommendation_query");
+        assert!(inner[0]["span"]["elapsed_ms"].is_u64());
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（商品を登録済み）が必要"]
+    fn primary_session_reads_the_primary_instead_of_the_replica() {
+        // 接続できないレプリカを指定し、どちらのデータベースを読むかを確かめる
+        let store = MySqlStore::new(
+            Arc::new(testing::database_pool()),
+            Some(testing::unconnected_pool()),
+        );
+
+        assert!(store.session().is_err());
+        let dimensions = store
+            .primary_session()
+            .unwrap()
+            .fetch_product_dimensions()
+            .unwrap();
+        assert!(dimensions.get_dimension() > 0);
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（商品を登録済み）が必要"]
+    fn session_queries_are_recorded_as_recommendation_query_spans() {
+        let store = MySqlStore::new(Arc::new(testing::database_pool()), None);
+        let mut session = store.session().unwrap();
+
+        let (categories, lines) = testing::capture_logs(|| session.fetch_product_categories());
+        categories.unwrap();
+
+        let spans = closed_spans(&lines, "recommendation_query");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0]["span"]["query"], "fetch_product_categories");
+        assert!(!closed_spans(&lines, "db_query").is_empty());
+    }
+
+    // 既存の注文を複製して days 日前の注文とし、指定した商品（商品ID・バリアントID・数量）を購入したことにする
+    fn insert_aged_order(
+        conn: &mut mysql::PooledConn,
+        source_order: &str,
+        days: u32,
+        lines: &[(&str, &str, u32)],
+    ) -> String {
+        use mysql::prelude::*;
+
+        let order_id = format!("aged-{}", uuid::Uuid::new_v4());
+        conn.exec_drop(
+            "CREATE TEMPORARY TABLE aged_order AS SELECT * FROM orders WHERE id = ?",
+            (source_order,),
+        )
+        .unwrap();
+        conn.exec_drop(
+            "UPDATE aged_order SET id = ?, created_at = NOW() - INTERVAL ? DAY",
+            (&order_id, days),
+        )
+        .unwrap();
+        conn.query_drop("INSERT INTO orders SELECT * FROM aged_order")
+            .unwrap();
+        conn.query_drop("DROP TEMPORARY TABLE aged_order").unwrap();
+        for (product_id, variant_id, quantity) in lines {
+            conn.exec_drop(
+                "INSERT INTO order_products (order_id, product_id, variant_id, quantity, price,
+                 is_subscription, is_brand_new_discount, created_at, updated_at)
+                 VALUES (?, ?, ?, ?, 100, 0, 0, NOW(), NOW())",
+                (&order_id, product_id, variant_id, quantity),
+            )
+            .unwrap();
+        }
+        order_id
+    }
+
+    // insert_aged_order で作成した注文を削除する
+    fn delete_order(conn: &mut mysql::PooledConn, order_id: &str) {
+        use mysql::prelude::*;
+
+        conn.exec_drop("DELETE FROM order_products WHERE order_id = ?", (order_id,))
+            .unwrap();
+        conn.exec_drop("DELETE FROM orders WHERE id = ?", (order_id,))
+            .unwrap();
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品の注文を登録済み）が必要"]
+    fn popular_products_window_excludes_older_orders() {
+        use mysql::prelude::*;
+
+        let pool = Arc::new(testing::database_pool());
+        let mut conn = pool.get_conn().unwrap();
+        let (source_order, product_id, variant_id): (String, String, String) = conn
+            .query_first(
+                "SELECT op.order_id, CAST(op.product_id AS CHAR), CAST(op.variant_id AS CHAR)
+                 FROM order_products op
+                 JOIN products p ON p.variant_id = op.variant_id
+                 WHERE p.is_suspension = false
+                 LIMIT 1",
+            )
+            .unwrap()
+            .expect("販売中の商品の注文がありません");
+
+        // 既存の注文を複製して400日前の注文にし、その商品を大量に購入したことにする
+        let old_quantity = 1_000_000;
+        let old_order = insert_aged_order(
+            &mut conn,
+            &source_order,
+            400,
+            &[(&product_id, &variant_id, old_quantity)],
+        );
+
+        let mut session = MySqlStore::new(pool.clone(), None).session().unwrap();
+        let mut quantity = |window_days: Option<u32>| -> f32 {
+            session
+                .fetch_popular_products(&[], window_days, None, &[], usize::MAX)
+                .unwrap()
+                .into_iter()
+                .find(|(id, _)| *id == variant_id)
+                .map_or(0.0, |(_, quantity)| quantity)
+        };
+        let all_time = quantity(None);
+        let last_year = quantity(Some(365));
+
+        delete_order(&mut conn, &old_order);
+
+        // 全期間では古い注文の数量が含まれ、365日以内では含まれない
+        assert!(all_time >= old_quantity as f32, "{}", all_time);
+        assert!(
+            all_time - last_year >= old_quantity as f32,
+            "{} - {}",
+            all_time,
+            last_year
+        );
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品の注文を登録済み）が必要"]
+    fn suspended_products_are_dropped_from_the_purchase_vectors() {
+        use mysql::prelude::*;
+
+        let pool = Arc::new(testing::database_pool());
+        let mut conn = pool.get_conn().unwrap();
+        let suspended: String = conn
+            .query_first(
+                "SELECT CAST(op.variant_id AS CHAR)
+                 FROM order_products op
+                 JOIN products p ON p.variant_id = op.variant_id
+                 WHERE p.is_suspension = false
+                 LIMIT 1",
+            )
+            .unwrap()
+            .expect("販売中の商品の注文がありません");
+        let set_suspension = |conn: &mut mysql::PooledConn, suspended_flag: bool| {
+            conn.exec_drop(
+                "UPDATE products SET is_suspension = ? WHERE variant_id = ?",
+                (suspended_flag, &suspended),
+            )
+            .unwrap();
+        };
+
+        // 次元情報の取得は事前計算済みベクトルの利用可否も切り替えるため、購入履歴とは別のストアで行う
+        let session = || MySqlStore::new(pool.clone(), None).session().unwrap();
+        set_suspension(&mut conn, true);
+        let active = session().fetch_product_dimensions();
+        // 停止した商品にも次元を割り当て、購入履歴の問い合わせで除かれることを確かめる
+        let mut variant_ids: Vec<String> = conn
+            .query("SELECT CAST(variant_id AS CHAR) FROM products WHERE is_suspension = false")
+            .unwrap();
+        variant_ids.push(suspended.clone());
+        let with_suspended = ProductDimensions::new(variant_ids);
+        let history = session().fetch_user_purchase_history(
+            &with_suspended,
+            VectorEncoding::default(),
+            1,
+            false,
+        );
+        set_suspension(&mut conn, false);
+
+        assert!(active.unwrap().get_index(&suspended).is_none());
+        let index = with_suspended.get_index(&suspended).unwrap();
+        let history = history.unwrap();
+        assert!(!history.is_empty());
+        assert!(
+            history
+                .iter()
+                .all(|(_, vector)| vector.product_vector[index] == 0.0)
+        );
+    }
+
+    #[test]
+    #[ignore = "TEST_DATABASE_URL のMySQL（販売中の商品を2件以上と注文を登録済み）が必要"]
+    fn cooccurrence_half_life_down_weights_old_orders() {
+        use mysql::prelude::*;
+
+        let pool = Arc::new(testing::database_pool());
+        let mut conn = pool.get_conn().unwrap();
+        let source_order: String = conn
+            .query_first("SELECT id FROM orders LIMIT 1")
+            .unwrap()
+            .expect("注文がありません");
+        let products: Vec<(String, String)> = conn
+            .query(
+                "SELECT CAST(product_id AS CHAR), CAST(variant_id AS CHAR)
+                 FROM products WHERE is_suspension = false LIMIT 2",
+            )
+            .unwrap();
+        let [(cart_product, cart_variant), (other_product, other_variant)] = &products[..] else {
+            panic!("販売中の商品が2件以上必要です");
+        };
+
+        let mut session = MySqlStore::new(pool.clone(), None).session().unwrap();
+        let mut cooccurrence = |half_life_days: Option<f32>| -> f32 {
+            session
+                .fetch_cooccurring_products(std::slice::from_ref(cart_variant), half_life_days)
+                .unwrap()
+                .get(other_variant)
+                .copied()
+                .unwrap_or(0.0)
+        };
+        let before = (cooccurrence(None), cooccurrence(Some(30.0)));
+        // 半減期の2倍（60日）前に2つの商品を一緒に購入した注文を追加する
+        let old_order = insert_aged_order(
+            &mut conn,
+            &source_order,
+            60,
+            &[
+                (cart_product, cart_variant, 1),
+                (other_product, other_variant, 1),
+            ],
+        );
+        let after = (cooccurrence(None), cooccurrence(Some(30.0)));
+        delete_order(&mut conn, &old_order);
+
+        // 減衰なしでは1回として数え、半減期30日では 0.5^2 = 0.25 回分になる
+        assert!(
+            (after.0 - before.0 - 1.0).abs() < 1e-3,
+            "{:?} -> {:?}",
+            before,
+            after
+        );
+        assert!(
+            (after.1 - before.1 - 0.25).abs() < 1e-3,
+            "{:?} -> {:?}",
+            before,
+            after
+        );
+    }
+}
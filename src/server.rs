@@ -0,0 +1,154 @@
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+// 接続の待ち時間の設定
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    // リクエストヘッダーを受信し終えるまでの時間の上限
+    // 使い回す HTTP/1.1 の接続が次のリクエストを待つ時間（アイドル時間の上限）も兼ねる
+    pub header: Duration,
+    // HTTP/2 の接続の生存確認（PING）の間隔（None の場合は接続を使い回さない）
+    pub keepalive_interval: Option<Duration>,
+}
+
+// 接続ごとの待ち時間を設定したサーバーのビルダーを作成する
+//
+// HTTP/1.1 では header の時間内にヘッダーを受信し終えなければ接続を閉じる。
+// hyper はこの時間を接続を使い回して次のリクエストを待つ間にも数えるため、
+// 使われていない HTTP/1.1 の接続は header の時間で閉じられる（keepalive_interval が None の場合は応答ごとに閉じる）。
+// HTTP/2 では keepalive_interval の間隔で PING を送り、同じ時間内に応答がなければ接続を閉じる
+pub fn builder(timeouts: Timeouts) -> auto::Builder<TokioExecutor> {
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(timeouts.header)
+        .keep_alive(timeouts.keepalive_interval.is_some());
+    if let Some(interval) = timeouts.keepalive_interval {
+        builder
+            .http2()
+            .timer(TokioTimer::new())
+            .keep_alive_interval(interval)
+            .keep_alive_timeout(interval);
+    }
+    builder
+}
+
+// 接続を受け付け、接続ごとにタスクを作ってルーターで処理する
+// axum::serve では接続の待ち時間を設定できないため、hyper のビルダーで直接処理する
+pub async fn serve(listener: TcpListener, app: Router, timeouts: Timeouts) {
+    let builder = builder(timeouts);
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                // 接続数の上限などによる一時的な失敗では待ち受けを止めない
+                tracing::warn!(error = %err, "接続の受け付けに失敗しました");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        let builder = builder.clone();
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(err) = builder
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::warn!(%remote_addr, error = %err, "接続の処理を終了しました");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    // 指定した待ち時間でサーバーを起動し、接続先を返す
+    async fn start(timeouts: Timeouts) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        tokio::spawn(serve(listener, app, timeouts));
+        addr
+    }
+
+    // サーバーが接続を閉じるまで読み続け、受信した内容を返す（閉じなければテストを失敗させる）
+    async fn read_until_closed(stream: &mut TcpStream) -> String {
+        let mut received = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut received))
+            .await
+            .expect("接続が閉じられません")
+            .ok();
+        String::from_utf8_lossy(&received).into_owned()
+    }
+
+    const TIMEOUTS: Timeouts = Timeouts {
+        header: Duration::from_millis(200),
+        keepalive_interval: Some(Duration::from_secs(60)),
+    };
+
+    #[tokio::test]
+    async fn incomplete_headers_are_cut_off_after_the_header_timeout() {
+        let addr = start(TIMEOUTS).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // ヘッダーの終わり（空行）を送らずに待つ
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n")
+            .await
+            .unwrap();
+        let started = tokio::time::Instant::now();
+        let received = read_until_closed(&mut stream).await;
+
+        assert!(started.elapsed() >= TIMEOUTS.header);
+        assert!(!received.contains("200 OK"), "{}", received);
+    }
+
+    #[tokio::test]
+    async fn idle_keep_alive_connections_close_after_the_header_timeout() {
+        let addr = start(TIMEOUTS).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let started = tokio::time::Instant::now();
+        let received = read_until_closed(&mut stream).await;
+
+        // 応答後も接続は使い回せるが、次のリクエストが来なければ HTTP_KEEPALIVE_SECS ではなく header の時間で閉じる
+        assert!(received.starts_with("HTTP/1.1 200 OK"), "{}", received);
+        assert!(started.elapsed() >= TIMEOUTS.header);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn disabled_keep_alive_closes_after_each_response() {
+        let addr = start(Timeouts {
+            keepalive_interval: None,
+            ..TIMEOUTS
+        })
+        .await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let started = tokio::time::Instant::now();
+        let received = read_until_closed(&mut stream).await;
+
+        assert!(received.starts_with("HTTP/1.1 200 OK"), "{}", received);
+        assert!(started.elapsed() < TIMEOUTS.header);
+    }
+}
@@ -1,5 +1,12 @@
 use mysql::prelude::Queryable;
-use std::{collections::HashMap, sync::Arc};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::region;
+pub use super::top_k::{Scored, select_top};
+use crate::db;
+use crate::repository::RecommendationSession;
 
 // 商品IDとインデックスのマッピングを保持する構造体
 #[derive(Debug)]
@@ -45,61 +52,387 @@ impl ProductDimensions {
     }
 }
 
+// 地域ベクトルの作り方の版（region_to_vector の作り方を変えた場合は上げる）
+// 異なる版で作成した地域ベクトル同士は比較できないため、類似度の計算前に確認する
+// 1: 都道府県番号の1次元、2: 都道府県ごとの one-hot
+pub const REGION_ENCODING_VERSION: u32 = 2;
+
 // ユーザーベクトル表現のための構造体
 
 #[derive(Clone, Debug)]
 pub struct OrderVector {
     pub region_vector: Vec<f32>,
     pub product_vector: Vec<f32>,
+    // 都道府県番号（隣接関係による地域類似度で使用）
+    pub province: Option<u32>,
+    // 地域ベクトルを作成したときの作り方の版（REGION_ENCODING_VERSION）
+    pub region_encoding: u32,
+}
+
+// 作り方の異なる地域ベクトル同士を比較しようとした（古い形式で保存したベクトルが混在しているなど）
+#[derive(Debug)]
+pub struct RegionEncodingMismatch {
+    // 比較しようとした2つのベクトルの版と次元数
+    pub versions: (u32, u32),
+    pub dimensions: (usize, usize),
+}
+
+impl std::fmt::Display for RegionEncodingMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "地域ベクトルの作り方が一致しません（版: {} と {}、次元数: {} と {}）",
+            self.versions.0, self.versions.1, self.dimensions.0, self.dimensions.1
+        )
+    }
+}
+
+// 推薦の計算のエラー
+#[derive(Debug)]
+pub enum RecommendError {
+    // データベースの問い合わせに失敗した
+    Database(mysql::Error),
+    // 作り方の異なる地域ベクトルが混在している
+    RegionEncodingMismatch(RegionEncodingMismatch),
+}
+
+impl From<mysql::Error> for RecommendError {
+    fn from(err: mysql::Error) -> Self {
+        RecommendError::Database(err)
+    }
+}
+
+impl From<RegionEncodingMismatch> for RecommendError {
+    fn from(err: RegionEncodingMismatch) -> Self {
+        RecommendError::RegionEncodingMismatch(err)
+    }
+}
+
+// 顧客IDとその購入ベクトルの組の一覧（事前計算したものを複製せずに共有する）
+pub type UserVectors = std::sync::Arc<Vec<(String, OrderVector)>>;
+
+// 地域類似度の計算方法
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionSimilarity {
+    // 地域ベクトルのコサイン類似度
+    #[default]
+    Cosine,
+    // 都道府県の隣接関係に基づく類似度
+    Adjacency,
+}
+
+// 商品類似度の計算方法
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityMetric {
+    // 商品ベクトルのコサイン類似度
+    #[default]
+    Cosine,
+    // 全顧客の商品ごとの平均を引いてからのコサイン類似度（adjusted cosine）
+    AdjustedCosine,
+}
+
+// 配送温度帯（products.shipping_temperature の値）
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Temperature {
+    Normal,
+    Cold,
+    Frozen,
+}
+
+impl Temperature {
+    pub const ALL: [Temperature; 3] = [Temperature::Normal, Temperature::Cold, Temperature::Frozen];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Temperature::Normal => "Normal",
+            Temperature::Cold => "Cold",
+            Temperature::Frozen => "Frozen",
+        }
+    }
+}
+
+// 地域と商品の類似度をまとめる方法
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityModel {
+    // 地域と商品のコサイン類似度をそれぞれ求めて重み付け合計する
+    #[default]
+    Blended,
+    // 重み付けした地域ベクトルと商品ベクトルを連結し、1つのコサイン類似度を求める
+    Unified,
+}
+
+// 商品ベクトルの正規化方法
+//
+// blended モデルの商品類似度はコサイン類似度のため、どの方法でも大きさの違いは比較時に打ち消され、
+// 結果は変わらない（L1はベクトルを定数倍するだけなので平均中心化後も向きは同じ）。
+// 一方、unified モデルは地域ベクトルと連結してから比較するため、正規化しない（None）場合は
+// 購入数量が多いほど商品の成分が地域の成分より支配的になる。L2 では商品部分の大きさが常に1になり、
+// region_weight の比率どおりに地域と商品が効く。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalizationMode {
+    // ユークリッドノルムで割る（大きさ1）
+    L2,
+    // 成分の絶対値の合計で割る（合計1）
+    L1,
+    // 数量（重み）をそのまま使う
+    #[default]
+    None,
+}
+
+impl NormalizationMode {
+    // 設定値の文字列（l2 / l1 / none）から変換する
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "l2" => Some(NormalizationMode::L2),
+            "l1" => Some(NormalizationMode::L1),
+            "none" => Some(NormalizationMode::None),
+            _ => None,
+        }
+    }
+
+    // 設定値の文字列（parse の逆）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalizationMode::L2 => "l2",
+            NormalizationMode::L1 => "l1",
+            NormalizationMode::None => "none",
+        }
+    }
+}
+
+// 正規化の前に数量（重み）に適用する変換
+// 多数の商品を含むカートを正規化すると各商品の重みが小さくなり、大量購入が他の商品を圧倒しやすい。
+// Sqrt / Log1p は大きな数量を抑えつつ、1個だけの購入の信号は残す
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum QuantityTransform {
+    // 数量（重み）をそのまま使う
+    #[default]
+    Identity,
+    // 平方根
+    Sqrt,
+    // ln(1 + x)
+    Log1p,
+    // 購入の有無だけを使う（数量によらず1）
+    Binary,
+}
+
+impl QuantityTransform {
+    // 設定値の文字列（identity / sqrt / log1p）から変換する
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "identity" => Some(QuantityTransform::Identity),
+            "sqrt" => Some(QuantityTransform::Sqrt),
+            "log1p" => Some(QuantityTransform::Log1p),
+            "binary" => Some(QuantityTransform::Binary),
+            _ => None,
+        }
+    }
+
+    // 設定値の文字列（parse の逆）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuantityTransform::Identity => "identity",
+            QuantityTransform::Sqrt => "sqrt",
+            QuantityTransform::Log1p => "log1p",
+            QuantityTransform::Binary => "binary",
+        }
+    }
+
+    // 値を変換する（負の重みは符号を保ったまま絶対値を変換する）
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            QuantityTransform::Identity => value,
+            QuantityTransform::Sqrt => value.signum() * value.abs().sqrt(),
+            QuantityTransform::Log1p => value.signum() * value.abs().ln_1p(),
+            // f32::signum は 0.0 に対しても1を返すため、0はそのまま残す
+            QuantityTransform::Binary if value == 0.0 => 0.0,
+            QuantityTransform::Binary => value.signum(),
+        }
+    }
+}
+
+// 購入の扱い方（?presence=quantity / binary）
+// 数量が当てにならない（まとめ買いや贈答が多い）場合は binary で購入の有無だけを比べる。
+// このとき商品ベクトルのコサイン類似度は、購入商品の集合どうしのOchiai係数になる
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    // 数量（重み）を設定済みの変換で使う
+    #[default]
+    Quantity,
+    // 購入したかどうかだけを使う
+    Binary,
+}
+
+impl Presence {
+    // ベクトルの作り方に反映する（binary の場合は数量の変換を置き換える）
+    pub fn apply(self, encoding: VectorEncoding) -> VectorEncoding {
+        match self {
+            Presence::Quantity => encoding,
+            Presence::Binary => VectorEncoding {
+                transform: QuantityTransform::Binary,
+                ..encoding
+            },
+        }
+    }
+}
+
+// 商品ベクトルの作り方（数量の変換と正規化方法の組）
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VectorEncoding {
+    pub transform: QuantityTransform,
+    pub normalization: NormalizationMode,
+}
+
+// ベクトルを指定した方法で正規化する（ゼロベクトルはそのまま）
+pub fn normalize(vector: &mut [f32], mode: NormalizationMode) {
+    let norm = match mode {
+        NormalizationMode::L2 => vector.iter().map(|&x| x * x).sum::<f32>().sqrt(),
+        NormalizationMode::L1 => vector.iter().map(|&x| x.abs()).sum::<f32>(),
+        NormalizationMode::None => return,
+    };
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+// 顧客間の類似度の計算方法
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimilarityMethod {
+    pub model: SimilarityModel,
+    // Blended の場合の地域類似度の計算方法（Unified は常に地域ベクトルを使う）
+    pub region: RegionSimilarity,
+    // Blended の場合の商品類似度の計算方法（Unified は常にコサイン類似度を使う）
+    pub metric: SimilarityMetric,
+}
+
+impl SimilarityMethod {
+    // item_means は AdjustedCosine の場合に使う商品ごとの平均（item_means で計算したもの）
+    pub fn similarity(
+        &self,
+        user1: &OrderVector,
+        user2: &OrderVector,
+        region_weight: f32,
+        item_means: Option<&[f32]>,
+    ) -> Result<f32, RegionEncodingMismatch> {
+        match self.model {
+            SimilarityModel::Blended => {
+                combined_similarity(user1, user2, region_weight, self.region, item_means)
+            }
+            SimilarityModel::Unified => unified_similarity(user1, user2, region_weight),
+        }
+    }
+
+    // 商品ごとの平均を事前に計算する必要があるか
+    pub fn needs_item_means(&self) -> bool {
+        self.model == SimilarityModel::Blended && self.metric == SimilarityMetric::AdjustedCosine
+    }
+}
+
+// 推薦候補の絞り込み条件
+#[derive(Clone, Debug, Default)]
+pub struct SuggestionFilter {
+    // 配送温度帯（未指定時は限定しない）
+    pub temperature: Option<Temperature>,
+    // 推薦から除く商品カテゴリ（products.category の値）
+    pub excluded_categories: Vec<String>,
+    // カート内の商品以外に推薦から除く商品（顧客の過去の購入商品など）
+    pub excluded_variant_ids: HashSet<String>,
+    // 推薦を求めた顧客（指定された場合だけ。ログに出す）
+    pub customer_id: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct CustomerScore {
-    pub customer_vector: OrderVector,
+    pub customer_id: String,
     pub score: f32,
 }
 
-// 地域コードをベクトルに変換する関数
-pub fn region_to_vector(province_code: &str) -> Vec<f32> {
-    // JP-XX 形式から数値部分を抽出
-    let region_value = if province_code.starts_with("JP-") && province_code.len() >= 5 {
-        // 数値部分を抽出して整数に変換
-        province_code[3..].parse::<u32>().unwrap_or(0)
-    } else {
-        // 不正な形式の場合はデフォルト値
-        0
-    };
+impl Scored for CustomerScore {
+    fn score(&self) -> f32 {
+        self.score
+    }
 
-    // 都道府県コードを1〜47の範囲で正規化
-    let normalized_value = if region_value >= 1 && region_value <= 47 {
-        region_value as f32 % 47.0
-    } else {
-        0.0
-    };
+    fn id(&self) -> &str {
+        &self.customer_id
+    }
+}
+
+impl Scored for ProductSuggestion {
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn id(&self) -> &str {
+        &self.product_id
+    }
+}
+
+// 近傍顧客1人分の商品スコアへの寄与
+#[derive(Clone, Debug)]
+pub struct NeighborContribution {
+    // 匿名化した近傍顧客のラベル（類似度の順位）
+    pub neighbor: String,
+    pub similarity: f32,
+    pub contribution: f32,
+}
+
+// 近傍顧客の匿名化したラベル（rank は0始まりの順位）
+pub fn neighbor_label(rank: usize) -> String {
+    format!("neighbor-{}", rank + 1)
+}
+
+// 推薦商品とそのスコアの内訳
+#[derive(Debug)]
+pub struct ProductSuggestion {
+    pub product_id: String,
+    pub score: f32,
+    pub contributions: Vec<NeighborContribution>,
+}
+
+// 地域コードを都道府県ごとの one-hot ベクトルに変換する関数
+// 大きさは常に1（L2正規化した商品ベクトルと同じ）のため、unified モデルで連結しても
+// 都道府県番号の大小や次元数の違いで地域の成分が偏らない
+// 不正な形式や範囲外の場合はすべて0
+pub fn region_to_vector(province_code: &str) -> Vec<f32> {
+    let mut vector = vec![0.0; region::PROVINCE_COUNT];
+    if let Some(number) = region::province_number(province_code) {
+        vector[number as usize - 1] = 1.0;
+    }
 
-    vec![normalized_value]
+    vector
 }
 
 // 商品情報を表す汎用的な構造体
+#[derive(Clone)]
 pub struct ProductItem {
     pub product_variant_id: String,
     pub quantity: u32,
+    // 小数の重み（指定時は数量の代わりに使う）
+    pub weight: Option<f32>,
 }
 
 // カート内商品をベクトルに変換する関数
+// 数量（重み）に encoding の変換を適用して並べたあと、encoding の正規化方法で正規化する
 pub fn products_to_vector(
     products: &[ProductItem],
     product_dimensions: &ProductDimensions,
+    encoding: VectorEncoding,
 ) -> Vec<f32> {
     let dimension = product_dimensions.get_dimension();
     let mut vector = vec![0.0; dimension];
     for product in products {
         // 商品IDに対応するインデックスを取得
         if let Some(index) = product_dimensions.get_index(&product.product_variant_id) {
-            // 数量を対応する次元に設定
-            vector[index] = product.quantity as f32;
+            // 重みがあれば重みを、なければ数量を対応する次元に設定
+            let value = product.weight.unwrap_or(product.quantity as f32);
+            vector[index] = encoding.transform.apply(value);
         }
     }
+    normalize(&mut vector, encoding.normalization);
     vector
 }
 
@@ -108,105 +441,631 @@ pub fn create_order_vector(
     region_code: &str,
     products: &[ProductItem],
     product_dimensions: &ProductDimensions,
+    encoding: VectorEncoding,
 ) -> OrderVector {
     OrderVector {
         region_vector: region_to_vector(region_code),
-        product_vector: products_to_vector(products, product_dimensions),
+        product_vector: products_to_vector(products, product_dimensions, encoding),
+        province: region::province_number(region_code),
+        region_encoding: REGION_ENCODING_VERSION,
+    }
+}
+
+// 購入した商品の数量から平均を引いた商品ベクトルを作成する（Pearson相関の前処理）
+// 大量に購入する顧客が大きさだけで類似度を支配しないよう、顧客ごとの購入傾向を揃える。
+// 購入していない次元は0のままにし、購入した商品が1種類だけの場合はすべて0になる
+pub fn mean_center(product_vector: &[f32]) -> Vec<f32> {
+    let purchased: Vec<f32> = product_vector
+        .iter()
+        .copied()
+        .filter(|&value| value != 0.0)
+        .collect();
+    if purchased.is_empty() {
+        return product_vector.to_vec();
+    }
+    let mean = purchased.iter().sum::<f32>() / purchased.len() as f32;
+
+    product_vector
+        .iter()
+        .map(|&value| if value != 0.0 { value - mean } else { 0.0 })
+        .collect()
+}
+
+// 商品ベクトルを平均中心化したユーザーベクトルを作成する関数
+pub fn mean_centered_order_vector(order: &OrderVector) -> OrderVector {
+    OrderVector {
+        region_vector: order.region_vector.clone(),
+        product_vector: mean_center(&order.product_vector),
+        province: order.province,
+        region_encoding: order.region_encoding,
     }
 }
 
 // データベースから有効な商品IDのリストを取得
-pub async fn fetch_product_dimensions(
-    pool: &mysql::Pool,
+pub fn fetch_product_dimensions(
+    conn: &mut mysql::PooledConn,
 ) -> Result<ProductDimensions, mysql::Error> {
-    let mut conn = pool.get_conn()?;
-
     // 有効な商品IDを取得するクエリ
-    let product_ids: Vec<String> = conn.query_map(
-        "SELECT variant_id FROM products WHERE is_suspension = false",
-        |variant_id: String| variant_id,
-    )?;
+    let product_ids: Vec<String> = db::timed("fetch_product_dimensions", || {
+        conn.query_map(
+            "SELECT variant_id FROM products WHERE is_suspension = false",
+            |row: mysql::Row| db::read_variant_id(&row, "variant_id"),
+        )
+    })?;
 
     Ok(ProductDimensions::new(product_ids))
 }
 
-pub async fn get_similar_products(
-    pool: &Arc<mysql::Pool>,
+// 推薦計算のパラメータ
+#[derive(Clone, Debug)]
+pub struct RecommendationConfig {
+    // 返す推薦商品の件数
+    pub suggestion_limit: usize,
+    // 推薦に使う近傍顧客の人数
+    pub top_users: usize,
+    // リクエストで指定できる近傍顧客の人数の上限
+    pub max_neighbors: usize,
+    // 類似度に占める地域類似度の重み
+    pub region_weight: f32,
+    // 最終的なソートの前に残す推薦候補の上限（メモリ使用量を抑えるため）
+    pub candidate_cap: usize,
+    // 類似度の計算前に商品ベクトルを平均中心化するか
+    pub mean_center: bool,
+    // 商品ベクトルの作り方（数量の変換と正規化方法）
+    pub encoding: VectorEncoding,
+    // 近傍顧客として扱うのに必要な購入商品の種類数（1の場合は購入履歴のある顧客すべて）
+    pub min_neighbor_items: usize,
+    // 近傍顧客として扱うのに必要なカートとの類似度（None の場合は類似度によらず上位 top_users 人）
+    pub min_neighbor_similarity: Option<f32>,
+    // 共起回数を注文の新しさで減衰させる半減期（日数、None の場合は減衰させない）
+    pub cooccurrence_half_life_days: Option<f32>,
+    // 同じリクエストに対する推薦結果をキャッシュする期間（0の場合はキャッシュしない）
+    pub cache_ttl: std::time::Duration,
+    // 顧客との類似度の分散がこれ未満の場合は近傍から推薦しない（0の場合は判定しない）
+    pub min_similarity_variance: f32,
+    // 注文のない顧客も地域だけのベクトル（商品ベクトルはすべて0）で近傍に含めるか
+    pub include_orderless_customers: bool,
+}
+
+impl Default for RecommendationConfig {
+    fn default() -> Self {
+        RecommendationConfig {
+            suggestion_limit: 5,
+            top_users: 10,
+            max_neighbors: 100,
+            region_weight: 0.8,
+            candidate_cap: 100,
+            mean_center: false,
+            encoding: VectorEncoding::default(),
+            min_neighbor_items: 1,
+            min_neighbor_similarity: None,
+            cooccurrence_half_life_days: None,
+            cache_ttl: std::time::Duration::ZERO,
+            min_similarity_variance: 1e-6,
+            include_orderless_customers: false,
+        }
+    }
+}
+
+pub fn get_similar_products(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
+    current_order: &OrderVector,
+    current_products: &[ProductItem],
+    product_dimensions: &ProductDimensions,
+    similarity: SimilarityMethod,
+    filter: &SuggestionFilter,
+) -> Result<Vec<ProductSuggestion>, RecommendError> {
+    let customer_scores = score_customers(
+        session,
+        config,
+        current_order,
+        product_dimensions,
+        similarity,
+    )?;
+
+    // 類似度がほぼ同じ値ばかりの場合は近傍の選び方が恣意的になるため、近傍からは推薦しない
+    // 空の結果を返し、呼び出し側の人気商品による代替に任せる
+    if is_degenerate(&customer_scores, config.min_similarity_variance) {
+        tracing::warn!(
+            customer_id = filter.customer_id.as_deref(),
+            min_variance = config.min_similarity_variance,
+            customers = customer_scores.len(),
+            "顧客との類似度の分散が小さすぎるため、近傍からは推薦しません"
+        );
+        return Ok(vec![]);
+    }
+
+    let suggestions = collect_similar_products(
+        session,
+        config,
+        current_products,
+        product_dimensions,
+        select_neighbors(customer_scores, config),
+        None,
+        Some(filter),
+    )?;
+
+    Ok(rank_suggestions(suggestions, config.suggestion_limit))
+}
+
+// 類似度の分散が min_variance 未満か（顧客が2人未満の場合は判定しない）
+// データが均質すぎる（全員が同じ地域で同じ商品を購入しているなど）と、類似度がほぼ1に揃ってしまう
+pub fn is_degenerate(scores: &[CustomerScore], min_variance: f32) -> bool {
+    if scores.len() < 2 {
+        return false;
+    }
+    let count = scores.len() as f32;
+    let mean = scores.iter().map(|s| s.score).sum::<f32>() / count;
+    let variance = scores.iter().map(|s| (s.score - mean).powi(2)).sum::<f32>() / count;
+    variance < min_variance
+}
+
+// カートとの類似度が高い上位 top_users 人の顧客を類似度の降順で返す（推薦の前半、商品の集計は行わない）
+// min_neighbor_similarity を指定した場合は、類似度がそれ未満の顧客を含めない
+pub fn find_neighbors(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
     current_order: &OrderVector,
+    product_dimensions: &ProductDimensions,
+    similarity: SimilarityMethod,
+) -> Result<Vec<CustomerScore>, RecommendError> {
+    let customer_scores = score_customers(
+        session,
+        config,
+        current_order,
+        product_dimensions,
+        similarity,
+    )?;
+    Ok(select_neighbors(customer_scores, config))
+}
+
+// 類似度が min_neighbor_similarity 以上の顧客から、類似度の高い上位 top_users 人を近傍顧客として選ぶ
+// 条件を満たす顧客が top_users 人に満たない場合はその顧客だけを使う（いない場合は空になり、人気商品で代替される）
+pub fn select_neighbors(
+    customer_scores: Vec<CustomerScore>,
+    config: &RecommendationConfig,
+) -> Vec<CustomerScore> {
+    select_top(
+        customer_scores.into_iter().filter(|customer_score| {
+            config
+                .min_neighbor_similarity
+                .is_none_or(|min_similarity| customer_score.score >= min_similarity)
+        }),
+        config.top_users,
+    )
+}
+
+// 購入履歴のあるすべての顧客とカートとの類似度を計算する（順不同）
+fn score_customers(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
+    current_order: &OrderVector,
+    product_dimensions: &ProductDimensions,
+    similarity: SimilarityMethod,
+) -> Result<Vec<CustomerScore>, RecommendError> {
+    // 他のユーザーの購入履歴を取得
+    let other_orders = session.fetch_user_purchase_history(
+        product_dimensions,
+        config.encoding,
+        config.min_neighbor_items,
+        config.include_orderless_customers,
+    )?;
+    tracing::debug!(
+        customers = other_orders.len(),
+        "購入履歴のある顧客を取得しました"
+    );
+
+    // 平均中心化する場合は現在のカートも中心化してから比較する
+    let centered_order = config
+        .mean_center
+        .then(|| mean_centered_order_vector(current_order));
+    let current_order = centered_order.as_ref().unwrap_or(current_order);
+
+    // adjusted cosine の場合は、比較するベクトル（中心化する場合は中心化後）から商品ごとの平均を一度だけ求める
+    let means = similarity.needs_item_means().then(|| {
+        item_means(other_orders.iter().map(|(_, other_order)| {
+            if config.mean_center {
+                Cow::Owned(mean_center(&other_order.product_vector))
+            } else {
+                Cow::Borrowed(other_order.product_vector.as_slice())
+            }
+        }))
+    });
+
+    // 類似度計算（作り方の異なる地域ベクトルが混在していればエラーにする）
+    let user_similarities: Vec<CustomerScore> = other_orders
+        .iter()
+        .map(|(customer_id, other_order)| {
+            let centered_other = config
+                .mean_center
+                .then(|| mean_centered_order_vector(other_order));
+            let other_order = centered_other.as_ref().unwrap_or(other_order);
+            Ok(CustomerScore {
+                customer_id: customer_id.clone(),
+                score: similarity.similarity(
+                    current_order,
+                    other_order,
+                    config.region_weight,
+                    means.as_deref(),
+                )?,
+            })
+        })
+        .collect::<Result<_, RegionEncodingMismatch>>()?;
+
+    Ok(user_similarities)
+}
+
+// 近傍顧客（類似度の降順）の購入商品から推薦候補を集計する（最終的なソート・件数制限前）
+// 候補はスコアの高い順に candidate_cap 件までに絞る
+// candidates を指定した場合は、その商品だけを集計する
+// filter を指定した場合は、件数を絞る前に除外・温度帯の条件を適用する
+// （後から適用すると、上位の候補がすべて除外されて推薦が空になることがある）
+fn collect_similar_products(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
     current_products: &[ProductItem],
     product_dimensions: &ProductDimensions,
-) -> Vec<(String, f32)> {
-    // 汎用的な(製品ID, スコア)のタプルを返す
+    top_customer_scores: Vec<CustomerScore>,
+    candidates: Option<&HashSet<String>>,
+    filter: Option<&SuggestionFilter>,
+) -> Result<Vec<ProductSuggestion>, mysql::Error> {
+    // 商品IDとスコア、近傍顧客ごとの寄与の内訳を返す
     // 現在のカートに含まれる商品IDのセットを作成
     let current_product_ids: std::collections::HashSet<String> = current_products
         .iter()
         .map(|p| p.product_variant_id.clone())
         .collect();
 
-    // 他のユーザーの購入履歴を取得
-    let other_orders = match fetch_user_purchase_history(pool, product_dimensions).await {
-        Ok(users) => {
-            println!("取得したユーザー数: {}", users.len());
-            users
-        }
-        Err(err) => {
-            eprintln!("ユーザー購入履歴取得エラー: {}", err);
-            return vec![]; // エラー時は空のベクトルを返す
+    // 上位ユーザーの購入商品をまとめて取得
+    let neighbor_ids: Vec<String> = top_customer_scores
+        .iter()
+        .map(|customer_score| customer_score.customer_id.clone())
+        .collect();
+    let neighbor_products = session.fetch_user_products(&neighbor_ids)?;
+
+    // 商品ごとに近傍顧客の寄与を集計
+    let mut product_contributions: HashMap<String, Vec<NeighborContribution>> = HashMap::new();
+
+    for (rank, customer_score) in top_customer_scores.iter().enumerate() {
+        // 購入商品が取得できなかった顧客は寄与なし
+        let products = neighbor_products
+            .get(&customer_score.customer_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        // 寄与は購入数量に比例させるため、変換も正規化もしない
+        let product_vector =
+            products_to_vector(products, product_dimensions, VectorEncoding::default());
+
+        for (index, &quantity) in product_vector.iter().enumerate() {
+            if quantity > 0.0
+                && let Some(product_id) = product_dimensions.get_product_id_from_index(index)
+                && !current_product_ids.contains(product_id)
+                && candidates.is_none_or(|candidates| candidates.contains(product_id))
+            {
+                product_contributions
+                    .entry(product_id.clone())
+                    .or_default()
+                    .push(NeighborContribution {
+                        neighbor: neighbor_label(rank),
+                        similarity: customer_score.score,
+                        contribution: customer_score.score * quantity,
+                    });
+            }
         }
-    };
+    }
 
-    // 類似度計算と上位ユーザー抽出
-    let mut user_similarities: Vec<CustomerScore> = other_orders
-        .iter()
-        .enumerate()
-        .map(|(_idx, other_order)| {
-            let similarity = combined_similarity(current_order, &other_order, 0.8);
-            CustomerScore {
-                customer_vector: other_order.clone(),
-                score: similarity,
+    // 寄与の合計を商品スコアとする
+    let suggestions: Vec<ProductSuggestion> = product_contributions
+        .into_iter()
+        .map(|(product_id, mut contributions)| {
+            contributions.sort_by(|a, b| {
+                b.contribution
+                    .partial_cmp(&a.contribution)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            ProductSuggestion {
+                score: contributions.iter().map(|c| c.contribution).sum(),
+                product_id,
+                contributions,
             }
         })
         .collect();
+    let suggestions = match filter {
+        Some(filter) => apply_filter(session, suggestions, filter)?,
+        None => suggestions,
+    };
+
+    // 推薦できる候補のうち、上位 candidate_cap 件だけを残す
+    let suggestions = select_top(suggestions, config.candidate_cap);
+    tracing::debug!(
+        scores = ?suggestions
+            .iter()
+            .map(|s| (&s.product_id, s.score))
+            .collect::<Vec<_>>(),
+        "類似商品のスコアを計算しました"
+    );
+
+    Ok(suggestions)
+}
+
+// 推薦候補から除外対象の商品と除外カテゴリの商品を除き、温度帯が指定されていればその温度帯の商品に限定する
+fn apply_filter(
+    session: &mut dyn RecommendationSession,
+    suggestions: Vec<ProductSuggestion>,
+    filter: &SuggestionFilter,
+) -> Result<Vec<ProductSuggestion>, mysql::Error> {
+    let mut suggestions: Vec<ProductSuggestion> = suggestions
+        .into_iter()
+        .filter(|suggestion| !filter.excluded_variant_ids.contains(&suggestion.product_id))
+        .collect();
 
-    // 類似度で降順ソート
-    user_similarities.sort_by(|a, b| {
+    if !filter.excluded_categories.is_empty() {
+        let variant_ids: Vec<String> = suggestions.iter().map(|s| s.product_id.clone()).collect();
+        let excluded =
+            session.fetch_variants_in_categories(&variant_ids, &filter.excluded_categories)?;
+        suggestions.retain(|suggestion| !excluded.contains(&suggestion.product_id));
+    }
+
+    let Some(temperature) = filter.temperature else {
+        return Ok(suggestions);
+    };
+
+    let variant_ids: Vec<String> = suggestions.iter().map(|s| s.product_id.clone()).collect();
+    let matched = session.fetch_variants_with_temperature(&variant_ids, temperature)?;
+    suggestions.retain(|suggestion| matched.contains(&suggestion.product_id));
+    Ok(suggestions)
+}
+
+// スコア順にソートし、上位の件数に限定する
+fn rank_suggestions(
+    mut suggestions: Vec<ProductSuggestion>,
+    limit: usize,
+) -> Vec<ProductSuggestion> {
+    // 同点の場合は商品IDの昇順にし、候補の集計順（HashMapの反復順）によらず同じ順位にする
+    suggestions.sort_by(|a, b| {
         b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
+            .total_cmp(&a.score)
+            .then_with(|| a.product_id.cmp(&b.product_id))
     });
 
-    // 上位N人のユーザーを抽出
-    const TOP_USERS: usize = 10;
-    let top_customer_scores: Vec<&CustomerScore> =
-        user_similarities.iter().take(TOP_USERS).collect();
-
-    // 商品スコアを集計
-    let mut product_scores: HashMap<String, f32> = HashMap::new();
-
-    for customer_score in &top_customer_scores {
-        let order_vector = &customer_score.customer_vector;
-
-        for (index, &quantity) in order_vector.product_vector.iter().enumerate() {
-            if quantity > 0.0 {
-                if let Some(product_id) = product_dimensions.get_product_id_from_index(index) {
-                    if !current_product_ids.contains(product_id) {
-                        *product_scores.entry(product_id.clone()).or_insert(0.0) +=
-                            customer_score.score * quantity;
-                    }
-                }
-            }
+    // 上位の件数に限定
+    suggestions.truncate(limit);
+
+    suggestions
+}
+
+// クライアントが指定した候補商品を、カートとの関連度（近傍顧客による協調フィルタリングのスコア）の高い順に並べ替える
+// 近傍顧客が購入していない候補やカート内の商品はスコア0とし、指定された順のまま末尾に並べる
+pub fn rerank_candidates(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
+    current_order: &OrderVector,
+    current_products: &[ProductItem],
+    product_dimensions: &ProductDimensions,
+    similarity: SimilarityMethod,
+    candidates: &[String],
+) -> Result<Vec<ProductSuggestion>, RecommendError> {
+    let candidate_ids: HashSet<String> = candidates.iter().cloned().collect();
+
+    // 候補はすべて返すため、候補数で件数を制限しない
+    let config = RecommendationConfig {
+        candidate_cap: candidate_ids.len(),
+        ..config.clone()
+    };
+    let neighbors = find_neighbors(
+        session,
+        &config,
+        current_order,
+        product_dimensions,
+        similarity,
+    )?;
+    let mut scored: HashMap<String, ProductSuggestion> = collect_similar_products(
+        session,
+        &config,
+        current_products,
+        product_dimensions,
+        neighbors,
+        Some(&candidate_ids),
+        None,
+    )?
+    .into_iter()
+    .map(|suggestion| (suggestion.product_id.clone(), suggestion))
+    .collect();
+
+    // 重複して指定された候補は最初の1件だけを残す
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut reranked: Vec<ProductSuggestion> = candidates
+        .iter()
+        .filter(|product_id| seen.insert(product_id.as_str()))
+        .map(|product_id| {
+            scored
+                .remove(product_id)
+                .unwrap_or_else(|| ProductSuggestion {
+                    product_id: product_id.clone(),
+                    score: 0.0,
+                    contributions: vec![],
+                })
+        })
+        .collect();
+
+    // 同じスコアの候補は指定された順を保つ（安定ソート）
+    reranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(reranked)
+}
+
+// 協調フィルタリングと共起のスコアをブレンドして推薦商品を取得
+// blend は 0.0 で協調フィルタリングのみ、1.0 で共起のみ
+//
+// 2つのスコアは尺度が異なるため、それぞれを min-max 正規化
+// ((score - min) / (max - min)) で0〜1に揃えてから
+// (1 - blend) * 協調 + blend * 共起 で商品ごとに合算する。
+// 片方にしか現れない商品は、もう片方のスコアを0として扱う。
+// 合算後のスコアは近傍ごとの寄与に分解できないため、内訳は空になる。
+#[allow(clippy::too_many_arguments)]
+pub fn get_blended_products(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
+    current_order: &OrderVector,
+    current_products: &[ProductItem],
+    product_dimensions: &ProductDimensions,
+    similarity: SimilarityMethod,
+    blend: f32,
+    filter: &SuggestionFilter,
+) -> Result<Vec<ProductSuggestion>, RecommendError> {
+    let neighbors = find_neighbors(
+        session,
+        config,
+        current_order,
+        product_dimensions,
+        similarity,
+    )?;
+    let collaborative_scores: HashMap<String, f32> = collect_similar_products(
+        session,
+        config,
+        current_products,
+        product_dimensions,
+        neighbors,
+        None,
+        Some(filter),
+    )?
+    .into_iter()
+    .map(|suggestion| (suggestion.product_id, suggestion.score))
+    .collect();
+
+    let cart_variant_ids: Vec<String> = current_products
+        .iter()
+        .map(|p| p.product_variant_id.clone())
+        .collect();
+    let cooccurrence_scores = session
+        .fetch_cooccurring_products(&cart_variant_ids, config.cooccurrence_half_life_days)?;
+
+    let suggestions = blend_scores(
+        &min_max_normalize(&collaborative_scores),
+        &min_max_normalize(&cooccurrence_scores),
+        blend,
+    )
+    .into_iter()
+    .map(|(product_id, score)| ProductSuggestion {
+        product_id,
+        score,
+        contributions: vec![],
+    })
+    .collect();
+
+    Ok(rank_suggestions(
+        apply_filter(session, suggestions, filter)?,
+        config.suggestion_limit,
+    ))
+}
+
+// 人気商品を推薦商品として取得（近傍から推薦できない場合の代替）
+// window_days を指定した場合は直近その日数の注文のみを集計する
+pub fn get_popular_products(
+    session: &mut dyn RecommendationSession,
+    config: &RecommendationConfig,
+    current_products: &[ProductItem],
+    window_days: Option<u32>,
+    filter: &SuggestionFilter,
+) -> Result<Vec<ProductSuggestion>, mysql::Error> {
+    // カート内の商品と除外対象の商品は推薦しない
+    let exclude_variant_ids: Vec<String> = current_products
+        .iter()
+        .map(|p| p.product_variant_id.clone())
+        .chain(filter.excluded_variant_ids.iter().cloned())
+        .collect();
+
+    let products = session.fetch_popular_products(
+        &exclude_variant_ids,
+        window_days,
+        filter.temperature,
+        &filter.excluded_categories,
+        config.suggestion_limit,
+    )?;
+
+    Ok(products
+        .into_iter()
+        .map(|(product_id, score)| ProductSuggestion {
+            product_id,
+            score,
+            contributions: vec![],
+        })
+        .collect())
+}
+
+// スコアを0〜1に min-max 正規化する
+// すべて同じ値の場合は差がないため、すべて1.0とする
+pub fn min_max_normalize(scores: &HashMap<String, f32>) -> HashMap<String, f32> {
+    let min = scores.values().copied().fold(f32::INFINITY, f32::min);
+    let max = scores.values().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+
+    scores
+        .iter()
+        .map(|(product_id, &score)| {
+            let normalized = if range > 0.0 {
+                (score - min) / range
+            } else {
+                1.0
+            };
+            (product_id.clone(), normalized)
+        })
+        .collect()
+}
+
+// 返す推薦スコアの尺度（?score_scale=raw / percent）
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreScale {
+    // 計算したスコアをそのまま返す
+    #[default]
+    Raw,
+    // 返す推薦商品の中で min-max 正規化し、0〜100にする（最上位が100）
+    Percent,
+}
+
+impl ScoreScale {
+    // スコアを尺度に合わせて書き換える（順位は変わらない）
+    // 1件だけの場合やすべて同じ値の場合は差がないため、すべて100とする
+    pub fn apply<'a>(self, scores: impl IntoIterator<Item = &'a mut f32>) {
+        if self == ScoreScale::Raw {
+            return;
+        }
+        let mut scores: Vec<&mut f32> = scores.into_iter().collect();
+        let min = scores.iter().map(|s| **s).fold(f32::INFINITY, f32::min);
+        let max = scores.iter().map(|s| **s).fold(f32::NEG_INFINITY, f32::max);
+        let range = max - min;
+        for score in scores.iter_mut() {
+            **score = if range > 0.0 {
+                (**score - min) / range * 100.0
+            } else {
+                100.0
+            };
         }
     }
-    println!("類似商品スコア: {:?}", product_scores);
-    // スコア順にソートして返す
-    let mut suggestions: Vec<(String, f32)> = product_scores.into_iter().collect();
+}
 
-    suggestions.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+// 正規化済みの2つのスコアを商品ごとに重み付けして合算する
+pub fn blend_scores(
+    collaborative: &HashMap<String, f32>,
+    cooccurrence: &HashMap<String, f32>,
+    blend: f32,
+) -> HashMap<String, f32> {
+    let mut blended: HashMap<String, f32> = HashMap::new();
 
-    // 上位5件に限定
-    suggestions.truncate(5);
+    // 重みが0の側は加えない（0や1では片方だけの推薦と同じ商品・順位にするため）
+    if blend < 1.0 {
+        for (product_id, &score) in collaborative {
+            *blended.entry(product_id.clone()).or_insert(0.0) += (1.0 - blend) * score;
+        }
+    }
+    if blend > 0.0 {
+        for (product_id, &score) in cooccurrence {
+            *blended.entry(product_id.clone()).or_insert(0.0) += blend * score;
+        }
+    }
 
-    suggestions
+    blended
 }
 
 pub fn cosine_similarity(vec1: &[f32], vec2: &[f32]) -> f32 {
@@ -226,24 +1085,258 @@ pub fn cosine_similarity(vec1: &[f32], vec2: &[f32]) -> f32 {
     }
 }
 
-pub fn combined_similarity(user1: &OrderVector, user2: &OrderVector, region_weight: f32) -> f32 {
-    let product_similarity = cosine_similarity(&user1.product_vector, &user2.product_vector);
-    let region_similarity = cosine_similarity(&user1.region_vector, &user2.region_vector);
+// 全顧客の商品ベクトルから商品（次元）ごとの平均を求める（購入していない顧客の0も平均に含める）
+// 顧客がいない場合は空のベクトルを返す
+pub fn item_means<V: AsRef<[f32]>>(product_vectors: impl Iterator<Item = V>) -> Vec<f32> {
+    let mut sums: Vec<f32> = Vec::new();
+    let mut count = 0usize;
+    for vector in product_vectors {
+        let vector = vector.as_ref();
+        if count == 0 {
+            sums = vec![0.0; vector.len()];
+        }
+        sums.iter_mut()
+            .zip(vector)
+            .for_each(|(sum, &value)| *sum += value);
+        count += 1;
+    }
+    sums.iter_mut().for_each(|sum| *sum /= count.max(1) as f32);
+    sums
+}
+
+// 商品ごとの平均を引いたベクトル同士のコサイン類似度（adjusted cosine）
+// 通常のコサイン類似度では共通の購入商品がない顧客同士は0になるが、人気商品の影響を差し引くことで
+// 「どちらも多くの人が買うものを買っていない」「どちらも大量に購入する」といった傾向の近さも反映される
+pub fn adjusted_cosine_similarity(vec1: &[f32], vec2: &[f32], item_means: &[f32]) -> f32 {
+    if vec1.len() != vec2.len() || vec1.len() != item_means.len() {
+        return 0.0;
+    }
 
-    // 重み付け合計
-    (1.0 - region_weight) * product_similarity + region_weight * region_similarity
+    let (mut dot_product, mut magnitude1, mut magnitude2) = (0.0f32, 0.0f32, 0.0f32);
+    for ((&a, &b), &mean) in vec1.iter().zip(vec2).zip(item_means) {
+        let (a, b) = (a - mean, b - mean);
+        dot_product += a * b;
+        magnitude1 += a * a;
+        magnitude2 += b * b;
+    }
+
+    if magnitude1 > 0.0 && magnitude2 > 0.0 {
+        dot_product / (magnitude1.sqrt() * magnitude2.sqrt())
+    } else {
+        0.0
+    }
 }
 
-// ユーザーの購入履歴を取得する関数
-async fn fetch_user_purchase_history(
-    pool: &Arc<mysql::Pool>,
-    product_dimensions: &ProductDimensions,
-) -> Result<Vec<OrderVector>, mysql::Error> {
-    let mut conn = pool.get_conn()?;
+// 地域ベクトルの作り方（エンコーディング）が同じであることを確認する
+// cosine_similarity は次元数が違うと0を返すため、異なるエンコーディングのベクトル（古い形式で保存したものなど）が
+// 混在すると地域類似度が黙って消えてしまう。その場合は計算を続けずにエラーを返す
+fn check_region_encoding(
+    user1: &OrderVector,
+    user2: &OrderVector,
+) -> Result<(), RegionEncodingMismatch> {
+    if user1.region_encoding == user2.region_encoding
+        && user1.region_vector.len() == user2.region_vector.len()
+    {
+        return Ok(());
+    }
+    Err(RegionEncodingMismatch {
+        versions: (user1.region_encoding, user2.region_encoding),
+        dimensions: (user1.region_vector.len(), user2.region_vector.len()),
+    })
+}
 
-    // ユーザーごとの地域情報と購入商品を取得
-    let rows = conn.exec_map(
-        "
+pub fn combined_similarity(
+    user1: &OrderVector,
+    user2: &OrderVector,
+    region_weight: f32,
+    region_mode: RegionSimilarity,
+    item_means: Option<&[f32]>,
+) -> Result<f32, RegionEncodingMismatch> {
+    let product_similarity = match item_means {
+        Some(item_means) => {
+            adjusted_cosine_similarity(&user1.product_vector, &user2.product_vector, item_means)
+        }
+        None => cosine_similarity(&user1.product_vector, &user2.product_vector),
+    };
+    let region_similarity = match region_mode {
+        RegionSimilarity::Cosine => {
+            check_region_encoding(user1, user2)?;
+            cosine_similarity(&user1.region_vector, &user2.region_vector)
+        }
+        RegionSimilarity::Adjacency => {
+            region::region_adjacency_similarity(user1.province, user2.province)
+        }
+    };
+
+    // 重み付け合計
+    Ok((1.0 - region_weight) * product_similarity + region_weight * region_similarity)
+}
+
+// 2つのカートの類似度とその内訳（商品・地域それぞれのコサイン類似度）
+pub struct SimilarityBreakdown {
+    pub combined: f32,
+    pub product: f32,
+    pub region: f32,
+}
+
+// 2つのカートを推薦と同じ方法（blended、地域はコサイン類似度）で比較し、内訳とともに返す
+pub fn similarity_breakdown(
+    user1: &OrderVector,
+    user2: &OrderVector,
+    region_weight: f32,
+) -> Result<SimilarityBreakdown, RegionEncodingMismatch> {
+    Ok(SimilarityBreakdown {
+        combined: combined_similarity(user1, user2, region_weight, RegionSimilarity::Cosine, None)?,
+        product: cosine_similarity(&user1.product_vector, &user2.product_vector),
+        region: cosine_similarity(&user1.region_vector, &user2.region_vector),
+    })
+}
+
+// 地域ベクトルを sqrt(region_weight)、商品ベクトルを sqrt(1 - region_weight) 倍して連結する
+// 各ベクトルは正規化しないため、combined_similarity と違い地域と商品の大きさの差も類似度に影響する
+pub fn create_unified_vector(user: &OrderVector, region_weight: f32) -> Vec<f32> {
+    let region_scale = region_weight.sqrt();
+    let product_scale = (1.0 - region_weight).sqrt();
+
+    user.region_vector
+        .iter()
+        .map(|&value| value * region_scale)
+        .chain(
+            user.product_vector
+                .iter()
+                .map(|&value| value * product_scale),
+        )
+        .collect()
+}
+
+// 連結したベクトル同士のコサイン類似度
+pub fn unified_similarity(
+    user1: &OrderVector,
+    user2: &OrderVector,
+    region_weight: f32,
+) -> Result<f32, RegionEncodingMismatch> {
+    check_region_encoding(user1, user2)?;
+    Ok(cosine_similarity(
+        &create_unified_vector(user1, region_weight),
+        &create_unified_vector(user2, region_weight),
+    ))
+}
+
+// 推薦のたびに読み込む購入履歴（事前計算済みのベクトル）の行数の上限
+const PURCHASE_HISTORY_LIMIT: usize = 10000;
+
+// ユーザーの購入履歴を取得する関数
+// 顧客IDとその購入ベクトルの組を返す
+// 購入した有効な商品が min_items 種類未満の顧客は含めない（購入の少ない顧客は類似度のばらつきが大きいため）
+// include_orderless の場合は注文のない顧客も地域だけのベクトルで含める（min_items が2以上の場合は含まれない）
+// use_cached_vectors は customer_vectors_are_fresh の結果（商品の次元を更新するときに確認しておく）
+pub fn fetch_user_purchase_history(
+    conn: &mut mysql::PooledConn,
+    product_dimensions: &ProductDimensions,
+    encoding: VectorEncoding,
+    min_items: usize,
+    include_orderless: bool,
+    use_cached_vectors: bool,
+) -> Result<Vec<(String, OrderVector)>, mysql::Error> {
+    // 最新の事前計算済みベクトルがあればそちらを使う
+    // customer_vectors テーブルには注文のある顧客しかないため、注文のない顧客を含める場合は使わない
+    if use_cached_vectors && !include_orderless {
+        match fetch_cached_user_vectors(conn, product_dimensions, encoding) {
+            Ok(Some(mut user_vectors)) => {
+                user_vectors.retain(|(_, order)| has_min_items(order, min_items));
+                return Ok(user_vectors);
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!(error = %err, "事前計算済みの顧客ベクトルを取得できません"),
+        }
+    }
+
+    // ユーザーごとの地域情報と購入商品を取得
+    let customer_products = fetch_customer_purchases(
+        conn,
+        Some(PURCHASE_HISTORY_LIMIT),
+        min_items,
+        include_orderless,
+    )?;
+
+    // 各ユーザーのベクトルを作成
+    let user_vectors: Vec<(String, OrderVector)> = customer_products
+        .into_iter()
+        .map(|(customer_id, (province_code, products))| {
+            let order_vector =
+                create_order_vector(&province_code, &products, product_dimensions, encoding);
+            (customer_id, order_vector)
+        })
+        .collect();
+
+    Ok(user_vectors)
+}
+
+// 購入した商品（ベクトルの0でない次元）が min_items 種類以上あるか
+pub fn has_min_items(order: &OrderVector, min_items: usize) -> bool {
+    min_items <= 1
+        || order
+            .product_vector
+            .iter()
+            .filter(|&&value| value != 0.0)
+            .count()
+            >= min_items
+}
+
+// 購入した商品（ベクトルの0でない次元）があるか（注文のない顧客は地域だけのベクトルになる）
+pub fn has_products(order: &OrderVector) -> bool {
+    order.product_vector.iter().any(|&value| value != 0.0)
+}
+
+// 顧客ごとの地域コードと購入商品を取得する関数
+// limit を指定した場合は購入明細の取得件数を制限する
+// min_items が2以上の場合は、有効な商品を min_items 種類以上購入した顧客だけを取得する
+//
+// 販売停止中の商品は次元に含まれないため、SQLの時点で除外しておく。
+// これにより products_to_vector で次元外の商品が黙って捨てられることがなく、
+// ベクトルは常に有効な商品の数量だけから作られる。
+// 数量の変換と正規化は VectorEncoding で選ぶ（既定は変換・正規化なし）。
+// blended モデルでは大きさの違いは cosine_similarity で比較時に打ち消される。
+//
+// include_orderless の場合は注文を LEFT JOIN し、注文のない顧客も購入商品なしで取得する
+// （注文はあるが有効な商品を購入していない顧客は、これまでどおり含めない）
+pub fn fetch_customer_purchases(
+    conn: &mut mysql::PooledConn,
+    limit: Option<usize>,
+    min_items: usize,
+    include_orderless: bool,
+) -> Result<HashMap<String, (String, Vec<ProductItem>)>, mysql::Error> {
+    let limit_clause = match limit {
+        Some(limit) => format!("LIMIT {}", limit),
+        None => String::new(),
+    };
+    // 購入商品の種類数を顧客ごとに集計し、HAVING で足りない顧客を除く
+    let (min_items_clause, params): (&str, Vec<mysql::Value>) = if min_items > 1 {
+        (
+            "
+                AND c.id IN (
+                  SELECT o2.customer_id
+                  FROM orders o2
+                  JOIN order_products op2 ON o2.id = op2.order_id
+                  JOIN products p2 ON p2.variant_id = op2.variant_id
+                  WHERE p2.is_suspension = false AND o2.customer_id IS NOT NULL
+                  GROUP BY o2.customer_id
+                  HAVING COUNT(DISTINCT op2.variant_id) >= ?
+                )",
+            vec![min_items.into()],
+        )
+    } else {
+        ("", vec![])
+    };
+    let (join, suspension_condition) = if include_orderless {
+        ("LEFT JOIN", "(o.id IS NULL OR p.is_suspension = false)")
+    } else {
+        ("JOIN", "p.is_suspension = false")
+    };
+    let rows = db::timed("fetch_customer_purchases", || {
+        conn.exec_map(
+            format!(
+                "
               SELECT 
                 c.id,
                 c.shipping_province_code,
@@ -251,48 +1344,1264 @@ async fn fetch_user_purchase_history(
                 op.quantity
               FROM
                 customers c
-              JOIN
+              {join}
                 orders o ON c.id = o.customer_id
-              JOIN
+              {join}
                 order_products op ON o.id = op.order_id
-              LIMIT 10000
-              ",
-        (),
-        |row: mysql::Row| {
-            let customer_id: String = row.get("id").unwrap_or_default();
+              {join}
+                products p ON p.variant_id = op.variant_id
+              WHERE
+                {suspension_condition}{min_items_clause}
+              {limit_clause}
+              "
+            ),
+            params,
+            |row: mysql::Row| {
+                let customer_id: String = row.get("id").unwrap_or_default();
 
-            let province_code: String = row.get("shipping_province_code").unwrap_or_default();
+                let province_code: String = row.get("shipping_province_code").unwrap_or_default();
 
-            let variant_id: i64 = row.get("variant_id").unwrap_or(0);
-            let variant_id_str = variant_id.to_string();
+                let variant_id_str = db::read_variant_id(&row, "variant_id");
 
-            let quantity: u32 = row.get("quantity").unwrap_or_default();
+                let quantity: u32 = row.get("quantity").unwrap_or_default();
 
-            (customer_id, province_code, variant_id_str, quantity)
-        },
-    )?;
+                // 注文のない顧客の行は商品の列が NULL になる
+                let product = (!variant_id_str.is_empty()).then_some((variant_id_str, quantity));
 
-    // customer IDごとにグループ化
+                (customer_id, province_code, product)
+            },
+        )
+    })?;
+
+    Ok(group_customer_purchases(rows))
+}
+
+// 顧客ID・地域コード・購入商品（注文のない顧客は None）の行を顧客IDごとにまとめる
+pub fn group_customer_purchases(
+    rows: impl IntoIterator<Item = (String, String, Option<(String, u32)>)>,
+) -> HashMap<String, (String, Vec<ProductItem>)> {
     let mut customer_products: HashMap<String, (String, Vec<ProductItem>)> = HashMap::new();
 
-    for (customer_id, province_code, product_variant_id, quantity) in rows {
+    for (customer_id, province_code, product) in rows {
         let entry = customer_products
             .entry(customer_id)
             .or_insert_with(|| (province_code, Vec::new()));
 
-        entry.1.push(ProductItem {
-            product_variant_id, // 変数名も変更
-            quantity,
-        });
+        if let Some((product_variant_id, quantity)) = product {
+            entry.1.push(ProductItem {
+                product_variant_id,
+                quantity,
+                weight: None,
+            });
+        }
     }
 
-    // 各ユーザーのベクトルを作成
-    let user_vectors: Vec<OrderVector> = customer_products
+    customer_products
+}
+
+// 購入商品を商品IDごとの値を持つ疎ベクトルに変換する関数
+// products_to_vector と同じく、同じ商品が複数あれば後の値で上書きする
+pub fn products_to_sparse_vector(products: &[ProductItem]) -> BTreeMap<String, f32> {
+    let mut vector = BTreeMap::new();
+    for product in products {
+        vector.insert(
+            product.product_variant_id.clone(),
+            product.weight.unwrap_or(product.quantity as f32),
+        );
+    }
+    vector
+}
+
+// customer_vectors テーブルがあり、注文の最終更新以降に作られたベクトルだけであるか
+// リクエストごとに問い合わせないよう、起動時と商品の次元の更新時にだけ確認する
+pub fn customer_vectors_are_fresh(conn: &mut mysql::PooledConn) -> Result<bool, mysql::Error> {
+    let table_exists: Option<u64> = db::timed("customer_vectors_exists", || {
+        conn.query_first(
+            "
+          SELECT COUNT(*)
+          FROM information_schema.tables
+          WHERE table_schema = DATABASE() AND table_name = 'customer_vectors'
+          ",
+        )
+    })?;
+    if table_exists.unwrap_or(0) == 0 {
+        return Ok(false);
+    }
+
+    // 最も古いベクトルが注文の最終更新日時以降に作られていれば最新とみなす
+    let is_fresh: Option<Option<bool>> = db::timed("customer_vectors_freshness", || {
+        conn.query_first(
+            "
+          SELECT
+            (SELECT MIN(updated_at) FROM customer_vectors)
+              >= (SELECT MAX(updated_at) FROM orders)
+          ",
+        )
+    })?;
+    Ok(is_fresh.flatten() == Some(true))
+}
+
+// customer_vectors テーブルから事前計算済みのベクトルを取得する関数
+// 不正なベクトルがある場合は None を返す（最新かどうかは customer_vectors_are_fresh で確認済みとする）
+fn fetch_cached_user_vectors(
+    conn: &mut mysql::PooledConn,
+    product_dimensions: &ProductDimensions,
+    encoding: VectorEncoding,
+) -> Result<Option<Vec<(String, OrderVector)>>, mysql::Error> {
+    // 購入履歴から計算する場合と同じ件数を上限にする
+    let rows: Vec<(String, String, String)> = db::timed("fetch_cached_user_vectors", || {
+        conn.exec(
+            "SELECT customer_id, province_code, vector FROM customer_vectors LIMIT ?",
+            (PURCHASE_HISTORY_LIMIT,),
+        )
+    })?;
+
+    let mut user_vectors = Vec::with_capacity(rows.len());
+    for (customer_id, province_code, vector) in rows {
+        match decode_cached_vector(&province_code, &vector, product_dimensions, encoding) {
+            Ok(order_vector) => user_vectors.push((customer_id, order_vector)),
+            Err(err) => {
+                tracing::warn!(
+                    customer_id = %customer_id,
+                    error = %err,
+                    "事前計算済みのベクトルが不正です"
+                );
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(Some(user_vectors))
+}
+
+// 購入商品を customer_vectors テーブルの vector 列の値（商品IDから数量へのJSON）にする
+pub fn encode_cached_vector(products: &[ProductItem]) -> String {
+    serde_json::to_string(&products_to_sparse_vector(products))
+        .expect("ベクトルのシリアライズに失敗")
+}
+
+// customer_vectors テーブルの vector 列の値から、購入履歴から計算する場合と同じベクトルを作る
+pub fn decode_cached_vector(
+    province_code: &str,
+    vector: &str,
+    product_dimensions: &ProductDimensions,
+    encoding: VectorEncoding,
+) -> Result<OrderVector, serde_json::Error> {
+    let sparse: BTreeMap<String, f32> = serde_json::from_str(vector)?;
+    let products: Vec<ProductItem> = sparse
         .into_iter()
-        .map(|(_, (province_code, products))| {
-            create_order_vector(&province_code, &products, product_dimensions)
+        .map(|(product_variant_id, value)| ProductItem {
+            product_variant_id,
+            quantity: 0,
+            weight: Some(value),
         })
         .collect();
+    Ok(create_order_vector(
+        province_code,
+        &products,
+        product_dimensions,
+        encoding,
+    ))
+}
 
-    Ok(user_vectors)
+// IN句のプレースホルダ (?, ?, ...) を作成
+fn in_placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+// 1回の問い合わせでIN句に渡すIDの上限
+// 近傍顧客や商品が多い場合に、プレースホルダの数や max_allowed_packet を超えないようにする
+pub const IN_CHUNK_SIZE: usize = 1000;
+
+// IDを IN_CHUNK_SIZE 件ずつに分けて問い合わせ、結果をまとめる
+// 各IDはいずれか1回の問い合わせにだけ含まれるため、結果は連結するだけでよい
+pub fn query_in_chunks<T, C: Default + Extend<T>>(
+    ids: &[String],
+    mut query: impl FnMut(&[String]) -> Result<Vec<T>, mysql::Error>,
+) -> Result<C, mysql::Error> {
+    let mut merged = C::default();
+    for chunk in ids.chunks(IN_CHUNK_SIZE) {
+        merged.extend(query(chunk)?);
+    }
+    Ok(merged)
+}
+
+// 指定した顧客の購入商品をまとめて取得する関数
+// 顧客IDごとに購入商品のリストを返す
+pub fn fetch_user_products(
+    conn: &mut mysql::PooledConn,
+    customer_ids: &[String],
+) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error> {
+    // 空のまま `IN ()` を組み立てると構文エラーになるため、問い合わせずに空の結果を返す
+    if customer_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows: Vec<(String, String, u32)> = query_in_chunks(customer_ids, |chunk| {
+        let query = format!(
+            "
+              SELECT
+                o.customer_id,
+                op.variant_id,
+                op.quantity
+              FROM
+                orders o
+              JOIN
+                order_products op ON o.id = op.order_id
+              WHERE
+                o.customer_id IN ({})
+              ",
+            in_placeholders(chunk.len())
+        );
+
+        db::timed("fetch_user_products", || {
+            conn.exec_map(query, chunk.to_vec(), |row: mysql::Row| {
+                let customer_id: String = row.get("customer_id").unwrap_or_default();
+
+                let variant_id = db::read_variant_id(&row, "variant_id");
+
+                let quantity: u32 = row.get("quantity").unwrap_or_default();
+
+                (customer_id, variant_id, quantity)
+            })
+        })
+    })?;
+
+    // customer IDごとにグループ化
+    let mut customer_products: HashMap<String, Vec<ProductItem>> = HashMap::new();
+
+    for (customer_id, product_variant_id, quantity) in rows {
+        customer_products
+            .entry(customer_id)
+            .or_default()
+            .push(ProductItem {
+                product_variant_id,
+                quantity,
+                weight: None,
+            });
+    }
+
+    Ok(customer_products)
+}
+
+// カート内の商品と同じ注文で購入された商品を共起回数とともに取得する関数
+// カート内の商品自体と販売停止中の商品は含めない
+//
+// half_life_days を指定した場合は、共起した注文ごとに経過日数に応じた減衰
+// 0.5 ^ (経過日数 / half_life_days) を掛けて合計する（未指定時は注文数をそのまま数える）
+pub fn fetch_cooccurring_products(
+    conn: &mut mysql::PooledConn,
+    variant_ids: &[String],
+    half_life_days: Option<f32>,
+) -> Result<HashMap<String, f32>, mysql::Error> {
+    // 空のまま `IN ()` を組み立てると構文エラーになるため、問い合わせずに空の結果を返す
+    if variant_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut params: Vec<mysql::Value> = Vec::new();
+    let weight = match half_life_days {
+        Some(half_life_days) => {
+            params.push(half_life_days.into());
+            "POW(0.5, GREATEST(DATEDIFF(NOW(), created_at), 0) / ?)"
+        }
+        None => "1",
+    };
+
+    // カート内の複数の商品と共起した注文を重複して数えないよう、注文と商品の組を先に一意にする
+    let query = format!(
+        "
+              SELECT
+                variant_id,
+                SUM({weight}) AS cooccurrence
+              FROM (
+                SELECT DISTINCT
+                  op2.order_id,
+                  op2.variant_id,
+                  o.created_at
+                FROM
+                  order_products op1
+                JOIN
+                  order_products op2 ON op1.order_id = op2.order_id
+                JOIN
+                  orders o ON o.id = op2.order_id
+                JOIN
+                  products p ON p.variant_id = op2.variant_id
+                WHERE
+                  op1.variant_id IN ({placeholders})
+                  AND op2.variant_id NOT IN ({placeholders})
+                  AND p.is_suspension = false
+              ) pairs
+              GROUP BY
+                variant_id
+              ",
+        placeholders = in_placeholders(variant_ids.len())
+    );
+
+    // IN句とNOT IN句で同じ商品IDを2回渡す
+    params.extend(
+        variant_ids
+            .iter()
+            .chain(variant_ids)
+            .map(|id| id.as_str().into()),
+    );
+
+    let rows = db::timed("fetch_cooccurring_products", || {
+        conn.exec_map(query, params, |row: mysql::Row| {
+            let variant_id = db::read_variant_id(&row, "variant_id");
+
+            let cooccurrence: f64 = row.get("cooccurrence").unwrap_or_default();
+
+            (variant_id, cooccurrence as f32)
+        })
+    })?;
+
+    Ok(rows.into_iter().collect())
+}
+
+// 指定した商品のうち、指定した温度帯の商品IDを取得する関数
+pub fn fetch_variants_with_temperature(
+    conn: &mut mysql::PooledConn,
+    variant_ids: &[String],
+    temperature: Temperature,
+) -> Result<HashSet<String>, mysql::Error> {
+    if variant_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    query_in_chunks(variant_ids, |chunk| {
+        let query = format!(
+            "SELECT variant_id FROM products WHERE shipping_temperature = ? AND variant_id IN ({})",
+            in_placeholders(chunk.len())
+        );
+        let mut params: Vec<mysql::Value> = vec![temperature.as_str().into()];
+        params.extend(chunk.iter().map(|id| id.as_str().into()));
+
+        db::timed("fetch_variants_with_temperature", || {
+            conn.exec_map(query, params, |row: mysql::Row| {
+                db::read_variant_id(&row, "variant_id")
+            })
+        })
+    })
+}
+
+// 指定した商品のうち、指定したカテゴリのいずれかに属する商品IDを取得する関数
+pub fn fetch_variants_in_categories(
+    conn: &mut mysql::PooledConn,
+    variant_ids: &[String],
+    categories: &[String],
+) -> Result<HashSet<String>, mysql::Error> {
+    if variant_ids.is_empty() || categories.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    // カテゴリの数は少ないため、分割するのは商品IDだけ
+    query_in_chunks(variant_ids, |chunk| {
+        let query = format!(
+            "SELECT variant_id FROM products WHERE category IN ({}) AND variant_id IN ({})",
+            in_placeholders(categories.len()),
+            in_placeholders(chunk.len())
+        );
+        let params: Vec<mysql::Value> = categories
+            .iter()
+            .chain(chunk)
+            .map(|value| value.as_str().into())
+            .collect();
+
+        db::timed("fetch_variants_in_categories", || {
+            conn.exec_map(query, params, |row: mysql::Row| {
+                db::read_variant_id(&row, "variant_id")
+            })
+        })
+    })
+}
+
+// 商品カテゴリの一覧を取得する関数
+// category 列がない（カテゴリを割り当てていない）場合は空を返す
+pub fn fetch_product_categories(
+    conn: &mut mysql::PooledConn,
+) -> Result<HashSet<String>, mysql::Error> {
+    let has_column: Option<u64> = db::timed("products_category_exists", || {
+        conn.query_first(
+            "SELECT COUNT(*) FROM information_schema.columns
+             WHERE table_schema = DATABASE() AND table_name = 'products' AND column_name = 'category'",
+        )
+    })?;
+    if has_column.unwrap_or(0) == 0 {
+        return Ok(HashSet::new());
+    }
+
+    let categories: Vec<String> = db::timed("fetch_product_categories", || {
+        conn.query("SELECT DISTINCT category FROM products")
+    })?;
+    Ok(categories.into_iter().collect())
+}
+
+// 販売数量の多い順に人気商品を取得する関数
+// カート内の商品・除外カテゴリの商品・販売停止中の商品は含めない
+pub fn fetch_popular_products(
+    conn: &mut mysql::PooledConn,
+    exclude_variant_ids: &[String],
+    window_days: Option<u32>,
+    temperature: Option<Temperature>,
+    excluded_categories: &[String],
+    limit: usize,
+) -> Result<Vec<(String, f32)>, mysql::Error> {
+    let mut conditions = vec!["p.is_suspension = false".to_string()];
+    let mut params: Vec<mysql::Value> = Vec::new();
+
+    // 期間指定がない場合は全期間を集計
+    if let Some(days) = window_days {
+        conditions.push("o.created_at >= NOW() - INTERVAL ? DAY".to_string());
+        params.push(days.into());
+    }
+    if let Some(temperature) = temperature {
+        conditions.push("p.shipping_temperature = ?".to_string());
+        params.push(temperature.as_str().into());
+    }
+    if !excluded_categories.is_empty() {
+        conditions.push(format!(
+            "p.category NOT IN ({})",
+            in_placeholders(excluded_categories.len())
+        ));
+        params.extend(excluded_categories.iter().map(|c| c.as_str().into()));
+    }
+    if !exclude_variant_ids.is_empty() {
+        conditions.push(format!(
+            "op.variant_id NOT IN ({})",
+            in_placeholders(exclude_variant_ids.len())
+        ));
+        params.extend(exclude_variant_ids.iter().map(|id| id.as_str().into()));
+    }
+    params.push(limit.into());
+
+    let query = format!(
+        "
+              SELECT
+                op.variant_id,
+                SUM(op.quantity) AS total_quantity
+              FROM
+                order_products op
+              JOIN
+                orders o ON o.id = op.order_id
+              JOIN
+                products p ON p.variant_id = op.variant_id
+              WHERE
+                {}
+              GROUP BY
+                op.variant_id
+              ORDER BY
+                total_quantity DESC,
+                op.variant_id
+              LIMIT ?
+              ",
+        conditions.join(" AND ")
+    );
+
+    db::timed("fetch_popular_products", || {
+        conn.exec_map(query, params, |row: mysql::Row| {
+            let variant_id = db::read_variant_id(&row, "variant_id");
+
+            let total_quantity: f64 = row.get("total_quantity").unwrap_or_default();
+
+            (variant_id, total_quantity as f32)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn order_vector(region_vector: Vec<f32>, product_vector: Vec<f32>) -> OrderVector {
+        OrderVector {
+            region_vector,
+            product_vector,
+            province: None,
+            region_encoding: REGION_ENCODING_VERSION,
+        }
+    }
+
+    #[test]
+    fn cosine_similarity_of_orthogonal_vectors_is_zero() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn cosine_similarity_of_identical_vectors_is_one() {
+        let v = [1.0, 2.0, 3.0];
+        assert!((cosine_similarity(&v, &v) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cosine_similarity_of_mismatched_lengths_is_zero() {
+        assert_eq!(cosine_similarity(&[1.0, 2.0], &[1.0, 2.0, 3.0]), 0.0);
+    }
+
+    #[test]
+    fn cosine_similarity_with_zero_vector_is_zero() {
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 2.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn mean_center_reduces_similarity_of_high_volume_buyer() {
+        // 少量のカートと、同じ商品を大量に買いつつ他の商品も買う顧客
+        let cart = [2.0, 1.0, 0.0];
+        let heavy_buyer = [30.0, 30.0, 2.0];
+
+        let raw = cosine_similarity(&cart, &heavy_buyer);
+        let centered = cosine_similarity(&mean_center(&cart), &mean_center(&heavy_buyer));
+        assert!(centered < raw, "{} >= {}", centered, raw);
+    }
+
+    #[test]
+    fn adjusted_cosine_similarity_matches_hand_computed_example() {
+        // 商品ごとの平均は [4/3, 4/3]
+        let users = [vec![3.0, 0.0], vec![0.0, 3.0], vec![1.0, 1.0]];
+        let means = item_means(users.iter());
+        assert!((means[0] - 4.0 / 3.0).abs() < 1e-6);
+        assert!((means[1] - 4.0 / 3.0).abs() < 1e-6);
+
+        // 平均を引くと [5/3, -4/3] と [-4/3, 5/3]、内積 -40/9、各ノルムの2乗 41/9
+        let similarity = adjusted_cosine_similarity(&users[0], &users[1], &means);
+        assert!((similarity - (-40.0 / 41.0)).abs() < 1e-6, "{}", similarity);
+        // 通常のコサイン類似度では共通の購入商品がないため0
+        assert_eq!(cosine_similarity(&users[0], &users[1]), 0.0);
+    }
+
+    #[test]
+    fn one_item_customer_is_excluded_when_min_items_is_raised() {
+        let one_item = order_vector(vec![1.0], vec![0.0, 5.0, 0.0]);
+        let two_items = order_vector(vec![1.0], vec![1.0, 0.0, 2.0]);
+
+        assert!(has_min_items(&one_item, 1));
+        assert!(!has_min_items(&one_item, 2));
+        assert!(has_min_items(&two_items, 2));
+        assert!(!has_min_items(&two_items, 3));
+    }
+
+    fn suggestion(product_id: &str, score: f32) -> ProductSuggestion {
+        ProductSuggestion {
+            product_id: product_id.to_string(),
+            score,
+            contributions: vec![],
+        }
+    }
+
+    #[test]
+    fn tied_scores_are_ordered_by_id() {
+        let ranked = rank_suggestions(
+            vec![
+                suggestion("c", 1.0),
+                suggestion("a", 1.0),
+                suggestion("z", 2.0),
+                suggestion("b", 1.0),
+            ],
+            3,
+        );
+        let ids: Vec<&str> = ranked.iter().map(|s| s.product_id.as_str()).collect();
+        assert_eq!(ids, ["z", "a", "b"]);
+
+        let customers = ["u3", "u1", "u2"].map(|customer_id| CustomerScore {
+            customer_id: customer_id.to_string(),
+            score: 0.5,
+        });
+        let top: Vec<String> = select_top(customers, 2)
+            .into_iter()
+            .map(|c| c.customer_id)
+            .collect();
+        assert_eq!(top, ["u1", "u2"]);
+    }
+
+    #[test]
+    fn combined_similarity_weight_extremes() {
+        // 商品は同一、地域は直交
+        let a = order_vector(vec![1.0, 0.0], vec![1.0, 2.0]);
+        let b = order_vector(vec![0.0, 1.0], vec![1.0, 2.0]);
+
+        // 地域の重みが0なら商品の類似度のみ
+        let product_only =
+            combined_similarity(&a, &b, 0.0, RegionSimilarity::Cosine, None).unwrap();
+        assert!((product_only - 1.0).abs() < 1e-6);
+
+        // 地域の重みが1なら地域の類似度のみ
+        let region_only = combined_similarity(&a, &b, 1.0, RegionSimilarity::Cosine, None).unwrap();
+        assert!(region_only.abs() < 1e-6);
+    }
+
+    #[test]
+    fn combined_similarity_rejects_mismatched_region_dimensions() {
+        // 1次元の地域ベクトルと、47次元（one-hot）の地域ベクトル
+        let scalar = order_vector(vec![13.0], vec![1.0, 2.0]);
+        let mut one_hot = vec![0.0; 47];
+        one_hot[12] = 1.0;
+        let one_hot = order_vector(one_hot, vec![1.0, 2.0]);
+
+        let mismatch = combined_similarity(&scalar, &one_hot, 0.5, RegionSimilarity::Cosine, None)
+            .unwrap_err();
+        assert_eq!(mismatch.dimensions, (1, 47));
+        assert!(unified_similarity(&scalar, &one_hot, 0.5).is_err());
+    }
+
+    #[test]
+    fn similarity_rejects_mismatched_region_encoding_versions() {
+        // 次元数は同じでも、作り方の版が違えば比較しない
+        let current = order_vector(vec![1.0, 0.0], vec![1.0, 2.0]);
+        let stale = OrderVector {
+            region_encoding: REGION_ENCODING_VERSION + 1,
+            ..current.clone()
+        };
+
+        let mismatch =
+            combined_similarity(&current, &stale, 0.5, RegionSimilarity::Cosine, None).unwrap_err();
+        assert_eq!(
+            mismatch.versions,
+            (REGION_ENCODING_VERSION, REGION_ENCODING_VERSION + 1)
+        );
+        assert!(similarity_breakdown(&current, &stale, 0.5).is_err());
+        assert!(
+            SimilarityMethod::default()
+                .similarity(&current, &stale, 0.5, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn unified_similarity_differs_from_blended_when_magnitudes_differ() {
+        // 地域は同じ、商品は同じ向きで大きさだけが違う
+        let a = order_vector(vec![13.0], vec![1.0, 1.0]);
+        let b = order_vector(vec![13.0], vec![10.0, 10.0]);
+
+        // 別々に求めると地域・商品ともに1
+        let blended = combined_similarity(&a, &b, 0.5, RegionSimilarity::Cosine, None).unwrap();
+        assert!((blended - 1.0).abs() < 1e-6);
+
+        // 連結すると商品の大きさの差で1より小さくなる
+        let unified = unified_similarity(&a, &b, 0.5).unwrap();
+        assert!(unified < blended, "{} >= {}", unified, blended);
+
+        // 地域の重みが0なら商品ベクトルだけのコサイン類似度と一致する
+        let product_only = unified_similarity(&a, &b, 0.0).unwrap();
+        assert!((product_only - 1.0).abs() < 1e-6);
+    }
+
+    // ベクトルの大きさ（ユークリッドノルム）
+    fn norm(vector: &[f32]) -> f32 {
+        vector.iter().map(|&x| x * x).sum::<f32>().sqrt()
+    }
+
+    #[test]
+    fn fractional_weights_override_quantities_in_the_vector() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into()]);
+        let products = [
+            ProductItem {
+                product_variant_id: "a".into(),
+                quantity: 3,
+                weight: Some(0.5),
+            },
+            ProductItem {
+                product_variant_id: "b".into(),
+                quantity: 2,
+                weight: None,
+            },
+        ];
+
+        let vector =
+            products_to_vector(&products, &dimensions, normalized(NormalizationMode::None));
+
+        assert_eq!(vector, [0.5, 2.0]);
+    }
+
+    #[test]
+    fn region_vector_is_one_hot_per_province() {
+        let tokyo = region_to_vector("JP-13");
+        assert_eq!(tokyo.len(), region::PROVINCE_COUNT);
+        assert_eq!(tokyo[12], 1.0);
+        assert!((norm(&tokyo) - 1.0).abs() < 1e-6);
+
+        // どの都道府県も同じ次元数・同じ大きさ（番号の大小で偏らない）
+        let okinawa = region_to_vector("JP-47");
+        assert_eq!(okinawa.len(), tokyo.len());
+        assert_eq!(norm(&okinawa), norm(&tokyo));
+
+        assert_eq!(region_to_vector("JP-99"), vec![0.0; region::PROVINCE_COUNT]);
+    }
+
+    #[test]
+    fn unified_vector_splits_its_norm_by_region_weight() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into(), "c".into()]);
+        let products = [ProductItem {
+            product_variant_id: "b".into(),
+            quantity: 5,
+            weight: None,
+        }];
+        let order = create_order_vector(
+            "JP-13",
+            &products,
+            &dimensions,
+            normalized(NormalizationMode::L2),
+        );
+
+        // L2正規化した商品部分と地域部分の大きさがそろうため、重みの比率どおりになる
+        let unified = create_unified_vector(&order, 0.3);
+        assert_eq!(
+            unified.len(),
+            region::PROVINCE_COUNT + dimensions.get_dimension()
+        );
+        let (region_part, product_part) = unified.split_at(region::PROVINCE_COUNT);
+        assert!((norm(region_part).powi(2) - 0.3).abs() < 1e-6);
+        assert!((norm(product_part).powi(2) - 0.7).abs() < 1e-6);
+    }
+
+    // 変換なしで指定した方法で正規化するベクトルの作り方
+    fn normalized(normalization: NormalizationMode) -> VectorEncoding {
+        VectorEncoding {
+            normalization,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn products_to_vector_applies_normalization_mode() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into(), "c".into()]);
+        let products = [
+            ProductItem {
+                product_variant_id: "a".into(),
+                quantity: 3,
+                weight: None,
+            },
+            ProductItem {
+                product_variant_id: "b".into(),
+                quantity: 4,
+                weight: None,
+            },
+        ];
+
+        let raw = products_to_vector(&products, &dimensions, normalized(NormalizationMode::None));
+        assert_eq!(raw, vec![3.0, 4.0, 0.0]);
+
+        let l2 = products_to_vector(&products, &dimensions, normalized(NormalizationMode::L2));
+        let l2_norm = l2.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((l2_norm - 1.0).abs() < 1e-6);
+
+        let l1 = products_to_vector(&products, &dimensions, normalized(NormalizationMode::L1));
+        let l1_norm = l1.iter().map(|x| x.abs()).sum::<f32>();
+        assert!((l1_norm - 1.0).abs() < 1e-6);
+
+        // 空のカートはどの方法でもゼロベクトルのまま
+        let empty = products_to_vector(&[], &dimensions, normalized(NormalizationMode::L2));
+        assert_eq!(empty, vec![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn quantity_transform_dampens_large_quantities() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into()]);
+        // 1個だけの商品と、大量（100個）に購入した商品
+        let products = [
+            ProductItem {
+                product_variant_id: "a".into(),
+                quantity: 1,
+                weight: None,
+            },
+            ProductItem {
+                product_variant_id: "b".into(),
+                quantity: 100,
+                weight: None,
+            },
+        ];
+        let ratio = |transform| {
+            let encoding = VectorEncoding {
+                transform,
+                normalization: NormalizationMode::L2,
+            };
+            let vector = products_to_vector(&products, &dimensions, encoding);
+            vector[1] / vector[0]
+        };
+
+        // 変換なしでは数量の比のまま、平方根では10倍、ln(1 + x) ではさらに差が縮まる
+        let identity = ratio(QuantityTransform::Identity);
+        let sqrt = ratio(QuantityTransform::Sqrt);
+        let log1p = ratio(QuantityTransform::Log1p);
+        assert!((identity - 100.0).abs() < 1e-3);
+        assert!((sqrt - 10.0).abs() < 1e-4);
+        assert!((log1p - 101f32.ln() / 2f32.ln()).abs() < 1e-4);
+        assert!(log1p < sqrt && sqrt < identity);
+    }
+
+    #[test]
+    fn binary_presence_ignores_quantities() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into(), "c".into()]);
+        let cart = |quantities: [u32; 2]| {
+            ["a", "b"]
+                .into_iter()
+                .zip(quantities)
+                .map(|(id, quantity)| ProductItem {
+                    product_variant_id: id.into(),
+                    quantity,
+                    weight: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        // 同じ商品の組を異なる数量で購入した2人
+        let user1 = cart([1, 20]);
+        let user2 = cart([7, 1]);
+        let similarity = |presence: Presence| {
+            let encoding = presence.apply(VectorEncoding::default());
+            cosine_similarity(
+                &products_to_vector(&user1, &dimensions, encoding),
+                &products_to_vector(&user2, &dimensions, encoding),
+            )
+        };
+
+        // binary では数量によらず一致し、数量を使う場合は一致しない
+        assert!((similarity(Presence::Binary) - 1.0).abs() < 1e-6);
+        assert!(similarity(Presence::Quantity) < 0.5);
+    }
+
+    #[test]
+    fn homogeneous_customers_are_degenerate() {
+        let dimensions = ProductDimensions::new(vec!["a".into(), "b".into(), "c".into()]);
+        let order = |region: &str, products: &[(&str, u32)]| {
+            let items: Vec<ProductItem> = products
+                .iter()
+                .map(|&(id, quantity)| ProductItem {
+                    product_variant_id: id.into(),
+                    quantity,
+                    weight: None,
+                })
+                .collect();
+            create_order_vector(region, &items, &dimensions, VectorEncoding::default())
+        };
+        let scores = |others: &[OrderVector]| {
+            let cart = order("JP-13", &[("a", 1), ("b", 1)]);
+            others
+                .iter()
+                .enumerate()
+                .map(|(i, other)| CustomerScore {
+                    customer_id: i.to_string(),
+                    score: SimilarityMethod::default()
+                        .similarity(&cart, other, 0.8, None)
+                        .unwrap(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let min_variance = RecommendationConfig::default().min_similarity_variance;
+
+        // 全員が同じ地域で同じ商品を購入している場合は類似度が揃い、人気商品での代替に切り替わる
+        let homogeneous = vec![order("JP-13", &[("a", 1), ("b", 1)]); 5];
+        assert!(is_degenerate(&scores(&homogeneous), min_variance));
+
+        // 購入商品や地域が異なれば近傍から推薦する
+        let varied = [
+            order("JP-13", &[("a", 1), ("b", 1)]),
+            order("JP-27", &[("c", 3)]),
+            order("JP-01", &[("a", 2), ("c", 1)]),
+        ];
+        assert!(!is_degenerate(&scores(&varied), min_variance));
+
+        // 顧客が1人だけの場合は判定しない
+        assert!(!is_degenerate(&scores(&homogeneous[..1]), min_variance));
+    }
+
+    #[test]
+    fn degenerate_similarities_warn_with_the_customer_id() {
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = [ProductItem {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 1,
+            weight: None,
+        }];
+        let order = create_order_vector("JP-13", &products, &dimensions, VectorEncoding::default());
+        // どんな分散でも下回る閾値にして、近傍からの推薦を止める
+        let config = RecommendationConfig {
+            min_similarity_variance: f32::MAX,
+            ..RecommendationConfig::default()
+        };
+        let filter = SuggestionFilter {
+            customer_id: Some("customer-1".to_string()),
+            ..SuggestionFilter::default()
+        };
+
+        let (suggestions, lines) = crate::testing::capture_logs(|| {
+            get_similar_products(
+                &mut session,
+                &config,
+                &order,
+                &products,
+                &dimensions,
+                SimilarityMethod::default(),
+                &filter,
+            )
+        });
+
+        assert!(suggestions.unwrap().is_empty());
+        let warning = lines
+            .iter()
+            .find(|line| line["level"] == "WARN")
+            .expect("警告が出ていません");
+        assert_eq!(warning["fields"]["customer_id"], "customer-1");
+        assert!(warning["fields"]["customers"].as_u64().unwrap() > 1);
+    }
+
+    #[test]
+    fn percent_scale_keeps_order_with_top_at_100() {
+        let raw = [0.031, 0.012, 0.004, 0.0005];
+        let mut scores = raw;
+        ScoreScale::Percent.apply(scores.iter_mut());
+
+        assert_eq!(scores[0], 100.0);
+        assert_eq!(scores[3], 0.0);
+        assert!(scores.windows(2).all(|pair| pair[0] > pair[1]));
+
+        // raw では変えない
+        let mut unchanged = raw;
+        ScoreScale::Raw.apply(unchanged.iter_mut());
+        assert_eq!(unchanged, raw);
+
+        // 1件だけ・すべて同じ値の場合も0除算せず100になる
+        let mut single = [0.2];
+        ScoreScale::Percent.apply(single.iter_mut());
+        assert_eq!(single, [100.0]);
+        let mut equal = [0.5, 0.5, 0.5];
+        ScoreScale::Percent.apply(equal.iter_mut());
+        assert_eq!(equal, [100.0; 3]);
+    }
+
+    #[test]
+    fn orderless_customer_gets_region_only_vector() {
+        let rows = vec![
+            (
+                "customer-1".to_string(),
+                "JP-13".to_string(),
+                Some(("variant-1".to_string(), 2)),
+            ),
+            ("customer-2".to_string(), "JP-27".to_string(), None),
+        ];
+        let customer_products = group_customer_purchases(rows);
+        let dimensions = ProductDimensions::new(vec!["variant-1".to_string()]);
+
+        let (province_code, products) = &customer_products["customer-2"];
+        assert!(products.is_empty());
+        let order = create_order_vector(
+            province_code,
+            products,
+            &dimensions,
+            VectorEncoding::default(),
+        );
+        assert_eq!(order.region_vector, region_to_vector("JP-27"));
+        assert_eq!(order.product_vector, [0.0]);
+        assert!(!has_products(&order));
+
+        let (province_code, products) = &customer_products["customer-1"];
+        let order = create_order_vector(
+            province_code,
+            products,
+            &dimensions,
+            VectorEncoding::default(),
+        );
+        assert!(has_products(&order));
+    }
+
+    #[test]
+    fn similarity_breakdown_of_identical_and_disjoint_carts() {
+        let dimensions =
+            ProductDimensions::new(vec!["variant-1".to_string(), "variant-2".to_string()]);
+        let cart = |province_code: &str, variant_id: &str| {
+            let products = [ProductItem {
+                product_variant_id: variant_id.to_string(),
+                quantity: 2,
+                weight: None,
+            }];
+            create_order_vector(
+                province_code,
+                &products,
+                &dimensions,
+                VectorEncoding::default(),
+            )
+        };
+
+        let identical = similarity_breakdown(
+            &cart("JP-13", "variant-1"),
+            &cart("JP-13", "variant-1"),
+            0.3,
+        )
+        .unwrap();
+        assert!((identical.combined - 1.0).abs() < 1e-6);
+        assert!((identical.product - 1.0).abs() < 1e-6);
+        assert!((identical.region - 1.0).abs() < 1e-6);
+
+        // 共通の商品がなければ、地域類似度に重みを掛けた分だけになる
+        let disjoint = similarity_breakdown(
+            &cart("JP-13", "variant-1"),
+            &cart("JP-27", "variant-2"),
+            0.3,
+        )
+        .unwrap();
+        assert_eq!(disjoint.product, 0.0);
+        assert!((disjoint.combined - 0.3 * disjoint.region).abs() < 1e-6);
+    }
+
+    #[test]
+    fn query_in_chunks_queries_every_id_and_merges_results() {
+        let ids: Vec<String> = (0..2500).map(|i| format!("variant-{}", i)).collect();
+        let mut chunk_sizes = Vec::new();
+
+        let merged: HashSet<String> = query_in_chunks(&ids, |chunk| {
+            chunk_sizes.push(chunk.len());
+            Ok(chunk.to_vec())
+        })
+        .unwrap();
+
+        assert_eq!(chunk_sizes, [1000, 1000, 500]);
+        assert_eq!(merged.len(), ids.len());
+        assert!(ids.iter().all(|id| merged.contains(id)));
+    }
+
+    #[test]
+    fn query_in_chunks_with_no_ids_does_not_query() {
+        // 購入商品のない近傍顧客だけの場合など、IDが空でも `IN ()` の問い合わせを組み立てない
+        let merged: HashSet<String> = query_in_chunks(&[], |chunk| -> Result<Vec<String>, _> {
+            panic!("空のIDで問い合わせました: {:?}", chunk)
+        })
+        .unwrap();
+
+        assert!(merged.is_empty());
+    }
+
+    // 固定データのストアで、近傍顧客の人数と推薦商品を求める
+    fn neighbors_and_suggestions(min_neighbor_similarity: Option<f32>) -> (usize, Vec<String>) {
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = [ProductItem {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 2,
+            weight: None,
+        }];
+        let order = create_order_vector("JP-13", &products, &dimensions, VectorEncoding::default());
+        let config = RecommendationConfig {
+            min_neighbor_similarity,
+            ..RecommendationConfig::default()
+        };
+        let similarity = SimilarityMethod::default();
+
+        let neighbors =
+            find_neighbors(&mut session, &config, &order, &dimensions, similarity).unwrap();
+        let suggestions = get_similar_products(
+            &mut session,
+            &config,
+            &order,
+            &products,
+            &dimensions,
+            similarity,
+            &SuggestionFilter::default(),
+        )
+        .unwrap();
+        (
+            neighbors.len(),
+            suggestions.into_iter().map(|s| s.product_id).collect(),
+        )
+    }
+
+    #[test]
+    fn blend_extremes_reproduce_the_pure_rankings() {
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = [ProductItem {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 2,
+            weight: None,
+        }];
+        let order = create_order_vector("JP-13", &products, &dimensions, VectorEncoding::default());
+        let config = RecommendationConfig::default();
+        let filter = SuggestionFilter::default();
+        let mut blended = |blend: f32| -> Vec<String> {
+            get_blended_products(
+                &mut session,
+                &config,
+                &order,
+                &products,
+                &dimensions,
+                SimilarityMethod::default(),
+                blend,
+                &filter,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|s| s.product_id)
+            .collect()
+        };
+        let collaborative_only = blended(0.0);
+        let cooccurrence_only = blended(1.0);
+
+        let collaborative: Vec<String> = get_similar_products(
+            &mut session,
+            &config,
+            &order,
+            &products,
+            &dimensions,
+            SimilarityMethod::default(),
+            &filter,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|s| s.product_id)
+        .collect();
+        let mut cooccurrence: Vec<(String, f32)> = session
+            .fetch_cooccurring_products(&["mock-variant-1".to_string()], None)
+            .unwrap()
+            .into_iter()
+            .collect();
+        cooccurrence.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let cooccurrence: Vec<String> = cooccurrence.into_iter().map(|(id, _)| id).collect();
+
+        assert!(!collaborative.is_empty());
+        assert_eq!(collaborative_only, collaborative);
+        assert_eq!(cooccurrence_only, cooccurrence);
+        // 2つの推薦が異なるため、どちらの比率も片方の結果を再現していることになる
+        assert_ne!(collaborative, cooccurrence);
+    }
+
+    #[test]
+    fn own_past_purchases_are_excluded_from_suggestions() {
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = [ProductItem {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 2,
+            weight: None,
+        }];
+        let order = create_order_vector("JP-13", &products, &dimensions, VectorEncoding::default());
+        let config = RecommendationConfig::default();
+        let customer_id = "mock-customer-3".to_string();
+        let purchased: HashSet<String> = session
+            .fetch_user_products(std::slice::from_ref(&customer_id))
+            .unwrap()
+            .remove(&customer_id)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.product_variant_id)
+            .collect();
+        let filter = SuggestionFilter {
+            excluded_variant_ids: purchased.clone(),
+            customer_id: Some(customer_id),
+            ..SuggestionFilter::default()
+        };
+        let mut suggest = |filter: &SuggestionFilter| -> (Vec<String>, Vec<String>) {
+            let similar = get_similar_products(
+                &mut session,
+                &config,
+                &order,
+                &products,
+                &dimensions,
+                SimilarityMethod::default(),
+                filter,
+            )
+            .unwrap();
+            let popular =
+                get_popular_products(&mut session, &config, &products, None, filter).unwrap();
+            (
+                similar.into_iter().map(|s| s.product_id).collect(),
+                popular.into_iter().map(|s| s.product_id).collect(),
+            )
+        };
+
+        let (similar, popular) = suggest(&SuggestionFilter::default());
+        let (own_excluded_similar, own_excluded_popular) = suggest(&filter);
+
+        // 除外しなければ顧客の購入済み商品も推薦される
+        assert!(similar.iter().any(|id| purchased.contains(id)));
+        assert!(popular.iter().any(|id| purchased.contains(id)));
+        // 除外すると購入済みの商品だけが取り除かれる
+        let without_purchased = |ids: &[String]| -> Vec<String> {
+            ids.iter()
+                .filter(|id| !purchased.contains(*id))
+                .cloned()
+                .collect()
+        };
+        assert!(!own_excluded_similar.is_empty());
+        assert_eq!(own_excluded_similar, without_purchased(&similar));
+        assert_eq!(own_excluded_popular, without_purchased(&popular));
+    }
+
+    #[test]
+    fn candidate_cap_does_not_drop_items_that_pass_the_filter() {
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = [ProductItem {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 2,
+            weight: None,
+        }];
+        let order = create_order_vector("JP-13", &products, &dimensions, VectorEncoding::default());
+        // 冷凍の商品（mock-variant-5）は近傍顧客の購入商品の中で上位ではない
+        let filter = SuggestionFilter {
+            temperature: Some(Temperature::Frozen),
+            ..SuggestionFilter::default()
+        };
+        let suggest = |session: &mut crate::mock::MockStore, candidate_cap: usize| {
+            let config = RecommendationConfig {
+                candidate_cap,
+                ..RecommendationConfig::default()
+            };
+            get_similar_products(
+                session,
+                &config,
+                &order,
+                &products,
+                &dimensions,
+                SimilarityMethod::default(),
+                &filter,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|s| s.product_id)
+            .collect::<Vec<_>>()
+        };
+
+        let uncapped = suggest(&mut session, 100);
+        assert_eq!(uncapped, ["mock-variant-5"]);
+        // 候補を1件に絞っても、条件を満たす商品が絞り込みで落ちることはない
+        assert_eq!(suggest(&mut session, 1), uncapped);
+    }
+
+    #[test]
+    fn min_neighbor_similarity_shrinks_neighbors_and_changes_results() {
+        let (all_neighbors, all_suggestions) = neighbors_and_suggestions(None);
+        let (close_neighbors, close_suggestions) = neighbors_and_suggestions(Some(0.9));
+        assert!(close_neighbors >= 1);
+        assert!(close_neighbors < all_neighbors);
+        assert_ne!(close_suggestions, all_suggestions);
+
+        // 条件を満たす顧客がいなければ近傍からは推薦しない（呼び出し側で人気商品に代替される）
+        assert_eq!(neighbors_and_suggestions(Some(1.0)), (0, vec![]));
+    }
+
+    // 同じ長さの有限なベクトルの組
+    fn vector_pair(values: std::ops::Range<f32>) -> impl Strategy<Value = (Vec<f32>, Vec<f32>)> {
+        (1usize..32).prop_flat_map(move |len| {
+            (
+                prop::collection::vec(values.clone(), len),
+                prop::collection::vec(values.clone(), len),
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn cosine_similarity_is_within_unit_range((a, b) in vector_pair(-100.0f32..100.0)) {
+            let similarity = cosine_similarity(&a, &b);
+            prop_assert!((-1.0 - 1e-5..=1.0 + 1e-5).contains(&similarity), "{}", similarity);
+        }
+
+        #[test]
+        fn cosine_similarity_of_non_negative_vectors_is_non_negative(
+            (a, b) in vector_pair(0.0f32..100.0)
+        ) {
+            let similarity = cosine_similarity(&a, &b);
+            prop_assert!((0.0..=1.0 + 1e-5).contains(&similarity), "{}", similarity);
+        }
+    }
 }
@@ -0,0 +1,160 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use super::cart::ProductDimensions;
+use crate::repository::RecommendationRepository;
+
+// 商品次元情報のキャッシュ
+// 更新時は新しいインスタンスに丸ごと差し替え、読み取り中のリクエストは古いものを使い続ける
+#[derive(Default)]
+pub struct DimensionsCache {
+    dimensions: RwLock<Option<Arc<ProductDimensions>>>,
+}
+
+impl DimensionsCache {
+    // キャッシュ済みの次元情報を取得（未取得の場合は None）
+    pub fn get(&self) -> Option<Arc<ProductDimensions>> {
+        self.dimensions
+            .read()
+            .expect("次元情報キャッシュのロックに失敗")
+            .clone()
+    }
+
+    // 次元情報を差し替え、差し替え後のものを返す
+    pub fn replace(&self, dimensions: ProductDimensions) -> Arc<ProductDimensions> {
+        let dimensions = Arc::new(dimensions);
+        *self
+            .dimensions
+            .write()
+            .expect("次元情報キャッシュのロックに失敗") = Some(dimensions.clone());
+        dimensions
+    }
+}
+
+// 商品次元情報を取得し直してキャッシュを差し替える
+pub async fn refresh(
+    cache: &DimensionsCache,
+    recommendations: Arc<dyn RecommendationRepository>,
+) -> Result<Arc<ProductDimensions>, mysql::Error> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let dimensions =
+        tokio::task::spawn_blocking(move || recommendations.session()?.fetch_product_dimensions())
+            .await
+            .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(cache.replace(dimensions))
+}
+
+// 商品を書き換えた直後に、主データベースから商品次元情報を取得し直してキャッシュを差し替える
+// レプリカから読むと書き込みが反映される前の次元情報でキャッシュしてしまうため
+pub async fn refresh_from_primary(
+    cache: &DimensionsCache,
+    recommendations: Arc<dyn RecommendationRepository>,
+) -> Result<Arc<ProductDimensions>, mysql::Error> {
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let dimensions = tokio::task::spawn_blocking(move || {
+        recommendations
+            .primary_session()?
+            .fetch_product_dimensions()
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    Ok(cache.replace(dimensions))
+}
+
+// 一定間隔でキャッシュを更新するタスクを起動する
+// 更新に失敗した場合は古い次元情報のまま提供を続ける
+pub fn spawn_refresh_task(
+    cache: Arc<DimensionsCache>,
+    recommendations: Arc<dyn RecommendationRepository>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match refresh(&cache, recommendations.clone()).await {
+                Ok(dimensions) => tracing::info!(
+                    dimensions = dimensions.get_dimension(),
+                    "商品次元情報を更新しました"
+                ),
+                Err(err) => tracing::error!(error = %err, "商品次元情報の更新に失敗しました"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockStore;
+    use crate::repository::RecommendationSession;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // 接続の可否を切り替えられるストア（接続できる場合は固定データの次元情報を返す）
+    #[derive(Default)]
+    struct SwitchableStore {
+        offline: AtomicBool,
+        attempts: AtomicUsize,
+    }
+
+    impl RecommendationRepository for SwitchableStore {
+        fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            if self.offline.load(Ordering::SeqCst) {
+                return Err(mysql::Error::IoError(std::io::Error::other("offline")));
+            }
+            Ok(Box::new(MockStore))
+        }
+    }
+
+    // 条件を満たすまで待つ（1秒で打ち切る）
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("条件を満たしませんでした");
+    }
+
+    // 固定データより次元の少ない、更新前の次元情報を入れたキャッシュ
+    fn stale_cache() -> (Arc<DimensionsCache>, Arc<ProductDimensions>) {
+        let cache = Arc::new(DimensionsCache::default());
+        let stale = cache.replace(ProductDimensions::new(vec!["old".to_string()]));
+        (cache, stale)
+    }
+
+    #[tokio::test]
+    async fn scheduled_refresh_replaces_the_cache() {
+        let (cache, _) = stale_cache();
+        let store = Arc::new(SwitchableStore::default());
+
+        spawn_refresh_task(cache.clone(), store, Duration::from_millis(10));
+
+        let expected = MockStore
+            .fetch_product_dimensions()
+            .unwrap()
+            .get_dimension();
+        wait_until(|| cache.get().unwrap().get_dimension() == expected).await;
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_the_old_cache() {
+        let (cache, stale) = stale_cache();
+        let store = Arc::new(SwitchableStore::default());
+        store.offline.store(true, Ordering::SeqCst);
+
+        spawn_refresh_task(cache.clone(), store.clone(), Duration::from_millis(10));
+
+        // 何度失敗しても古い次元情報を提供し続ける
+        wait_until(|| store.attempts.load(Ordering::SeqCst) >= 3).await;
+        assert!(Arc::ptr_eq(&cache.get().unwrap(), &stale));
+
+        // 接続できるようになれば次の更新で差し替わる
+        store.offline.store(false, Ordering::SeqCst);
+        wait_until(|| !Arc::ptr_eq(&cache.get().unwrap(), &stale)).await;
+    }
+}
@@ -0,0 +1,46 @@
+// 商品間の類似度（item_similarity テーブルは migrate コマンドで作成しておく）
+use mysql::TxOpts;
+use mysql::prelude::*;
+
+use crate::db;
+
+// 同じ注文で購入された商品の組ごとに類似度を計算する
+// 2つの商品を含む注文の集合のコサイン類似度（共起した注文数 / √(一方の注文数 × もう一方の注文数)）
+const ITEM_SIMILARITY: &str = "
+    SELECT
+      op1.variant_id,
+      op2.variant_id,
+      COUNT(DISTINCT op1.order_id) / SQRT(c1.orders * c2.orders),
+      NOW()
+    FROM
+      order_products op1
+    JOIN
+      order_products op2 ON op1.order_id = op2.order_id AND op1.variant_id <> op2.variant_id
+    JOIN
+      (SELECT variant_id, COUNT(DISTINCT order_id) AS orders FROM order_products GROUP BY variant_id) c1
+      ON c1.variant_id = op1.variant_id
+    JOIN
+      (SELECT variant_id, COUNT(DISTINCT order_id) AS orders FROM order_products GROUP BY variant_id) c2
+      ON c2.variant_id = op2.variant_id
+    GROUP BY
+      op1.variant_id, op2.variant_id, c1.orders, c2.orders";
+
+// 注文履歴から item_similarity テーブルを作り直し、保存した商品の組の数を返す
+// 作り直している間も以前の内容を読めるよう、削除と保存は1つのトランザクションで行う
+pub fn rebuild_item_similarity(conn: &mut mysql::PooledConn) -> Result<u64, mysql::Error> {
+    let mut tx = conn.start_transaction(TxOpts::default())?;
+
+    db::timed("clear_item_similarity", || {
+        tx.query_drop("DELETE FROM item_similarity")
+    })?;
+    db::timed("rebuild_item_similarity", || {
+        tx.query_drop(format!(
+            "INSERT INTO item_similarity (variant_id, similar_variant_id, score, updated_at) {}",
+            ITEM_SIMILARITY
+        ))
+    })?;
+    let saved = tx.affected_rows();
+
+    tx.commit()?;
+    Ok(saved)
+}
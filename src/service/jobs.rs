@@ -0,0 +1,75 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+// バックグラウンドで実行する処理の状態
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+// バックグラウンドで実行する処理
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: String,
+    // 処理の種類（同じ種類の処理は同時に1つしか実行しない）
+    pub kind: &'static str,
+    pub status: JobStatus,
+    // 完了時の結果、または失敗時のエラー内容
+    pub message: Option<String>,
+}
+
+// 実行中・実行済みの処理の一覧（サーバーの再起動で消える）
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: RwLock<HashMap<String, Job>>,
+}
+
+impl JobRegistry {
+    // 同じ種類の処理が実行中でなければ新しい処理として登録し、IDを返す（実行中の場合は None）
+    pub fn try_start(&self, kind: &'static str) -> Option<String> {
+        let mut jobs = self.jobs.write().expect("ジョブ一覧のロックに失敗");
+        if jobs
+            .values()
+            .any(|job| job.kind == kind && job.status == JobStatus::Running)
+        {
+            return None;
+        }
+
+        let id = Uuid::new_v4().to_string();
+        jobs.insert(
+            id.clone(),
+            Job {
+                id: id.clone(),
+                kind,
+                status: JobStatus::Running,
+                message: None,
+            },
+        );
+        Some(id)
+    }
+
+    // 処理の結果を記録する
+    pub fn finish(&self, id: &str, result: Result<String, String>) {
+        let mut jobs = self.jobs.write().expect("ジョブ一覧のロックに失敗");
+        if let Some(job) = jobs.get_mut(id) {
+            (job.status, job.message) = match result {
+                Ok(message) => (JobStatus::Completed, Some(message)),
+                Err(message) => (JobStatus::Failed, Some(message)),
+            };
+        }
+    }
+
+    // IDに対応する処理を取得（存在しない場合は None）
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs
+            .read()
+            .expect("ジョブ一覧のロックに失敗")
+            .get(id)
+            .cloned()
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// 同時に実行する重い処理（推薦の計算）の数を制限する
+// 上限に達している場合は待たずに断り、リクエストが溜まって接続やブロッキングスレッドを使い切るのを防ぐ
+pub struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(permits: usize) -> Self {
+        ConcurrencyLimiter {
+            semaphore: Arc::new(Semaphore::new(permits)),
+        }
+    }
+
+    // 空きがあれば枠を確保する（枠は戻り値を破棄したときに返る）。空きがなければ None
+    // 枠はリミッターを借用しないため、ブロッキングタスクに渡して処理が終わるまで保持できる
+    pub fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.semaphore.clone().try_acquire_owned().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_while_all_permits_are_held() {
+        let limiter = ConcurrencyLimiter::new(1);
+
+        // 1件目が枠を保持している間は2件目を断る
+        let first = limiter.try_acquire();
+        assert!(first.is_some());
+        assert!(limiter.try_acquire().is_none());
+
+        // 1件目が終われば次のリクエストを受け付ける
+        drop(first);
+        assert!(limiter.try_acquire().is_some());
+    }
+}
@@ -1 +1,12 @@
 pub mod cart;
+pub mod dimensions;
+pub mod item_similarity;
+pub mod jobs;
+pub mod limiter;
+pub mod recommender;
+pub mod region;
+pub mod stats;
+pub mod suggestion_cache;
+pub mod top_k;
+pub mod user_products;
+pub mod user_vectors;
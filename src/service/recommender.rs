@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::cart::{
+    self, OrderVector, ProductDimensions, ProductItem, ProductSuggestion, RecommendError,
+    RecommendationConfig, SimilarityMethod, SuggestionFilter,
+};
+use crate::repository::RecommendationSession;
+
+// blended 指定時にブレンド比率が省略された場合の比率
+const DEFAULT_BLEND: f32 = 0.5;
+
+// 1回の推薦に必要な入力
+pub struct RecommendContext<'a> {
+    pub config: &'a RecommendationConfig,
+    // カートから作成したベクトル
+    pub current_order: &'a OrderVector,
+    pub current_products: &'a [ProductItem],
+    pub product_dimensions: &'a ProductDimensions,
+    pub similarity: SimilarityMethod,
+    // 協調フィルタリングと共起のブレンド比率（blended のみ使用）
+    pub blend: Option<f32>,
+    // 人気商品の集計期間（popular のみ使用）
+    pub popular_window: Option<u32>,
+    pub filter: &'a SuggestionFilter,
+}
+
+// 推薦アルゴリズム
+// データベースの問い合わせに失敗した場合や、ベクトルを比較できない場合は、空の推薦で隠さずにエラーを返す
+pub trait Recommender: Send + Sync {
+    fn recommend(
+        &self,
+        session: &mut dyn RecommendationSession,
+        context: &RecommendContext<'_>,
+    ) -> Result<Vec<ProductSuggestion>, RecommendError>;
+}
+
+// 近傍顧客の購入商品による協調フィルタリング
+pub struct CollaborativeRecommender;
+
+impl Recommender for CollaborativeRecommender {
+    fn recommend(
+        &self,
+        session: &mut dyn RecommendationSession,
+        context: &RecommendContext<'_>,
+    ) -> Result<Vec<ProductSuggestion>, RecommendError> {
+        cart::get_similar_products(
+            session,
+            context.config,
+            context.current_order,
+            context.current_products,
+            context.product_dimensions,
+            context.similarity,
+            context.filter,
+        )
+    }
+}
+
+// カート内の商品と同じ注文で購入された商品（共起）による推薦
+pub struct CooccurrenceRecommender;
+
+impl Recommender for CooccurrenceRecommender {
+    fn recommend(
+        &self,
+        session: &mut dyn RecommendationSession,
+        context: &RecommendContext<'_>,
+    ) -> Result<Vec<ProductSuggestion>, RecommendError> {
+        // ブレンド比率1.0は共起のみのスコアになる
+        cart::get_blended_products(
+            session,
+            context.config,
+            context.current_order,
+            context.current_products,
+            context.product_dimensions,
+            context.similarity,
+            1.0,
+            context.filter,
+        )
+    }
+}
+
+// 協調フィルタリングと共起のスコアのブレンド
+pub struct BlendedRecommender;
+
+impl Recommender for BlendedRecommender {
+    fn recommend(
+        &self,
+        session: &mut dyn RecommendationSession,
+        context: &RecommendContext<'_>,
+    ) -> Result<Vec<ProductSuggestion>, RecommendError> {
+        cart::get_blended_products(
+            session,
+            context.config,
+            context.current_order,
+            context.current_products,
+            context.product_dimensions,
+            context.similarity,
+            context.blend.unwrap_or(DEFAULT_BLEND),
+            context.filter,
+        )
+    }
+}
+
+// 販売数量の多い人気商品
+pub struct PopularRecommender;
+
+impl Recommender for PopularRecommender {
+    fn recommend(
+        &self,
+        session: &mut dyn RecommendationSession,
+        context: &RecommendContext<'_>,
+    ) -> Result<Vec<ProductSuggestion>, RecommendError> {
+        cart::get_popular_products(
+            session,
+            context.config,
+            context.current_products,
+            context.popular_window,
+            context.filter,
+        )
+        .map_err(RecommendError::from)
+    }
+}
+
+// 名前で選択できる推薦アルゴリズムの一覧
+pub struct RecommenderRegistry {
+    recommenders: BTreeMap<&'static str, Arc<dyn Recommender>>,
+}
+
+impl RecommenderRegistry {
+    // 推薦アルゴリズムを名前で登録する
+    pub fn register(&mut self, name: &'static str, recommender: Arc<dyn Recommender>) {
+        self.recommenders.insert(name, recommender);
+    }
+
+    // 名前に対応する推薦アルゴリズムを取得（未登録の場合は None）
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Recommender>> {
+        self.recommenders.get(name).cloned()
+    }
+
+    // 登録されている名前の一覧
+    pub fn names(&self) -> Vec<&'static str> {
+        self.recommenders.keys().copied().collect()
+    }
+}
+
+impl Default for RecommenderRegistry {
+    // 組み込みの推薦アルゴリズムをすべて登録した一覧
+    fn default() -> Self {
+        let mut registry = RecommenderRegistry {
+            recommenders: BTreeMap::new(),
+        };
+        registry.register("collaborative", Arc::new(CollaborativeRecommender));
+        registry.register("cooccurrence", Arc::new(CooccurrenceRecommender));
+        registry.register("blended", Arc::new(BlendedRecommender));
+        registry.register("popular", Arc::new(PopularRecommender));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::cart::{VectorEncoding, create_order_vector};
+
+    // 推薦結果を商品IDとスコアの組にする
+    fn ranked(suggestions: Vec<ProductSuggestion>) -> Vec<(String, f32)> {
+        suggestions
+            .into_iter()
+            .map(|s| (s.product_id, s.score))
+            .collect()
+    }
+
+    #[test]
+    fn each_name_dispatches_to_its_algorithm() {
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let products = [ProductItem {
+            product_variant_id: "mock-variant-1".to_string(),
+            quantity: 2,
+            weight: None,
+        }];
+        let order = create_order_vector("JP-13", &products, &dimensions, VectorEncoding::default());
+        let config = RecommendationConfig::default();
+        let filter = SuggestionFilter::default();
+        let context = RecommendContext {
+            config: &config,
+            current_order: &order,
+            current_products: &products,
+            product_dimensions: &dimensions,
+            similarity: SimilarityMethod::default(),
+            blend: None,
+            popular_window: None,
+            filter: &filter,
+        };
+        let registry = RecommenderRegistry::default();
+        let mut dispatched = |name: &str| {
+            let recommender = registry.get(name).unwrap();
+            ranked(recommender.recommend(&mut session, &context).unwrap())
+        };
+        let collaborative = dispatched("collaborative");
+        let cooccurrence = dispatched("cooccurrence");
+        let blended = dispatched("blended");
+        let popular = dispatched("popular");
+
+        let method = SimilarityMethod::default();
+        let blend = |session: &mut crate::mock::MockStore, blend: f32| {
+            cart::get_blended_products(
+                session,
+                &config,
+                &order,
+                &products,
+                &dimensions,
+                method,
+                blend,
+                &filter,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            collaborative,
+            ranked(
+                cart::get_similar_products(
+                    &mut session,
+                    &config,
+                    &order,
+                    &products,
+                    &dimensions,
+                    method,
+                    &filter,
+                )
+                .unwrap()
+            )
+        );
+        assert_eq!(cooccurrence, ranked(blend(&mut session, 1.0)));
+        assert_eq!(blended, ranked(blend(&mut session, DEFAULT_BLEND)));
+        assert_eq!(
+            popular,
+            ranked(
+                cart::get_popular_products(&mut session, &config, &products, None, &filter)
+                    .unwrap()
+            )
+        );
+        // 各アルゴリズムの結果が異なるため、別の実装に振り分けられていればいずれかの比較で失敗する
+        let results = [&collaborative, &cooccurrence, &blended, &popular];
+        for (i, a) in results.iter().enumerate() {
+            for b in &results[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    // 呼ばれたことだけを示す推薦結果を返す推薦アルゴリズム
+    struct FixedRecommender(&'static str);
+
+    impl Recommender for FixedRecommender {
+        fn recommend(
+            &self,
+            _session: &mut dyn RecommendationSession,
+            _context: &RecommendContext<'_>,
+        ) -> Result<Vec<ProductSuggestion>, RecommendError> {
+            Ok(vec![ProductSuggestion {
+                product_id: self.0.to_string(),
+                score: 1.0,
+                contributions: vec![],
+            }])
+        }
+    }
+
+    #[test]
+    fn registered_recommenders_replace_builtins_and_unknown_names_are_none() {
+        let mut registry = RecommenderRegistry::default();
+        registry.register("popular", Arc::new(FixedRecommender("replaced")));
+        registry.register("custom", Arc::new(FixedRecommender("custom")));
+
+        let mut session = crate::mock::MockStore;
+        let dimensions = session.fetch_product_dimensions().unwrap();
+        let order = create_order_vector("JP-13", &[], &dimensions, VectorEncoding::default());
+        let config = RecommendationConfig::default();
+        let filter = SuggestionFilter::default();
+        let context = RecommendContext {
+            config: &config,
+            current_order: &order,
+            current_products: &[],
+            product_dimensions: &dimensions,
+            similarity: SimilarityMethod::default(),
+            blend: None,
+            popular_window: None,
+            filter: &filter,
+        };
+        let mut dispatched = |name: &str| {
+            let recommender = registry.get(name).unwrap();
+            ranked(recommender.recommend(&mut session, &context).unwrap())
+        };
+
+        assert_eq!(dispatched("popular"), [("replaced".to_string(), 1.0)]);
+        assert_eq!(dispatched("custom"), [("custom".to_string(), 1.0)]);
+        assert!(registry.get("unknown").is_none());
+        assert_eq!(
+            registry.names(),
+            [
+                "blended",
+                "collaborative",
+                "cooccurrence",
+                "custom",
+                "popular"
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// 都道府県の数
+pub const PROVINCE_COUNT: usize = 47;
+
+// 都道府県名（インデックスは都道府県コード - 1）
+const PREFECTURE_NAMES: [&str; PROVINCE_COUNT] = [
+    "北海道",
+    "青森県",
+    "岩手県",
+    "宮城県",
+    "秋田県",
+    "山形県",
+    "福島県",
+    "茨城県",
+    "栃木県",
+    "群馬県",
+    "埼玉県",
+    "千葉県",
+    "東京都",
+    "神奈川県",
+    "新潟県",
+    "富山県",
+    "石川県",
+    "福井県",
+    "山梨県",
+    "長野県",
+    "岐阜県",
+    "静岡県",
+    "愛知県",
+    "三重県",
+    "滋賀県",
+    "京都府",
+    "大阪府",
+    "兵庫県",
+    "奈良県",
+    "和歌山県",
+    "鳥取県",
+    "島根県",
+    "岡山県",
+    "広島県",
+    "山口県",
+    "徳島県",
+    "香川県",
+    "愛媛県",
+    "高知県",
+    "福岡県",
+    "佐賀県",
+    "長崎県",
+    "熊本県",
+    "大分県",
+    "宮崎県",
+    "鹿児島県",
+    "沖縄県",
+];
+
+// 都道府県の隣接関係（陸続き、または橋・トンネルで結ばれた都道府県）
+// インデックスは都道府県コード - 1
+const PREFECTURE_ADJACENCY: [&[u32]; 47] = [
+    &[2],                              // 01 北海道
+    &[1, 3, 5],                        // 02 青森県
+    &[2, 4, 5],                        // 03 岩手県
+    &[3, 5, 6, 7],                     // 04 宮城県
+    &[2, 3, 4, 6],                     // 05 秋田県
+    &[4, 5, 7, 15],                    // 06 山形県
+    &[4, 6, 8, 9, 10, 15],             // 07 福島県
+    &[7, 9, 11, 12],                   // 08 茨城県
+    &[7, 8, 10, 11],                   // 09 栃木県
+    &[7, 9, 11, 15, 20],               // 10 群馬県
+    &[8, 9, 10, 12, 13, 19, 20],       // 11 埼玉県
+    &[8, 11, 13, 14],                  // 12 千葉県
+    &[11, 12, 14, 19],                 // 13 東京都
+    &[12, 13, 19, 22],                 // 14 神奈川県
+    &[6, 7, 10, 16, 20],               // 15 新潟県
+    &[15, 17, 20, 21],                 // 16 富山県
+    &[16, 18, 21],                     // 17 石川県
+    &[17, 21, 25, 26],                 // 18 福井県
+    &[11, 13, 14, 20, 22],             // 19 山梨県
+    &[10, 11, 15, 16, 19, 21, 22, 23], // 20 長野県
+    &[16, 17, 18, 20, 23, 24, 25],     // 21 岐阜県
+    &[14, 19, 20, 23],                 // 22 静岡県
+    &[20, 21, 22, 24],                 // 23 愛知県
+    &[21, 23, 25, 26, 29, 30],         // 24 三重県
+    &[18, 21, 24, 26],                 // 25 滋賀県
+    &[18, 24, 25, 27, 28, 29],         // 26 京都府
+    &[26, 28, 29, 30],                 // 27 大阪府
+    &[26, 27, 31, 33, 36],             // 28 兵庫県
+    &[24, 26, 27, 30],                 // 29 奈良県
+    &[24, 27, 29],                     // 30 和歌山県
+    &[28, 32, 33, 34],                 // 31 鳥取県
+    &[31, 34, 35],                     // 32 島根県
+    &[28, 31, 34, 37],                 // 33 岡山県
+    &[31, 32, 33, 35, 38],             // 34 広島県
+    &[32, 34, 40],                     // 35 山口県
+    &[28, 37, 38, 39],                 // 36 徳島県
+    &[33, 36, 38],                     // 37 香川県
+    &[34, 36, 37, 39],                 // 38 愛媛県
+    &[36, 38],                         // 39 高知県
+    &[35, 41, 43, 44],                 // 40 福岡県
+    &[40, 42],                         // 41 佐賀県
+    &[41],                             // 42 長崎県
+    &[40, 44, 45, 46],                 // 43 熊本県
+    &[40, 43, 45],                     // 44 大分県
+    &[43, 44, 46],                     // 45 宮崎県
+    &[43, 45],                         // 46 鹿児島県
+    &[],                               // 47 沖縄県
+];
+
+// 隣接する都道府県同士の類似度
+const ADJACENT_SIMILARITY: f32 = 0.5;
+
+// JP-XX 形式の都道府県コードから1〜47の番号を取得
+pub fn province_number(province_code: &str) -> Option<u32> {
+    let number = province_code
+        .strip_prefix("JP-")
+        .filter(|digits| digits.len() >= 2)?
+        .parse::<u32>()
+        .ok()?;
+
+    (1..=47).contains(&number).then_some(number)
+}
+
+// 都道府県コード（JP-01〜JP-47）と都道府県名の一覧
+pub fn provinces() -> impl Iterator<Item = (String, &'static str)> {
+    PREFECTURE_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (format!("JP-{:02}", i + 1), *name))
+}
+
+// 2つの都道府県が隣接しているかどうか
+pub fn is_adjacent(province1: u32, province2: u32) -> bool {
+    PREFECTURE_ADJACENCY
+        .get(province1.wrapping_sub(1) as usize)
+        .is_some_and(|neighbors| neighbors.contains(&province2))
+}
+
+// 都道府県の隣接関係に基づく地域類似度
+// 同一都道府県は1.0、隣接する都道府県は部分的な類似度、それ以外は0.0
+pub fn region_adjacency_similarity(province1: Option<u32>, province2: Option<u32>) -> f32 {
+    match (province1, province2) {
+        (Some(p1), Some(p2)) if p1 == p2 => 1.0,
+        (Some(p1), Some(p2)) if is_adjacent(p1, p2) => ADJACENT_SIMILARITY,
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::db::{self, DatasetStats};
+
+// データセットの集計値を一定期間キャッシュする
+pub struct StatsCache {
+    ttl: Duration,
+    cached: RwLock<Option<(Instant, DatasetStats)>>,
+}
+
+impl StatsCache {
+    pub fn new(ttl: Duration) -> Self {
+        StatsCache {
+            ttl,
+            cached: RwLock::new(None),
+        }
+    }
+
+    // キャッシュが有効期間内ならそれを返し、期限切れなら集計し直す
+    pub async fn get(&self, pool: Arc<mysql::Pool>) -> Result<DatasetStats, mysql::Error> {
+        if let Some((fetched_at, stats)) = self
+            .cached
+            .read()
+            .expect("集計値キャッシュのロックに失敗")
+            .as_ref()
+            && fetched_at.elapsed() < self.ttl
+        {
+            return Ok(stats.clone());
+        }
+
+        let stats = db::get_dataset_stats(pool).await?;
+        *self.cached.write().expect("集計値キャッシュのロックに失敗") =
+            Some((Instant::now(), stats.clone()));
+
+        Ok(stats)
+    }
+}
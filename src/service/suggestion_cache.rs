@@ -0,0 +1,59 @@
+// 推薦結果のキャッシュ（suggestion_cache テーブルは migrate コマンドで作成しておく）
+use mysql::prelude::*;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::db;
+
+// キャッシュキーをリクエスト内容から決定的に生成するための名前空間
+const CACHE_KEY_NAMESPACE: Uuid = Uuid::from_u128(0x3b9e7a21_5c4d_4e8f_a1b2_c3d4e5f60718);
+
+// 正規化したリクエスト内容からキャッシュキー（ハッシュ）を作成する
+pub fn cache_key(canonical: &str) -> String {
+    Uuid::new_v5(&CACHE_KEY_NAMESPACE, canonical.as_bytes()).to_string()
+}
+
+// 有効期限内のキャッシュ済み推薦結果（JSON）を取得する関数
+pub fn fetch_cached_suggestions(
+    conn: &mut mysql::PooledConn,
+    key: &str,
+    ttl: Duration,
+) -> Result<Option<String>, mysql::Error> {
+    db::timed("fetch_cached_suggestions", || {
+        conn.exec_first(
+            "SELECT suggestions FROM suggestion_cache
+             WHERE cache_key = ? AND created_at >= NOW() - INTERVAL ? SECOND",
+            (key, ttl.as_secs()),
+        )
+    })
+}
+
+// 推薦結果（JSON）をキャッシュに保存し、期限切れのキャッシュを削除する関数
+pub fn store_cached_suggestions(
+    conn: &mut mysql::PooledConn,
+    key: &str,
+    suggestions: &str,
+    ttl: Duration,
+) -> Result<(), mysql::Error> {
+    db::timed("store_cached_suggestions", || {
+        conn.exec_drop(
+            "INSERT INTO suggestion_cache (cache_key, suggestions, created_at)
+             VALUES (?, ?, NOW())
+             ON DUPLICATE KEY UPDATE suggestions = VALUES(suggestions), created_at = NOW()",
+            (key, suggestions),
+        )
+    })?;
+    db::timed("expire_cached_suggestions", || {
+        conn.exec_drop(
+            "DELETE FROM suggestion_cache WHERE created_at < NOW() - INTERVAL ? SECOND",
+            (ttl.as_secs(),),
+        )
+    })
+}
+
+// キャッシュ済みの推薦結果をすべて削除する関数（推薦対象の商品が変わった場合に使用）
+pub fn clear_cached_suggestions(conn: &mut mysql::PooledConn) -> Result<(), mysql::Error> {
+    db::timed("clear_cached_suggestions", || {
+        conn.query_drop("DELETE FROM suggestion_cache")
+    })
+}
@@ -0,0 +1,102 @@
+// 上位k件の選択（標準ライブラリだけを使い、benches/select_top.rs からも読み込む）
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+// ヒープで上位を選ぶ対象（スコアとIDで順位を決める）
+pub trait Scored {
+    fn score(&self) -> f32;
+    fn id(&self) -> &str;
+}
+
+// ヒープで順位を比較するためのラッパー
+// スコアが高いほど大きく、同点の場合はIDが小さいほど大きい
+struct Ranked<T>(T);
+
+impl<T: Scored> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .score()
+            .total_cmp(&other.0.score())
+            .then_with(|| other.0.id().cmp(self.0.id()))
+    }
+}
+
+impl<T: Scored> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Scored> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Scored> Eq for Ranked<T> {}
+
+// スコアの高い上位k件を降順で返す
+// 全件をソートせず、最下位を先頭に持つk件のヒープで O(N log k) で選ぶ
+pub fn select_top<T: Scored>(items: impl IntoIterator<Item = T>, k: usize) -> Vec<T> {
+    let mut heap: BinaryHeap<Reverse<Ranked<T>>> = BinaryHeap::with_capacity(k + 1);
+    for item in items {
+        heap.push(Reverse(Ranked(item)));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+
+    // 昇順の Reverse を並べ直すと降順になる
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(Ranked(item))| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Item(String, f32);
+
+    impl Scored for Item {
+        fn score(&self) -> f32 {
+            self.1
+        }
+
+        fn id(&self) -> &str {
+            &self.0
+        }
+    }
+
+    // 同点が多く出るよう、スコアは少ない種類の値から選ぶ（IDは重複しない）
+    fn items() -> impl Strategy<Value = Vec<Item>> {
+        prop::collection::vec(0u8..8, 0..200).prop_map(|scores| {
+            scores
+                .into_iter()
+                .enumerate()
+                .map(|(i, score)| Item(format!("item-{:03}", i), f32::from(score) / 4.0))
+                .collect()
+        })
+    }
+
+    // 全件をソートして上位k件を返す（select_top と同じ順位付け）
+    fn sort_top(mut items: Vec<Item>, k: usize) -> Vec<Item> {
+        items.sort_by(|a, b| {
+            b.score()
+                .total_cmp(&a.score())
+                .then_with(|| a.id().cmp(b.id()))
+        });
+        items.truncate(k);
+        items
+    }
+
+    proptest! {
+        #[test]
+        fn heap_selection_matches_a_full_sort(items in items(), k in 0usize..50) {
+            prop_assert_eq!(select_top(items.clone(), k), sort_top(items, k));
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use lru::LruCache;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::cart::{ProductDimensions, ProductItem, Temperature, UserVectors, VectorEncoding};
+use crate::repository::{RecommendationRepository, RecommendationSession};
+
+// 近傍顧客の購入商品を顧客IDごとに一定期間キャッシュする（最近使われていない顧客から追い出す）
+// 注文はあまり変わらないため、期間内は同じ顧客の購入商品をデータベースに問い合わせない
+pub struct UserProductsCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<String, (Instant, Vec<ProductItem>)>>,
+}
+
+impl UserProductsCache {
+    pub fn new(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        UserProductsCache {
+            ttl,
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    // キャッシュ済みの購入商品と、キャッシュになかった（期限切れを含む）顧客IDを返す
+    fn lookup(&self, customer_ids: &[String]) -> (HashMap<String, Vec<ProductItem>>, Vec<String>) {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("購入商品キャッシュのロックに失敗");
+
+        let mut products = HashMap::new();
+        let mut missing = Vec::new();
+        for customer_id in customer_ids {
+            match entries.get(customer_id) {
+                Some((fetched_at, items)) if fetched_at.elapsed() < self.ttl => {
+                    // 購入商品のない顧客はデータベースからの取得結果と同じく結果に含めない
+                    if !items.is_empty() {
+                        products.insert(customer_id.clone(), items.clone());
+                    }
+                }
+                Some(_) => {
+                    entries.pop(customer_id);
+                    missing.push(customer_id.clone());
+                }
+                None => missing.push(customer_id.clone()),
+            }
+        }
+        (products, missing)
+    }
+
+    // データベースから取得した購入商品を保存する（購入商品のない顧客も空として保存する）
+    fn store(&self, customer_ids: &[String], products: &HashMap<String, Vec<ProductItem>>) {
+        let mut entries = self
+            .entries
+            .lock()
+            .expect("購入商品キャッシュのロックに失敗");
+
+        let now = Instant::now();
+        for customer_id in customer_ids {
+            let items = products.get(customer_id).cloned().unwrap_or_default();
+            entries.put(customer_id.clone(), (now, items));
+        }
+    }
+}
+
+// 近傍顧客の購入商品をキャッシュする推薦データのストア
+// 購入商品の取得だけをキャッシュし、それ以外は元のストアに任せる
+pub struct CachedUserProductsRepository {
+    inner: Arc<dyn RecommendationRepository>,
+    cache: Arc<UserProductsCache>,
+}
+
+impl CachedUserProductsRepository {
+    pub fn new(inner: Arc<dyn RecommendationRepository>, cache: Arc<UserProductsCache>) -> Self {
+        CachedUserProductsRepository { inner, cache }
+    }
+}
+
+impl RecommendationRepository for CachedUserProductsRepository {
+    fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(CachedUserProductsSession {
+            inner: self.inner.session()?,
+            cache: self.cache.clone(),
+        }))
+    }
+
+    fn primary_session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(CachedUserProductsSession {
+            inner: self.inner.primary_session()?,
+            cache: self.cache.clone(),
+        }))
+    }
+}
+
+struct CachedUserProductsSession {
+    inner: Box<dyn RecommendationSession>,
+    cache: Arc<UserProductsCache>,
+}
+
+impl RecommendationSession for CachedUserProductsSession {
+    fn fetch_product_dimensions(&mut self) -> Result<ProductDimensions, mysql::Error> {
+        self.inner.fetch_product_dimensions()
+    }
+
+    fn fetch_user_purchase_history(
+        &mut self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        min_items: usize,
+        include_orderless: bool,
+    ) -> Result<UserVectors, mysql::Error> {
+        self.inner.fetch_user_purchase_history(
+            product_dimensions,
+            encoding,
+            min_items,
+            include_orderless,
+        )
+    }
+
+    fn fetch_user_products(
+        &mut self,
+        customer_ids: &[String],
+    ) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error> {
+        let (mut products, missing) = self.cache.lookup(customer_ids);
+        if missing.is_empty() {
+            return Ok(products);
+        }
+
+        // キャッシュになかった顧客だけをまとめて取得する
+        let fetched = self.inner.fetch_user_products(&missing)?;
+        self.cache.store(&missing, &fetched);
+        products.extend(fetched);
+        Ok(products)
+    }
+
+    fn fetch_cooccurring_products(
+        &mut self,
+        variant_ids: &[String],
+        half_life_days: Option<f32>,
+    ) -> Result<HashMap<String, f32>, mysql::Error> {
+        self.inner
+            .fetch_cooccurring_products(variant_ids, half_life_days)
+    }
+
+    fn fetch_variants_with_temperature(
+        &mut self,
+        variant_ids: &[String],
+        temperature: Temperature,
+    ) -> Result<HashSet<String>, mysql::Error> {
+        self.inner
+            .fetch_variants_with_temperature(variant_ids, temperature)
+    }
+
+    fn fetch_variants_in_categories(
+        &mut self,
+        variant_ids: &[String],
+        categories: &[String],
+    ) -> Result<HashSet<String>, mysql::Error> {
+        self.inner
+            .fetch_variants_in_categories(variant_ids, categories)
+    }
+
+    fn fetch_product_categories(&mut self) -> Result<HashSet<String>, mysql::Error> {
+        self.inner.fetch_product_categories()
+    }
+
+    fn fetch_popular_products(
+        &mut self,
+        exclude_variant_ids: &[String],
+        window_days: Option<u32>,
+        temperature: Option<Temperature>,
+        excluded_categories: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, f32)>, mysql::Error> {
+        self.inner.fetch_popular_products(
+            exclude_variant_ids,
+            window_days,
+            temperature,
+            excluded_categories,
+            limit,
+        )
+    }
+
+    fn fetch_cached_suggestions(
+        &mut self,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>, mysql::Error> {
+        self.inner.fetch_cached_suggestions(key, ttl)
+    }
+
+    fn store_cached_suggestions(
+        &mut self,
+        key: &str,
+        suggestions: &str,
+        ttl: Duration,
+    ) -> Result<(), mysql::Error> {
+        self.inner.store_cached_suggestions(key, suggestions, ttl)
+    }
+
+    fn clear_cached_suggestions(&mut self) -> Result<(), mysql::Error> {
+        self.inner.clear_cached_suggestions()
+    }
+
+    fn rebuild_item_similarity(&mut self) -> Result<u64, mysql::Error> {
+        self.inner.rebuild_item_similarity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::RecordingStore;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    // 容量 capacity の購入商品キャッシュを挟んだセッションと、問い合わせを記録する元のストア
+    fn cached_session(capacity: usize) -> (Box<dyn RecommendationSession>, RecordingStore) {
+        let store = RecordingStore::default();
+        let cache = Arc::new(UserProductsCache::new(
+            NonZeroUsize::new(capacity).unwrap(),
+            Duration::from_secs(60),
+        ));
+        let repository = CachedUserProductsRepository::new(Arc::new(store.clone()), cache);
+        (repository.session().unwrap(), store)
+    }
+
+    #[test]
+    fn cached_customers_are_not_fetched_again() {
+        let (mut session, store) = cached_session(10);
+        let customers = ids(&["mock-customer-1", "mock-customer-5"]);
+
+        let fetched = session.fetch_user_products(&customers).unwrap();
+        let cached = session.fetch_user_products(&customers).unwrap();
+
+        assert_eq!(store.product_queries(), [customers]);
+        // 購入商品のない顧客は、キャッシュから返す場合も結果に含めない
+        assert_eq!(
+            cached.keys().collect::<Vec<_>>(),
+            fetched.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            cached["mock-customer-1"]
+                .iter()
+                .map(|p| (&p.product_variant_id, p.quantity))
+                .collect::<Vec<_>>(),
+            fetched["mock-customer-1"]
+                .iter()
+                .map(|p| (&p.product_variant_id, p.quantity))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn least_recently_used_customer_is_evicted_at_capacity() {
+        let (mut session, store) = cached_session(2);
+
+        session
+            .fetch_user_products(&ids(&["mock-customer-1"]))
+            .unwrap();
+        session
+            .fetch_user_products(&ids(&["mock-customer-2"]))
+            .unwrap();
+        // customer-1 を使ってから customer-3 を追加すると、最も使われていない customer-2 が追い出される
+        session
+            .fetch_user_products(&ids(&["mock-customer-1"]))
+            .unwrap();
+        session
+            .fetch_user_products(&ids(&["mock-customer-3"]))
+            .unwrap();
+        session
+            .fetch_user_products(&ids(&["mock-customer-1", "mock-customer-2"]))
+            .unwrap();
+
+        assert_eq!(
+            store.product_queries(),
+            [
+                ids(&["mock-customer-1"]),
+                ids(&["mock-customer-2"]),
+                ids(&["mock-customer-3"]),
+                ids(&["mock-customer-2"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_hits_fetch_only_the_missing_customers() {
+        let (mut session, store) = cached_session(10);
+        session
+            .fetch_user_products(&ids(&["mock-customer-1", "mock-customer-2"]))
+            .unwrap();
+
+        let products = session
+            .fetch_user_products(&ids(&[
+                "mock-customer-2",
+                "mock-customer-3",
+                "mock-customer-1",
+            ]))
+            .unwrap();
+
+        assert_eq!(
+            store.product_queries(),
+            [
+                ids(&["mock-customer-1", "mock-customer-2"]),
+                ids(&["mock-customer-3"]),
+            ]
+        );
+        // キャッシュ済みと新たに取得した顧客をまとめて返す
+        let mut customers: Vec<&String> = products.keys().collect();
+        customers.sort();
+        assert_eq!(
+            customers,
+            ["mock-customer-1", "mock-customer-2", "mock-customer-3"]
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use super::cart::{
+    ProductDimensions, ProductItem, Temperature, UserVectors, VectorEncoding, has_min_items,
+};
+use super::dimensions::{self, DimensionsCache};
+use crate::repository::{RecommendationRepository, RecommendationSession};
+
+// 事前計算したユーザーベクトルと、計算に使った次元情報・ベクトルの作り方・注文のない顧客を含めたか
+struct WarmUserVectors {
+    dimensions: Arc<ProductDimensions>,
+    encoding: VectorEncoding,
+    include_orderless: bool,
+    vectors: UserVectors,
+}
+
+// 起動時に事前計算したユーザーベクトルのキャッシュ（WARM_USER_VECTORS=true で有効）
+// 次元情報が差し替わるとベクトルの添字が合わなくなるため、同じ次元情報で計算したものだけを返す
+#[derive(Default)]
+pub struct UserVectorsCache {
+    warm: RwLock<Option<Arc<WarmUserVectors>>>,
+}
+
+impl UserVectorsCache {
+    // 指定した次元情報・ベクトルの作り方・注文のない顧客を含めるかで計算済みのベクトルを取得（なければ None）
+    pub fn get(
+        &self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        include_orderless: bool,
+    ) -> Option<UserVectors> {
+        let warm = self
+            .warm
+            .read()
+            .expect("ユーザーベクトルキャッシュのロックに失敗")
+            .clone()?;
+
+        (std::ptr::eq(warm.dimensions.as_ref(), product_dimensions)
+            && warm.encoding == encoding
+            && warm.include_orderless == include_orderless)
+            .then(|| warm.vectors.clone())
+    }
+
+    // ベクトルを差し替える
+    fn replace(
+        &self,
+        dimensions: Arc<ProductDimensions>,
+        encoding: VectorEncoding,
+        include_orderless: bool,
+        vectors: UserVectors,
+    ) {
+        *self
+            .warm
+            .write()
+            .expect("ユーザーベクトルキャッシュのロックに失敗") = Some(Arc::new(WarmUserVectors {
+            dimensions,
+            encoding,
+            include_orderless,
+            vectors,
+        }));
+    }
+}
+
+// 商品次元情報を取得し直し、その次元でユーザーベクトルを計算してキャッシュを差し替える
+// 新しい次元情報はキャッシュ済みのものと一致しないため、ベクトルは必ずデータベースから計算される
+pub async fn refresh(
+    cache: &UserVectorsCache,
+    dimensions_cache: &DimensionsCache,
+    recommendations: Arc<dyn RecommendationRepository>,
+    encoding: VectorEncoding,
+    include_orderless: bool,
+) -> Result<usize, mysql::Error> {
+    let product_dimensions = dimensions::refresh(dimensions_cache, recommendations.clone()).await?;
+
+    // MySQLはasyncに対応していないため、tokioのブロッキング実行を使用
+    let dimensions = product_dimensions.clone();
+    let vectors = tokio::task::spawn_blocking(move || {
+        recommendations.session()?.fetch_user_purchase_history(
+            &dimensions,
+            encoding,
+            1,
+            include_orderless,
+        )
+    })
+    .await
+    .expect("ブロッキングタスクの実行に失敗")?;
+
+    let count = vectors.len();
+    cache.replace(product_dimensions, encoding, include_orderless, vectors);
+    Ok(count)
+}
+
+// 商品次元情報とユーザーベクトルを起動直後に計算し、interval の指定があれば一定間隔で更新する
+// 更新に失敗した場合は古いベクトルのまま提供を続ける（次元情報が変わった場合はデータベースから計算する）
+pub fn spawn_refresh_task(
+    cache: Arc<UserVectorsCache>,
+    dimensions_cache: Arc<DimensionsCache>,
+    recommendations: Arc<dyn RecommendationRepository>,
+    encoding: VectorEncoding,
+    include_orderless: bool,
+    interval: Option<Duration>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = interval.map(tokio::time::interval);
+        loop {
+            if let Some(ticker) = ticker.as_mut() {
+                ticker.tick().await;
+            }
+            match refresh(
+                &cache,
+                &dimensions_cache,
+                recommendations.clone(),
+                encoding,
+                include_orderless,
+            )
+            .await
+            {
+                Ok(count) => tracing::info!(count, "ユーザーベクトルを事前計算しました"),
+                Err(err) => {
+                    tracing::error!(error = %err, "ユーザーベクトルの事前計算に失敗しました")
+                }
+            }
+            if ticker.is_none() {
+                break;
+            }
+        }
+    });
+}
+
+// 事前計算したユーザーベクトルを使う推薦データのストア
+// 購入履歴の取得だけをキャッシュから返し、それ以外は元のストアに任せる
+pub struct WarmRecommendationRepository {
+    inner: Arc<dyn RecommendationRepository>,
+    cache: Arc<UserVectorsCache>,
+}
+
+impl WarmRecommendationRepository {
+    pub fn new(inner: Arc<dyn RecommendationRepository>, cache: Arc<UserVectorsCache>) -> Self {
+        WarmRecommendationRepository { inner, cache }
+    }
+}
+
+impl RecommendationRepository for WarmRecommendationRepository {
+    fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(WarmSession {
+            inner: self.inner.session()?,
+            cache: self.cache.clone(),
+        }))
+    }
+
+    fn primary_session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(WarmSession {
+            inner: self.inner.primary_session()?,
+            cache: self.cache.clone(),
+        }))
+    }
+}
+
+struct WarmSession {
+    inner: Box<dyn RecommendationSession>,
+    cache: Arc<UserVectorsCache>,
+}
+
+impl RecommendationSession for WarmSession {
+    fn fetch_product_dimensions(&mut self) -> Result<ProductDimensions, mysql::Error> {
+        self.inner.fetch_product_dimensions()
+    }
+
+    fn fetch_user_purchase_history(
+        &mut self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        min_items: usize,
+        include_orderless: bool,
+    ) -> Result<UserVectors, mysql::Error> {
+        match self
+            .cache
+            .get(product_dimensions, encoding, include_orderless)
+        {
+            // 事前計算はすべての顧客について行うため、購入商品の少ない顧客はここで除く
+            Some(vectors) if min_items > 1 => Ok(Arc::new(
+                vectors
+                    .iter()
+                    .filter(|(_, order)| has_min_items(order, min_items))
+                    .cloned()
+                    .collect(),
+            )),
+            Some(vectors) => Ok(vectors),
+            None => self.inner.fetch_user_purchase_history(
+                product_dimensions,
+                encoding,
+                min_items,
+                include_orderless,
+            ),
+        }
+    }
+
+    fn fetch_user_products(
+        &mut self,
+        customer_ids: &[String],
+    ) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error> {
+        self.inner.fetch_user_products(customer_ids)
+    }
+
+    fn fetch_cooccurring_products(
+        &mut self,
+        variant_ids: &[String],
+        half_life_days: Option<f32>,
+    ) -> Result<HashMap<String, f32>, mysql::Error> {
+        self.inner
+            .fetch_cooccurring_products(variant_ids, half_life_days)
+    }
+
+    fn fetch_variants_with_temperature(
+        &mut self,
+        variant_ids: &[String],
+        temperature: Temperature,
+    ) -> Result<HashSet<String>, mysql::Error> {
+        self.inner
+            .fetch_variants_with_temperature(variant_ids, temperature)
+    }
+
+    fn fetch_variants_in_categories(
+        &mut self,
+        variant_ids: &[String],
+        categories: &[String],
+    ) -> Result<HashSet<String>, mysql::Error> {
+        self.inner
+            .fetch_variants_in_categories(variant_ids, categories)
+    }
+
+    fn fetch_product_categories(&mut self) -> Result<HashSet<String>, mysql::Error> {
+        self.inner.fetch_product_categories()
+    }
+
+    fn fetch_popular_products(
+        &mut self,
+        exclude_variant_ids: &[String],
+        window_days: Option<u32>,
+        temperature: Option<Temperature>,
+        excluded_categories: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, f32)>, mysql::Error> {
+        self.inner.fetch_popular_products(
+            exclude_variant_ids,
+            window_days,
+            temperature,
+            excluded_categories,
+            limit,
+        )
+    }
+
+    fn fetch_cached_suggestions(
+        &mut self,
+        key: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>, mysql::Error> {
+        self.inner.fetch_cached_suggestions(key, ttl)
+    }
+
+    fn store_cached_suggestions(
+        &mut self,
+        key: &str,
+        suggestions: &str,
+        ttl: Duration,
+    ) -> Result<(), mysql::Error> {
+        self.inner.store_cached_suggestions(key, suggestions, ttl)
+    }
+
+    fn clear_cached_suggestions(&mut self) -> Result<(), mysql::Error> {
+        self.inner.clear_cached_suggestions()
+    }
+
+    fn rebuild_item_similarity(&mut self) -> Result<u64, mysql::Error> {
+        self.inner.rebuild_item_similarity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    use crate::mock::MockStore;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn suggestion_after_warmup_does_not_refetch_purchase_history() {
+        let store = testing::RecordingStore::default();
+        let mut state = testing::state(Arc::new(MockStore), Arc::new(store.clone()));
+        state.recommendations = Arc::new(WarmRecommendationRepository::new(
+            Arc::new(store.clone()),
+            state.user_vectors.clone(),
+        ));
+        let config = state.recommendation_config.read().unwrap().clone();
+
+        let count = refresh(
+            &state.user_vectors,
+            &state.dimensions,
+            state.recommendations.clone(),
+            config.encoding,
+            config.include_orderless_customers,
+        )
+        .await
+        .unwrap();
+        assert!(count > 0);
+        assert_eq!(store.history_queries(), 1);
+
+        // 近傍顧客の探索は事前計算したベクトルを使い、購入履歴は取得し直さない
+        let uri = format!(
+            "/suggestions?{}",
+            serde_urlencoded::to_string([
+                ("province_code", "JP-13"),
+                ("products", r#"[{"product_variant_id": "mock-variant-1"}]"#),
+            ])
+            .unwrap()
+        );
+        let (status, body) = testing::send(testing::app(state), testing::get(&uri)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(store.history_queries(), 1);
+    }
+}
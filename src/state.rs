@@ -0,0 +1,152 @@
+use axum::extract::FromRef;
+use std::sync::{Arc, RwLock};
+
+use crate::config;
+use crate::repository::{MySqlStore, ProductRepository, RecommendationRepository, UserRepository};
+use crate::service::cart::RecommendationConfig;
+use crate::service::dimensions::DimensionsCache;
+use crate::service::jobs::JobRegistry;
+use crate::service::limiter::ConcurrencyLimiter;
+use crate::service::recommender::RecommenderRegistry;
+use crate::service::stats::StatsCache;
+use crate::service::user_products::{CachedUserProductsRepository, UserProductsCache};
+use crate::service::user_vectors::{UserVectorsCache, WarmRecommendationRepository};
+
+// ルーターで共有する状態
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: Arc<mysql::Pool>,
+    pub users: Arc<dyn UserRepository>,
+    pub products: Arc<dyn ProductRepository>,
+    pub recommendations: Arc<dyn RecommendationRepository>,
+    pub dimensions: Arc<DimensionsCache>,
+    pub user_vectors: Arc<UserVectorsCache>,
+    // 推薦の設定（SIGHUP で .env を読み込み直すと差し替わる）
+    pub recommendation_config: Arc<RwLock<RecommendationConfig>>,
+    pub recommenders: Arc<RecommenderRegistry>,
+    pub stats: Arc<StatsCache>,
+    pub jobs: Arc<JobRegistry>,
+    // 同時に計算する推薦の数の制限
+    pub suggestion_limiter: Arc<ConcurrencyLimiter>,
+}
+
+impl AppState {
+    // MySQLのデータストアを使う状態を作成
+    // replica_pool を指定した場合は推薦データの読み込みにレプリカを使い、それ以外（書き込みや管理用の処理）は pool を使う
+    pub fn new(pool: Arc<mysql::Pool>, replica_pool: Option<Arc<mysql::Pool>>) -> Self {
+        let store = Arc::new(MySqlStore::new(pool.clone(), replica_pool));
+        Self::with_store(pool, store.clone(), store.clone()).with_products(store)
+    }
+
+    // 指定したデータストアを使う状態を作成（開発用の固定データのストアなどに使う）
+    pub fn with_store(
+        pool: Arc<mysql::Pool>,
+        users: Arc<dyn UserRepository>,
+        store: Arc<dyn RecommendationRepository>,
+    ) -> Self {
+        // 近傍顧客の購入商品のキャッシュを有効にした場合は、取得結果をメモリにキャッシュする
+        let mut recommendations = store;
+        if let Some((size, ttl)) = config::cache::get_user_products_cache() {
+            recommendations = Arc::new(CachedUserProductsRepository::new(
+                recommendations,
+                Arc::new(UserProductsCache::new(size, ttl)),
+            ));
+        }
+
+        // 事前計算を有効にした場合は、購入履歴の取得を事前計算したベクトルで置き換える
+        let user_vectors = Arc::new(UserVectorsCache::default());
+        if config::cache::get_warm_user_vectors() {
+            recommendations = Arc::new(WarmRecommendationRepository::new(
+                recommendations,
+                user_vectors.clone(),
+            ));
+        }
+
+        AppState {
+            products: Arc::new(MySqlStore::new(pool.clone(), None)),
+            pool,
+            users,
+            recommendations,
+            dimensions: Arc::new(DimensionsCache::default()),
+            user_vectors,
+            recommendation_config: Arc::new(RwLock::new(config::recommendation::load())),
+            recommenders: Arc::new(RecommenderRegistry::default()),
+            stats: Arc::new(StatsCache::new(config::cache::get_stats_cache_ttl())),
+            jobs: Arc::new(JobRegistry::default()),
+            suggestion_limiter: Arc::new(ConcurrencyLimiter::new(
+                config::server::get_max_concurrent_suggestions(),
+            )),
+        }
+    }
+
+    // 商品一覧の取得に使うデータストアを差し替える（既定は pool のMySQL）
+    pub fn with_products(mut self, products: Arc<dyn ProductRepository>) -> Self {
+        self.products = products;
+        self
+    }
+}
+
+impl FromRef<AppState> for Arc<mysql::Pool> {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn UserRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.users.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn ProductRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.products.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn RecommendationRepository> {
+    fn from_ref(state: &AppState) -> Self {
+        state.recommendations.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<DimensionsCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.dimensions.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<RecommenderRegistry> {
+    fn from_ref(state: &AppState) -> Self {
+        state.recommenders.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<StatsCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.stats.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<JobRegistry> {
+    fn from_ref(state: &AppState) -> Self {
+        state.jobs.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ConcurrencyLimiter> {
+    fn from_ref(state: &AppState) -> Self {
+        state.suggestion_limiter.clone()
+    }
+}
+
+// リクエストの処理中に設定が差し替わっても影響しないよう、その時点の設定の複製を渡す
+impl FromRef<AppState> for RecommendationConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state
+            .recommendation_config
+            .read()
+            .expect("推薦の設定のロックに失敗")
+            .clone()
+    }
+}
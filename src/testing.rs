@@ -0,0 +1,297 @@
+// テスト用の補助: MySQLなしでルーターを組み立て、リクエストを送って応答を確かめる
+use async_trait::async_trait;
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower::ServiceExt;
+use tower_http::cors::CorsLayer;
+
+use crate::app::{self, Limits};
+use crate::command;
+use crate::config;
+use crate::db::{Page, User};
+use crate::mock::MockStore;
+use crate::repository::{RecommendationRepository, RecommendationSession, UserRepository};
+use crate::service::cart::{
+    ProductDimensions, ProductItem, Temperature, UserVectors, VectorEncoding,
+};
+use crate::state::AppState;
+
+// 接続しないプール（データベースを使うルートは接続に失敗する）
+pub fn unconnected_pool() -> Arc<mysql::Pool> {
+    Arc::new(
+        mysql::Pool::new(config::database::get_mock_database_opts()).expect("プールの作成に失敗"),
+    )
+}
+
+// TEST_DATABASE_URL のMySQLに接続するプール（実際のデータベースを使う #[ignore] のテスト用）
+// 未適用のスキーマ変更は接続時に適用する
+pub fn database_pool() -> mysql::Pool {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL を指定してください");
+    let pool = mysql::Pool::new(url.as_str()).expect("テスト用のデータベースに接続できません");
+    command::migrate::apply_migrations(&pool).expect("スキーマ変更の適用に失敗");
+    pool
+}
+
+// 指定したデータストアを使う状態を作成
+pub fn state(
+    users: Arc<dyn UserRepository>,
+    recommendations: Arc<dyn RecommendationRepository>,
+) -> AppState {
+    AppState::with_store(unconnected_pool(), users, recommendations)
+}
+
+// 既定の上限（環境変数の既定値）
+pub fn limits() -> Limits {
+    Limits::from_env()
+}
+
+// すべてのオリジンを許可するルーターを作成
+pub fn app(state: AppState) -> Router {
+    app::router(state, CorsLayer::permissive(), limits())
+}
+
+// GETリクエストを作成
+pub fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+// ボディのないPOSTリクエストを作成
+pub fn post(uri: &str) -> Request<Body> {
+    Request::post(uri).body(Body::empty()).unwrap()
+}
+
+// JSONボディのPOSTリクエストを作成
+pub fn post_json(uri: &str, body: impl Into<Body>) -> Request<Body> {
+    Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
+
+// TokenUsers で認証されるAPIトークン
+pub const API_TOKEN: &str = "test-token";
+
+// リクエストに API_TOKEN の認証ヘッダーを付ける
+pub fn authorized(mut request: Request<Body>) -> Request<Body> {
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        format!("Bearer {}", API_TOKEN).parse().unwrap(),
+    );
+    request
+}
+
+// リクエストを送り、ステータスとJSONの本文を返す（本文が空またはJSONでなければ Null）
+pub async fn send(app: Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+    (status, json)
+}
+
+// API_TOKEN を持つ1人のユーザーだけがいるユーザー一覧
+pub struct TokenUsers;
+
+impl TokenUsers {
+    fn user() -> User {
+        User {
+            id: 1,
+            name: "Test User".to_string(),
+            email: "test-user@example.com".to_string(),
+            api_token: Some(API_TOKEN.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for TokenUsers {
+    async fn get_users(&self, _page: Page<i32>) -> Result<Vec<User>, mysql::Error> {
+        Ok(vec![Self::user()])
+    }
+
+    async fn count_users(&self) -> Result<u64, mysql::Error> {
+        Ok(1)
+    }
+
+    async fn find_user_by_api_token(&self, token: &str) -> Result<Option<User>, mysql::Error> {
+        Ok((token == API_TOKEN).then(Self::user))
+    }
+}
+
+// 固定データのストアに、購入履歴の取得回数の記録と推薦結果のキャッシュ（メモリ上、期限なし）を加えたもの
+#[derive(Clone, Default)]
+pub struct RecordingStore {
+    history_queries: Arc<AtomicUsize>,
+    product_queries: Arc<Mutex<Vec<Vec<String>>>>,
+    cached_suggestions: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl RecordingStore {
+    // 購入履歴を取得した回数
+    pub fn history_queries(&self) -> usize {
+        self.history_queries.load(Ordering::SeqCst)
+    }
+
+    // 購入商品を取得した顧客ID（問い合わせごと）
+    pub fn product_queries(&self) -> Vec<Vec<String>> {
+        self.product_queries.lock().unwrap().clone()
+    }
+}
+
+impl RecommendationRepository for RecordingStore {
+    fn session(&self) -> Result<Box<dyn RecommendationSession>, mysql::Error> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl RecommendationSession for RecordingStore {
+    fn fetch_product_dimensions(&mut self) -> Result<ProductDimensions, mysql::Error> {
+        MockStore.fetch_product_dimensions()
+    }
+
+    fn fetch_user_purchase_history(
+        &mut self,
+        product_dimensions: &ProductDimensions,
+        encoding: VectorEncoding,
+        min_items: usize,
+        include_orderless: bool,
+    ) -> Result<UserVectors, mysql::Error> {
+        self.history_queries.fetch_add(1, Ordering::SeqCst);
+        MockStore.fetch_user_purchase_history(
+            product_dimensions,
+            encoding,
+            min_items,
+            include_orderless,
+        )
+    }
+
+    fn fetch_user_products(
+        &mut self,
+        customer_ids: &[String],
+    ) -> Result<HashMap<String, Vec<ProductItem>>, mysql::Error> {
+        self.product_queries
+            .lock()
+            .unwrap()
+            .push(customer_ids.to_vec());
+        MockStore.fetch_user_products(customer_ids)
+    }
+
+    fn fetch_cooccurring_products(
+        &mut self,
+        variant_ids: &[String],
+        half_life_days: Option<f32>,
+    ) -> Result<HashMap<String, f32>, mysql::Error> {
+        MockStore.fetch_cooccurring_products(variant_ids, half_life_days)
+    }
+
+    fn fetch_variants_with_temperature(
+        &mut self,
+        variant_ids: &[String],
+        temperature: Temperature,
+    ) -> Result<HashSet<String>, mysql::Error> {
+        MockStore.fetch_variants_with_temperature(variant_ids, temperature)
+    }
+
+    fn fetch_variants_in_categories(
+        &mut self,
+        variant_ids: &[String],
+        categories: &[String],
+    ) -> Result<HashSet<String>, mysql::Error> {
+        MockStore.fetch_variants_in_categories(variant_ids, categories)
+    }
+
+    fn fetch_product_categories(&mut self) -> Result<HashSet<String>, mysql::Error> {
+        MockStore.fetch_product_categories()
+    }
+
+    fn fetch_popular_products(
+        &mut self,
+        exclude_variant_ids: &[String],
+        window_days: Option<u32>,
+        temperature: Option<Temperature>,
+        excluded_categories: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, f32)>, mysql::Error> {
+        MockStore.fetch_popular_products(
+            exclude_variant_ids,
+            window_days,
+            temperature,
+            excluded_categories,
+            limit,
+        )
+    }
+
+    fn fetch_cached_suggestions(
+        &mut self,
+        key: &str,
+        _ttl: Duration,
+    ) -> Result<Option<String>, mysql::Error> {
+        Ok(self.cached_suggestions.lock().unwrap().get(key).cloned())
+    }
+
+    fn store_cached_suggestions(
+        &mut self,
+        key: &str,
+        suggestions: &str,
+        _ttl: Duration,
+    ) -> Result<(), mysql::Error> {
+        self.cached_suggestions
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), suggestions.to_string());
+        Ok(())
+    }
+
+    fn clear_cached_suggestions(&mut self) -> Result<(), mysql::Error> {
+        self.cached_suggestions.lock().unwrap().clear();
+        Ok(())
+    }
+
+    fn rebuild_item_similarity(&mut self) -> Result<u64, mysql::Error> {
+        MockStore.rebuild_item_similarity()
+    }
+}
+
+// ログの書き込み先（書き込まれた内容をメモリに溜める）
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// f の実行中に出力されたログ（イベントとスパンの終了）をJSONの行として集める
+// 購読者は現在のスレッドだけに設定するため、同期的に実行する処理に使う
+pub fn capture_logs<T>(f: impl FnOnce() -> T) -> (T, Vec<serde_json::Value>) {
+    let buffer = LogBuffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(tracing::Level::TRACE)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .with_writer(move || writer.clone())
+        .finish();
+    let result = tracing::subscriber::with_default(subscriber, f);
+
+    let output = buffer.0.lock().unwrap().clone();
+    let lines = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    (result, lines)
+}